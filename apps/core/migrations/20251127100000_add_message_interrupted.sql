@@ -0,0 +1,2 @@
+-- Migration: Flag assistant messages whose generation was cancelled by the user
+ALTER TABLE messages ADD COLUMN interrupted BOOLEAN NOT NULL DEFAULT 0;
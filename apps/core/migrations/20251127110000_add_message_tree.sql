@@ -0,0 +1,20 @@
+-- Migration: Store messages as a tree so answers can be regenerated and earlier user
+-- messages edited without losing the original branch.
+ALTER TABLE messages ADD COLUMN parent_id INTEGER DEFAULT NULL;
+
+-- Existing conversations are linear: each message replies to the previous one
+UPDATE messages SET parent_id = (
+    SELECT prev.id FROM messages AS prev
+    WHERE prev.session_id = messages.session_id AND prev.id < messages.id
+    ORDER BY prev.id DESC
+    LIMIT 1
+);
+
+-- Leaf of the branch currently shown for each session
+ALTER TABLE sessions ADD COLUMN active_message_id INTEGER DEFAULT NULL;
+
+UPDATE sessions SET active_message_id = (
+    SELECT MAX(id) FROM messages WHERE messages.session_id = sessions.id
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_parent_id ON messages (parent_id);
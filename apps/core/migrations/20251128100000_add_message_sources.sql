@@ -0,0 +1,17 @@
+-- Migration: Keep the retrieved chunks each assistant answer was grounded on, so answers
+-- can be checked against their sources.
+CREATE TABLE message_sources (
+    message_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    chunk_id TEXT NOT NULL,
+    file_id TEXT,
+    file_name TEXT,
+    chunk_index INTEGER NOT NULL DEFAULT 0,
+    page INTEGER,
+    char_start INTEGER,
+    char_end INTEGER,
+    heading_path TEXT,
+    score REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (message_id, position),
+    FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE
+);
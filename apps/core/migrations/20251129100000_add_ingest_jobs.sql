@@ -0,0 +1,15 @@
+-- Migration: Queue file ingestion so it runs in the background and resumes after a restart.
+-- A file has at most one job; queuing it again resets that job.
+CREATE TABLE ingest_jobs (
+    id TEXT PRIMARY KEY,
+    file_id TEXT NOT NULL UNIQUE,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    run_after INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY(file_id) REFERENCES library_files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_ingest_jobs_status ON ingest_jobs(status, run_after);
@@ -0,0 +1,8 @@
+-- Migration: Remember what each library file was indexed from, so reindexing can skip the
+-- files whose content and embedding model did not change.
+-- embedding_model is NULL while the file has no up-to-date chunks.
+ALTER TABLE library_files ADD COLUMN content_hash TEXT;
+ALTER TABLE library_files ADD COLUMN embedding_model TEXT;
+
+-- A forced job reindexes its file even if it looks unchanged.
+ALTER TABLE ingest_jobs ADD COLUMN force INTEGER NOT NULL DEFAULT 0;
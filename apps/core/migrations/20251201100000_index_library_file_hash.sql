@@ -0,0 +1,2 @@
+-- Migration: Look up library files by content hash to deduplicate uploads.
+CREATE INDEX idx_library_files_content_hash ON library_files(content_hash);
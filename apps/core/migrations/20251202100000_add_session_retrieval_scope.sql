@@ -0,0 +1,5 @@
+-- Migration: Let each session choose which documents it retrieves from.
+-- Which documents a session searches: its linked files ('linked'), a document folder
+-- ('folder', with retrieval_folder_id), the whole library ('library') or nothing ('none')
+ALTER TABLE sessions ADD COLUMN retrieval_scope TEXT NOT NULL DEFAULT 'linked';
+ALTER TABLE sessions ADD COLUMN retrieval_folder_id TEXT DEFAULT NULL;
@@ -0,0 +1,399 @@
+//! LanceDB schema of the knowledge base.
+//!
+//! Every chunk is stored with typed provenance columns (file, position, page, headings,
+//! ingestion time and embedding model) so search results can be filtered by file and traced
+//! back to their source. Tables from earlier versions only had `id`, `content`, a free-text
+//! `metadata` column holding `file:<id>`, and `vector`; [`migrate_legacy_batch`] converts
+//! their rows.
+//!
+//! Each embedding model has its own table, named by [`table_name`] and recording the model in
+//! its schema metadata, so vectors of different models are never mixed or compared.
+
+use crate::actors::messages::{ChunkMetadata, SearchResult};
+use crate::embedding_config::EmbeddingModelKind;
+use arrow::array::{
+    Array, ArrayRef, FixedSizeListBuilder, Float32Builder, Int64Array, RecordBatch, StringArray,
+    UInt32Array, UInt64Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Table holding the chunks embedded by the default model, with the typed schema below.
+pub const TABLE_NAME: &str = "knowledge_chunks";
+/// Table used before typed metadata columns existed.
+pub const LEGACY_TABLE_NAME: &str = "knowledge_base";
+/// Model that embedded the legacy table and the tables created before models were selectable.
+pub const LEGACY_EMBEDDING_MODEL: EmbeddingModelKind = EmbeddingModelKind::AllMiniLmL6V2;
+/// Schema metadata key naming the model that embedded a table.
+pub const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+/// Column holding the chunk text, which has the full-text index.
+pub const CONTENT_COLUMN: &str = "content";
+
+/// Table holding the chunks embedded by `model`; the default model keeps the original name.
+pub fn table_name(model: EmbeddingModelKind) -> String {
+    if model == LEGACY_EMBEDDING_MODEL {
+        TABLE_NAME.to_string()
+    } else {
+        format!(
+            "{}_{}",
+            TABLE_NAME,
+            model
+                .name()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+    }
+}
+
+/// Columns returned by searches; everything but the vector.
+pub const RESULT_COLUMNS: [&str; 10] = [
+    "id",
+    "content",
+    "file_id",
+    "chunk_index",
+    "char_start",
+    "char_end",
+    "page",
+    "heading_path",
+    "ingested_at",
+    "embedding_model",
+];
+
+pub fn schema(model: EmbeddingModelKind) -> SchemaRef {
+    let fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("content", DataType::Utf8, false),
+        Field::new("file_id", DataType::Utf8, true),
+        Field::new("chunk_index", DataType::UInt32, false),
+        Field::new("char_start", DataType::UInt64, true),
+        Field::new("char_end", DataType::UInt64, true),
+        Field::new("page", DataType::UInt32, true),
+        Field::new("heading_path", DataType::Utf8, true),
+        Field::new("ingested_at", DataType::Int64, false),
+        Field::new("embedding_model", DataType::Utf8, false),
+        Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                model.dimension() as i32,
+            ),
+            true,
+        ),
+    ];
+    let metadata = HashMap::from([(EMBEDDING_MODEL_KEY.to_string(), model.name().to_string())]);
+    Arc::new(Schema::new_with_metadata(fields, metadata))
+}
+
+/// A chunk ready to be written, with its embedding.
+pub struct NewChunk {
+    pub id: String,
+    pub content: String,
+    pub metadata: ChunkMetadata,
+    pub vector: Vec<f32>,
+}
+
+/// Builds a record batch in the schema of the table of `model`.
+pub fn build_batch(chunks: &[NewChunk], model: EmbeddingModelKind) -> Result<RecordBatch, String> {
+    let dim = model.dimension();
+    let mut vector_builder = FixedSizeListBuilder::new(
+        Float32Builder::with_capacity(chunks.len() * dim),
+        dim as i32,
+    );
+    for chunk in chunks {
+        if chunk.vector.len() != dim {
+            return Err(format!(
+                "Embedding has {} dimensions, expected {}",
+                chunk.vector.len(),
+                dim
+            ));
+        }
+        vector_builder.values().append_slice(&chunk.vector);
+        vector_builder.append(true);
+    }
+
+    let metadata: Vec<&ChunkMetadata> = chunks.iter().map(|c| &c.metadata).collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            chunks.iter().map(|c| c.id.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            chunks.iter().map(|c| c.content.as_str()),
+        )),
+        Arc::new(StringArray::from_iter(
+            metadata.iter().map(|m| m.file_id.as_deref()),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            metadata.iter().map(|m| m.chunk_index),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            metadata.iter().map(|m| m.char_start),
+        )),
+        Arc::new(UInt64Array::from_iter(metadata.iter().map(|m| m.char_end))),
+        Arc::new(UInt32Array::from_iter(metadata.iter().map(|m| m.page))),
+        Arc::new(StringArray::from_iter(
+            metadata.iter().map(|m| m.heading_path.as_deref()),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            metadata.iter().map(|m| m.ingested_at),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            metadata.iter().map(|m| m.embedding_model.as_str()),
+        )),
+        Arc::new(vector_builder.finish()),
+    ];
+
+    RecordBatch::try_new(schema(model), columns)
+        .map_err(|e| format!("Failed to create RecordBatch: {}", e))
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, String> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| format!("Column '{}' not found", name))?
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| format!("Failed to downcast {} column", name))
+}
+
+fn string_at(array: &StringArray, i: usize) -> Option<String> {
+    (!array.is_null(i)).then(|| array.value(i).to_string())
+}
+
+/// Reads the chunks of a query result batch; their score is left at 0.
+pub fn read_chunks(batch: &RecordBatch) -> Result<Vec<SearchResult>, String> {
+    let ids = column::<StringArray>(batch, "id")?;
+    let contents = column::<StringArray>(batch, "content")?;
+    let file_ids = column::<StringArray>(batch, "file_id")?;
+    let chunk_indexes = column::<UInt32Array>(batch, "chunk_index")?;
+    let char_starts = column::<UInt64Array>(batch, "char_start")?;
+    let char_ends = column::<UInt64Array>(batch, "char_end")?;
+    let pages = column::<UInt32Array>(batch, "page")?;
+    let heading_paths = column::<StringArray>(batch, "heading_path")?;
+    let ingested_ats = column::<Int64Array>(batch, "ingested_at")?;
+    let embedding_models = column::<StringArray>(batch, "embedding_model")?;
+
+    Ok((0..batch.num_rows())
+        .filter(|&i| !ids.is_null(i) && !contents.is_null(i))
+        .map(|i| SearchResult {
+            id: ids.value(i).to_string(),
+            content: contents.value(i).to_string(),
+            metadata: ChunkMetadata {
+                file_id: string_at(file_ids, i),
+                chunk_index: chunk_indexes.value(i),
+                char_start: (!char_starts.is_null(i)).then(|| char_starts.value(i)),
+                char_end: (!char_ends.is_null(i)).then(|| char_ends.value(i)),
+                page: (!pages.is_null(i)).then(|| pages.value(i)),
+                heading_path: string_at(heading_paths, i),
+                ingested_at: ingested_ats.value(i),
+                embedding_model: string_at(embedding_models, i).unwrap_or_default(),
+            },
+            score: 0.0,
+        })
+        .collect())
+}
+
+/// Converts a batch of the legacy table to the typed schema.
+///
+/// `file:<id>` metadata becomes the `file_id` (other non-empty values are kept as-is) and
+/// chunks are numbered per file in table order, counting from `next_index`. Offsets, pages
+/// and headings were never recorded, so they stay empty.
+pub fn migrate_legacy_batch(
+    batch: &RecordBatch,
+    ingested_at: i64,
+    next_index: &mut HashMap<Option<String>, u32>,
+) -> Result<RecordBatch, String> {
+    let metadata = column::<StringArray>(batch, "metadata")?;
+    let existing = |name: &str| {
+        batch
+            .column_by_name(name)
+            .cloned()
+            .ok_or_else(|| format!("Column '{}' not found", name))
+    };
+    let rows = batch.num_rows();
+
+    let file_ids: Vec<Option<String>> = (0..rows)
+        .map(|i| {
+            string_at(metadata, i)
+                .map(|value| value.strip_prefix("file:").unwrap_or(&value).to_string())
+                .filter(|value| !value.is_empty())
+        })
+        .collect();
+    let chunk_indexes: Vec<u32> = file_ids
+        .iter()
+        .map(|file_id| {
+            let next = next_index.entry(file_id.clone()).or_default();
+            *next += 1;
+            *next - 1
+        })
+        .collect();
+
+    let columns: Vec<ArrayRef> = vec![
+        existing("id")?,
+        existing("content")?,
+        Arc::new(StringArray::from_iter(
+            file_ids.iter().map(|id| id.as_deref()),
+        )),
+        Arc::new(UInt32Array::from(chunk_indexes)),
+        Arc::new(UInt64Array::new_null(rows)),
+        Arc::new(UInt64Array::new_null(rows)),
+        Arc::new(UInt32Array::new_null(rows)),
+        Arc::new(StringArray::new_null(rows)),
+        Arc::new(Int64Array::from_value(ingested_at, rows)),
+        Arc::new(StringArray::from(vec![LEGACY_EMBEDDING_MODEL.name(); rows])),
+        existing("vector")?,
+    ];
+
+    RecordBatch::try_new(schema(LEGACY_EMBEDDING_MODEL), columns)
+        .map_err(|e| format!("Failed to migrate RecordBatch: {}", e))
+}
+
+/// Quotes a value as an SQL string literal for LanceDB filters.
+///
+/// LanceDB filters are SQL strings without bind parameters, so values are escaped by
+/// doubling single quotes; a quote in an id can then never end the literal.
+pub fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The filter restricting a search to the given files, or `None` for no restriction.
+pub fn file_filter(file_ids: &[String]) -> Option<String> {
+    if file_ids.is_empty() {
+        return None;
+    }
+    let values: Vec<String> = file_ids.iter().map(|id| sql_string(id)).collect();
+    Some(format!("file_id IN ({})", values.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{FixedSizeListArray, Float32Array};
+
+    fn chunk(id: &str, file_id: Option<&str>, index: u32) -> NewChunk {
+        NewChunk {
+            id: id.to_string(),
+            content: format!("content of {}", id),
+            metadata: ChunkMetadata {
+                file_id: file_id.map(str::to_string),
+                chunk_index: index,
+                char_start: Some(10),
+                char_end: Some(42),
+                page: Some(3),
+                heading_path: Some("Install > Linux".to_string()),
+                ingested_at: 1_700_000_000,
+                embedding_model: LEGACY_EMBEDDING_MODEL.name().to_string(),
+            },
+            vector: vec![0.5; LEGACY_EMBEDDING_MODEL.dimension()],
+        }
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let chunks = vec![chunk("a", Some("file-1"), 0), chunk("b", None, 1)];
+        let batch = build_batch(&chunks, LEGACY_EMBEDDING_MODEL).unwrap();
+        assert_eq!(batch.schema(), schema(LEGACY_EMBEDDING_MODEL));
+        assert_eq!(
+            batch.schema().metadata().get(EMBEDDING_MODEL_KEY).unwrap(),
+            "all-MiniLM-L6-v2"
+        );
+
+        let results = read_chunks(&batch).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "a");
+        assert_eq!(results[0].content, "content of a");
+        assert_eq!(results[0].metadata, chunks[0].metadata);
+        assert_eq!(results[1].metadata.file_id, None);
+    }
+
+    #[test]
+    fn test_build_batch_rejects_wrong_dimension() {
+        let mut bad = chunk("a", None, 0);
+        bad.vector.truncate(3);
+        assert!(build_batch(&[bad], LEGACY_EMBEDDING_MODEL).is_err());
+
+        // 384 values do not fit the 768 dimensions of nomic
+        let other = chunk("b", None, 0);
+        assert!(build_batch(&[other], EmbeddingModelKind::NomicEmbedTextV15).is_err());
+    }
+
+    #[test]
+    fn test_table_name_per_model() {
+        assert_eq!(table_name(LEGACY_EMBEDDING_MODEL), TABLE_NAME);
+        assert_eq!(
+            table_name(EmbeddingModelKind::MultilingualE5Small),
+            "knowledge_chunks_multilingual_e5_small"
+        );
+        assert_eq!(
+            table_name(EmbeddingModelKind::BgeSmallEnV15),
+            "knowledge_chunks_bge_small_en_v1_5"
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_batch() {
+        let dim = LEGACY_EMBEDDING_MODEL.dimension();
+        let legacy_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("content", DataType::Utf8, false),
+            Field::new("metadata", DataType::Utf8, true),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::Float32, true)),
+                    dim as i32,
+                ),
+                true,
+            ),
+        ]));
+        let mut vectors = FixedSizeListBuilder::new(Float32Builder::new(), dim as i32);
+        for _ in 0..3 {
+            vectors.values().append_slice(&vec![0.25; dim]);
+            vectors.append(true);
+        }
+        let legacy = RecordBatch::try_new(
+            legacy_schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(StringArray::from(vec!["one", "two", "three"])),
+                Arc::new(StringArray::from(vec![
+                    Some("file:doc-1"),
+                    Some("file:doc-1"),
+                    None,
+                ])),
+                Arc::new(vectors.finish()),
+            ],
+        )
+        .unwrap();
+
+        let mut next_index = HashMap::new();
+        let migrated = migrate_legacy_batch(&legacy, 1_700_000_000, &mut next_index).unwrap();
+        let results = read_chunks(&migrated).unwrap();
+
+        assert_eq!(results[0].metadata.file_id.as_deref(), Some("doc-1"));
+        assert_eq!(results[1].metadata.chunk_index, 1);
+        assert_eq!(results[2].metadata.file_id, None);
+        assert_eq!(results[2].metadata.chunk_index, 0);
+        assert_eq!(results[0].metadata.char_start, None);
+        assert_eq!(
+            results[0].metadata.embedding_model,
+            LEGACY_EMBEDDING_MODEL.name()
+        );
+        let vector = column::<FixedSizeListArray>(&migrated, "vector").unwrap();
+        let first = vector.value(0);
+        let first = first.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(first.value(0), 0.25);
+    }
+
+    #[test]
+    fn test_file_filter_escapes_quotes() {
+        assert_eq!(file_filter(&[]), None);
+        assert_eq!(
+            file_filter(&["a".to_string(), "b".to_string()]).as_deref(),
+            Some("file_id IN ('a', 'b')")
+        );
+        assert_eq!(
+            file_filter(&["x' OR '1'='1".to_string()]).as_deref(),
+            Some("file_id IN ('x'' OR ''1''=''1')")
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Background processing of the ingestion job queue.
+//!
+//! Uploads and reindexing only queue jobs in the `ingest_jobs` table and return. The worker,
+//! spawned by the supervisor, extracts and embeds the queued files one at a time and reports
+//! every status change with an `ingest-progress` event. Jobs survive restarts: those that were
+//! running when the app stopped are queued again when the worker starts.
+//!
+//! A file whose content hash and embedding model match what it was last indexed from is
+//! skipped, so reindexing the whole library only redoes the files that changed.
+
+use crate::actors::traits::RagActor;
+use crate::chunking::ChunkingConfig;
+use crate::database;
+use crate::embedding_config::active_model;
+use crate::models::{IngestJob, IngestJobCounts, IngestJobStatus};
+use crate::text_extract;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqlitePool;
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Window};
+use tokio::sync::Notify;
+use tokio::time::{timeout, Duration};
+use tracing::{error, info, warn};
+
+/// Attempts before a failing job is given up.
+pub const MAX_ATTEMPTS: i64 = 3;
+/// Delay before the first retry, doubled for each following one.
+const RETRY_DELAY_SECS: i64 = 10;
+/// Pause before polling the queue again after a database error.
+const ERROR_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Payload of the `ingest-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestProgress {
+    #[serde(flatten)]
+    pub job: IngestJob,
+    /// Jobs still pending or running, this one included while it runs.
+    pub remaining: i64,
+    /// Jobs in each status, i.e. the results of a reindex once `remaining` drops to 0.
+    pub counts: IngestJobCounts,
+}
+
+/// The window progress is reported to, once one is known.
+pub type ProgressWindow = Arc<Mutex<Option<Window>>>;
+
+enum JobError {
+    /// Worth another attempt, e.g. the embedding model was not loaded yet.
+    Transient(String),
+    /// Fails the same way every time, e.g. a file without any text.
+    Permanent(String),
+}
+
+pub struct IngestWorker<R: RagActor> {
+    rag_actor: Arc<R>,
+    pool: SqlitePool,
+    /// Notified when jobs are queued.
+    wake: Arc<Notify>,
+    window: ProgressWindow,
+}
+
+impl<R: RagActor> IngestWorker<R> {
+    pub fn new(
+        rag_actor: Arc<R>,
+        pool: SqlitePool,
+        wake: Arc<Notify>,
+        window: ProgressWindow,
+    ) -> Self {
+        Self {
+            rag_actor,
+            pool,
+            wake,
+            window,
+        }
+    }
+
+    /// Processes queued jobs until the task is aborted.
+    pub async fn run(self) {
+        match database::requeue_running_ingest_jobs(&self.pool).await {
+            Ok(0) => {}
+            Ok(count) => info!("Resuming {} interrupted ingestion job(s)", count),
+            Err(e) => error!("Failed to requeue interrupted ingestion jobs: {}", e),
+        }
+
+        let mut busy = false;
+        loop {
+            match database::claim_next_ingest_job(&self.pool).await {
+                Ok(Some(job)) => {
+                    busy = true;
+                    self.process(job).await;
+                }
+                Ok(None) => {
+                    if std::mem::take(&mut busy) {
+                        self.log_summary().await;
+                    }
+                    self.wait_for_jobs().await;
+                }
+                Err(e) => {
+                    error!("Failed to claim ingestion job: {}", e);
+                    tokio::time::sleep(ERROR_BACKOFF).await;
+                }
+            }
+        }
+    }
+
+    /// Sleeps until jobs are queued or the next retry is due.
+    async fn wait_for_jobs(&self) {
+        match database::next_ingest_job_at(&self.pool).await {
+            Ok(Some(due)) => {
+                let delay = (due - chrono::Utc::now().timestamp()).max(1) as u64;
+                let _ = timeout(Duration::from_secs(delay), self.wake.notified()).await;
+            }
+            Ok(None) => self.wake.notified().await,
+            Err(e) => {
+                error!("Failed to read the ingestion queue: {}", e);
+                tokio::time::sleep(ERROR_BACKOFF).await;
+            }
+        }
+    }
+
+    /// Logs what the jobs came to once the queue is empty.
+    async fn log_summary(&self) {
+        match database::count_ingest_jobs(&self.pool).await {
+            Ok(counts) if counts.remaining() == 0 => info!(
+                "Ingestion queue drained: {} indexed, {} unchanged, {} failed",
+                counts.done, counts.skipped, counts.failed
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Failed to count ingestion jobs: {}", e),
+        }
+    }
+
+    async fn process(&self, job: IngestJob) {
+        info!("Ingesting {} (attempt {})", job.file_name, job.attempts);
+        report_progress(&self.window, &self.pool, &job).await;
+
+        let (status, error, run_after) = match self.index_file(&job).await {
+            Ok(status) => (status, None, 0),
+            Err(JobError::Transient(e)) if job.attempts < MAX_ATTEMPTS => {
+                let delay = RETRY_DELAY_SECS << (job.attempts - 1).clamp(0, 10);
+                warn!(
+                    "Ingesting {} failed, retrying in {}s: {}",
+                    job.file_name, delay, e
+                );
+                let retry_at = chrono::Utc::now().timestamp() + delay;
+                (IngestJobStatus::Pending, Some(e), retry_at)
+            }
+            Err(JobError::Transient(e) | JobError::Permanent(e)) => {
+                error!("Ingesting {} failed: {}", job.file_name, e);
+                (IngestJobStatus::Failed, Some(e), 0)
+            }
+        };
+
+        match database::finish_ingest_job(&self.pool, &job.id, status, error.as_deref(), run_after)
+            .await
+        {
+            Ok(true) => match database::get_ingest_job(&self.pool, &job.id).await {
+                Ok(job) => report_progress(&self.window, &self.pool, &job).await,
+                Err(e) => error!("Failed to reload ingestion job {}: {}", job.id, e),
+            },
+            Ok(false) => {
+                // Queued again (the new job redoes the work) or deleted meanwhile; in the
+                // latter case the chunks just added belong to nothing
+                if database::get_library_file(&self.pool, &job.file_id)
+                    .await
+                    .is_err()
+                {
+                    if let Err(e) = self.rag_actor.delete_for_file(job.file_id.clone()).await {
+                        error!("Failed to delete chunks of removed file: {:?}", e);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to record ingestion job outcome: {}", e),
+        }
+    }
+
+    /// Extracts the text of the job's file and replaces its chunks in the knowledge base.
+    ///
+    /// Returns [`IngestJobStatus::Skipped`] without touching the chunks if the file is
+    /// unchanged since it was indexed with the current embedding model.
+    async fn index_file(&self, job: &IngestJob) -> Result<IngestJobStatus, JobError> {
+        let file = database::get_library_file(&self.pool, &job.file_id)
+            .await
+            .map_err(|e| JobError::Permanent(format!("File not found in library: {}", e)))?;
+        let data = tokio::fs::read(&file.path)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => {
+                    JobError::Permanent(format!("File not found on disk: {}", file.path))
+                }
+                _ => JobError::Transient(format!("Failed to read file: {}", e)),
+            })?;
+
+        let content_hash = hex::encode(Sha256::digest(&data));
+        if !job.force
+            && file.content_hash.as_deref() == Some(content_hash.as_str())
+            && file.embedding_model.as_deref() == Some(active_model().name())
+        {
+            info!("   ✓ {}: unchanged, skipped", file.name);
+            return Ok(IngestJobStatus::Skipped);
+        }
+
+        // PDF and DOCX parsing is CPU-bound, keep it off the async runtime
+        let name = file.name.clone();
+        let content =
+            tokio::task::spawn_blocking(move || text_extract::extract_text_from_file(&name, &data))
+                .await
+                .map_err(|e| JobError::Transient(format!("Text extraction panicked: {}", e)))?
+                .map_err(JobError::Permanent)?;
+        if content.trim().is_empty() {
+            return Err(JobError::Permanent(
+                "No text content could be extracted from the file".to_string(),
+            ));
+        }
+
+        // Drop the chunks of a previous run so retries and reindexing never duplicate them
+        database::invalidate_library_file_index(&self.pool, &file.id, &content_hash)
+            .await
+            .map_err(|e| JobError::Transient(e.to_string()))?;
+        self.rag_actor
+            .delete_for_file(file.id.clone())
+            .await
+            .map_err(|e| JobError::Transient(e.to_string()))?;
+        let chunking = ChunkingConfig::load().options_for(&file.name);
+        let result = self
+            .rag_actor
+            .ingest_with_options(content, Some(file.id.clone()), chunking)
+            .await
+            .map_err(|e| JobError::Transient(e.to_string()))?;
+        database::mark_library_file_indexed(
+            &self.pool,
+            &file.id,
+            &job.id,
+            &content_hash,
+            active_model().name(),
+        )
+        .await
+        .map_err(|e| JobError::Transient(e.to_string()))?;
+        info!("   ✓ {}: {}", file.name, result);
+        Ok(IngestJobStatus::Done)
+    }
+}
+
+/// Emits an `ingest-progress` event for `job`, if a window is known.
+pub async fn report_progress(window: &ProgressWindow, pool: &SqlitePool, job: &IngestJob) {
+    let Some(window) = window.lock().ok().and_then(|window| window.clone()) else {
+        return;
+    };
+    let counts = database::count_ingest_jobs(pool).await.unwrap_or_default();
+    let progress = IngestProgress {
+        job: job.clone(),
+        remaining: counts.remaining(),
+        counts,
+    };
+    if let Err(e) = window.emit("ingest-progress", progress) {
+        warn!("Failed to emit ingest-progress event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::traits::mocks::MockRagActor;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::atomic::Ordering;
+    use tempfile::TempDir;
+
+    async fn setup_test_db() -> (SqlitePool, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("test.sqlite").display()
+        );
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&db_url)
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        (pool, temp_dir)
+    }
+
+    /// Adds a library file with the given content and queues it.
+    async fn queue_file(pool: &SqlitePool, dir: &TempDir, name: &str, content: &str) -> IngestJob {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        database::add_library_file(
+            pool,
+            name,
+            name,
+            &path.to_string_lossy(),
+            "text/plain",
+            content.len() as i64,
+        )
+        .await
+        .unwrap();
+        database::enqueue_ingest_job(pool, name, false)
+            .await
+            .unwrap()
+    }
+
+    fn worker(rag: Arc<MockRagActor>, pool: &SqlitePool) -> IngestWorker<MockRagActor> {
+        IngestWorker::new(rag, pool.clone(), Arc::default(), Arc::default())
+    }
+
+    #[tokio::test]
+    async fn test_worker_indexes_queued_file() {
+        let (pool, dir) = setup_test_db().await;
+        let rag = Arc::new(MockRagActor::new());
+        queue_file(&pool, &dir, "notes.md", "# Notes\n\nSome content to index.").await;
+
+        let worker = worker(rag.clone(), &pool);
+        let job = database::claim_next_ingest_job(&pool)
+            .await
+            .unwrap()
+            .unwrap();
+        worker.process(job).await;
+
+        let job = &database::list_ingest_jobs(&pool).await.unwrap()[0];
+        assert_eq!(job.status, IngestJobStatus::Done);
+        assert_eq!(rag.delete_count.load(Ordering::SeqCst), 1);
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            rag.last_ingested.lock().await.as_deref(),
+            Some("# Notes\n\nSome content to index.")
+        );
+    }
+
+    /// Claims the next job and processes it, returning the job as it ended.
+    async fn run_next(worker: &IngestWorker<MockRagActor>, pool: &SqlitePool) -> IngestJob {
+        let job = database::claim_next_ingest_job(pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let id = job.id.clone();
+        worker.process(job).await;
+        database::get_ingest_job(pool, &id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_worker_skips_unchanged_files() {
+        let (pool, dir) = setup_test_db().await;
+        let rag = Arc::new(MockRagActor::new());
+        let worker = worker(rag.clone(), &pool);
+
+        queue_file(&pool, &dir, "notes.md", "# Notes\n\nFirst version.").await;
+        assert_eq!(run_next(&worker, &pool).await.status, IngestJobStatus::Done);
+        let file = database::get_library_file(&pool, "notes.md").await.unwrap();
+        assert!(file.content_hash.is_some());
+        assert_eq!(file.embedding_model.as_deref(), Some(active_model().name()));
+
+        // Reindexing an unchanged file does nothing
+        database::enqueue_ingest_job(&pool, "notes.md", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            run_next(&worker, &pool).await.status,
+            IngestJobStatus::Skipped
+        );
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 1);
+
+        // Unless forced
+        database::enqueue_ingest_job(&pool, "notes.md", true)
+            .await
+            .unwrap();
+        assert_eq!(run_next(&worker, &pool).await.status, IngestJobStatus::Done);
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 2);
+
+        // A changed file or one embedded by another model is indexed again
+        std::fs::write(dir.path().join("notes.md"), "# Notes\n\nSecond version.").unwrap();
+        database::enqueue_ingest_job(&pool, "notes.md", false)
+            .await
+            .unwrap();
+        assert_eq!(run_next(&worker, &pool).await.status, IngestJobStatus::Done);
+        sqlx::query("UPDATE library_files SET embedding_model = 'older-model'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        database::enqueue_ingest_job(&pool, "notes.md", false)
+            .await
+            .unwrap();
+        assert_eq!(run_next(&worker, &pool).await.status, IngestJobStatus::Done);
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 4);
+
+        let counts = database::count_ingest_jobs(&pool).await.unwrap();
+        assert_eq!(counts.remaining(), 0);
+        assert_eq!(counts.done, 1);
+    }
+
+    #[tokio::test]
+    async fn test_worker_retries_transient_failures() {
+        let (pool, dir) = setup_test_db().await;
+        let rag = Arc::new(MockRagActor::with_failure());
+        queue_file(&pool, &dir, "notes.txt", "Some content to index.").await;
+
+        let worker = worker(rag.clone(), &pool);
+        let job = database::claim_next_ingest_job(&pool)
+            .await
+            .unwrap()
+            .unwrap();
+        worker.process(job).await;
+
+        let job = &database::list_ingest_jobs(&pool).await.unwrap()[0];
+        assert_eq!(job.status, IngestJobStatus::Pending);
+        assert!(job.error.is_some());
+        assert!(job.run_after > chrono::Utc::now().timestamp());
+
+        // The last attempt gives up
+        sqlx::query("UPDATE ingest_jobs SET status = 'running', attempts = ?")
+            .bind(MAX_ATTEMPTS)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let job = database::get_ingest_job(&pool, &job.id).await.unwrap();
+        worker.process(job).await;
+        let job = &database::list_ingest_jobs(&pool).await.unwrap()[0];
+        assert_eq!(job.status, IngestJobStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_worker_does_not_retry_files_without_text() {
+        let (pool, dir) = setup_test_db().await;
+        let rag = Arc::new(MockRagActor::new());
+        queue_file(&pool, &dir, "empty.txt", "   \n").await;
+
+        let worker = worker(rag.clone(), &pool);
+        let job = database::claim_next_ingest_job(&pool)
+            .await
+            .unwrap()
+            .unwrap();
+        worker.process(job).await;
+
+        let job = &database::list_ingest_jobs(&pool).await.unwrap()[0];
+        assert_eq!(job.status, IngestJobStatus::Failed);
+        assert_eq!(job.attempts, 1);
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_worker_drains_queue_when_woken() {
+        let (pool, dir) = setup_test_db().await;
+        let rag = Arc::new(MockRagActor::new());
+        let wake = Arc::new(Notify::new());
+        let task = tokio::spawn(
+            IngestWorker::new(rag.clone(), pool.clone(), wake.clone(), Arc::default()).run(),
+        );
+
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            queue_file(&pool, &dir, name, "Some content to index.").await;
+        }
+        wake.notify_one();
+
+        timeout(Duration::from_secs(5), async {
+            while database::count_ingest_jobs(&pool)
+                .await
+                .unwrap()
+                .remaining()
+                > 0
+            {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("Queue not drained");
+        task.abort();
+
+        assert_eq!(rag.ingest_count.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,11 +1,17 @@
-use crate::actors::messages::{ActorError, AppError, LlmMessage};
+use crate::actors::messages::{ActorError, AppError, ChatMessage, LlmMessage};
 use crate::actors::traits::LlmActor;
+use crate::chat_template::ChatTemplate;
 use crate::fs_manager::PortablePathManager;
+use crate::launch_profile::LaunchProfile;
+use crate::llama_release;
+use crate::model_store::resolve_model_path;
+use crate::models::{SamplingParams, MAX_RESPONSE_TOKENS};
 use async_trait::async_trait;
 use futures::StreamExt;
 use reqwest::header::{HeaderMap, AUTHORIZATION};
 use reqwest::Client;
 use std::env;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::time::{Duration, Instant};
 use tokio::process::Command;
@@ -21,6 +27,8 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct LlmActorHandle {
     sender: mpsc::Sender<LlmMessage>,
+    /// The model requests from this handle are served with; `None` uses the loaded model.
+    model_path: Option<PathBuf>,
 }
 
 impl LlmActorHandle {
@@ -31,11 +39,25 @@ impl LlmActorHandle {
     /// # Arguments
     ///
     /// * `model_path` - The path to the GGUF model file for `llama-server`.
-    pub fn new(model_path: std::path::PathBuf) -> Self {
+    pub fn new(model_path: PathBuf) -> Self {
         let (sender, receiver) = mpsc::channel(32);
         let actor = LlmActorRunner::new(receiver, model_path);
         tokio::spawn(async move { actor.run().await });
-        Self { sender }
+        Self {
+            sender,
+            model_path: None,
+        }
+    }
+
+    /// Returns a handle to the same actor whose requests are served with `model_path`.
+    ///
+    /// The actor restarts `llama-server` when a request names a different model than the
+    /// one currently loaded.
+    pub fn with_model_path(&self, model_path: PathBuf) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            model_path: Some(model_path),
+        }
     }
 
     /// A convenience method for generating text with default parameters.
@@ -47,17 +69,21 @@ impl LlmActorHandle {
 
 #[async_trait]
 impl LlmActor for LlmActorHandle {
-    async fn generate_with_params(
+    fn for_model(&self, model_id: &str) -> Option<Box<dyn LlmActor>> {
+        let model_path = resolve_model_path(model_id)?;
+        Some(Box::new(self.with_model_path(model_path)))
+    }
+
+    async fn chat_with_params(
         &self,
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
     ) -> Result<String, AppError> {
         let (send, recv) = oneshot::channel();
         let msg = LlmMessage::GenerateWithParams {
-            prompt,
-            system_prompt,
-            temperature,
+            messages,
+            params,
+            model_path: self.model_path.clone(),
             responder: send,
         };
 
@@ -69,18 +95,17 @@ impl LlmActor for LlmActorHandle {
         })?
     }
 
-    async fn stream_generate_with_params(
+    async fn stream_chat_with_params(
         &self,
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
         chunk_sender: mpsc::Sender<Result<String, AppError>>,
     ) -> Result<(), AppError> {
         let (send, recv) = oneshot::channel();
         let msg = LlmMessage::StreamGenerateWithParams {
-            prompt,
-            system_prompt,
-            temperature,
+            messages,
+            params,
+            model_path: self.model_path.clone(),
             chunk_sender,
             responder: send,
         };
@@ -97,6 +122,16 @@ impl LlmActor for LlmActorHandle {
 }
 
 // --- Constants ---
+/// Default total context size passed to `llama-server` via `-c`.
+pub const CONTEXT_SIZE: usize = 8192;
+/// Default number of parallel slots (`-np`); `llama-server` splits the context evenly between them.
+pub const PARALLEL_SLOTS: usize = 2;
+/// Sampling defaults for parameters a session leaves unset.
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+const DEFAULT_TOP_K: u32 = 40;
+const DEFAULT_TOP_P: f32 = 0.95;
+const DEFAULT_MIN_P: f32 = 0.05;
+const DEFAULT_REPEAT_PENALTY: f32 = 1.1;
 const COMPLETION_TIMEOUT: Duration = Duration::from_secs(120);
 const STREAM_CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
 const MAX_RESTART_ATTEMPTS: u32 = 3;
@@ -107,7 +142,10 @@ struct LlmActorRunner {
     receiver: mpsc::Receiver<LlmMessage>,
     child: Option<tokio::process::Child>,
     server_url: String,
-    model_path: std::path::PathBuf,
+    model_path: PathBuf,
+    chat_template: ChatTemplate,
+    /// Response budget of one slot of the running server, from its fitted launch profile.
+    response_tokens: usize,
     client: Client,
     auth_token: Option<String>,
     // Circuit Breaker State
@@ -122,7 +160,7 @@ impl Drop for LlmActorRunner {
 }
 
 impl LlmActorRunner {
-    fn new(receiver: mpsc::Receiver<LlmMessage>, model_path: std::path::PathBuf) -> Self {
+    fn new(receiver: mpsc::Receiver<LlmMessage>, model_path: PathBuf) -> Self {
         // Prioritize env var, fallback to generated UUID
         let auth_token = env::var("LLAMA_AUTH_TOKEN").ok().or_else(|| {
             let token = Uuid::new_v4().to_string();
@@ -136,7 +174,9 @@ impl LlmActorRunner {
         Self {
             receiver,
             child: None,
-            server_url: "http://localhost:8080".to_string(),
+            server_url: String::new(),
+            chat_template: ChatTemplate::for_model_path(&model_path),
+            response_tokens: MAX_RESPONSE_TOKENS,
             model_path,
             client: Client::new(),
             auth_token,
@@ -216,6 +256,30 @@ impl LlmActorRunner {
         }
     }
 
+    /// Makes `model_path` the model served by `llama-server`, stopping the running server
+    /// if it holds another model. The next request starts it again with the new model.
+    async fn switch_model(&mut self, model_path: PathBuf) {
+        if self.model_path == model_path {
+            return;
+        }
+
+        info!(
+            "Switching model from {:?} to {:?}",
+            self.model_path, model_path
+        );
+        if let Some(mut child) = self.child.take() {
+            // Wait for the old process so both models are never loaded at once
+            if let Err(e) = child.kill().await {
+                error!("Failed to stop llama-server for model switch: {}", e);
+            }
+        }
+
+        self.chat_template = ChatTemplate::for_model_path(&model_path);
+        self.model_path = model_path;
+        // A different model gets a fresh set of restart attempts
+        self.restart_attempts = 0;
+    }
+
     async fn start_server(&mut self) -> Result<(), AppError> {
         if self.child.is_some() {
             return Ok(());
@@ -245,12 +309,8 @@ impl LlmActorRunner {
         );
 
         // Determine llama-server executable path
-        let server_bin_name = if cfg!(windows) {
-            "llama-server.exe"
-        } else {
-            "llama-server"
-        };
-        let mut server_path = std::path::PathBuf::from(server_bin_name);
+        let server_bin_name = llama_release::server_binary_name();
+        let mut server_path = PathBuf::from(server_bin_name);
 
         // Check if in PATH
         if which::which(server_bin_name).is_err() {
@@ -268,25 +328,31 @@ impl LlmActorRunner {
             }
         }
 
-        let mut cmd = Command::new(server_path);
+        // The profile is re-read at every start so saved changes apply without a rebuild
+        let profile = LaunchProfile::for_model(&self.model_path);
+        let port = profile.resolve_port()?;
+        self.response_tokens = profile.response_tokens();
+        self.server_url = format!("http://127.0.0.1:{port}");
+        info!("llama-server launch profile: {:?} (port {})", profile, port);
+
+        let mut cmd = Command::new(&server_path);
         cmd.arg("-m")
             .arg(&self.model_path)
-            .arg("--host")
-            .arg("127.0.0.1")
-            .arg("--port")
-            .arg("8080")
-            .arg("-c")
-            .arg("8192") // Context size: 8K tokens
-            .arg("-np")
-            .arg("2"); // Support 2 parallel requests
-
-        // Enable GPU acceleration if available (will be ignored if no GPU)
-        cmd.arg("-ngl").arg("99");
+            .args(profile.server_args(port));
 
         if let Some(token) = &self.auth_token {
             cmd.arg("--api-key").arg(token);
         }
 
+        // Builds from the tools directory ship their shared libraries next to the binary
+        if let Some((var, value)) = server_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .and_then(llama_release::library_path_env)
+        {
+            cmd.env(var, value);
+        }
+
         let child = cmd
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -352,6 +418,11 @@ impl LlmActorRunner {
     }
 
     async fn handle_message(&mut self, msg: LlmMessage) {
+        if let Some(model_path) = msg.model_path() {
+            let model_path = model_path.clone();
+            self.switch_model(model_path).await;
+        }
+
         // Ensure server is running before processing message
         if let Err(e) = self.start_server().await {
             error!("Failed to start LLM server on demand: {}", e);
@@ -361,55 +432,47 @@ impl LlmActorRunner {
 
         match msg {
             LlmMessage::Generate {
-                prompt,
-                system_prompt,
-                temperature,
+                messages,
+                params,
                 responder,
+                ..
             } => {
-                let result = self
-                    .generate_completion(prompt, system_prompt, temperature)
-                    .await;
+                let result = self.generate_completion(messages, params).await;
                 if responder.send(result).is_err() {
                     warn!("Failed to send generate response (channel closed)");
                 }
             }
             LlmMessage::GenerateWithParams {
-                prompt,
-                system_prompt,
-                temperature,
+                messages,
+                params,
                 responder,
+                ..
             } => {
-                let result = self
-                    .generate_completion(prompt, system_prompt, temperature)
-                    .await;
+                let result = self.generate_completion(messages, params).await;
                 if responder.send(result).is_err() {
                     warn!("Failed to send generate_with_params response (channel closed)");
                 }
             }
             LlmMessage::StreamGenerate {
-                prompt,
-                system_prompt,
-                temperature,
+                messages,
+                params,
                 chunk_sender,
                 responder,
+                ..
             } => {
-                let result = self
-                    .stream_completion(prompt, system_prompt, temperature, chunk_sender)
-                    .await;
+                let result = self.stream_completion(messages, params, chunk_sender).await;
                 if responder.send(result).is_err() {
                     warn!("Failed to send stream_generate response (channel closed)");
                 }
             }
             LlmMessage::StreamGenerateWithParams {
-                prompt,
-                system_prompt,
-                temperature,
+                messages,
+                params,
                 chunk_sender,
                 responder,
+                ..
             } => {
-                let result = self
-                    .stream_completion(prompt, system_prompt, temperature, chunk_sender)
-                    .await;
+                let result = self.stream_completion(messages, params, chunk_sender).await;
                 if responder.send(result).is_err() {
                     warn!("Failed to send stream_generate_with_params response (channel closed)");
                 }
@@ -434,54 +497,28 @@ impl LlmActorRunner {
         }
     }
 
+    /// Renders a conversation with the chat template of the loaded model.
+    fn render_prompt(&self, messages: &[ChatMessage]) -> String {
+        let turns: Vec<_> = messages
+            .iter()
+            .map(|m| (m.role, m.content.as_str()))
+            .collect();
+        self.chat_template.render(&turns)
+    }
+
     async fn generate_completion(
         &self,
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
     ) -> Result<String, AppError> {
-        info!("LLM Generating for prompt: {}", prompt);
-
-        // Build the full prompt using ChatML format for Qwen2.5
-        let full_prompt = if let Some(system) = &system_prompt {
-            if !system.is_empty() {
-                format!(
-                    "<|im_start|>system\n{}<|im_end|>\n<|im_start|>user\n{}<|im_end|>\n<|im_start|>assistant\n",
-                    system, prompt
-                )
-            } else {
-                format!(
-                    "<|im_start|>user\n{}<|im_end|>\n<|im_start|>assistant\n",
-                    prompt
-                )
-            }
-        } else {
-            format!(
-                "<|im_start|>user\n{}<|im_end|>\n<|im_start|>assistant\n",
-                prompt
-            )
-        };
-
-        let mut payload = serde_json::json!({
-            "prompt": full_prompt,
-            "stream": false,
-            "n_predict": 2048,
-            "top_k": 40,
-            "top_p": 0.95,
-            "min_p": 0.05,
-            "repeat_penalty": 1.1,
-            "repeat_last_n": 64,
-            "stop": ["<|im_end|>", "<|im_start|>"]
-        });
-
-        // Add temperature if provided, otherwise use 0.7 as default
-        let temp = temperature.unwrap_or(0.7);
-        payload["temperature"] = serde_json::Value::Number(
-            serde_json::Number::from_f64(temp as f64).unwrap_or_else(|| {
-                warn!("Invalid temperature value: {}. Using default 0.7.", temp);
-                // NOTE: from_f64(0.7) is guaranteed to succeed since 0.7 is a valid float
-                serde_json::Number::from_f64(0.7).expect("0.7 is a valid float constant")
-            }),
+        info!("LLM Generating for {} messages", messages.len());
+
+        let payload = completion_payload(
+            self.render_prompt(&messages),
+            self.chat_template.stop_tokens(),
+            &params,
+            self.response_tokens,
+            false,
         );
 
         let request_future = self.build_request("completion", &payload)?.send();
@@ -507,46 +544,18 @@ impl LlmActorRunner {
 
     async fn stream_completion(
         &self,
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
         chunk_sender: mpsc::Sender<Result<String, AppError>>,
     ) -> Result<(), AppError> {
-        info!("LLM Streaming for prompt: {prompt}");
-
-        // Build the full prompt using ChatML format for Qwen2.5
-        let full_prompt = if let Some(system) = &system_prompt {
-            if !system.is_empty() {
-                format!(
-                    "<|im_start|>system\n{system}<|im_end|>\n<|im_start|>user\n{prompt}<|im_end|>\n<|im_start|>assistant\n"
-                )
-            } else {
-                format!("<|im_start|>user\n{prompt}<|im_end|>\n<|im_start|>assistant\n")
-            }
-        } else {
-            format!("<|im_start|>user\n{prompt}<|im_end|>\n<|im_start|>assistant\n")
-        };
-
-        let mut payload = serde_json::json!({
-            "prompt": full_prompt,
-            "stream": true,
-            "n_predict": 2048,
-            "top_k": 40,
-            "top_p": 0.95,
-            "min_p": 0.05,
-            "repeat_penalty": 1.1,
-            "repeat_last_n": 64,
-            "stop": ["<|im_end|>", "<|im_start|>"]
-        });
-
-        // Add temperature if provided, otherwise use 0.7 as default
-        let temp = temperature.unwrap_or(0.7);
-        payload["temperature"] = serde_json::Value::Number(
-            serde_json::Number::from_f64(temp as f64).unwrap_or_else(|| {
-                warn!("Invalid temperature value: {}. Using default 0.7.", temp);
-                // NOTE: from_f64(0.7) is guaranteed to succeed since 0.7 is a valid float
-                serde_json::Number::from_f64(0.7).expect("0.7 is a valid float constant")
-            }),
+        info!("LLM Streaming for {} messages", messages.len());
+
+        let payload = completion_payload(
+            self.render_prompt(&messages),
+            self.chat_template.stop_tokens(),
+            &params,
+            self.response_tokens,
+            true,
         );
 
         let request_future = self.build_request("completion", &payload)?.send();
@@ -602,3 +611,132 @@ impl LlmActorRunner {
         Ok(())
     }
 }
+
+/// Builds the `/completion` request body, filling unset sampling parameters with defaults.
+///
+/// `n_predict` never exceeds `max_tokens`, the space the context budget keeps free for the
+/// answer, and the template's stop tokens are always included.
+fn completion_payload(
+    prompt: String,
+    template_stop: &[&str],
+    params: &SamplingParams,
+    max_tokens: usize,
+    stream: bool,
+) -> serde_json::Value {
+    let mut stop: Vec<&str> = template_stop.to_vec();
+    stop.extend(params.stop.iter().map(String::as_str));
+
+    let temperature = params.temperature.unwrap_or(DEFAULT_TEMPERATURE);
+    let temperature = if temperature.is_finite() {
+        temperature
+    } else {
+        warn!(
+            "Invalid temperature value: {}. Using default {}.",
+            temperature, DEFAULT_TEMPERATURE
+        );
+        DEFAULT_TEMPERATURE
+    };
+
+    serde_json::json!({
+        "prompt": prompt,
+        "stream": stream,
+        "temperature": temperature,
+        "n_predict": params.n_predict.unwrap_or(max_tokens).min(max_tokens),
+        "top_k": params.top_k.unwrap_or(DEFAULT_TOP_K),
+        "top_p": params.top_p.unwrap_or(DEFAULT_TOP_P),
+        "min_p": params.min_p.unwrap_or(DEFAULT_MIN_P),
+        "repeat_penalty": params.repeat_penalty.unwrap_or(DEFAULT_REPEAT_PENALTY),
+        "repeat_last_n": 64,
+        "stop": stop
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_switch_model_updates_template() {
+        let (_sender, receiver) = mpsc::channel(1);
+        let mut runner = LlmActorRunner::new(receiver, PathBuf::from("qwen2.5-7b.gguf"));
+        runner.restart_attempts = MAX_RESTART_ATTEMPTS;
+
+        runner.switch_model(PathBuf::from("qwen2.5-7b.gguf")).await;
+        assert_eq!(runner.restart_attempts, MAX_RESTART_ATTEMPTS);
+
+        runner
+            .switch_model(PathBuf::from("Llama-3.2-3B-Instruct.gguf"))
+            .await;
+        assert_eq!(
+            runner.model_path,
+            PathBuf::from("Llama-3.2-3B-Instruct.gguf")
+        );
+        assert_eq!(runner.chat_template, ChatTemplate::Llama3);
+        assert_eq!(runner.restart_attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_model_path_tags_requests() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let handle = LlmActorHandle {
+            sender,
+            model_path: None,
+        }
+        .with_model_path(PathBuf::from("coder.gguf"));
+
+        tokio::spawn(async move {
+            let _ = handle
+                .chat_with_params(vec![ChatMessage::user("Hi")], SamplingParams::default())
+                .await;
+        });
+
+        let msg = receiver.recv().await.unwrap();
+        assert_eq!(msg.model_path(), Some(&PathBuf::from("coder.gguf")));
+    }
+
+    #[test]
+    fn test_completion_payload_uses_defaults() {
+        let payload = completion_payload(
+            "Hi".to_string(),
+            &["<|im_end|>"],
+            &SamplingParams::default(),
+            MAX_RESPONSE_TOKENS,
+            true,
+        );
+
+        assert_eq!(payload["stream"], true);
+        assert_eq!(payload["n_predict"], MAX_RESPONSE_TOKENS);
+        assert_eq!(payload["top_k"], DEFAULT_TOP_K);
+        assert_eq!(payload["stop"], serde_json::json!(["<|im_end|>"]));
+        assert!((payload["temperature"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_completion_payload_applies_session_params() {
+        let params = SamplingParams {
+            temperature: Some(0.2),
+            top_k: Some(10),
+            top_p: Some(0.5),
+            min_p: Some(0.0),
+            repeat_penalty: Some(1.0),
+            n_predict: Some(MAX_RESPONSE_TOKENS * 4),
+            stop: vec!["###".to_string()],
+        };
+
+        let payload = completion_payload(
+            "Hi".to_string(),
+            &["<|im_end|>"],
+            &params,
+            MAX_RESPONSE_TOKENS,
+            false,
+        );
+
+        assert_eq!(payload["top_k"], 10);
+        assert_eq!(payload["min_p"], 0.0);
+        assert_eq!(payload["repeat_penalty"], 1.0);
+        assert_eq!(payload["n_predict"], MAX_RESPONSE_TOKENS);
+        assert_eq!(payload["stop"], serde_json::json!(["<|im_end|>", "###"]));
+        assert!((payload["top_p"].as_f64().unwrap() - 0.5).abs() < 1e-6);
+        assert!((payload["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,9 @@
+use crate::chat_template::ChatRole;
+use crate::chunking::ChunkingOptions;
+use crate::library_search::{LibrarySearchFilter, LibrarySearchHit};
+use crate::models::{IngestJob, RerankOptions, SamplingParams};
 use serde::Serialize;
+use std::path::PathBuf;
 use tauri::Window;
 use tokio::sync::oneshot;
 
@@ -29,32 +34,78 @@ impl From<tokio::time::error::Elapsed> for ActorError {
 // Re-export AppError for convenience
 pub use crate::error::AppError;
 
+/// A single role-tagged turn of a conversation sent to an LLM backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::User, content)
+    }
+
+    #[allow(dead_code)]
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::Assistant, content)
+    }
+
+    pub fn context(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::Context, content)
+    }
+
+    /// Builds the `[system, user]` pair used by single-prompt callers.
+    pub fn single_turn(prompt: String, system_prompt: Option<String>) -> Vec<Self> {
+        let mut messages = Vec::with_capacity(2);
+        if let Some(system) = system_prompt.filter(|s| !s.is_empty()) {
+            messages.push(Self::system(system));
+        }
+        messages.push(Self::user(prompt));
+        messages
+    }
+}
+
 /// Messages that can be sent to the `LlmActor`.
 #[derive(Debug)]
 pub enum LlmMessage {
     /// A request to generate a complete text response.
     #[allow(dead_code)]
     Generate {
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        /// The model file to answer with; `None` keeps the currently loaded model.
+        model_path: Option<PathBuf>,
         /// A channel to send the final `String` result back.
         responder: oneshot::Sender<Result<String, AppError>>,
     },
     /// A request to generate a complete text response with specific parameters.
     GenerateWithParams {
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        /// The model file to answer with; `None` keeps the currently loaded model.
+        model_path: Option<PathBuf>,
         /// A channel to send the final `String` result back.
         responder: oneshot::Sender<Result<String, AppError>>,
     },
     /// A request to generate a streaming text response.
     #[allow(dead_code)]
     StreamGenerate {
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        /// The model file to answer with; `None` keeps the currently loaded model.
+        model_path: Option<PathBuf>,
         /// A channel to send each generated token (chunk) back.
         chunk_sender: tokio::sync::mpsc::Sender<Result<String, AppError>>,
         /// A channel to signal completion or an error for the whole stream.
@@ -62,9 +113,10 @@ pub enum LlmMessage {
     },
     /// A request to generate a streaming text response with specific parameters.
     StreamGenerateWithParams {
-        prompt: String,
-        system_prompt: Option<String>,
-        temperature: Option<f32>,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        /// The model file to answer with; `None` keeps the currently loaded model.
+        model_path: Option<PathBuf>,
         /// A channel to send each generated token (chunk) back.
         chunk_sender: tokio::sync::mpsc::Sender<Result<String, AppError>>,
         /// A channel to signal completion or an error for the whole stream.
@@ -72,14 +124,28 @@ pub enum LlmMessage {
     },
 }
 
+impl LlmMessage {
+    /// The model file requested by this message, if any.
+    pub fn model_path(&self) -> Option<&PathBuf> {
+        match self {
+            LlmMessage::Generate { model_path, .. }
+            | LlmMessage::GenerateWithParams { model_path, .. }
+            | LlmMessage::StreamGenerate { model_path, .. }
+            | LlmMessage::StreamGenerateWithParams { model_path, .. } => model_path.as_ref(),
+        }
+    }
+}
+
 /// Messages that can be sent to the `RagActor`.
 #[derive(Debug)]
 pub enum RagMessage {
     /// A request to ingest content into the knowledge base.
     Ingest {
         content: String,
-        /// Optional metadata (as a JSON string) to associate with the content.
-        metadata: Option<String>,
+        /// The library file the content comes from, stored with every chunk for filtering.
+        file_id: Option<String>,
+        /// How the content is split into chunks.
+        chunking: ChunkingOptions,
         /// A channel to send the result (e.g., a confirmation message) back.
         responder: oneshot::Sender<Result<String, AppError>>,
     },
@@ -88,11 +154,25 @@ pub enum RagMessage {
         query: String,
         /// A list of file IDs to filter the search.
         file_ids: Vec<String>,
-        /// The maximum number of results to return.
-        limit: usize,
+        /// Result count and the weights of the vector and lexical rankings.
+        options: SearchOptions,
         /// A channel to send the search results back.
         responder: oneshot::Sender<Result<Vec<SearchResult>, AppError>>,
     },
+    /// A request to rescore search results with the cross-encoder reranker.
+    Rerank {
+        query: String,
+        results: Vec<SearchResult>,
+        options: RerankOptions,
+        /// A channel to send the kept results back, best first.
+        responder: oneshot::Sender<Result<Vec<SearchResult>, AppError>>,
+    },
+    /// A request to fetch a single chunk by id, e.g. to show a cited source.
+    GetChunk {
+        chunk_id: String,
+        /// Receives `None` if the chunk no longer exists (file removed or reindexed).
+        responder: oneshot::Sender<Result<Option<SearchResult>, AppError>>,
+    },
     /// A request to delete all vectors associated with a specific file.
     DeleteForFile {
         file_id: String,
@@ -102,11 +182,71 @@ pub enum RagMessage {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
+    /// Id of the chunk in the vector store.
+    pub id: String,
     pub content: String,
-    pub metadata: Option<String>,
+    pub metadata: ChunkMetadata,
+    /// Fused relevance score; higher is better.
     pub score: f32,
 }
 
+/// Where a chunk comes from, stored as typed columns next to its vector.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ChunkMetadata {
+    /// The library file the chunk was ingested from, if any.
+    pub file_id: Option<String>,
+    /// Position of the chunk within its document, from 0.
+    pub chunk_index: u32,
+    /// Character range of the extracted text the chunk covers (end exclusive).
+    /// Unknown for chunks ingested before offsets were recorded.
+    pub char_start: Option<u64>,
+    pub char_end: Option<u64>,
+    /// 1-based page number, for paginated documents such as PDFs.
+    pub page: Option<u32>,
+    /// The markdown headings enclosing the chunk, e.g. `Install > Linux`.
+    pub heading_path: Option<String>,
+    /// Unix timestamp of the ingestion.
+    pub ingested_at: i64,
+    /// The embedding model that produced the chunk's vector.
+    pub embedding_model: String,
+}
+
+/// Settings for a hybrid (vector + lexical) search.
+///
+/// Both rankings are merged with reciprocal-rank fusion; a weight of 0 disables that side,
+/// so `lexical_weight: 0.0` gives a pure vector search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    /// The maximum number of results to return.
+    pub limit: usize,
+    pub vector_weight: f32,
+    pub lexical_weight: f32,
+    /// Extra terms for the lexical side, e.g. keywords extracted from the query.
+    pub keywords: Vec<String>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            limit: 3,
+            vector_weight: 1.0,
+            lexical_weight: 1.0,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+/// What the supervisor should generate a response to.
+#[derive(Debug, Clone)]
+pub enum UserTurn {
+    /// A new user message appended to the active branch.
+    New { content: String },
+    /// A replacement for an earlier user message, added as a sibling branch.
+    Edit { message_id: i64, content: String },
+    /// A new answer to a user message (or to the user message an assistant message replied to).
+    Regenerate { message_id: i64 },
+}
+
 /// Messages that can be sent to the `SupervisorActor`.
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
@@ -114,23 +254,47 @@ pub enum SupervisorMessage {
     /// A request to process a user's message from a specific session.
     ProcessUserMessage {
         session_id: String,
-        content: String,
+        turn: UserTurn,
         /// The Tauri window, used for emitting events back to the frontend.
         window: Option<Window>,
         /// A channel to send the final assistant response back.
         responder: oneshot::Sender<Result<String, AppError>>,
     },
+    /// A request to stop the generation currently running for a session.
+    CancelGeneration {
+        session_id: String,
+        /// Receives `true` if a generation was running and has been signalled to stop.
+        responder: oneshot::Sender<bool>,
+    },
     /// A request to ingest content, which the supervisor will delegate to the RAG actor.
     IngestContent {
         content: String,
-        metadata: Option<String>,
+        file_id: Option<String>,
+        chunking: ChunkingOptions,
         responder: oneshot::Sender<Result<String, AppError>>,
     },
-    /// A request to reindex a specific file (delete vectors + ingest).
-    ReindexFile {
-        file_id: String,
-        content: String,
-        responder: oneshot::Sender<Result<String, AppError>>,
+    /// A request to queue library files for (re)indexing by the background worker.
+    EnqueueIngest {
+        file_ids: Vec<String>,
+        /// Reindex the files even if they look unchanged.
+        force: bool,
+        /// Window to report `ingest-progress` events to.
+        window: Option<Window>,
+        responder: oneshot::Sender<Result<Vec<IngestJob>, AppError>>,
+    },
+    /// A request to report ingestion progress to a window, e.g. for jobs resumed at startup.
+    WatchIngestJobs { window: Window },
+    /// A request to search the whole library, or part of it, outside of any chat.
+    SearchLibrary {
+        query: String,
+        filter: LibrarySearchFilter,
+        limit: usize,
+        responder: oneshot::Sender<Result<Vec<LibrarySearchHit>, AppError>>,
+    },
+    /// A request for the text of a cited chunk, delegated to the RAG actor.
+    GetChunk {
+        chunk_id: String,
+        responder: oneshot::Sender<Result<Option<SearchResult>, AppError>>,
     },
     /// A command to shut down the supervisor and its child actors.
     #[allow(dead_code)]
@@ -1,5 +1,8 @@
+pub mod chunk_schema;
+pub mod ingest_worker;
 pub mod llm;
 pub mod messages;
 pub mod rag;
+pub mod remote_llm;
 pub mod supervisor;
 pub mod traits;
@@ -1,24 +1,37 @@
-use crate::actors::messages::{ActorError, AppError, RagMessage, SearchResult};
+use crate::actors::chunk_schema::{
+    self, build_batch, file_filter, migrate_legacy_batch, read_chunks, sql_string, table_name,
+    NewChunk, CONTENT_COLUMN, LEGACY_TABLE_NAME, RESULT_COLUMNS, TABLE_NAME,
+};
+use crate::actors::messages::{
+    ActorError, AppError, ChunkMetadata, RagMessage, SearchOptions, SearchResult,
+};
 use crate::actors::traits::RagActor;
+use crate::chunking::ChunkingOptions;
+use crate::database;
+use crate::embedding_config::{active_model, EmbeddingModelKind};
 use crate::fs_manager::PortablePathManager;
-use arrow::array::{
-    Array, FixedSizeListBuilder, Float32Array, Float32Builder, RecordBatch, RecordBatchIterator,
-    StringArray, StringBuilder,
-};
-use arrow::datatypes::{DataType, Field, Schema};
+use crate::hybrid_search::{reciprocal_rank_fusion, tokenize};
+use crate::models::RerankOptions;
+use arrow::array::{RecordBatch, RecordBatchIterator};
 use async_trait::async_trait;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use fastembed::{RerankInitOptions, RerankerModel, TextEmbedding, TextRerank};
 use futures::TryStreamExt;
 use lancedb::{
+    arrow::SendableRecordBatchStream,
     connect,
-    query::{ExecutableQuery, QueryBase},
-    Connection,
+    index::{
+        scalar::{FtsIndexBuilder, FullTextSearchQuery},
+        Index,
+    },
+    query::{ExecutableQuery, QueryBase, Select},
+    table::{OptimizeAction, OptimizeOptions},
+    Connection, Table,
 };
 use lru::LruCache;
 use sqlx::sqlite::SqlitePool;
+use std::collections::{HashMap, HashSet};
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
-use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info, warn};
 
@@ -57,11 +70,17 @@ impl RagActorHandle {
 
 #[async_trait]
 impl RagActor for RagActorHandle {
-    async fn ingest(&self, content: String, metadata: Option<String>) -> Result<String, AppError> {
+    async fn ingest_with_options(
+        &self,
+        content: String,
+        file_id: Option<String>,
+        chunking: ChunkingOptions,
+    ) -> Result<String, AppError> {
         let (send, recv) = oneshot::channel();
         let msg = RagMessage::Ingest {
             content,
-            metadata,
+            file_id,
+            chunking,
             responder: send,
         };
         self.sender
@@ -79,12 +98,54 @@ impl RagActor for RagActorHandle {
         &self,
         query: String,
         file_ids: Vec<String>,
+        options: SearchOptions,
     ) -> Result<Vec<SearchResult>, AppError> {
         let (send, recv) = oneshot::channel();
         let msg = RagMessage::Search {
             query,
             file_ids,
-            limit: 3, // Default limit
+            options,
+            responder: send,
+        };
+        self.sender
+            .send(msg)
+            .await
+            .map_err(|_| AppError::Actor(ActorError::Internal("RAG Actor closed".to_string())))?;
+        Ok(recv.await.map_err(|_| {
+            AppError::Actor(ActorError::Internal(
+                "RAG Actor failed to respond".to_string(),
+            ))
+        })??)
+    }
+
+    async fn rerank(
+        &self,
+        query: String,
+        results: Vec<SearchResult>,
+        options: RerankOptions,
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let (send, recv) = oneshot::channel();
+        let msg = RagMessage::Rerank {
+            query,
+            results,
+            options,
+            responder: send,
+        };
+        self.sender
+            .send(msg)
+            .await
+            .map_err(|_| AppError::Actor(ActorError::Internal("RAG Actor closed".to_string())))?;
+        Ok(recv.await.map_err(|_| {
+            AppError::Actor(ActorError::Internal(
+                "RAG Actor failed to respond".to_string(),
+            ))
+        })??)
+    }
+
+    async fn get_chunk(&self, chunk_id: String) -> Result<Option<SearchResult>, AppError> {
+        let (send, recv) = oneshot::channel();
+        let msg = RagMessage::GetChunk {
+            chunk_id,
             responder: send,
         };
         self.sender
@@ -119,12 +180,15 @@ impl RagActor for RagActorHandle {
 // --- Actor Runner (Internal Logic) ---
 struct RagActorRunner {
     receiver: mpsc::Receiver<RagMessage>,
+    /// The configured model, whose table is searched.
+    embedding: EmbeddingModelKind,
     embedding_model: Option<TextEmbedding>,
     embedding_cache: LruCache<String, Vec<f32>>,
+    /// Cross-encoder, loaded (and downloaded if needed) on the first rerank request.
+    reranker: Option<TextRerank>,
     db_connection: Option<Connection>,
     table_name: String,
     db_path_override: Option<PathBuf>,
-    #[allow(dead_code)]
     pool: Option<SqlitePool>,
 }
 
@@ -135,17 +199,23 @@ impl RagActorRunner {
     // We use new_unchecked because Option::expect is not const-stable in Rust 1.80.0 (requires 1.83.0)
     const CACHE_SIZE: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(1000) };
 
+    /// Candidates each ranking contributes to fusion, per requested result.
+    const CANDIDATES_PER_RESULT: usize = 4;
+
     fn new(
         receiver: mpsc::Receiver<RagMessage>,
         db_path_override: Option<PathBuf>,
         pool: Option<SqlitePool>,
     ) -> Self {
+        let embedding = active_model();
         Self {
             receiver,
+            embedding,
             embedding_model: None,
             embedding_cache: LruCache::new(Self::CACHE_SIZE),
+            reranker: None,
             db_connection: None,
-            table_name: "knowledge_base".to_string(),
+            table_name: table_name(embedding),
             db_path_override,
             pool,
         }
@@ -173,14 +243,12 @@ impl RagActorRunner {
     /// Initializes the FastEmbed embedding model.
     /// Returns an error if the model cannot be loaded.
     fn initialize_embedding_model(&mut self) -> Result<(), ActorError> {
-        let embeddings_dir = PortablePathManager::models_dir().join("embeddings");
-        let mut options = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
-        options.show_download_progress = false;
-        options.cache_dir = embeddings_dir;
-
-        match TextEmbedding::try_new(options) {
+        match TextEmbedding::try_new(self.embedding.init_options()) {
             Ok(model) => {
-                info!("Embedding model loaded successfully");
+                info!(
+                    "Embedding model {} loaded successfully",
+                    self.embedding.name()
+                );
                 self.embedding_model = Some(model);
                 Ok(())
             }
@@ -191,6 +259,23 @@ impl RagActorRunner {
         }
     }
 
+    /// Loads the BGE cross-encoder used to rerank search results.
+    fn initialize_reranker(&mut self) -> Result<&TextRerank, ActorError> {
+        if self.reranker.is_none() {
+            let options = RerankInitOptions::new(RerankerModel::BGERerankerBase)
+                .with_cache_dir(PortablePathManager::models_dir().join("rerankers"))
+                .with_show_download_progress(false);
+            let reranker = TextRerank::try_new(options).map_err(|e| {
+                ActorError::RagError(format!("Failed to load reranker model: {}", e))
+            })?;
+            info!("Reranker model loaded successfully");
+            self.reranker = Some(reranker);
+        }
+        self.reranker
+            .as_ref()
+            .ok_or(ActorError::RagError("Reranker not loaded".to_string()))
+    }
+
     /// Initializes the LanceDB vector database connection.
     /// Returns an error if the database path is invalid or connection fails.
     async fn initialize_lancedb(&mut self) -> Result<(), ActorError> {
@@ -221,6 +306,8 @@ impl RagActorRunner {
         match connect(db_path_str).execute().await {
             Ok(conn) => {
                 info!("Connected to LanceDB at {:?}", db_path);
+                Self::migrate_legacy_table(&conn).await?;
+                self.reembed_previous_tables(&conn).await?;
                 self.db_connection = Some(conn);
                 Ok(())
             }
@@ -231,14 +318,226 @@ impl RagActorRunner {
         }
     }
 
+    /// Moves the chunks of the legacy table, whose only metadata was a `file:<id>` string,
+    /// into the typed table, then drops the legacy table.
+    ///
+    /// The legacy table is dropped last, so an interrupted migration is simply redone from
+    /// scratch on the next start.
+    async fn migrate_legacy_table(conn: &Connection) -> Result<(), ActorError> {
+        let table_names = conn
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to list tables: {}", e)))?;
+        if !table_names.iter().any(|name| name == LEGACY_TABLE_NAME) {
+            return Ok(());
+        }
+
+        info!("Migrating knowledge base to the typed chunk schema");
+        if table_names.iter().any(|name| name == TABLE_NAME) {
+            conn.drop_table(TABLE_NAME).await.map_err(|e| {
+                ActorError::RagError(format!("Failed to drop partial migration: {}", e))
+            })?;
+        }
+
+        let legacy = conn
+            .open_table(LEGACY_TABLE_NAME)
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to open table: {}", e)))?;
+        let mut batches = legacy
+            .query()
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to read legacy table: {}", e)))?;
+
+        let ingested_at = chrono::Utc::now().timestamp();
+        let mut next_index = HashMap::new();
+        let mut table: Option<Table> = None;
+        let mut migrated = 0;
+        while let Some(batch) = batches
+            .try_next()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Stream error: {}", e)))?
+        {
+            let batch = migrate_legacy_batch(&batch, ingested_at, &mut next_index)
+                .map_err(ActorError::RagError)?;
+            migrated += batch.num_rows();
+            Self::append_batch(conn, &mut table, TABLE_NAME, batch).await?;
+        }
+
+        conn.drop_table(LEGACY_TABLE_NAME)
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to drop legacy table: {}", e)))?;
+        info!("Migrated {} chunks to the typed chunk schema", migrated);
+        Ok(())
+    }
+
+    /// Re-embeds the chunks of the tables of previously configured models into the table of
+    /// the current one, then drops the previous tables.
+    ///
+    /// Every previous table is read, so chunks are not lost when one of them is the partial
+    /// output of an interrupted switch. Re-embeddings keep the chunk ids, so the tables are read
+    /// largest first and chunks whose id was already copied are skipped.
+    ///
+    /// As with the legacy migration, the previous tables are dropped last: while they exist, the
+    /// current table is incomplete and is rebuilt from scratch, so an interrupted re-embedding
+    /// is redone on the next start and vectors of different models are never mixed.
+    async fn reembed_previous_tables(&self, conn: &Connection) -> Result<(), ActorError> {
+        let table_names = conn
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to list tables: {}", e)))?;
+        let previous: Vec<EmbeddingModelKind> = EmbeddingModelKind::ALL
+            .into_iter()
+            .filter(|&model| model != self.embedding && table_names.contains(&table_name(model)))
+            .collect();
+        if previous.is_empty() {
+            return Ok(());
+        }
+        let names: Vec<&str> = previous.iter().map(|model| model.name()).collect();
+        let model = self.embedding_model.as_ref().ok_or_else(|| {
+            ActorError::RagError(format!(
+                "Cannot re-embed the {} chunks: embedding model not loaded",
+                names.join(", ")
+            ))
+        })?;
+
+        info!(
+            "Re-embedding knowledge base from {} to {}",
+            names.join(", "),
+            self.embedding.name()
+        );
+        if table_names.contains(&self.table_name) {
+            conn.drop_table(&self.table_name).await.map_err(|e| {
+                ActorError::RagError(format!("Failed to drop partial re-embedding: {}", e))
+            })?;
+        }
+
+        let mut sources = Vec::with_capacity(previous.len());
+        for source in &previous {
+            let source_table = conn
+                .open_table(table_name(*source))
+                .execute()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Failed to open table: {}", e)))?;
+            let rows = source_table
+                .count_rows(None)
+                .await
+                .map_err(|e| ActorError::RagError(format!("Failed to count rows: {}", e)))?;
+            sources.push((rows, source_table));
+        }
+        sources.sort_by_key(|(rows, _)| std::cmp::Reverse(*rows));
+
+        let mut table: Option<Table> = None;
+        let mut copied = HashSet::new();
+        for (_, source_table) in &sources {
+            let mut batches = source_table
+                .query()
+                .execute()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Failed to read table: {}", e)))?;
+            while let Some(batch) = batches
+                .try_next()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Stream error: {}", e)))?
+            {
+                let mut chunks = read_chunks(&batch).map_err(ActorError::RagError)?;
+                chunks.retain(|chunk| copied.insert(chunk.id.clone()));
+                if chunks.is_empty() {
+                    continue;
+                }
+                let texts: Vec<String> = chunks
+                    .iter()
+                    .map(|chunk| format!("{}{}", self.embedding.passage_prefix(), chunk.content))
+                    .collect();
+                let vectors = model
+                    .embed(texts, None)
+                    .map_err(|e| ActorError::RagError(format!("Embedding failed: {}", e)))?;
+                let chunks: Vec<NewChunk> = chunks
+                    .into_iter()
+                    .zip(vectors)
+                    .map(|(chunk, vector)| NewChunk {
+                        id: chunk.id,
+                        content: chunk.content,
+                        metadata: ChunkMetadata {
+                            embedding_model: self.embedding.name().to_string(),
+                            ..chunk.metadata
+                        },
+                        vector,
+                    })
+                    .collect();
+                let batch = build_batch(&chunks, self.embedding).map_err(ActorError::RagError)?;
+                Self::append_batch(conn, &mut table, &self.table_name, batch).await?;
+            }
+        }
+
+        for model in &previous {
+            conn.drop_table(table_name(*model)).await.map_err(|e| {
+                ActorError::RagError(format!("Failed to drop previous table: {}", e))
+            })?;
+        }
+        // The files stay indexed, so reindexing does not embed them once more
+        if let Some(pool) = &self.pool {
+            for model in &previous {
+                if let Err(e) = database::replace_library_embedding_model(
+                    pool,
+                    model.name(),
+                    self.embedding.name(),
+                )
+                .await
+                {
+                    warn!("Failed to record the re-embedded files: {}", e);
+                }
+            }
+        }
+        info!(
+            "Re-embedded {} chunks with {}",
+            copied.len(),
+            self.embedding.name()
+        );
+        Ok(())
+    }
+
+    /// Adds a batch to `table`, creating the table `name` from the first batch.
+    async fn append_batch(
+        conn: &Connection,
+        table: &mut Option<Table>,
+        name: &str,
+        batch: RecordBatch,
+    ) -> Result<(), ActorError> {
+        let schema = batch.schema();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        match table {
+            Some(table) => table
+                .add(Box::new(reader))
+                .execute()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Failed to add data: {}", e)))?,
+            None => {
+                *table = Some(
+                    conn.create_table(name, Box::new(reader))
+                        .execute()
+                        .await
+                        .map_err(|e| {
+                            ActorError::RagError(format!("Failed to create table: {}", e))
+                        })?,
+                )
+            }
+        }
+        Ok(())
+    }
+
     async fn handle_message(&mut self, msg: RagMessage) {
         match msg {
             RagMessage::Ingest {
                 content,
-                metadata,
+                file_id,
+                chunking,
                 responder,
             } => {
-                let result = self.ingest_document(content, metadata).await;
+                let result = self.ingest_document(content, file_id, chunking).await;
                 if responder.send(result.map_err(AppError::from)).is_err() {
                     warn!("Failed to send ingest response (channel closed)");
                 }
@@ -246,14 +545,34 @@ impl RagActorRunner {
             RagMessage::Search {
                 query,
                 file_ids,
-                limit,
+                options,
                 responder,
             } => {
-                let result = self.search_documents(query, file_ids, limit).await;
+                let result = self.search_documents(query, file_ids, options).await;
                 if responder.send(result.map_err(AppError::from)).is_err() {
                     warn!("Failed to send search response (channel closed)");
                 }
             }
+            RagMessage::Rerank {
+                query,
+                results,
+                options,
+                responder,
+            } => {
+                let result = self.rerank_results(&query, results, &options);
+                if responder.send(result.map_err(AppError::from)).is_err() {
+                    warn!("Failed to send rerank response (channel closed)");
+                }
+            }
+            RagMessage::GetChunk {
+                chunk_id,
+                responder,
+            } => {
+                let result = self.get_chunk(&chunk_id).await;
+                if responder.send(result.map_err(AppError::from)).is_err() {
+                    warn!("Failed to send get_chunk response (channel closed)");
+                }
+            }
             RagMessage::DeleteForFile { file_id, responder } => {
                 let result = self.delete_document_vectors(file_id).await;
                 if responder.send(result.map_err(AppError::from)).is_err() {
@@ -266,7 +585,8 @@ impl RagActorRunner {
     async fn ingest_document(
         &self,
         content: String,
-        metadata: Option<String>,
+        file_id: Option<String>,
+        chunking: ChunkingOptions,
     ) -> Result<String, ActorError> {
         let model = self.embedding_model.as_ref().ok_or(ActorError::RagError(
             "Embedding model not loaded".to_string(),
@@ -276,49 +596,10 @@ impl RagActorRunner {
             .as_ref()
             .ok_or(ActorError::RagError("DB not connected".to_string()))?;
 
-        // 1. Improved Chunking with overlap
-        // We accumulate lines until we reach a target size (e.g., 512 chars)
-        // and then emit a chunk. We keep an overlap (e.g., 50 chars) from the previous chunk.
-        let target_chunk_size = 512;
-        let overlap_size = 50;
-        let mut chunks: Vec<String> = Vec::new();
-        let mut current_chunk = String::new();
-
-        for line in content.split('\n') {
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-
-            if current_chunk.len() + trimmed.len() > target_chunk_size {
-                // Chunk is full, push it
-                chunks.push(current_chunk.clone());
-
-                // Start new chunk with overlap
-                let start_index = if current_chunk.len() > overlap_size {
-                    current_chunk.len() - overlap_size
-                } else {
-                    0
-                };
-                let overlap = current_chunk[start_index..].to_string();
-                current_chunk = overlap + " " + trimmed;
-            } else {
-                if !current_chunk.is_empty() {
-                    current_chunk.push(' ');
-                }
-                current_chunk.push_str(trimmed);
-            }
-        }
+        // 1. Structure-aware chunking
+        let spans = crate::chunking::split(&content, &chunking);
 
-        // Push the last chunk if not empty
-        if !current_chunk.is_empty() {
-            chunks.push(current_chunk);
-        }
-
-        // Filter out very small chunks that might be noise
-        let chunks: Vec<String> = chunks.into_iter().filter(|s| s.len() > 20).collect();
-
-        if chunks.is_empty() {
+        if spans.is_empty() {
             warn!(
                 "Document ingestion skipped: No valid chunks found (content length: {})",
                 content.len()
@@ -329,63 +610,38 @@ impl RagActorRunner {
         }
 
         // 2. Generate Embeddings
+        let texts: Vec<String> = spans
+            .iter()
+            .map(|span| format!("{}{}", self.embedding.passage_prefix(), span.text))
+            .collect();
         let embeddings = model
-            .embed(chunks.clone(), None)
+            .embed(texts, None)
             .map_err(|e| ActorError::RagError(format!("Embedding failed: {}", e)))?;
 
         // 3. Construct Arrow RecordBatch
-        let total_chunks = chunks.len();
-        let embedding_dim = 384; // AllMiniLML6V2 dimension
-
-        // Define Schema with metadata column
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("id", DataType::Utf8, false),
-            Field::new("content", DataType::Utf8, false),
-            Field::new("metadata", DataType::Utf8, true),
-            Field::new(
-                "vector",
-                DataType::FixedSizeList(
-                    Arc::new(Field::new("item", DataType::Float32, true)),
-                    embedding_dim as i32,
-                ),
-                true,
-            ),
-        ]));
-
-        // Build Arrays
-        let mut id_builder = StringBuilder::with_capacity(total_chunks, total_chunks * 36);
-        let mut content_builder = StringBuilder::with_capacity(total_chunks, total_chunks * 256);
-        let mut metadata_builder = StringBuilder::with_capacity(total_chunks, total_chunks * 64);
-
-        // Vector Builder: List of Floats
-        let values_builder = Float32Builder::with_capacity(total_chunks * embedding_dim);
-        let mut vector_builder = FixedSizeListBuilder::new(values_builder, embedding_dim as i32);
-
-        // Use metadata (e.g., "session:uuid" format)
-        let metadata_value = metadata.as_deref().unwrap_or("");
-
-        for (i, chunk) in chunks.iter().enumerate() {
-            id_builder.append_value(uuid::Uuid::new_v4().to_string());
-            content_builder.append_value(chunk);
-            metadata_builder.append_value(metadata_value);
-
-            // Append vector
-            if let Some(embedding) = embeddings.get(i) {
-                vector_builder.values().append_slice(embedding);
-                vector_builder.append(true);
-            }
-        }
-
-        let batch = RecordBatch::try_new(
-            schema.clone(),
-            vec![
-                Arc::new(id_builder.finish()),
-                Arc::new(content_builder.finish()),
-                Arc::new(metadata_builder.finish()),
-                Arc::new(vector_builder.finish()),
-            ],
-        )
-        .map_err(|e| ActorError::RagError(format!("Failed to create RecordBatch: {}", e)))?;
+        let total_chunks = spans.len();
+        let ingested_at = chrono::Utc::now().timestamp();
+        let chunks: Vec<NewChunk> = spans
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(index, (span, vector))| NewChunk {
+                id: uuid::Uuid::new_v4().to_string(),
+                content: span.text,
+                metadata: ChunkMetadata {
+                    file_id: file_id.clone(),
+                    chunk_index: index as u32,
+                    char_start: Some(span.char_start),
+                    char_end: Some(span.char_end),
+                    page: span.page,
+                    heading_path: span.heading_path,
+                    ingested_at,
+                    embedding_model: self.embedding.name().to_string(),
+                },
+                vector,
+            })
+            .collect();
+        let batch = build_batch(&chunks, self.embedding).map_err(ActorError::RagError)?;
 
         // 4. Ingest into LanceDB
         // Open or Create table
@@ -396,9 +652,10 @@ impl RagActorRunner {
             .map_err(|e| ActorError::RagError(format!("Failed to list tables: {}", e)))?
             .contains(&self.table_name);
 
-        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let reader =
+            RecordBatchIterator::new(vec![Ok(batch)], chunk_schema::schema(self.embedding));
 
-        if table_exists {
+        let table = if table_exists {
             let table = conn
                 .open_table(&self.table_name)
                 .execute()
@@ -410,22 +667,68 @@ impl RagActorRunner {
                 .execute()
                 .await
                 .map_err(|e| ActorError::RagError(format!("Failed to add data: {}", e)))?;
+            table
         } else {
             conn.create_table(&self.table_name, Box::new(reader))
                 .execute()
                 .await
-                .map_err(|e| ActorError::RagError(format!("Failed to create table: {}", e)))?;
-        }
+                .map_err(|e| ActorError::RagError(format!("Failed to create table: {}", e)))?
+        };
+        Self::update_text_index(&table).await?;
 
         info!("Ingested {} chunks into LanceDB", total_chunks);
         Ok(format!("Ingested {} chunks", total_chunks))
     }
 
+    /// Whether `table` has the full-text index on the chunk text.
+    async fn has_text_index(table: &Table) -> Result<bool, ActorError> {
+        let indices = table
+            .list_indices()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to list indices: {}", e)))?;
+        Ok(indices
+            .iter()
+            .any(|index| index.columns == [CONTENT_COLUMN]))
+    }
+
+    async fn create_text_index(table: &Table) -> Result<(), ActorError> {
+        info!("Building the full-text index of the knowledge base");
+        table
+            .create_index(
+                &[CONTENT_COLUMN],
+                Index::FTS(FtsIndexBuilder::default().with_position(false)),
+            )
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to build full-text index: {}", e)))
+    }
+
+    /// Adds the chunks written since the last update to the full-text index, creating it if
+    /// the table has none. Lexical search only sees indexed chunks.
+    async fn update_text_index(table: &Table) -> Result<(), ActorError> {
+        if !Self::has_text_index(table).await? {
+            return Self::create_text_index(table).await;
+        }
+        table
+            .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+            .await
+            .map_err(|e| {
+                ActorError::RagError(format!("Failed to update full-text index: {}", e))
+            })?;
+        Ok(())
+    }
+
+    /// Ranks chunks by embedding similarity and by BM25 over their text, then fuses both
+    /// rankings so exact identifiers are found even when their embedding is a poor match.
+    ///
+    /// Both sides are served by an index (nearest neighbours and LanceDB's full-text index)
+    /// and return at most `limit * CANDIDATES_PER_RESULT` chunks, so a search never scans the
+    /// whole library.
     async fn search_documents(
         &mut self,
         query: String,
         file_ids: Vec<String>,
-        limit: usize,
+        options: SearchOptions,
     ) -> Result<Vec<SearchResult>, ActorError> {
         let model = self.embedding_model.as_ref().ok_or(ActorError::RagError(
             "Embedding model not loaded".to_string(),
@@ -436,23 +739,30 @@ impl RagActorRunner {
             .ok_or(ActorError::RagError("DB not connected".to_string()))?;
 
         // 1. Embed Query (with cache)
-        let query_vec = match self.embedding_cache.get(&query) {
-            Some(embedding) => {
-                info!("Cache hit for query: '{}'", query);
-                embedding.clone()
-            }
-            None => {
-                info!("Cache miss for query: '{}'", query);
-                let query_embedding = model
-                    .embed(vec![query.clone()], None)
-                    .map_err(|e| ActorError::RagError(format!("Embedding failed: {}", e)))?;
-                let embedding = query_embedding
-                    .first()
-                    .ok_or(ActorError::RagError("No embedding generated".to_string()))?
-                    .clone();
-                self.embedding_cache.put(query.clone(), embedding.clone());
-                embedding
-            }
+        let query_vec = if options.vector_weight > 0.0 {
+            Some(match self.embedding_cache.get(&query) {
+                Some(embedding) => {
+                    info!("Cache hit for query: '{}'", query);
+                    embedding.clone()
+                }
+                None => {
+                    info!("Cache miss for query: '{}'", query);
+                    let query_embedding = model
+                        .embed(
+                            vec![format!("{}{}", self.embedding.query_prefix(), query)],
+                            None,
+                        )
+                        .map_err(|e| ActorError::RagError(format!("Embedding failed: {}", e)))?;
+                    let embedding = query_embedding
+                        .first()
+                        .ok_or(ActorError::RagError("No embedding generated".to_string()))?
+                        .clone();
+                    self.embedding_cache.put(query.clone(), embedding.clone());
+                    embedding
+                }
+            })
+        } else {
+            None
         };
 
         // 2. Search in LanceDB
@@ -473,92 +783,166 @@ impl RagActorRunner {
             .await
             .map_err(|e| ActorError::RagError(format!("Failed to open table: {}", e)))?;
 
-        let mut query = table.query();
-
         // Apply file filter if provided
-        if !file_ids.is_empty() {
-            // Construct OR filter: metadata = 'file:ID1' OR metadata = 'file:ID2'
-            // Note: metadata field stores "file:{id}"
-            let filter = file_ids
-                .iter()
-                .map(|id| format!("metadata = 'file:{}'", id))
-                .collect::<Vec<_>>()
-                .join(" OR ");
-            query = query.only_if(filter);
-        }
-
-        let mut results = query
-            .limit(limit)
-            .nearest_to(query_vec)
-            .map_err(|e| ActorError::RagError(format!("Query setup failed: {}", e)))?
-            .execute()
-            .await
-            .map_err(|e| ActorError::RagError(format!("Search failed: {}", e)))?;
+        let filter = file_filter(&file_ids);
+
+        // Each side proposes more candidates than requested, so chunks ranked fairly well
+        // by both can overtake a chunk only one side likes
+        let candidates = options.limit * Self::CANDIDATES_PER_RESULT;
+        let mut chunks: HashMap<String, SearchResult> = HashMap::new();
+
+        // 3. Vector ranking
+        let mut vector_ranking = Vec::new();
+        if let Some(query_vec) = query_vec {
+            let mut lance_query = table.query();
+            if let Some(filter) = &filter {
+                lance_query = lance_query.only_if(filter);
+            }
+            let results = lance_query
+                .limit(candidates)
+                .nearest_to(query_vec)
+                .map_err(|e| ActorError::RagError(format!("Query setup failed: {}", e)))?
+                .execute()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Search failed: {}", e)))?;
 
-        // 3. Extract Content
-        let mut documents = Vec::new();
+            for result in Self::collect_chunks(results).await? {
+                vector_ranking.push(result.id.clone());
+                chunks.entry(result.id.clone()).or_insert(result);
+            }
+        }
+
+        // 4. Lexical ranking (BM25 from the full-text index)
+        let mut terms = tokenize(&query);
+        for keyword in &options.keywords {
+            terms.extend(tokenize(keyword));
+        }
+        let mut lexical_ranking = Vec::new();
+        if options.lexical_weight > 0.0 && !terms.is_empty() {
+            // Tables written before the index existed get it on their first search
+            if !Self::has_text_index(&table).await? {
+                Self::create_text_index(&table).await?;
+            }
+            let text_query = FullTextSearchQuery::new(terms.join(" "))
+                .columns(Some(vec![CONTENT_COLUMN.to_string()]));
+            let mut lance_query = table
+                .query()
+                .full_text_search(text_query)
+                .select(Select::columns(&RESULT_COLUMNS))
+                .limit(candidates);
+            if let Some(filter) = &filter {
+                lance_query = lance_query.only_if(filter);
+            }
+            let results = lance_query
+                .execute()
+                .await
+                .map_err(|e| ActorError::RagError(format!("Search failed: {}", e)))?;
+
+            // Results come best first
+            for result in Self::collect_chunks(results).await? {
+                lexical_ranking.push(result.id.clone());
+                chunks.entry(result.id.clone()).or_insert(result);
+            }
+        }
+
+        // 5. Fuse both rankings
+        let fused = reciprocal_rank_fusion(&[
+            (vector_ranking.as_slice(), options.vector_weight),
+            (lexical_ranking.as_slice(), options.lexical_weight),
+        ]);
+        info!(
+            "Hybrid search: {} vector and {} lexical candidates, {} fused",
+            vector_ranking.len(),
+            lexical_ranking.len(),
+            fused.len()
+        );
+
+        Ok(fused
+            .into_iter()
+            .take(options.limit)
+            .filter_map(|(id, score)| {
+                chunks
+                    .remove(&id)
+                    .map(|result| SearchResult { score, ..result })
+            })
+            .collect())
+    }
+
+    /// Reads the chunks of a query result stream.
+    async fn collect_chunks(
+        mut results: SendableRecordBatchStream,
+    ) -> Result<Vec<SearchResult>, ActorError> {
+        let mut chunks = Vec::new();
 
         while let Some(batch) = results
             .try_next()
             .await
             .map_err(|e| ActorError::RagError(format!("Stream error: {}", e)))?
         {
-            let content_col = batch.column_by_name("content").ok_or(ActorError::RagError(
-                "Column 'content' not found".to_string(),
-            ))?;
-            let metadata_col = batch
-                .column_by_name("metadata")
-                .ok_or(ActorError::RagError(
-                    "Column 'metadata' not found".to_string(),
-                ))?;
-            let distance_col = batch
-                .column_by_name("_distance")
-                .ok_or(ActorError::RagError(
-                    "Column '_distance' not found".to_string(),
-                ))?;
-
-            let content_array =
-                content_col
-                    .as_any()
-                    .downcast_ref::<StringArray>()
-                    .ok_or(ActorError::RagError(
-                        "Failed to downcast content column".to_string(),
-                    ))?;
-            let metadata_array =
-                metadata_col
-                    .as_any()
-                    .downcast_ref::<StringArray>()
-                    .ok_or(ActorError::RagError(
-                        "Failed to downcast metadata column".to_string(),
-                    ))?;
-            let distance_array = distance_col.as_any().downcast_ref::<Float32Array>().ok_or(
-                ActorError::RagError("Failed to downcast distance column".to_string()),
-            )?;
-
-            for i in 0..content_array.len() {
-                if !content_array.is_null(i) {
-                    let text = content_array.value(i).to_string();
-                    let meta = if metadata_array.is_null(i) {
-                        None
-                    } else {
-                        Some(metadata_array.value(i).to_string())
-                    };
-                    let score = if distance_array.is_null(i) {
-                        0.0
-                    } else {
-                        distance_array.value(i)
-                    };
-
-                    documents.push(SearchResult {
-                        content: text,
-                        metadata: meta,
-                        score,
-                    });
-                }
+            chunks.extend(read_chunks(&batch).map_err(ActorError::RagError)?);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Scores each result against the query with the cross-encoder.
+    fn rerank_results(
+        &mut self,
+        query: &str,
+        results: Vec<SearchResult>,
+        options: &RerankOptions,
+    ) -> Result<Vec<SearchResult>, ActorError> {
+        if results.is_empty() {
+            return Ok(results);
+        }
+        let reranker = self.initialize_reranker()?;
+
+        let documents: Vec<&str> = results.iter().map(|r| r.content.as_str()).collect();
+        let mut logits = vec![f32::NEG_INFINITY; results.len()];
+        for scored in reranker
+            .rerank(query, documents, false, None)
+            .map_err(|e| ActorError::RagError(format!("Reranking failed: {}", e)))?
+        {
+            if let Some(logit) = logits.get_mut(scored.index) {
+                *logit = scored.score;
             }
         }
 
-        Ok(documents)
+        Ok(select_reranked(results, &logits, options))
+    }
+
+    async fn get_chunk(&self, chunk_id: &str) -> Result<Option<SearchResult>, ActorError> {
+        let conn = self
+            .db_connection
+            .as_ref()
+            .ok_or(ActorError::RagError("DB not connected".to_string()))?;
+
+        let table_names = conn
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to list tables: {}", e)))?;
+
+        if !table_names.contains(&self.table_name) {
+            return Ok(None);
+        }
+
+        let table = conn
+            .open_table(&self.table_name)
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Failed to open table: {}", e)))?;
+
+        let results = table
+            .query()
+            .select(Select::columns(&RESULT_COLUMNS))
+            .only_if(format!("id = {}", sql_string(chunk_id)))
+            .limit(1)
+            .execute()
+            .await
+            .map_err(|e| ActorError::RagError(format!("Chunk lookup failed: {}", e)))?;
+
+        Ok(Self::collect_chunks(results).await?.into_iter().next())
     }
 
     async fn delete_document_vectors(&self, file_id: String) -> Result<(), ActorError> {
@@ -583,8 +967,7 @@ impl RagActorRunner {
             .await
             .map_err(|e| ActorError::RagError(format!("Failed to open table: {}", e)))?;
 
-        // Delete where metadata = 'file:{file_id}'
-        let predicate = format!("metadata = 'file:{}'", file_id);
+        let predicate = format!("file_id = {}", sql_string(&file_id));
 
         table
             .delete(&predicate)
@@ -596,6 +979,29 @@ impl RagActorRunner {
     }
 }
 
+/// Keeps the `top_k` best results whose relevance reaches `min_score`, best first.
+///
+/// The cross-encoder outputs logits; they are mapped to a 0-1 relevance with a sigmoid so the
+/// threshold reads the same whatever the model.
+fn select_reranked(
+    results: Vec<SearchResult>,
+    logits: &[f32],
+    options: &RerankOptions,
+) -> Vec<SearchResult> {
+    let mut scored: Vec<SearchResult> = results
+        .into_iter()
+        .zip(logits)
+        .map(|(result, logit)| SearchResult {
+            score: 1.0 / (1.0 + (-logit).exp()),
+            ..result
+        })
+        .filter(|result| result.score >= options.min_score)
+        .collect();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored.truncate(options.top_k);
+    scored
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -626,7 +1032,7 @@ mod tests {
         // Actor should be running - test by attempting a search (should return empty)
         let result = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("test query".to_string(), vec![]),
+            handle.search_with_filters("test query".to_string(), vec![], SearchOptions::default()),
         )
         .await;
 
@@ -649,7 +1055,7 @@ mod tests {
 
         let result = timeout(
             Duration::from_secs(30),
-            handle.ingest(content.to_string(), Some("file:test-doc-1".to_string())),
+            handle.ingest(content.to_string(), Some("test-doc-1".to_string())),
         )
         .await;
 
@@ -681,7 +1087,7 @@ mod tests {
 
         let ingest_result = timeout(
             Duration::from_secs(30),
-            handle.ingest(content.to_string(), Some("file:rust-doc".to_string())),
+            handle.ingest(content.to_string(), Some("rust-doc".to_string())),
         )
         .await
         .expect("Ingest timeout")
@@ -692,7 +1098,11 @@ mod tests {
         // Search for relevant content
         let search_result = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("memory safety in Rust".to_string(), vec![]),
+            handle.search_with_filters(
+                "memory safety in Rust".to_string(),
+                vec![],
+                SearchOptions::default(),
+            ),
         )
         .await
         .expect("Search timeout")
@@ -720,7 +1130,7 @@ mod tests {
 
         timeout(
             Duration::from_secs(30),
-            handle.ingest(doc1.to_string(), Some("file:python-doc".to_string())),
+            handle.ingest(doc1.to_string(), Some("python-doc".to_string())),
         )
         .await
         .expect("Ingest timeout")
@@ -728,7 +1138,7 @@ mod tests {
 
         timeout(
             Duration::from_secs(30),
-            handle.ingest(doc2.to_string(), Some("file:js-doc".to_string())),
+            handle.ingest(doc2.to_string(), Some("js-doc".to_string())),
         )
         .await
         .expect("Ingest timeout")
@@ -740,6 +1150,7 @@ mod tests {
             handle.search_with_filters(
                 "programming language".to_string(),
                 vec!["python-doc".to_string()],
+                SearchOptions::default(),
             ),
         )
         .await
@@ -748,15 +1159,48 @@ mod tests {
 
         // Should only return Python-related content
         for doc in &result {
-            if let Some(meta) = &doc.metadata {
-                assert!(
-                    meta.contains("python"),
-                    "Filtered results should only contain python docs"
-                );
-            }
+            assert_eq!(
+                doc.metadata.file_id.as_deref(),
+                Some("python-doc"),
+                "Filtered results should only contain python docs"
+            );
         }
     }
 
+    #[tokio::test]
+    async fn test_get_chunk_by_id() {
+        let (handle, _temp_dir) = create_test_rag_actor().await;
+
+        let content = "Citations let reviewers open the exact chunk an answer was based on.";
+        timeout(
+            Duration::from_secs(30),
+            handle.ingest(content.to_string(), Some("cited-doc".to_string())),
+        )
+        .await
+        .expect("Ingest timeout")
+        .expect("Ingest failed");
+
+        let found = handle
+            .search_with_filters("citations".to_string(), vec![], SearchOptions::default())
+            .await
+            .expect("Search failed");
+        let cited = &found[0];
+
+        let chunk = handle
+            .get_chunk(cited.id.clone())
+            .await
+            .expect("Lookup failed")
+            .expect("Chunk should exist");
+        assert_eq!(chunk.content, cited.content);
+        assert_eq!(chunk.metadata, cited.metadata);
+
+        let missing = handle
+            .get_chunk("no-such-chunk' OR '1'='1".to_string())
+            .await
+            .expect("Lookup failed");
+        assert!(missing.is_none());
+    }
+
     #[tokio::test]
     async fn test_delete_document_vectors() {
         let (handle, _temp_dir) = create_test_rag_actor().await;
@@ -767,7 +1211,7 @@ mod tests {
         // Ingest document
         timeout(
             Duration::from_secs(30),
-            handle.ingest(content.to_string(), Some(format!("file:{}", file_id))),
+            handle.ingest(content.to_string(), Some(file_id.to_string())),
         )
         .await
         .expect("Ingest timeout")
@@ -776,7 +1220,11 @@ mod tests {
         // Verify it was ingested
         let search_before = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("deleted vector database".to_string(), vec![]),
+            handle.search_with_filters(
+                "deleted vector database".to_string(),
+                vec![],
+                SearchOptions::default(),
+            ),
         )
         .await
         .expect("Search timeout")
@@ -803,6 +1251,7 @@ mod tests {
             handle.search_with_filters(
                 "deleted vector database".to_string(),
                 vec![file_id.to_string()],
+                SearchOptions::default(),
             ),
         )
         .await
@@ -837,7 +1286,7 @@ mod tests {
         let (handle, _temp_dir) = create_test_rag_actor().await;
 
         // Create a large document with newlines to trigger chunking
-        // The chunking algorithm splits on '\n' and chunks at ~512 chars
+        // Paragraphs are packed into chunks of about 170 tokens
         let large_content = (0..50)
             .map(|i| {
                 format!(
@@ -854,7 +1303,7 @@ mod tests {
 
         let result = timeout(
             Duration::from_secs(60),
-            handle.ingest(large_content, Some("file:large-doc".to_string())),
+            handle.ingest(large_content, Some("large-doc".to_string())),
         )
         .await
         .expect("Ingest timeout")
@@ -879,7 +1328,7 @@ mod tests {
         // Search on empty database should return empty results, not error
         let result = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("any query".to_string(), vec![]),
+            handle.search_with_filters("any query".to_string(), vec![], SearchOptions::default()),
         )
         .await
         .expect("Search timeout")
@@ -894,15 +1343,15 @@ mod tests {
 
         // Ingest documents about different topics
         let docs = vec![
-            ("file:cooking", "Recipes for delicious pasta dishes. How to cook Italian food. Ingredients include tomatoes and basil."),
-            ("file:programming", "Software development best practices. Code review guidelines. Writing clean and maintainable code."),
-            ("file:gardening", "Growing vegetables in your backyard. Planting tomatoes and herbs. Organic gardening tips."),
+            ("cooking", "Recipes for delicious pasta dishes. How to cook Italian food. Ingredients include tomatoes and basil."),
+            ("programming", "Software development best practices. Code review guidelines. Writing clean and maintainable code."),
+            ("gardening", "Growing vegetables in your backyard. Planting tomatoes and herbs. Organic gardening tips."),
         ];
 
-        for (meta, content) in docs {
+        for (file_id, content) in docs {
             timeout(
                 Duration::from_secs(30),
-                handle.ingest(content.to_string(), Some(meta.to_string())),
+                handle.ingest(content.to_string(), Some(file_id.to_string())),
             )
             .await
             .expect("Ingest timeout")
@@ -912,7 +1361,11 @@ mod tests {
         // Search for programming-related content
         let result = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("software code development".to_string(), vec![]),
+            handle.search_with_filters(
+                "software code development".to_string(),
+                vec![],
+                SearchOptions::default(),
+            ),
         )
         .await
         .expect("Search timeout")
@@ -933,6 +1386,88 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_hybrid_search_finds_exact_identifiers() {
+        let (handle, _temp_dir) = create_test_rag_actor().await;
+
+        let docs = vec![
+            ("errors", "Troubleshooting guide. When the sync stops, check the logs for ERR_QUOTA_4471 and raise the storage quota."),
+            ("network", "Network problems usually come from a proxy or a firewall blocking the synchronisation service."),
+            ("install", "Installing the desktop client requires administrator rights and a restart of the computer."),
+        ];
+        for (file_id, content) in docs {
+            timeout(
+                Duration::from_secs(30),
+                handle.ingest(content.to_string(), Some(file_id.to_string())),
+            )
+            .await
+            .expect("Ingest timeout")
+            .expect("Ingest failed");
+        }
+
+        // Lexical ranking only: the identifier must match exactly
+        let lexical_only = SearchOptions {
+            vector_weight: 0.0,
+            ..SearchOptions::default()
+        };
+        let result = timeout(
+            Duration::from_secs(10),
+            handle.search_with_filters("ERR_QUOTA_4471".to_string(), vec![], lexical_only),
+        )
+        .await
+        .expect("Search timeout")
+        .expect("Search failed");
+
+        assert_eq!(result.len(), 1, "Only one chunk contains the identifier");
+        assert_eq!(result[0].metadata.file_id.as_deref(), Some("errors"));
+
+        // Default hybrid search ranks it first too
+        let result = timeout(
+            Duration::from_secs(10),
+            handle.search_with_filters(
+                "what does ERR_QUOTA_4471 mean".to_string(),
+                vec![],
+                SearchOptions::default(),
+            ),
+        )
+        .await
+        .expect("Search timeout")
+        .expect("Search failed");
+
+        assert_eq!(result[0].metadata.file_id.as_deref(), Some("errors"));
+        assert!(result.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn test_select_reranked_keeps_best_above_threshold() {
+        let result = |content: &str| SearchResult {
+            id: content.to_string(),
+            content: content.to_string(),
+            metadata: ChunkMetadata::default(),
+            score: 0.5,
+        };
+        let results = vec![result("a"), result("b"), result("c"), result("d")];
+        let options = RerankOptions {
+            candidates: 4,
+            top_k: 2,
+            min_score: 0.5,
+        };
+
+        // Logits: a is irrelevant, c is the best match
+        let kept = select_reranked(results, &[-3.0, 1.0, 4.0, 0.5], &options);
+
+        let contents: Vec<&str> = kept.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, vec!["c", "b"]);
+        assert!(kept.iter().all(|r| r.score > 0.5 && r.score < 1.0));
+
+        let strict = RerankOptions {
+            min_score: 0.99,
+            ..options
+        };
+        let kept = select_reranked(vec![result("a")], &[1.0], &strict);
+        assert!(kept.is_empty());
+    }
+
     #[tokio::test]
     async fn test_multiple_ingests_same_file() {
         let (handle, _temp_dir) = create_test_rag_actor().await;
@@ -944,7 +1479,7 @@ mod tests {
             let content = format!("Version {} of the document content.", i);
             timeout(
                 Duration::from_secs(30),
-                handle.ingest(content, Some(format!("file:{}", file_id))),
+                handle.ingest(content, Some(file_id.to_string())),
             )
             .await
             .expect("Ingest timeout")
@@ -954,7 +1489,11 @@ mod tests {
         // Search should return results (all versions are stored)
         let result = timeout(
             Duration::from_secs(10),
-            handle.search_with_filters("Version document".to_string(), vec![file_id.to_string()]),
+            handle.search_with_filters(
+                "Version document".to_string(),
+                vec![file_id.to_string()],
+                SearchOptions::default(),
+            ),
         )
         .await
         .expect("Search timeout")
@@ -0,0 +1,528 @@
+use crate::actors::messages::{ActorError, AppError, ChatMessage};
+use crate::actors::traits::LlmActor;
+use crate::models::{SamplingParams, MAX_RESPONSE_TOKENS};
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::Client;
+use std::env;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+/// Prefix of `ModelConfig.model_id` values that select the remote backend
+/// (e.g. `remote:qwen2.5-coder-7b-instruct`).
+pub const REMOTE_MODEL_PREFIX: &str = "remote:";
+
+// --- Constants ---
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(120);
+const STREAM_CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection settings for an OpenAI-compatible inference server.
+#[derive(Debug, Clone)]
+pub struct RemoteLlmConfig {
+    /// Base URL of the server, with or without the trailing `/v1` (e.g. `http://gpu-box:8000`).
+    pub base_url: String,
+    /// Optional bearer token sent in the `Authorization` header.
+    pub api_key: Option<String>,
+    /// Model name sent when the session does not specify one.
+    pub default_model: Option<String>,
+}
+
+impl RemoteLlmConfig {
+    /// Reads the remote backend configuration from the environment.
+    ///
+    /// Returns `None` when `REMOTE_LLM_URL` is not set, which disables the remote backend.
+    pub fn from_env() -> Option<Self> {
+        let base_url = env::var("REMOTE_LLM_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())?;
+
+        Some(Self {
+            base_url,
+            api_key: env::var("REMOTE_LLM_API_KEY")
+                .ok()
+                .filter(|key| !key.is_empty()),
+            default_model: env::var("REMOTE_LLM_MODEL")
+                .ok()
+                .filter(|model| !model.is_empty()),
+        })
+    }
+
+    /// Returns the full `/v1/chat/completions` endpoint for this server.
+    fn chat_completions_url(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        if base.ends_with("/v1") {
+            format!("{base}/chat/completions")
+        } else {
+            format!("{base}/v1/chat/completions")
+        }
+    }
+}
+
+/// Returns the remote model name if `model_id` selects the remote backend.
+///
+/// An empty name (`"remote:"`) means "use the server's default model".
+pub fn remote_model_name(model_id: &str) -> Option<&str> {
+    model_id.strip_prefix(REMOTE_MODEL_PREFIX)
+}
+
+/// An `LlmActor` that talks to any OpenAI-compatible `/v1/chat/completions` endpoint
+/// (vLLM, LM Studio, llama.cpp on another machine, ...).
+///
+/// Unlike `LlmActorHandle` there is no local process to supervise, so requests are sent
+/// directly from the caller's task. The handle is cheap to clone.
+#[derive(Clone)]
+pub struct RemoteLlmActor {
+    config: RemoteLlmConfig,
+    model: Option<String>,
+    client: Client,
+}
+
+impl RemoteLlmActor {
+    /// Creates a new remote backend from the given configuration.
+    pub fn new(config: RemoteLlmConfig) -> Self {
+        let model = config.default_model.clone();
+        Self {
+            config,
+            model,
+            client: Client::new(),
+        }
+    }
+
+    /// Creates a remote backend from environment variables, if configured.
+    pub fn from_env() -> Option<Self> {
+        RemoteLlmConfig::from_env().map(Self::new)
+    }
+
+    /// Returns a copy of this backend targeting a specific model name.
+    ///
+    /// An empty name keeps the configured default model.
+    pub fn with_model(&self, model: &str) -> Self {
+        let mut actor = self.clone();
+        if !model.is_empty() {
+            actor.model = Some(model.to_string());
+        }
+        actor
+    }
+
+    /// Builds the chat completions body. Only the sampling parameters defined by the OpenAI
+    /// API are sent; `top_k`, `min_p` and `repeat_penalty` are local-backend settings.
+    fn build_payload(
+        &self,
+        messages: &[ChatMessage],
+        params: &SamplingParams,
+        stream: bool,
+    ) -> serde_json::Value {
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|m| serde_json::json!({ "role": m.role.as_str(), "content": m.content }))
+            .collect();
+
+        let mut payload = serde_json::json!({
+            "messages": messages,
+            "stream": stream,
+            "max_tokens": params.n_predict.unwrap_or(MAX_RESPONSE_TOKENS),
+            "top_p": params.top_p.unwrap_or(0.95),
+        });
+
+        if let Some(model) = &self.model {
+            payload["model"] = serde_json::Value::String(model.clone());
+        }
+
+        if !params.stop.is_empty() {
+            payload["stop"] = serde_json::json!(params.stop);
+        }
+
+        // Add temperature if provided, otherwise use 0.7 as default
+        let temp = params.temperature.unwrap_or(0.7);
+        payload["temperature"] = serde_json::Value::Number(
+            serde_json::Number::from_f64(temp as f64).unwrap_or_else(|| {
+                warn!("Invalid temperature value: {}. Using default 0.7.", temp);
+                // NOTE: from_f64(0.7) is guaranteed to succeed since 0.7 is a valid float
+                serde_json::Number::from_f64(0.7).expect("0.7 is a valid float constant")
+            }),
+        );
+
+        payload
+    }
+
+    fn build_request(
+        &self,
+        payload: &serde_json::Value,
+    ) -> Result<reqwest::RequestBuilder, AppError> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &self.config.api_key {
+            let auth_value = format!("Bearer {token}");
+            headers.insert(
+                AUTHORIZATION,
+                auth_value.parse().map_err(|e| {
+                    AppError::Actor(ActorError::Internal(format!(
+                        "Failed to parse remote API key: {e}"
+                    )))
+                })?,
+            );
+        }
+
+        Ok(self
+            .client
+            .post(self.config.chat_completions_url())
+            .headers(headers)
+            .json(payload))
+    }
+
+    async fn send(&self, payload: &serde_json::Value) -> Result<reqwest::Response, AppError> {
+        let request_future = self.build_request(payload)?.send();
+        let res = timeout(COMPLETION_TIMEOUT, request_future).await??;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(AppError::Actor(ActorError::LlmError(format!(
+                "Remote completion request failed with status {status}: {body}"
+            ))));
+        }
+
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl LlmActor for RemoteLlmActor {
+    async fn chat_with_params(
+        &self,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+    ) -> Result<String, AppError> {
+        info!(
+            "Remote LLM generating with model {:?} at {}",
+            self.model, self.config.base_url
+        );
+
+        let payload = self.build_payload(&messages, &params, false);
+        let res = self.send(&payload).await?;
+
+        let json: serde_json::Value = res
+            .json()
+            .await
+            .map_err(|e| AppError::Actor(ActorError::Internal(e.to_string())))?;
+
+        Ok(json["choices"]
+            .get(0)
+            .and_then(|c| c.get("message"))
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .unwrap_or("")
+            .to_string())
+    }
+
+    async fn stream_chat_with_params(
+        &self,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        chunk_sender: mpsc::Sender<Result<String, AppError>>,
+    ) -> Result<(), AppError> {
+        info!(
+            "Remote LLM streaming with model {:?} at {}",
+            self.model, self.config.base_url
+        );
+
+        let payload = self.build_payload(&messages, &params, true);
+        let res = self.send(&payload).await?;
+
+        let mut stream = res.bytes_stream();
+        let mut parser = SseParser::default();
+
+        loop {
+            match timeout(STREAM_CHUNK_TIMEOUT, stream.next()).await {
+                Ok(Some(chunk_result)) => {
+                    let chunk = chunk_result.map_err(|e| {
+                        AppError::Actor(ActorError::Internal(format!("Stream chunk error: {e}")))
+                    })?;
+
+                    for event in parser.feed(&chunk) {
+                        match event {
+                            SseEvent::Done => return Ok(()),
+                            SseEvent::Content(content) => {
+                                if chunk_sender.send(Ok(content)).await.is_err() {
+                                    warn!("Stream receiver dropped, stopping stream");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    }
+                }
+                Ok(None) => break, // End of stream
+                Err(e) => {
+                    return Err(AppError::Actor(ActorError::Internal(format!(
+                        "Stream chunk timeout: {}",
+                        e
+                    ))))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An event decoded from an OpenAI-style server-sent event stream.
+#[derive(Debug, PartialEq)]
+enum SseEvent {
+    /// A piece of generated text (`choices[0].delta.content`).
+    Content(String),
+    /// The `[DONE]` sentinel marking the end of the stream.
+    Done,
+}
+
+/// Incremental parser for `data:` lines of a server-sent event stream.
+///
+/// Network chunks do not respect line boundaries, so incomplete lines are buffered
+/// until the rest arrives.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim_start();
+
+            if data == "[DONE]" {
+                events.push(SseEvent::Done);
+                continue;
+            }
+
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+                let content = json["choices"]
+                    .get(0)
+                    .and_then(|c| c.get("delta"))
+                    .and_then(|d| d.get("content"))
+                    .and_then(|c| c.as_str());
+
+                if let Some(content) = content.filter(|c| !c.is_empty()) {
+                    events.push(SseEvent::Content(content.to_string()));
+                }
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_actor(server: &MockServer, api_key: Option<&str>) -> RemoteLlmActor {
+        RemoteLlmActor::new(RemoteLlmConfig {
+            base_url: server.uri(),
+            api_key: api_key.map(str::to_string),
+            default_model: Some("default-remote".to_string()),
+        })
+    }
+
+    #[test]
+    fn test_remote_model_name() {
+        assert_eq!(remote_model_name("remote:qwen"), Some("qwen"));
+        assert_eq!(remote_model_name("remote:"), Some(""));
+        assert_eq!(remote_model_name("default-model.gguf"), None);
+    }
+
+    #[test]
+    fn test_chat_completions_url() {
+        let mut config = RemoteLlmConfig {
+            base_url: "http://box:8000".to_string(),
+            api_key: None,
+            default_model: None,
+        };
+        assert_eq!(
+            config.chat_completions_url(),
+            "http://box:8000/v1/chat/completions"
+        );
+
+        config.base_url = "http://box:8000/v1/".to_string();
+        assert_eq!(
+            config.chat_completions_url(),
+            "http://box:8000/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn test_sse_parser_handles_split_lines() {
+        let mut parser = SseParser::default();
+
+        let events = parser.feed(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel");
+        assert!(events.is_empty());
+
+        let events = parser.feed(b"lo\"}}]}\n\ndata: [DONE]\n\n");
+        assert_eq!(
+            events,
+            vec![SseEvent::Content("Hello".to_string()), SseEvent::Done]
+        );
+    }
+
+    #[test]
+    fn test_sse_parser_ignores_comments_and_role_deltas() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(
+            b": keep-alive\r\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\r\n",
+        );
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_generate_sends_bearer_token_and_model() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer secret-token"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "coder",
+                "stream": false
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Hi there" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let actor = test_actor(&server, Some("secret-token")).with_model("coder");
+        let result = actor
+            .generate_with_params("Hello".to_string(), Some("Be brief".to_string()), None)
+            .await
+            .expect("Remote generation failed");
+
+        assert_eq!(result, "Hi there");
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_assistant_turns_and_context_as_system() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "user", "content": "Hi" },
+                    { "role": "assistant", "content": "Hello!" },
+                    { "role": "system", "content": "Context: docs" },
+                    { "role": "user", "content": "And now?" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Sure" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let actor = test_actor(&server, None);
+        let messages = vec![
+            ChatMessage::user("Hi"),
+            ChatMessage::assistant("Hello!"),
+            ChatMessage::context("Context: docs"),
+            ChatMessage::user("And now?"),
+        ];
+        let result = actor
+            .chat_with_params(messages, SamplingParams::default())
+            .await
+            .expect("Remote chat failed");
+
+        assert_eq!(result, "Sure");
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_session_sampling_params() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "max_tokens": 256,
+                "top_p": 0.5,
+                "stop": ["###"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Done" } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let actor = test_actor(&server, None);
+        let params = SamplingParams {
+            top_p: Some(0.5),
+            n_predict: Some(256),
+            stop: vec!["###".to_string()],
+            ..SamplingParams::default()
+        };
+        let result = actor
+            .chat_with_params(vec![ChatMessage::user("Hi")], params)
+            .await
+            .expect("Remote chat failed");
+
+        assert_eq!(result, "Done");
+    }
+
+    #[tokio::test]
+    async fn test_generate_reports_http_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("bad key"))
+            .mount(&server)
+            .await;
+
+        let actor = test_actor(&server, Some("wrong"));
+        let result = actor
+            .generate_with_params("Hello".to_string(), None, None)
+            .await;
+
+        let err = result.expect_err("401 should be an error").to_string();
+        assert!(err.contains("401"), "Unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_stream_parses_sse_chunks() {
+        let server = MockServer::start().await;
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\" world\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let actor = test_actor(&server, None);
+        let (tx, mut rx) = mpsc::channel(32);
+        actor
+            .stream_generate_with_params("Hello".to_string(), None, Some(0.2), tx)
+            .await
+            .expect("Remote stream failed");
+
+        let mut collected = String::new();
+        while let Some(Ok(token)) = rx.recv().await {
+            collected.push_str(&token);
+        }
+        assert_eq!(collected, "Hello world");
+    }
+}
@@ -1,16 +1,31 @@
+use crate::actors::ingest_worker::{report_progress, IngestWorker, ProgressWindow};
 use crate::actors::llm::LlmActorHandle;
-use crate::actors::messages::{AppError, SupervisorMessage};
+use crate::actors::messages::{
+    AppError, ChatMessage, SearchOptions, SearchResult, SupervisorMessage, UserTurn,
+};
 use crate::actors::rag::RagActorHandle;
+use crate::actors::remote_llm::{remote_model_name, RemoteLlmActor};
 use crate::actors::traits::{LlmActor, RagActor};
 use crate::brain::BrainAnalyzer;
+use crate::chat_template::ChatRole;
+use crate::chunking::ChunkingOptions;
+use crate::context_budget::ContextBudget;
 use crate::database;
 use crate::fs_manager::PortablePathManager;
+use crate::launch_profile::LaunchProfile;
+use crate::library_search::{group_by_file, LibrarySearchFilter, LibrarySearchHit};
+use crate::model_store::resolve_model_path;
+use crate::models::{IngestJob, LibraryFile, Message, MessageSource, RetrievalScope};
+use serde::Serialize;
 use sqlx::sqlite::SqlitePool;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use tauri::{Emitter, Window};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
 use tokio::time::{timeout, Duration};
 use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
 
 /// A handle to the `SupervisorActor`.
 ///
@@ -72,12 +87,33 @@ impl SupervisorHandle {
         Self { sender }
     }
 
+    /// Creates a new `SupervisorActor` with injected actors and a remote LLM backend,
+    /// used for sessions whose `model_id` starts with `remote:`.
+    #[allow(dead_code)]
+    pub fn new_with_actors_and_remote<L, R>(
+        llm: Arc<L>,
+        remote_llm: RemoteLlmActor,
+        rag: Arc<R>,
+        db_pool: Option<SqlitePool>,
+    ) -> Self
+    where
+        L: LlmActor + Send + Sync + 'static,
+        R: RagActor + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(32);
+        let brain_analyzer = Arc::new(BrainAnalyzer::new());
+        let runner = SupervisorRunner::new(receiver, llm, rag, brain_analyzer, db_pool)
+            .with_remote_llm(Some(remote_llm));
+        tokio::spawn(async move { runner.run().await });
+        Self { sender }
+    }
+
     /// Processes a user message from a specific session.
     ///
     /// This is the core logic loop:
     /// 1. Stores the user message in the database.
     /// 2. Performs a RAG search for relevant context.
-    /// 3. Builds a final prompt with history and context.
+    /// 3. Builds a multi-turn conversation from the history, context and new message.
     /// 4. Streams the LLM response back to the frontend via events.
     /// 5. Stores the assistant's final response in the database.
     ///
@@ -96,11 +132,55 @@ impl SupervisorHandle {
         session_id: String,
         content: String,
         window: Option<&Window>,
+    ) -> Result<String, AppError> {
+        self.process_turn(session_id, UserTurn::New { content }, window)
+            .await
+    }
+
+    /// Generates a new answer to a user message, or to the user message that the given
+    /// assistant message replied to. The previous answer is kept as a sibling branch.
+    #[instrument(skip(self, window))]
+    pub async fn regenerate_message(
+        &self,
+        session_id: String,
+        message_id: i64,
+        window: Option<&Window>,
+    ) -> Result<String, AppError> {
+        self.process_turn(session_id, UserTurn::Regenerate { message_id }, window)
+            .await
+    }
+
+    /// Replaces an earlier user message with `content` and answers it. The original
+    /// message and everything after it are kept as a sibling branch.
+    #[instrument(skip(self, window))]
+    pub async fn edit_message(
+        &self,
+        session_id: String,
+        message_id: i64,
+        content: String,
+        window: Option<&Window>,
+    ) -> Result<String, AppError> {
+        self.process_turn(
+            session_id,
+            UserTurn::Edit {
+                message_id,
+                content,
+            },
+            window,
+        )
+        .await
+    }
+
+    async fn process_turn(
+        &self,
+        session_id: String,
+        turn: UserTurn,
+        window: Option<&Window>,
     ) -> Result<String, AppError> {
         let (send, recv) = oneshot::channel();
         let msg = SupervisorMessage::ProcessUserMessage {
             session_id,
-            content,
+            turn,
             window: window.cloned(),
             responder: send,
         };
@@ -125,7 +205,8 @@ impl SupervisorHandle {
     /// # Arguments
     ///
     /// * `content` - The text content to ingest.
-    /// * `metadata` - Optional JSON string metadata to associate with the content.
+    /// * `file_id` - The library file the content comes from, if any.
+    /// * `chunking` - How the content is split into chunks.
     ///
     /// # Returns
     ///
@@ -134,12 +215,14 @@ impl SupervisorHandle {
     pub async fn ingest_content(
         &self,
         content: String,
-        metadata: Option<String>,
+        file_id: Option<String>,
+        chunking: ChunkingOptions,
     ) -> Result<String, AppError> {
         let (send, recv) = oneshot::channel();
         let msg = SupervisorMessage::IngestContent {
             content,
-            metadata,
+            file_id,
+            chunking,
             responder: send,
         };
         self.sender.send(msg).await.map_err(|e| {
@@ -152,25 +235,119 @@ impl SupervisorHandle {
             })?
     }
 
-    pub async fn reindex_file(&self, file_id: String, content: String) -> Result<String, AppError> {
+    /// Fetches the text and provenance of a chunk cited by an assistant message.
+    ///
+    /// Returns `None` if the chunk no longer exists (its file was removed or reindexed).
+    #[instrument(skip(self))]
+    pub async fn get_chunk(&self, chunk_id: String) -> Result<Option<SearchResult>, AppError> {
         let (send, recv) = oneshot::channel();
-        let msg = SupervisorMessage::ReindexFile {
-            file_id,
-            content,
+        let msg = SupervisorMessage::GetChunk {
+            chunk_id,
             responder: send,
         };
         self.sender.send(msg).await.map_err(|e| {
             AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
         })?;
-        timeout(Duration::from_secs(120), recv) // Reindex can be longer
-            .await?
+        timeout(Duration::from_secs(10), recv).await?.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?
+    }
+
+    /// Searches the library files matching `filter` for chunks relevant to `query`, and
+    /// returns them grouped by file.
+    #[instrument(skip(self))]
+    pub async fn search_library(
+        &self,
+        query: String,
+        filter: LibrarySearchFilter,
+        limit: usize,
+    ) -> Result<Vec<LibrarySearchHit>, AppError> {
+        let (send, recv) = oneshot::channel();
+        let msg = SupervisorMessage::SearchLibrary {
+            query,
+            filter,
+            limit,
+            responder: send,
+        };
+        self.sender.send(msg).await.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?;
+        timeout(Duration::from_secs(30), recv).await?.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?
+    }
+
+    /// Stops the generation currently running for a session.
+    ///
+    /// The partial response is kept and stored as an interrupted assistant message.
+    /// Returns `false` if no generation was running for that session.
+    #[instrument(skip(self))]
+    pub async fn cancel_generation(&self, session_id: String) -> Result<bool, AppError> {
+        let (send, recv) = oneshot::channel();
+        let msg = SupervisorMessage::CancelGeneration {
+            session_id,
+            responder: send,
+        };
+        self.sender.send(msg).await.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?;
+        timeout(Duration::from_secs(5), recv).await?.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })
+    }
+
+    /// Queues library files for (re)indexing and returns their jobs right away.
+    ///
+    /// The background worker extracts and embeds them one at a time, reporting every status
+    /// change with an `ingest-progress` event to `window`. Files unchanged since they were
+    /// last indexed are skipped unless `force` is set.
+    #[instrument(skip(self, window))]
+    pub async fn enqueue_ingest(
+        &self,
+        file_ids: Vec<String>,
+        force: bool,
+        window: Option<&Window>,
+    ) -> Result<Vec<IngestJob>, AppError> {
+        let (send, recv) = oneshot::channel();
+        let msg = SupervisorMessage::EnqueueIngest {
+            file_ids,
+            force,
+            window: window.cloned(),
+            responder: send,
+        };
+        self.sender.send(msg).await.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?;
+        timeout(Duration::from_secs(30), recv).await?.map_err(|e| {
+            AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
+        })?
+    }
+
+    /// Reports ingestion progress to `window` from now on, including for the jobs resumed
+    /// from a previous run.
+    pub async fn watch_ingest_jobs(&self, window: Window) -> Result<(), AppError> {
+        self.sender
+            .send(SupervisorMessage::WatchIngestJobs { window })
+            .await
             .map_err(|e| {
                 AppError::Actor(crate::actors::messages::ActorError::Internal(e.to_string()))
-            })?
+            })
     }
 }
 
 // --- Actor Runner ---
+
+/// Cancellation handle for the generation running in a session.
+struct ActiveGeneration {
+    id: Uuid,
+    cancel: oneshot::Sender<()>,
+}
+
+type ActiveGenerations = Arc<Mutex<HashMap<String, ActiveGeneration>>>;
+
+/// The launch profile fitted to the last local model used, with that model's path.
+type FittedProfile = Arc<Mutex<Option<(std::path::PathBuf, LaunchProfile)>>>;
+
 pub struct SupervisorRunner<L, R>
 where
     L: LlmActor + Send + Sync + 'static,
@@ -178,9 +355,19 @@ where
 {
     receiver: mpsc::Receiver<SupervisorMessage>,
     llm_actor: Arc<L>,
+    /// Optional OpenAI-compatible backend, selected per session via `ModelConfig.model_id`.
+    remote_llm_actor: Option<RemoteLlmActor>,
     rag_actor: Arc<R>,
     brain_analyzer: Arc<BrainAnalyzer>,
     db_pool: Option<SqlitePool>,
+    /// Generations in flight, keyed by session ID.
+    active_generations: ActiveGenerations,
+    /// Sizes the context budget of local sessions; refitted when the model changes.
+    fitted_profile: FittedProfile,
+    /// Wakes the ingestion worker when jobs are queued.
+    ingest_wake: Arc<Notify>,
+    /// Where the ingestion worker reports progress.
+    ingest_window: ProgressWindow,
 }
 
 fn new_production_runner(
@@ -191,9 +378,14 @@ fn new_production_runner(
     SupervisorRunner {
         receiver,
         llm_actor: Arc::new(LlmActorHandle::new(model_path)),
+        remote_llm_actor: RemoteLlmActor::from_env(),
         rag_actor: Arc::new(RagActorHandle::new_with_options(None, db_pool.clone())),
         brain_analyzer: Arc::new(BrainAnalyzer::new()),
         db_pool,
+        active_generations: Arc::default(),
+        fitted_profile: Arc::default(),
+        ingest_wake: Arc::default(),
+        ingest_window: Arc::default(),
     }
 }
 
@@ -213,39 +405,87 @@ where
         Self {
             receiver,
             llm_actor,
+            remote_llm_actor: None,
             rag_actor,
             brain_analyzer,
             db_pool,
+            active_generations: Arc::default(),
+            fitted_profile: Arc::default(),
+            ingest_wake: Arc::default(),
+            ingest_window: Arc::default(),
         }
     }
 
+    /// Sets the remote LLM backend used for `remote:` sessions.
+    pub fn with_remote_llm(mut self, remote_llm_actor: Option<RemoteLlmActor>) -> Self {
+        self.remote_llm_actor = remote_llm_actor;
+        self
+    }
+
     async fn run(mut self) {
         info!("Supervisor started");
+        let ingest_worker = self.db_pool.clone().map(|pool| {
+            let worker = IngestWorker::new(
+                self.rag_actor.clone(),
+                pool,
+                self.ingest_wake.clone(),
+                self.ingest_window.clone(),
+            );
+            tokio::spawn(worker.run())
+        });
+
         while let Some(msg) = self.receiver.recv().await {
             // Clone resources to move into the spawned task
             let llm_actor = self.llm_actor.clone();
+            let remote_llm_actor = self.remote_llm_actor.clone();
             let rag_actor = self.rag_actor.clone();
             let brain_analyzer = self.brain_analyzer.clone();
             let db_pool = self.db_pool.clone();
+            let active_generations = self.active_generations.clone();
+            let fitted_profile = self.fitted_profile.clone();
 
             match msg {
                 SupervisorMessage::ProcessUserMessage {
                     session_id,
-                    content,
+                    turn,
                     window,
                     responder,
                 } => {
+                    let (cancel_tx, cancel_rx) = oneshot::channel();
+                    let generation_id = Uuid::new_v4();
+                    active_generations.lock().await.insert(
+                        session_id.clone(),
+                        ActiveGeneration {
+                            id: generation_id,
+                            cancel: cancel_tx,
+                        },
+                    );
+
                     tokio::spawn(async move {
                         let result = Self::handle_user_message(
                             llm_actor,
+                            remote_llm_actor,
                             rag_actor,
                             brain_analyzer,
                             db_pool,
-                            session_id,
-                            content,
+                            fitted_profile,
+                            session_id.clone(),
+                            turn,
                             window,
+                            cancel_rx,
                         )
                         .await;
+
+                        // Only clear our own entry; a newer message may have replaced it
+                        let mut active = active_generations.lock().await;
+                        if active
+                            .get(&session_id)
+                            .is_some_and(|generation| generation.id == generation_id)
+                        {
+                            active.remove(&session_id);
+                        }
+                        drop(active);
+
                         if let Err(e) = &result {
                             error!("Error processing user message: {:?}", e);
                         }
@@ -254,14 +494,33 @@ where
                         }
                     });
                 }
+                SupervisorMessage::CancelGeneration {
+                    session_id,
+                    responder,
+                } => {
+                    let cancelled = match active_generations.lock().await.remove(&session_id) {
+                        Some(generation) => generation.cancel.send(()).is_ok(),
+                        None => false,
+                    };
+                    info!(
+                        "Cancel requested for session {} (running: {})",
+                        session_id, cancelled
+                    );
+                    if responder.send(cancelled).is_err() {
+                        warn!("Failed to send cancel_generation response (channel closed)");
+                    }
+                }
                 SupervisorMessage::IngestContent {
                     content,
-                    metadata,
+                    file_id,
+                    chunking,
                     responder,
                 } => {
                     tokio::spawn(async move {
                         info!("Supervisor orchestrating ingestion...");
-                        let result = rag_actor.ingest(content, metadata).await;
+                        let result = rag_actor
+                            .ingest_with_options(content, file_id, chunking)
+                            .await;
                         if let Err(e) = &result {
                             error!("Error ingesting content: {:?}", e);
                         }
@@ -270,32 +529,57 @@ where
                         }
                     });
                 }
-                SupervisorMessage::ReindexFile {
-                    file_id,
-                    content,
+                SupervisorMessage::EnqueueIngest {
+                    file_ids,
+                    force,
+                    window,
                     responder,
                 } => {
+                    if window.is_some() {
+                        if let Ok(mut current) = self.ingest_window.lock() {
+                            *current = window;
+                        }
+                    }
+                    let wake = self.ingest_wake.clone();
+                    let progress_window = self.ingest_window.clone();
                     tokio::spawn(async move {
-                        info!(
-                            "Supervisor orchestrating reindexing for file {}...",
-                            file_id
-                        );
-                        // 1. Delete existing vectors
-                        if let Err(e) = rag_actor.delete_for_file(file_id.clone()).await {
-                            error!("Error deleting vectors for file {}: {:?}", file_id, e);
-                            // Continue anyway to try ingest? Or fail?
-                            // If delete fails, we might duplicate data, but better than nothing.
+                        let result =
+                            Self::enqueue_ingest(db_pool, &progress_window, file_ids, force).await;
+                        if result.is_ok() {
+                            wake.notify_one();
                         }
-
-                        // 2. Ingest new content
-                        let metadata = Some(format!("file:{}", file_id));
-                        let result = rag_actor.ingest(content, metadata).await;
-
-                        if let Err(e) = &result {
-                            error!("Error reindexing file {}: {:?}", file_id, e);
+                        if responder.send(result).is_err() {
+                            warn!("Failed to send enqueue_ingest response (channel closed)");
+                        }
+                    });
+                }
+                SupervisorMessage::WatchIngestJobs { window } => {
+                    if let Ok(mut current) = self.ingest_window.lock() {
+                        *current = Some(window);
+                    }
+                }
+                SupervisorMessage::SearchLibrary {
+                    query,
+                    filter,
+                    limit,
+                    responder,
+                } => {
+                    tokio::spawn(async move {
+                        let result =
+                            Self::search_library(db_pool, rag_actor, query, filter, limit).await;
+                        if responder.send(result).is_err() {
+                            warn!("Failed to send search_library response (channel closed)");
                         }
+                    });
+                }
+                SupervisorMessage::GetChunk {
+                    chunk_id,
+                    responder,
+                } => {
+                    tokio::spawn(async move {
+                        let result = rag_actor.get_chunk(chunk_id).await;
                         if responder.send(result).is_err() {
-                            warn!("Failed to send reindex_file response (channel closed)");
+                            warn!("Failed to send get_chunk response (channel closed)");
                         }
                     });
                 }
@@ -307,21 +591,114 @@ where
                 }
             }
         }
+        // A job interrupted here is requeued by the next worker
+        if let Some(worker) = ingest_worker {
+            worker.abort();
+        }
         info!("Supervisor stopped");
     }
 
+    /// Queues the files and reports them as pending.
+    async fn enqueue_ingest(
+        db_pool: Option<SqlitePool>,
+        progress_window: &ProgressWindow,
+        file_ids: Vec<String>,
+        force: bool,
+    ) -> Result<Vec<IngestJob>, AppError> {
+        let pool = db_pool
+            .as_ref()
+            .ok_or(AppError::Config("Database not initialized".to_string()))?;
+
+        let mut jobs = Vec::with_capacity(file_ids.len());
+        for file_id in file_ids {
+            let job = database::enqueue_ingest_job(pool, &file_id, force).await?;
+            report_progress(progress_window, pool, &job).await;
+            jobs.push(job);
+        }
+        info!("Queued {} file(s) for ingestion", jobs.len());
+        Ok(jobs)
+    }
+
+    /// Searches the chunks of the library files matching `filter` and groups them by file.
+    async fn search_library(
+        db_pool: Option<SqlitePool>,
+        rag_actor: Arc<R>,
+        query: String,
+        filter: LibrarySearchFilter,
+        limit: usize,
+    ) -> Result<Vec<LibrarySearchHit>, AppError> {
+        let pool = db_pool
+            .as_ref()
+            .ok_or(AppError::Config("Database not initialized".to_string()))?;
+
+        let files: HashMap<String, LibraryFile> = database::list_library_files(pool)
+            .await?
+            .into_iter()
+            .filter(|file| filter.matches(file))
+            .map(|file| (file.id.clone(), file))
+            .collect();
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Without a filter the whole table is searched rather than listing every file
+        let file_ids = if filter.is_empty() {
+            Vec::new()
+        } else {
+            files.keys().cloned().collect()
+        };
+        let options = SearchOptions {
+            limit,
+            ..SearchOptions::default()
+        };
+        let results = rag_actor
+            .search_with_filters(query.clone(), file_ids, options)
+            .await?;
+        Ok(group_by_file(&query, results, &files))
+    }
+
+    /// Returns the launch profile fitted to `model_path`, reading the GGUF header only when
+    /// the model differs from the one fitted last.
+    async fn fitted_profile(
+        cache: &FittedProfile,
+        model_path: std::path::PathBuf,
+    ) -> LaunchProfile {
+        let mut cached = cache.lock().await;
+        match cached.as_ref() {
+            Some((path, profile)) if *path == model_path => profile.clone(),
+            _ => {
+                let profile = LaunchProfile::for_model(&model_path);
+                *cached = Some((model_path, profile.clone()));
+                profile
+            }
+        }
+    }
+
     // Now a static method (associated function) to allow independent execution
-    #[instrument(skip(llm_actor, rag_actor, brain_analyzer, db_pool, window))]
+    #[instrument(skip(
+        llm_actor,
+        remote_llm_actor,
+        rag_actor,
+        brain_analyzer,
+        db_pool,
+        fitted_profile,
+        window,
+        cancel_rx
+    ))]
+    #[allow(clippy::too_many_arguments)]
     async fn handle_user_message(
         llm_actor: Arc<L>,
+        remote_llm_actor: Option<RemoteLlmActor>,
         rag_actor: Arc<R>,
         brain_analyzer: Arc<BrainAnalyzer>,
         db_pool: Option<SqlitePool>,
+        fitted_profile: FittedProfile,
         session_id: String,
-        content: String,
+        turn: UserTurn,
         window: Option<Window>,
+        mut cancel_rx: oneshot::Receiver<()>,
     ) -> Result<String, AppError> {
-        info!("Supervisor received: {}", content);
+        info!("Supervisor received: {:?}", turn);
 
         let pool = db_pool
             .as_ref()
@@ -329,13 +706,48 @@ where
 
         // --- Database Operations ---
         let session = database::get_session(pool, &session_id).await?;
-        database::add_message(pool, &session_id, "user", &content).await?;
-        let session_messages = database::get_session_messages(pool, &session_id).await?;
+        let regenerating = matches!(turn, UserTurn::Regenerate { .. });
+        let (user_message, history) = Self::prepare_turn(pool, &session_id, turn).await?;
+        let content = user_message.content.clone();
 
         // --- Configuration ---
         let config = session.model_config;
-        let system_prompt = Some(config.system_prompt.clone());
-        let temperature = Some(config.temperature);
+        let params = config.sampling_params();
+
+        // Sessions whose model_id is "remote:<model>" are served by the remote backend
+        let remote_llm = match remote_model_name(&config.model_id) {
+            Some(model) => Some(
+                remote_llm_actor
+                    .as_ref()
+                    .ok_or_else(|| {
+                        AppError::Config(
+                            "Session uses a remote model but REMOTE_LLM_URL is not configured"
+                                .to_string(),
+                        )
+                    })?
+                    .with_model(model),
+            ),
+            None => None,
+        };
+        // Other sessions use the installed model named by model_id, hot-swapped if needed
+        let local_llm = match &remote_llm {
+            Some(_) => None,
+            None => {
+                let local = llm_actor.for_model(&config.model_id);
+                if local.is_none() {
+                    warn!(
+                        "Model '{}' is not installed, using the loaded model",
+                        config.model_id
+                    );
+                }
+                local
+            }
+        };
+        let llm: &dyn LlmActor = match (&remote_llm, &local_llm) {
+            (Some(remote), _) => remote,
+            (None, Some(local)) => local.as_ref(),
+            (None, None) => llm_actor.as_ref(),
+        };
 
         // --- Thinking Steps & Analysis ---
         Self::emit_thinking(&window, "thinking.analyzing").await;
@@ -356,19 +768,113 @@ where
         )
         .await;
 
+        // A cancel request is also honoured before each slow step, not only while streaming
+        let mut cancel_closed = false;
+        if Self::cancel_requested(&mut cancel_rx, &mut cancel_closed) {
+            return Self::save_interrupted(
+                pool,
+                &window,
+                &session_id,
+                &user_message,
+                "",
+                &[],
+                regenerating,
+            )
+            .await;
+        }
+
         // --- Context Search ---
-        let mut context_str = String::new();
+        let mut context_chunks: Vec<String> = Vec::new();
+        let mut sources: Vec<MessageSource> = Vec::new();
 
-        if context_packet.should_use_rag {
+        if context_packet.should_use_rag && session.retrieval_scope != RetrievalScope::None {
             Self::emit_thinking(&window, "thinking.searching_context").await;
 
-            // Fetch files linked to this session
-            let files = database::get_session_files(pool, &session_id).await?;
-            let file_ids: Vec<String> = files.into_iter().map(|f| f.id).collect();
-
-            let search_results = rag_actor
-                .search_with_filters(content.clone(), file_ids)
-                .await?;
+            // Only the files in the session's retrieval scope are searched
+            let scoped_files = Self::retrieval_files(
+                pool,
+                &session_id,
+                session.retrieval_scope,
+                session.retrieval_folder_id.as_deref(),
+            )
+            .await?;
+            let search_everything = scoped_files.is_none();
+            let mut file_names = scoped_files.unwrap_or_default();
+            let file_ids: Vec<String> = file_names.keys().cloned().collect();
+
+            // Keywords feed the lexical side of the hybrid search; with reranking on, the
+            // search returns a wider pool for the cross-encoder to choose from
+            let rerank = config.rerank_options();
+            let defaults = SearchOptions::default();
+            let options = SearchOptions {
+                limit: rerank.as_ref().map_or(defaults.limit, |r| r.candidates),
+                keywords: context_packet
+                    .keywords
+                    .iter()
+                    .map(|k| k.keyword.clone())
+                    .collect(),
+                ..defaults
+            };
+            // An empty file list would search the whole library
+            let mut search_results = if search_everything || !file_ids.is_empty() {
+                rag_actor
+                    .search_with_filters(content.clone(), file_ids, options)
+                    .await?
+            } else {
+                info!(
+                    "No files in the {:?} retrieval scope of session {}",
+                    session.retrieval_scope, session_id
+                );
+                Vec::new()
+            };
+
+            if let Some(rerank) = rerank.filter(|_| !search_results.is_empty()) {
+                if Self::cancel_requested(&mut cancel_rx, &mut cancel_closed) {
+                    return Self::save_interrupted(
+                        pool,
+                        &window,
+                        &session_id,
+                        &user_message,
+                        "",
+                        &[],
+                        regenerating,
+                    )
+                    .await;
+                }
+                Self::emit_thinking(&window, "thinking.reranking").await;
+                let candidates = search_results.len();
+                let started = Instant::now();
+                match rag_actor
+                    .rerank(content.clone(), search_results.clone(), rerank.clone())
+                    .await
+                {
+                    Ok(reranked) => {
+                        let elapsed_ms = started.elapsed().as_millis();
+                        info!(
+                            "Reranked {} candidates, kept {} in {} ms",
+                            candidates,
+                            reranked.len(),
+                            elapsed_ms
+                        );
+                        Self::emit_thinking(
+                            &window,
+                            &format!(
+                                "thinking.reranked|{}|{}|{}",
+                                reranked.len(),
+                                candidates,
+                                elapsed_ms
+                            ),
+                        )
+                        .await;
+                        search_results = reranked;
+                    }
+                    Err(e) => {
+                        // Keep the search order rather than failing the whole answer
+                        warn!("Reranking failed, keeping search order: {}", e);
+                        search_results.truncate(rerank.top_k);
+                    }
+                }
+            }
 
             if !search_results.is_empty() {
                 Self::emit_thinking(
@@ -377,20 +883,26 @@ where
                 )
                 .await;
 
-                // Format context with source metadata
-                context_str = search_results
+                // Number each chunk and name its file, page and section for the model
+                sources = Self::resolve_sources(pool, &search_results, &mut file_names).await;
+                context_chunks = search_results
                     .iter()
-                    .map(|result| {
-                        let source = result.metadata.as_deref().unwrap_or("unknown");
-                        // Clean up source ID (remove "file:" prefix if present)
-                        let clean_source = source.strip_prefix("file:").unwrap_or(source);
-                        format!("[Source: {}]\n{}", clean_source, result.content)
+                    .zip(&sources)
+                    .enumerate()
+                    .map(|(i, (result, source))| {
+                        format!(
+                            "[Source {}: {}]\n{}",
+                            i + 1,
+                            source_label(source),
+                            result.content
+                        )
                     })
-                    .collect::<Vec<_>>()
-                    .join("\n\n");
+                    .collect();
             } else {
                 Self::emit_thinking(&window, "thinking.no_documents").await;
             }
+        } else if context_packet.should_use_rag {
+            info!("Document search is off for session {}", session_id);
         } else {
             // Skip RAG if not needed (e.g. greeting)
             info!(
@@ -399,42 +911,135 @@ where
             );
         }
 
+        // --- Context Budget ---
+        // The local server's slots follow the saved launch profile and the model's limits
+        let budget = if remote_llm.is_some() {
+            ContextBudget::default()
+        } else {
+            let profile = match resolve_model_path(&config.model_id) {
+                Some(model_path) => Self::fitted_profile(&fitted_profile, model_path).await,
+                None => LaunchProfile::load(),
+            };
+            ContextBudget::for_profile(&profile)
+        };
+        let fitted = budget.fit(&config.system_prompt, &history, &context_chunks, &content);
+        if fitted.is_trimmed() {
+            info!(
+                "Context trimmed to ~{} tokens: dropped {} history messages and {} chunks",
+                fitted.estimated_tokens, fitted.dropped_messages, fitted.dropped_chunks
+            );
+        }
+        if fitted.dropped_messages > 0 {
+            Self::emit_thinking(
+                &window,
+                &format!("thinking.history_trimmed|{}", fitted.dropped_messages),
+            )
+            .await;
+        }
+        if fitted.dropped_chunks > 0 {
+            Self::emit_thinking(
+                &window,
+                &format!("thinking.documents_trimmed|{}", fitted.dropped_chunks),
+            )
+            .await;
+        }
+        // Only the chunks that made it into the prompt are cited
+        sources.truncate(fitted.chunks.len());
+
         // --- Generation ---
+        if Self::cancel_requested(&mut cancel_rx, &mut cancel_closed) {
+            return Self::save_interrupted(
+                pool,
+                &window,
+                &session_id,
+                &user_message,
+                "",
+                &[],
+                regenerating,
+            )
+            .await;
+        }
         Self::emit_thinking(&window, "thinking.generating_response").await;
-        let conversation_history = session_messages
-            .iter()
-            .map(|msg| format!("{}: {}", msg.role, msg.content))
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        let final_prompt = build_final_prompt(&conversation_history, &context_str, &content);
+        let context_str = fitted.chunks.join("\n\n");
+        let messages = build_chat_messages(
+            &config.system_prompt,
+            fitted.history,
+            &context_str,
+            &content,
+        );
 
         // --- Streaming Response ---
+        // Tokens are consumed while the backend is still producing them, so a cancel
+        // request can stop the stream at any point.
         let (chunk_tx, mut chunk_rx) = mpsc::channel(32);
-        llm_actor
-            .stream_generate_with_params(final_prompt, system_prompt, temperature, chunk_tx)
-            .await?;
+        let generation = llm.stream_chat_with_params(messages, params, chunk_tx);
+        tokio::pin!(generation);
 
         let mut full_response = String::new();
-        while let Some(result) = chunk_rx.recv().await {
-            match result {
-                Ok(token) => {
-                    full_response.push_str(&token);
-                    if let Some(win) = &window {
-                        if let Err(e) = win.emit("chat-token", &token) {
-                            warn!("Failed to emit chat-token event: {}", e);
+        let mut generation_done = false;
+        let mut interrupted = false;
+        loop {
+            tokio::select! {
+                result = &mut generation, if !generation_done => {
+                    result?;
+                    generation_done = true;
+                }
+                chunk = chunk_rx.recv() => match chunk {
+                    Some(Ok(token)) => {
+                        full_response.push_str(&token);
+                        if let Some(win) = &window {
+                            if let Err(e) = win.emit("chat-token", &token) {
+                                warn!("Failed to emit chat-token event: {}", e);
+                            }
                         }
                     }
-                }
-                Err(e) => {
-                    error!("Streaming error: {}", e);
-                    // Decide if we should continue or abort
-                }
+                    Some(Err(e)) => {
+                        error!("Streaming error: {}", e);
+                    }
+                    None => break,
+                },
+                cancel = &mut cancel_rx, if !cancel_closed => match cancel {
+                    Ok(()) => {
+                        interrupted = true;
+                        break;
+                    }
+                    // The handle was replaced by a newer message for this session
+                    Err(_) => cancel_closed = true,
+                },
             }
         }
 
+        if interrupted {
+            // Dropping the receiver makes the backend stop reading, which closes the
+            // HTTP stream and frees the llama-server slot.
+            drop(chunk_rx);
+            return Self::save_interrupted(
+                pool,
+                &window,
+                &session_id,
+                &user_message,
+                &full_response,
+                &sources,
+                regenerating,
+            )
+            .await;
+        }
+
+        if !generation_done {
+            generation.await?;
+        }
+
         if !full_response.trim().is_empty() {
-            database::add_message(pool, &session_id, "assistant", &full_response).await?;
+            let message = database::add_child_message(
+                pool,
+                &session_id,
+                Some(user_message.id),
+                "assistant",
+                &full_response,
+                false,
+            )
+            .await?;
+            Self::save_sources(pool, &window, &message, &sources).await?;
         } else {
             warn!("Generated response was empty, skipping database save.");
         }
@@ -442,39 +1047,293 @@ where
         Ok(full_response)
     }
 
-    async fn emit_thinking(window: &Option<Window>, step: &str) {
-        if let Some(win) = window {
-            if let Err(e) = win.emit("thinking-step", step) {
-                warn!("Failed to emit thinking-step event: {}", e);
+    /// Whether the user asked to stop this generation. A closed channel is not a cancel:
+    /// a newer message for the session took over the handle.
+    fn cancel_requested(cancel_rx: &mut oneshot::Receiver<()>, cancel_closed: &mut bool) -> bool {
+        if *cancel_closed {
+            return false;
+        }
+        match cancel_rx.try_recv() {
+            Ok(()) => true,
+            Err(oneshot::error::TryRecvError::Empty) => false,
+            Err(oneshot::error::TryRecvError::Closed) => {
+                *cancel_closed = true;
+                false
             }
         }
     }
-}
 
-fn build_final_prompt(conversation_history: &str, context_str: &str, content: &str) -> String {
-    let mut prompt_parts = Vec::new();
-    if !conversation_history.is_empty() {
-        prompt_parts.push(format!("Conversation History:\n{}", conversation_history));
+    /// Stores the reply stopped by a cancel request, marked as interrupted, and returns
+    /// the partial answer. A reply stopped before its first token is stored empty, except
+    /// when regenerating: the previous answer then stays the one shown.
+    async fn save_interrupted(
+        pool: &SqlitePool,
+        window: &Option<Window>,
+        session_id: &str,
+        user_message: &Message,
+        partial: &str,
+        sources: &[MessageSource],
+        regenerating: bool,
+    ) -> Result<String, AppError> {
+        info!(
+            "Generation cancelled for session {} after {} chars",
+            session_id,
+            partial.len()
+        );
+        if regenerating && partial.trim().is_empty() {
+            return Ok(String::new());
+        }
+        let message = database::add_child_message(
+            pool,
+            session_id,
+            Some(user_message.id),
+            "assistant",
+            partial,
+            true,
+        )
+        .await?;
+        Self::save_sources(pool, window, &message, sources).await?;
+        Ok(partial.to_string())
     }
-    if !context_str.is_empty() {
-        prompt_parts.push(format!("Context:\n{}", context_str));
+
+    /// Stores or locates the user message to answer and loads the history preceding it.
+    async fn prepare_turn(
+        pool: &SqlitePool,
+        session_id: &str,
+        turn: UserTurn,
+    ) -> Result<(Message, Vec<Message>), AppError> {
+        match turn {
+            UserTurn::New { content } => {
+                let history = database::get_session_messages(pool, session_id).await?;
+                let message = database::add_message(pool, session_id, "user", &content).await?;
+                Ok((message, history))
+            }
+            UserTurn::Edit {
+                message_id,
+                content,
+            } => {
+                let original = Self::get_session_message(pool, session_id, message_id).await?;
+                if original.role != "user" {
+                    return Err(AppError::Validation(
+                        "Only user messages can be edited".to_string(),
+                    ));
+                }
+                let history = match original.parent_id {
+                    Some(parent_id) => database::get_branch(pool, parent_id).await?,
+                    None => Vec::new(),
+                };
+                let message = database::add_child_message(
+                    pool,
+                    session_id,
+                    original.parent_id,
+                    "user",
+                    &content,
+                    false,
+                )
+                .await?;
+                Ok((message, history))
+            }
+            UserTurn::Regenerate { message_id } => {
+                let mut message = Self::get_session_message(pool, session_id, message_id).await?;
+                if message.role != "user" {
+                    let parent_id = message.parent_id.ok_or_else(|| {
+                        AppError::Validation("Message has no user message to answer".to_string())
+                    })?;
+                    message = Self::get_session_message(pool, session_id, parent_id).await?;
+                }
+                // The active branch only moves once the new answer is stored, so a failed
+                // or cancelled regeneration keeps showing the previous answer
+                let history = match message.parent_id {
+                    Some(parent_id) => database::get_branch(pool, parent_id).await?,
+                    None => Vec::new(),
+                };
+                Ok((message, history))
+            }
+        }
     }
-    prompt_parts.push(format!("User Request: {}", content));
-    prompt_parts.join("\n\n")
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::actors::messages::SearchResult;
-    use crate::actors::traits::mocks::{MockLlmActor, MockRagActor};
-    use crate::database;
-    use crate::models::ModelConfig;
-    use sqlx::sqlite::SqlitePoolOptions;
-    use std::sync::Arc;
-    use tempfile::TempDir;
+    async fn get_session_message(
+        pool: &SqlitePool,
+        session_id: &str,
+        message_id: i64,
+    ) -> Result<Message, AppError> {
+        let message = database::get_message(pool, message_id).await?;
+        if message.session_id != session_id {
+            return Err(AppError::Validation(format!(
+                "Message {} does not belong to session {}",
+                message_id, session_id
+            )));
+        }
+        Ok(message)
+    }
 
-    /// Creates a test database with migrations applied
+    /// Names the files a session searches, by id, or returns `None` when it searches the
+    /// whole library.
+    async fn retrieval_files(
+        pool: &SqlitePool,
+        session_id: &str,
+        scope: RetrievalScope,
+        folder_id: Option<&str>,
+    ) -> Result<Option<HashMap<String, Option<String>>>, AppError> {
+        let files: Vec<(String, String)> = match (scope, folder_id) {
+            (RetrievalScope::Library, _) => return Ok(None),
+            (RetrievalScope::Linked, _) => database::get_session_files(pool, session_id)
+                .await?
+                .into_iter()
+                .map(|f| (f.id, f.name))
+                .collect(),
+            (RetrievalScope::Folder, Some(folder_id)) => {
+                database::list_folder_files(pool, folder_id)
+                    .await?
+                    .into_iter()
+                    .map(|f| (f.id, f.name))
+                    .collect()
+            }
+            (RetrievalScope::Folder, None) | (RetrievalScope::None, _) => Vec::new(),
+        };
+        Ok(Some(
+            files
+                .into_iter()
+                .map(|(id, name)| (id, Some(name)))
+                .collect(),
+        ))
+    }
+
+    /// Builds the citation of each search result, looking up the names of files outside
+    /// the session's scope (searches of the whole library).
+    async fn resolve_sources(
+        pool: &SqlitePool,
+        results: &[SearchResult],
+        file_names: &mut HashMap<String, Option<String>>,
+    ) -> Vec<MessageSource> {
+        let mut sources = Vec::with_capacity(results.len());
+        for result in results {
+            let metadata = &result.metadata;
+            let file_name = match &metadata.file_id {
+                Some(file_id) => match file_names.get(file_id) {
+                    Some(name) => name.clone(),
+                    None => {
+                        let name = database::get_library_file(pool, file_id)
+                            .await
+                            .ok()
+                            .map(|file| file.name);
+                        file_names.insert(file_id.clone(), name.clone());
+                        name
+                    }
+                },
+                None => None,
+            };
+            sources.push(MessageSource {
+                chunk_id: result.id.clone(),
+                file_id: metadata.file_id.clone(),
+                file_name,
+                chunk_index: metadata.chunk_index.into(),
+                page: metadata.page.map(i64::from),
+                char_start: metadata.char_start.map(|offset| offset as i64),
+                char_end: metadata.char_end.map(|offset| offset as i64),
+                heading_path: metadata.heading_path.clone(),
+                score: result.score.into(),
+            });
+        }
+        sources
+    }
+
+    /// Stores the sources of an assistant message and sends them to the frontend.
+    async fn save_sources(
+        pool: &SqlitePool,
+        window: &Option<Window>,
+        message: &Message,
+        sources: &[MessageSource],
+    ) -> Result<(), AppError> {
+        if sources.is_empty() {
+            return Ok(());
+        }
+        database::add_message_sources(pool, message.id, sources).await?;
+
+        if let Some(win) = window {
+            let payload = ChatSources {
+                session_id: &message.session_id,
+                message_id: message.id,
+                sources,
+            };
+            if let Err(e) = win.emit("chat-sources", &payload) {
+                warn!("Failed to emit chat-sources event: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn emit_thinking(window: &Option<Window>, step: &str) {
+        if let Some(win) = window {
+            if let Err(e) = win.emit("thinking-step", step) {
+                warn!("Failed to emit thinking-step event: {}", e);
+            }
+        }
+    }
+}
+
+/// Payload of the `chat-sources` event, sent once an assistant message is stored.
+#[derive(Serialize)]
+struct ChatSources<'a> {
+    session_id: &'a str,
+    message_id: i64,
+    sources: &'a [MessageSource],
+}
+
+/// Describes a source for the model, e.g. `report.pdf, page 3, Install > Linux`.
+fn source_label(source: &MessageSource) -> String {
+    let mut label = source
+        .file_name
+        .clone()
+        .or_else(|| source.file_id.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    if let Some(page) = source.page {
+        label.push_str(&format!(", page {}", page));
+    }
+    if let Some(heading_path) = &source.heading_path {
+        label.push_str(&format!(", {}", heading_path));
+    }
+    label
+}
+
+/// Builds the conversation sent to the model: the system prompt, every stored turn,
+/// retrieved context (if any), then the new user message.
+fn build_chat_messages(
+    system_prompt: &str,
+    history: &[Message],
+    context_str: &str,
+    content: &str,
+) -> Vec<ChatMessage> {
+    let mut messages = Vec::with_capacity(history.len() + 3);
+    if !system_prompt.is_empty() {
+        messages.push(ChatMessage::system(system_prompt));
+    }
+    messages.extend(
+        history
+            .iter()
+            .filter(|msg| !msg.content.trim().is_empty())
+            .map(|msg| ChatMessage::new(ChatRole::from_db_role(&msg.role), msg.content.as_str())),
+    );
+    if !context_str.is_empty() {
+        messages.push(ChatMessage::context(format!("Context:\n{}", context_str)));
+    }
+    messages.push(ChatMessage::user(content));
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::{ChunkMetadata, SearchResult};
+    use crate::actors::traits::mocks::{MockLlmActor, MockRagActor};
+    use crate::chunking::ChunkingConfig;
+    use crate::database;
+    use crate::models::{ModelConfig, SamplingParams};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Creates a test database with migrations applied
     async fn setup_test_db() -> (SqlitePool, TempDir) {
         std::env::set_var("ENCRYPTION_KEY", "01234567890123456789012345678901");
 
@@ -496,6 +1355,10 @@ mod tests {
         (pool, temp_dir)
     }
 
+    /// Complex enough for the brain analyzer to always ask for RAG.
+    const RAG_QUESTION: &str = "Explain the difference between microservices and monolith \
+        architecture in terms of database transactions and container orchestration";
+
     /// Creates a SupervisorHandle with mock actors
     fn create_test_supervisor(
         llm: Arc<MockLlmActor>,
@@ -505,49 +1368,94 @@ mod tests {
         SupervisorHandle::new_with_actors(llm, rag, pool)
     }
 
+    /// Creates a search result for a chunk of the given file
+    fn search_result(file_id: &str, content: &str, score: f32) -> SearchResult {
+        SearchResult {
+            id: format!("{}#0", file_id),
+            content: content.to_string(),
+            metadata: ChunkMetadata {
+                file_id: Some(file_id.to_string()),
+                ..ChunkMetadata::default()
+            },
+            score,
+        }
+    }
+
     // ==================== Unit Tests ====================
 
-    #[test]
-    fn test_build_final_prompt_all_parts() {
-        let history = "user: Hello\nassistant: Hi!";
-        let context = "Relevant document about Rust.";
-        let content = "How do I create a struct?";
+    fn stored_message(role: &str, content: &str) -> Message {
+        Message {
+            id: 0,
+            session_id: "session".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: 0,
+            interrupted: false,
+            parent_id: None,
+            sibling_ids: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
 
-        let prompt = build_final_prompt(history, context, content);
+    #[test]
+    fn test_build_chat_messages_all_parts() {
+        let history = vec![
+            stored_message("user", "Hello"),
+            stored_message("assistant", "Hi!"),
+        ];
+
+        let messages = build_chat_messages(
+            "Be helpful.",
+            &history,
+            "Relevant document about Rust.",
+            "How do I create a struct?",
+        );
 
-        assert!(prompt.contains("Conversation History:"));
-        assert!(prompt.contains("user: Hello"));
-        assert!(prompt.contains("Context:"));
-        assert!(prompt.contains("Relevant document"));
-        assert!(prompt.contains("User Request:"));
-        assert!(prompt.contains("How do I create a struct?"));
+        assert_eq!(
+            messages,
+            vec![
+                ChatMessage::system("Be helpful."),
+                ChatMessage::user("Hello"),
+                ChatMessage::assistant("Hi!"),
+                ChatMessage::context("Context:\nRelevant document about Rust."),
+                ChatMessage::user("How do I create a struct?"),
+            ]
+        );
     }
 
     #[test]
-    fn test_build_final_prompt_no_history() {
-        let prompt = build_final_prompt("", "Some context", "Question");
+    fn test_build_chat_messages_no_history() {
+        let messages = build_chat_messages("", &[], "Some context", "Question");
 
-        assert!(!prompt.contains("Conversation History:"));
-        assert!(prompt.contains("Context:"));
-        assert!(prompt.contains("Question"));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, ChatRole::Context);
+        assert_eq!(messages[1], ChatMessage::user("Question"));
     }
 
     #[test]
-    fn test_build_final_prompt_no_context() {
-        let prompt = build_final_prompt("user: hi", "", "Question");
+    fn test_build_chat_messages_no_context() {
+        let history = vec![stored_message("user", "hi")];
+        let messages = build_chat_messages("System", &history, "", "Question");
 
-        assert!(prompt.contains("Conversation History:"));
-        assert!(!prompt.contains("Context:"));
-        assert!(prompt.contains("Question"));
+        assert!(messages.iter().all(|m| m.role != ChatRole::Context));
+        assert_eq!(messages.last(), Some(&ChatMessage::user("Question")));
     }
 
     #[test]
-    fn test_build_final_prompt_only_content() {
-        let prompt = build_final_prompt("", "", "Simple question");
+    fn test_build_chat_messages_skips_empty_turns() {
+        let history = vec![
+            stored_message("user", "hi"),
+            stored_message("assistant", "   "),
+        ];
+        let messages = build_chat_messages("", &history, "", "Simple question");
 
-        assert!(!prompt.contains("Conversation History:"));
-        assert!(!prompt.contains("Context:"));
-        assert!(prompt.contains("User Request: Simple question"));
+        assert_eq!(
+            messages,
+            vec![
+                ChatMessage::user("hi"),
+                ChatMessage::user("Simple question")
+            ]
+        );
     }
 
     // ==================== Integration Tests with Mocks ====================
@@ -560,7 +1468,11 @@ mod tests {
         let supervisor = create_test_supervisor(llm, rag.clone(), None);
 
         let result = supervisor
-            .ingest_content("Test content to ingest".to_string(), None)
+            .ingest_content(
+                "Test content to ingest".to_string(),
+                None,
+                ChunkingOptions::default(),
+            )
             .await;
 
         assert!(result.is_ok());
@@ -577,29 +1489,116 @@ mod tests {
 
         let supervisor = create_test_supervisor(llm, rag.clone(), None);
 
+        let chunking = ChunkingConfig::default().options_for("test.pdf");
         let result = supervisor
             .ingest_content(
                 "Document content".to_string(),
-                Some("file:test.pdf".to_string()),
+                Some("test.pdf".to_string()),
+                chunking,
             )
             .await;
 
         assert!(result.is_ok());
+        // The chunking options reach the RAG actor unchanged
+        assert_eq!(*rag.last_chunking.lock().await, Some(chunking));
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_search_library_groups_filtered_files() {
+        let (pool, _temp) = setup_test_db().await;
+        for (id, name) in [("a", "guide.md"), ("b", "spec.pdf")] {
+            database::add_library_file(&pool, id, name, "/f", "text/plain", 10)
+                .await
+                .unwrap();
+        }
+        let llm = Arc::new(MockLlmActor::new("Response"));
+        let rag = Arc::new(
+            MockRagActor::with_results(vec![
+                search_result("b", "The spec defines the license.", 0.9),
+                search_result("a", "Install the license key.", 0.8),
+            ])
+            .await,
+        );
+        let supervisor = create_test_supervisor(llm, rag.clone(), Some(pool));
+
+        let hits = supervisor
+            .search_library("license".to_string(), LibrarySearchFilter::default(), 5)
+            .await
+            .unwrap();
+        let files: Vec<&str> = hits.iter().map(|hit| hit.file_name.as_str()).collect();
+        assert_eq!(files, vec!["spec.pdf", "guide.md"]);
+        assert!(hits[0].matches[0]
+            .segments
+            .iter()
+            .any(|segment| segment.highlighted && segment.text == "license"));
+        assert_eq!(rag.last_options.lock().await.as_ref().unwrap().limit, 5);
+
+        // Chunks of the files left out by the filter are dropped
+        let filter = LibrarySearchFilter {
+            folder_id: None,
+            file_types: vec!["md".to_string()],
+        };
+        let hits = supervisor
+            .search_library("license".to_string(), filter, 5)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].file_id, "a");
+
+        // No file in the folder, nothing to search
+        let filter = LibrarySearchFilter {
+            folder_id: Some("empty".to_string()),
+            file_types: Vec::new(),
+        };
+        let hits = supervisor
+            .search_library("license".to_string(), filter, 5)
+            .await
+            .unwrap();
+        assert!(hits.is_empty());
     }
 
     #[tokio::test]
-    async fn test_supervisor_reindex_file() {
+    async fn test_supervisor_enqueue_ingest_runs_in_background() {
+        let (pool, temp) = setup_test_db().await;
         let llm = Arc::new(MockLlmActor::new("Response"));
         let rag = Arc::new(MockRagActor::new());
 
-        let supervisor = create_test_supervisor(llm, rag.clone(), None);
+        let path = temp.path().join("notes.md");
+        std::fs::write(&path, "# Notes\n\nNew content").unwrap();
+        database::add_library_file(
+            &pool,
+            "file-123",
+            "notes.md",
+            &path.to_string_lossy(),
+            "text/markdown",
+            20,
+        )
+        .await
+        .unwrap();
 
-        let result = supervisor
-            .reindex_file("file-123".to_string(), "New content".to_string())
-            .await;
+        let supervisor = create_test_supervisor(llm, rag.clone(), Some(pool.clone()));
 
-        assert!(result.is_ok());
-        // Reindex should delete then ingest
+        let jobs = supervisor
+            .enqueue_ingest(vec!["file-123".to_string()], false, None)
+            .await
+            .unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].file_name, "notes.md");
+
+        timeout(Duration::from_secs(5), async {
+            while database::count_ingest_jobs(&pool)
+                .await
+                .unwrap()
+                .remaining()
+                > 0
+            {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("Job not processed");
+
+        // Reindexing deletes the previous chunks, then ingests
         assert_eq!(
             rag.delete_count.load(std::sync::atomic::Ordering::SeqCst),
             1
@@ -608,6 +1607,14 @@ mod tests {
             rag.ingest_count.load(std::sync::atomic::Ordering::SeqCst),
             1
         );
+        let job = database::get_ingest_job(&pool, &jobs[0].id).await.unwrap();
+        assert_eq!(job.status, crate::models::IngestJobStatus::Done);
+
+        // Unknown files are rejected
+        let result = supervisor
+            .enqueue_ingest(vec!["missing".to_string()], false, None)
+            .await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -658,38 +1665,263 @@ mod tests {
         )
         .await
         .unwrap();
+        // Search the whole library, as no file is linked to the session
+        database::set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Library, None)
+            .await
+            .unwrap();
 
         let llm = Arc::new(MockLlmActor::new(
             "Based on the context, here is my answer.",
         ));
 
         // Create RAG with pre-populated results
-        let rag_results = vec![SearchResult {
-            content: "Rust is a systems programming language.".to_string(),
-            metadata: Some("file:rust-docs.txt".to_string()),
-            score: 0.95,
-        }];
+        let rag_results = vec![search_result(
+            "rust-docs.txt",
+            "Rust is a systems programming language.",
+            0.95,
+        )];
         let rag = Arc::new(MockRagActor::with_results(rag_results).await);
 
         let supervisor = create_test_supervisor(llm.clone(), rag.clone(), Some(pool.clone()));
 
-        // Use a more complex query that should trigger RAG
-        // Questions about specific technical topics typically use RAG
         let result = supervisor
+            .process_message(session.id.clone(), RAG_QUESTION.to_string(), None)
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            rag.search_count.load(std::sync::atomic::Ordering::SeqCst),
+            1,
+            "A complex technical question should search the knowledge base"
+        );
+
+        // The extracted keywords feed the lexical side of the hybrid search
+        let options = rag
+            .last_options
+            .lock()
+            .await
+            .clone()
+            .expect("The search should receive options");
+        assert!(
+            !options.keywords.is_empty(),
+            "Keywords should be passed to the hybrid search"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_reranks_search_results_when_enabled() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let config = ModelConfig {
+            rerank: true,
+            rerank_candidates: Some(10),
+            rerank_top_k: Some(1),
+            ..ModelConfig::default()
+        };
+        let session = database::create_session(&pool, "Rerank Session".to_string(), config)
+            .await
+            .unwrap();
+        // Search the whole library, as no file is linked to the session
+        database::set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Library, None)
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Answer based on the best document."));
+        let rag_results = vec![
+            search_result(
+                "architecture.md",
+                "Microservices keep one database per service.",
+                0.9,
+            ),
+            search_result("monolith.md", "A monolith shares a single database.", 0.8),
+        ];
+        let rag = Arc::new(MockRagActor::with_results(rag_results).await);
+
+        let supervisor = create_test_supervisor(llm.clone(), rag.clone(), Some(pool.clone()));
+
+        let result = supervisor
+            .process_message(session.id.clone(), RAG_QUESTION.to_string(), None)
+            .await;
+
+        assert!(result.is_ok());
+
+        // The search fetches the candidate pool and the reranker keeps the best one
+        let options = rag
+            .last_options
+            .lock()
+            .await
+            .clone()
+            .expect("The search should receive options");
+        assert_eq!(options.limit, 10, "The reranker gets the candidate pool");
+        assert_eq!(
+            rag.rerank_count.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        let prompt: String = llm
+            .last_messages
+            .lock()
+            .await
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert!(prompt.contains("architecture.md"));
+        assert!(
+            !prompt.contains("monolith.md"),
+            "Only the reranked top chunk reaches the prompt"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_stores_sources_with_answer() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session =
+            database::create_session(&pool, "Sources".to_string(), ModelConfig::default())
+                .await
+                .unwrap();
+        // Search the whole library, as no file is linked to the session
+        database::set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Library, None)
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Each service owns its data [Source 1]."));
+        let rag_results = vec![
+            search_result(
+                "architecture.md",
+                "Microservices keep one database per service.",
+                0.9,
+            ),
+            search_result("monolith.md", "A monolith shares a single database.", 0.8),
+        ];
+        let rag = Arc::new(MockRagActor::with_results(rag_results).await);
+
+        let supervisor = create_test_supervisor(llm.clone(), rag.clone(), Some(pool.clone()));
+        supervisor
             .process_message(
                 session.id.clone(),
                 "Explain the difference between microservices and monolith architecture in terms of database transactions".to_string(),
                 None,
             )
-            .await;
+            .await
+            .unwrap();
 
-        assert!(result.is_ok());
+        // RAG usage depends on the brain analyzer; when it searches, the answer cites its chunks
+        if rag.search_count.load(std::sync::atomic::Ordering::SeqCst) > 0 {
+            let messages = database::get_session_messages(&pool, &session.id)
+                .await
+                .unwrap();
+            let chunk_ids: Vec<&str> = messages[1]
+                .sources
+                .iter()
+                .map(|s| s.chunk_id.as_str())
+                .collect();
+            assert_eq!(chunk_ids, vec!["architecture.md#0", "monolith.md#0"]);
+            assert!(messages[0].sources.is_empty());
+
+            let prompt = llm.last_messages.lock().await.clone();
+            assert!(prompt
+                .iter()
+                .any(|m| m.content.contains("[Source 1: architecture.md]")));
+        }
+    }
 
-        // For complex technical questions, RAG should be used
-        // Note: The brain analyzer decides based on complexity/intent
-        let search_count = rag.search_count.load(std::sync::atomic::Ordering::SeqCst);
-        // We just verify the flow works, RAG usage depends on brain analyzer
-        assert!(search_count >= 0, "RAG search count should be non-negative");
+    #[tokio::test]
+    async fn test_supervisor_searches_only_the_retrieval_scope() {
+        let (pool, _temp) = setup_test_db().await;
+        let session = database::create_session(&pool, "Scope".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+        let folder = database::create_folder(
+            &pool,
+            "Docs".to_string(),
+            None,
+            Some("document".to_string()),
+        )
+        .await
+        .unwrap();
+        for id in ["in-folder", "elsewhere"] {
+            database::add_library_file(&pool, id, &format!("{}.md", id), "/f", "text/plain", 10)
+                .await
+                .unwrap();
+        }
+        database::move_file_to_folder(&pool, "in-folder", Some(&folder.id))
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Answer."));
+        let rag = Arc::new(
+            MockRagActor::with_results(vec![search_result(
+                "in-folder",
+                "Microservices keep one database per service.",
+                0.9,
+            )])
+            .await,
+        );
+        let supervisor = create_test_supervisor(llm, rag.clone(), Some(pool.clone()));
+
+        // No linked file: the library is not searched behind the session's back
+        supervisor
+            .process_message(session.id.clone(), RAG_QUESTION.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            rag.search_count.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+
+        // Document search turned off
+        database::set_session_retrieval_scope(&pool, &session.id, RetrievalScope::None, None)
+            .await
+            .unwrap();
+        supervisor
+            .process_message(session.id.clone(), RAG_QUESTION.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            rag.search_count.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+
+        // Only the folder's files are searched
+        database::set_session_retrieval_scope(
+            &pool,
+            &session.id,
+            RetrievalScope::Folder,
+            Some(&folder.id),
+        )
+        .await
+        .unwrap();
+        supervisor
+            .process_message(session.id.clone(), RAG_QUESTION.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            rag.search_count.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        let file_ids = rag.last_file_ids.lock().await.clone();
+        assert_eq!(file_ids, Some(vec!["in-folder".to_string()]));
+    }
+
+    #[test]
+    fn test_source_label() {
+        let mut source = MessageSource {
+            chunk_id: "c".to_string(),
+            file_id: Some("file-1".to_string()),
+            file_name: None,
+            chunk_index: 0,
+            page: None,
+            char_start: None,
+            char_end: None,
+            heading_path: None,
+            score: 0.5,
+        };
+        assert_eq!(source_label(&source), "file-1");
+
+        source.file_name = Some("report.pdf".to_string());
+        source.page = Some(3);
+        source.heading_path = Some("Install > Linux".to_string());
+        assert_eq!(source_label(&source), "report.pdf, page 3, Install > Linux");
     }
 
     #[tokio::test]
@@ -789,14 +2021,194 @@ mod tests {
 
         assert!(result.is_ok());
 
-        // Verify prompt contains history
-        let last_prompt = llm.last_prompt.lock().await.clone();
-        assert!(last_prompt.is_some());
-        let prompt = last_prompt.unwrap();
-        assert!(
-            prompt.contains("First question") || prompt.contains("Conversation History"),
-            "Prompt should contain conversation history"
+        // Verify history is sent as real turns, followed by the new message
+        let messages = llm.last_messages.lock().await.clone();
+        let turns: Vec<_> = messages
+            .iter()
+            .filter(|m| m.role != ChatRole::System)
+            .cloned()
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ChatMessage::user("First question"),
+                ChatMessage::assistant("First answer"),
+                ChatMessage::user("Follow-up question"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_trims_long_history_to_budget() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session =
+            database::create_session(&pool, "Long Session".to_string(), ModelConfig::default())
+                .await
+                .unwrap();
+
+        for i in 0..200 {
+            let role = if i % 2 == 0 { "user" } else { "assistant" };
+            let content = format!("Turn {} {}", i, "lorem ipsum ".repeat(20));
+            database::add_message(&pool, &session.id, role, &content)
+                .await
+                .unwrap();
+        }
+
+        let llm = Arc::new(MockLlmActor::new("Still here"));
+        let rag = Arc::new(MockRagActor::new());
+
+        let supervisor = create_test_supervisor(llm.clone(), rag, Some(pool.clone()));
+
+        let result = supervisor
+            .process_message(session.id.clone(), "Latest question".to_string(), None)
+            .await;
+
+        assert!(result.is_ok());
+
+        let messages = llm.last_messages.lock().await.clone();
+        assert!(messages.len() < 200, "History should have been trimmed");
+        assert_eq!(messages.last(), Some(&ChatMessage::user("Latest question")));
+        assert!(messages.iter().any(|m| m.content.starts_with("Turn 199 ")));
+        assert!(!messages.iter().any(|m| m.content.starts_with("Turn 0 ")));
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_regenerate_keeps_previous_answer_as_branch() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session =
+            database::create_session(&pool, "Regenerate".to_string(), ModelConfig::default())
+                .await
+                .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("First answer"));
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = create_test_supervisor(llm.clone(), rag, Some(pool.clone()));
+
+        supervisor
+            .process_message(session.id.clone(), "Question".to_string(), None)
+            .await
+            .unwrap();
+        let first = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+
+        llm.set_response("Second answer").await;
+        supervisor
+            .regenerate_message(session.id.clone(), first[1].id, None)
+            .await
+            .unwrap();
+
+        // The question is sent again, without the discarded answer
+        let sent = llm.last_messages.lock().await.clone();
+        assert!(!sent.iter().any(|m| m.content.contains("First answer")));
+        assert_eq!(sent.last(), Some(&ChatMessage::user("Question")));
+
+        let messages = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].content.trim(), "Second answer");
+        assert_eq!(messages[1].parent_id, Some(first[0].id));
+        assert_eq!(messages[1].sibling_ids, vec![first[1].id, messages[1].id]);
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_failed_regenerate_keeps_previous_answer_shown() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = database::create_session(
+            &pool,
+            "Failed Regenerate".to_string(),
+            ModelConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("First answer"));
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = create_test_supervisor(llm.clone(), rag, Some(pool.clone()));
+
+        supervisor
+            .process_message(session.id.clone(), "Question".to_string(), None)
+            .await
+            .unwrap();
+        let first = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+
+        llm.should_fail
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let result = supervisor
+            .regenerate_message(session.id.clone(), first[1].id, None)
+            .await;
+        assert!(result.is_err());
+
+        let messages = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].id, first[1].id);
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_edit_message_branches_from_parent() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = database::create_session(&pool, "Edit".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Answer"));
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = create_test_supervisor(llm.clone(), rag, Some(pool.clone()));
+
+        supervisor
+            .process_message(session.id.clone(), "First".to_string(), None)
+            .await
+            .unwrap();
+        supervisor
+            .process_message(session.id.clone(), "Typo questoin".to_string(), None)
+            .await
+            .unwrap();
+        let original = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        assert_eq!(original.len(), 4);
+
+        supervisor
+            .edit_message(
+                session.id.clone(),
+                original[2].id,
+                "Fixed question".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let sent = llm.last_messages.lock().await.clone();
+        assert!(!sent.iter().any(|m| m.content.contains("Typo")));
+        assert_eq!(sent.last(), Some(&ChatMessage::user("Fixed question")));
+
+        let messages = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        let contents: Vec<_> = messages.iter().map(|m| m.content.trim()).collect();
+        assert_eq!(
+            contents,
+            vec!["First", "Answer", "Fixed question", "Answer"]
         );
+        assert_eq!(
+            messages[2].sibling_ids,
+            vec![original[2].id, messages[2].id]
+        );
+
+        // Assistant messages cannot be edited
+        let result = supervisor
+            .edit_message(session.id.clone(), messages[3].id, "x".to_string(), None)
+            .await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -807,6 +2219,10 @@ mod tests {
             model_id: "custom-model.gguf".to_string(),
             temperature: 0.9,
             system_prompt: "You are a pirate assistant.".to_string(),
+            top_p: Some(0.8),
+            repeat_penalty: Some(1.3),
+            stop: vec!["###".to_string()],
+            ..ModelConfig::default()
         };
 
         let session = database::create_session(&pool, "Custom Config".to_string(), custom_config)
@@ -828,6 +2244,236 @@ mod tests {
         let last_system_prompt = llm.last_system_prompt.lock().await.clone();
         assert!(last_system_prompt.is_some());
         assert!(last_system_prompt.unwrap().contains("pirate"));
+
+        // Verify the session's model was requested
+        assert_eq!(
+            *llm.requested_models.lock().unwrap(),
+            vec!["custom-model.gguf".to_string()]
+        );
+
+        // Verify sampling parameters were passed through
+        let params = llm.last_params.lock().await.clone().unwrap();
+        assert_eq!(params.temperature, Some(0.9));
+        assert_eq!(params.top_p, Some(0.8));
+        assert_eq!(params.repeat_penalty, Some(1.3));
+        assert_eq!(params.top_k, None);
+        assert_eq!(params.stop, vec!["###".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_routes_remote_sessions_to_remote_backend() {
+        use crate::actors::remote_llm::RemoteLlmConfig;
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let (pool, _temp) = setup_test_db().await;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "model": "lan-coder" })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"From the LAN box\"}}]}\n\ndata: [DONE]\n\n",
+                "text/event-stream",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let remote = RemoteLlmActor::new(RemoteLlmConfig {
+            base_url: server.uri(),
+            api_key: None,
+            default_model: None,
+        });
+
+        let config = ModelConfig {
+            model_id: "remote:lan-coder".to_string(),
+            ..ModelConfig::default()
+        };
+        let session = database::create_session(&pool, "Remote".to_string(), config)
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Local answer"));
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = SupervisorHandle::new_with_actors_and_remote(
+            llm.clone(),
+            remote,
+            rag,
+            Some(pool.clone()),
+        );
+
+        let response = supervisor
+            .process_message(session.id.clone(), "Hello".to_string(), None)
+            .await
+            .expect("Remote session should succeed");
+
+        assert_eq!(response, "From the LAN box");
+        assert_eq!(llm.get_call_count(), 0, "Local backend must not be used");
+        assert!(llm.requested_models.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_remote_session_without_backend_fails() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let config = ModelConfig {
+            model_id: "remote:lan-coder".to_string(),
+            ..ModelConfig::default()
+        };
+        let session = database::create_session(&pool, "Remote".to_string(), config)
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Local answer"));
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = create_test_supervisor(llm.clone(), rag, Some(pool.clone()));
+
+        let result = supervisor
+            .process_message(session.id.clone(), "Hello".to_string(), None)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(llm.get_call_count(), 0);
+    }
+
+    /// Streams one token every 10ms from a separate task, like `LlmActorRunner`, until the
+    /// receiver goes away.
+    struct EndlessLlmActor {
+        stopped: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmActor for EndlessLlmActor {
+        async fn chat_with_params(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _params: SamplingParams,
+        ) -> Result<String, AppError> {
+            Ok(String::new())
+        }
+
+        async fn stream_chat_with_params(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _params: SamplingParams,
+            chunk_sender: mpsc::Sender<Result<String, AppError>>,
+        ) -> Result<(), AppError> {
+            let stopped = self.stopped.clone();
+            let producer = tokio::spawn(async move {
+                while chunk_sender.send(Ok("word ".to_string())).await.is_ok() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                stopped.store(true, std::sync::atomic::Ordering::SeqCst);
+            });
+            producer
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_cancel_generation_keeps_partial_answer() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session =
+            database::create_session(&pool, "Cancel Session".to_string(), ModelConfig::default())
+                .await
+                .unwrap();
+
+        let stopped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let llm = Arc::new(EndlessLlmActor {
+            stopped: stopped.clone(),
+        });
+        let rag = Arc::new(MockRagActor::new());
+        let supervisor = SupervisorHandle::new_with_actors(llm, rag, Some(pool.clone()));
+
+        let pending = tokio::spawn({
+            let supervisor = supervisor.clone();
+            let session_id = session.id.clone();
+            async move {
+                supervisor
+                    .process_message(session_id, "Write forever".to_string(), None)
+                    .await
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let cancelled = supervisor
+            .cancel_generation(session.id.clone())
+            .await
+            .unwrap();
+        assert!(cancelled);
+
+        let partial = pending
+            .await
+            .unwrap()
+            .expect("Cancelled chat should succeed");
+        assert!(partial.starts_with("word "));
+
+        let messages = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].role, "assistant");
+        assert!(messages[1].interrupted);
+        assert_eq!(messages[1].content, partial);
+
+        // The backend notices the closed channel and stops streaming
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(stopped.load(std::sync::atomic::Ordering::SeqCst));
+
+        // Nothing is running anymore
+        assert!(!supervisor.cancel_generation(session.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_cancel_before_search_skips_rag_and_generation() {
+        let (pool, _temp) = setup_test_db().await;
+        let session =
+            database::create_session(&pool, "Early Cancel".to_string(), ModelConfig::default())
+                .await
+                .unwrap();
+        database::set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Library, None)
+            .await
+            .unwrap();
+
+        let llm = Arc::new(MockLlmActor::new("Never sent."));
+        let rag = Arc::new(MockRagActor::new());
+        // The cancel arrives while the message is still being analysed
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        cancel_tx.send(()).unwrap();
+
+        let partial = SupervisorRunner::<MockLlmActor, MockRagActor>::handle_user_message(
+            llm.clone(),
+            None,
+            rag.clone(),
+            Arc::new(BrainAnalyzer::new()),
+            Some(pool.clone()),
+            Arc::default(),
+            session.id.clone(),
+            UserTurn::New {
+                content: RAG_QUESTION.to_string(),
+            },
+            None,
+            cancel_rx,
+        )
+        .await
+        .unwrap();
+
+        assert!(partial.is_empty());
+        assert_eq!(
+            rag.search_count.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+        assert_eq!(llm.get_call_count(), 0);
+        let messages = database::get_session_messages(&pool, &session.id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].role, "assistant");
+        assert!(messages[1].interrupted);
+        assert!(messages[1].content.is_empty());
     }
 
     #[tokio::test]
@@ -1,4 +1,6 @@
-use crate::actors::messages::{AppError, SearchResult};
+use crate::actors::messages::{AppError, ChatMessage, SearchOptions, SearchResult};
+use crate::chunking::ChunkingOptions;
+use crate::models::{RerankOptions, SamplingParams};
 use async_trait::async_trait;
 use tokio::sync::mpsc;
 
@@ -8,22 +10,60 @@ use tokio::sync::mpsc;
 /// backends (e.g., local llama.cpp, remote API) to be used interchangeably.
 #[async_trait]
 pub trait LlmActor: Send + Sync + 'static {
+    /// Generates a complete text response to a conversation of role-tagged messages.
+    async fn chat_with_params(
+        &self,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+    ) -> Result<String, AppError>;
+
+    /// Generates a streaming response to a conversation, sending chunks of text as they
+    /// are produced.
+    async fn stream_chat_with_params(
+        &self,
+        messages: Vec<ChatMessage>,
+        params: SamplingParams,
+        chunk_sender: mpsc::Sender<Result<String, AppError>>,
+    ) -> Result<(), AppError>;
+
+    /// Returns a backend that answers with the installed model `model_id`.
+    ///
+    /// Returns `None` if this backend cannot switch models or the model is not installed,
+    /// in which case requests go to the currently loaded model.
+    fn for_model(&self, _model_id: &str) -> Option<Box<dyn LlmActor>> {
+        None
+    }
+
     /// Generates a complete text response based on a prompt and optional parameters.
     async fn generate_with_params(
         &self,
         prompt: String,
         system_prompt: Option<String>,
         temperature: Option<f32>,
-    ) -> Result<String, AppError>;
+    ) -> Result<String, AppError> {
+        self.chat_with_params(
+            ChatMessage::single_turn(prompt, system_prompt),
+            SamplingParams::with_temperature(temperature),
+        )
+        .await
+    }
 
     /// Generates a streaming response, sending chunks of text as they are produced.
+    #[allow(dead_code)]
     async fn stream_generate_with_params(
         &self,
         prompt: String,
         system_prompt: Option<String>,
         temperature: Option<f32>,
         chunk_sender: mpsc::Sender<Result<String, AppError>>,
-    ) -> Result<(), AppError>;
+    ) -> Result<(), AppError> {
+        self.stream_chat_with_params(
+            ChatMessage::single_turn(prompt, system_prompt),
+            SamplingParams::with_temperature(temperature),
+            chunk_sender,
+        )
+        .await
+    }
 }
 
 /// Defines the public interface for a RAG (Retrieval-Augmented Generation) actor.
@@ -31,16 +71,41 @@ pub trait LlmActor: Send + Sync + 'static {
 /// This trait abstracts the logic for managing and querying a knowledge base.
 #[async_trait]
 pub trait RagActor: Send + Sync + 'static {
-    /// Ingests new content into the knowledge base.
-    async fn ingest(&self, content: String, metadata: Option<String>) -> Result<String, AppError>;
+    /// Ingests new content into the knowledge base, tagging its chunks with `file_id`.
+    async fn ingest(&self, content: String, file_id: Option<String>) -> Result<String, AppError> {
+        self.ingest_with_options(content, file_id, ChunkingOptions::default())
+            .await
+    }
+
+    /// Ingests new content, splitting it into chunks as configured.
+    async fn ingest_with_options(
+        &self,
+        content: String,
+        file_id: Option<String>,
+        chunking: ChunkingOptions,
+    ) -> Result<String, AppError>;
 
-    /// Searches the knowledge base for content relevant to a query.
+    /// Searches the knowledge base for content relevant to a query, in the given files or,
+    /// if `file_ids` is empty, in the whole library, combining vector and lexical rankings
+    /// as configured.
     async fn search_with_filters(
         &self,
         query: String,
         file_ids: Vec<String>,
+        options: SearchOptions,
+    ) -> Result<Vec<SearchResult>, AppError>;
+
+    /// Rescores results with the cross-encoder and keeps the best ones above the threshold.
+    async fn rerank(
+        &self,
+        query: String,
+        results: Vec<SearchResult>,
+        options: RerankOptions,
     ) -> Result<Vec<SearchResult>, AppError>;
 
+    /// Fetches a chunk by id, or `None` if it no longer exists.
+    async fn get_chunk(&self, chunk_id: String) -> Result<Option<SearchResult>, AppError>;
+
     /// Deletes all vectors associated with a specific file.
     async fn delete_for_file(&self, file_id: String) -> Result<(), AppError>;
 }
@@ -48,6 +113,7 @@ pub trait RagActor: Send + Sync + 'static {
 #[cfg(test)]
 pub mod mocks {
     use super::*;
+    use crate::chat_template::ChatRole;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
     use tokio::sync::Mutex;
@@ -58,6 +124,9 @@ pub mod mocks {
         pub call_count: AtomicUsize,
         pub last_prompt: Arc<Mutex<Option<String>>>,
         pub last_system_prompt: Arc<Mutex<Option<String>>>,
+        pub last_messages: Arc<Mutex<Vec<ChatMessage>>>,
+        pub last_params: Arc<Mutex<Option<SamplingParams>>>,
+        pub requested_models: std::sync::Mutex<Vec<String>>,
         pub should_fail: std::sync::atomic::AtomicBool,
     }
 
@@ -68,6 +137,9 @@ pub mod mocks {
                 call_count: AtomicUsize::new(0),
                 last_prompt: Arc::new(Mutex::new(None)),
                 last_system_prompt: Arc::new(Mutex::new(None)),
+                last_messages: Arc::new(Mutex::new(Vec::new())),
+                last_params: Arc::new(Mutex::new(None)),
+                requested_models: std::sync::Mutex::new(Vec::new()),
                 should_fail: std::sync::atomic::AtomicBool::new(false),
             }
         }
@@ -86,19 +158,42 @@ pub mod mocks {
         pub fn get_call_count(&self) -> usize {
             self.call_count.load(Ordering::SeqCst)
         }
+
+        /// Records a call; `last_prompt` is the final user turn and `last_system_prompt`
+        /// the first system turn, as seen by single-prompt callers.
+        async fn record(&self, messages: Vec<ChatMessage>, params: SamplingParams) {
+            self.call_count.fetch_add(1, Ordering::SeqCst);
+            *self.last_prompt.lock().await = messages
+                .iter()
+                .rev()
+                .find(|m| m.role == ChatRole::User)
+                .map(|m| m.content.clone());
+            *self.last_system_prompt.lock().await = messages
+                .iter()
+                .find(|m| m.role == ChatRole::System)
+                .map(|m| m.content.clone());
+            *self.last_messages.lock().await = messages;
+            *self.last_params.lock().await = Some(params);
+        }
     }
 
     #[async_trait]
     impl LlmActor for MockLlmActor {
-        async fn generate_with_params(
+        /// Records the model and keeps answering itself.
+        fn for_model(&self, model_id: &str) -> Option<Box<dyn LlmActor>> {
+            self.requested_models
+                .lock()
+                .unwrap()
+                .push(model_id.to_string());
+            None
+        }
+
+        async fn chat_with_params(
             &self,
-            prompt: String,
-            system_prompt: Option<String>,
-            _temperature: Option<f32>,
+            messages: Vec<ChatMessage>,
+            params: SamplingParams,
         ) -> Result<String, AppError> {
-            self.call_count.fetch_add(1, Ordering::SeqCst);
-            *self.last_prompt.lock().await = Some(prompt);
-            *self.last_system_prompt.lock().await = system_prompt;
+            self.record(messages, params).await;
 
             if self.should_fail.load(Ordering::SeqCst) {
                 return Err(AppError::Internal("Mock LLM failure".to_string()));
@@ -107,16 +202,13 @@ pub mod mocks {
             Ok(self.response.lock().await.clone())
         }
 
-        async fn stream_generate_with_params(
+        async fn stream_chat_with_params(
             &self,
-            prompt: String,
-            system_prompt: Option<String>,
-            _temperature: Option<f32>,
+            messages: Vec<ChatMessage>,
+            params: SamplingParams,
             chunk_sender: mpsc::Sender<Result<String, AppError>>,
         ) -> Result<(), AppError> {
-            self.call_count.fetch_add(1, Ordering::SeqCst);
-            *self.last_prompt.lock().await = Some(prompt);
-            *self.last_system_prompt.lock().await = system_prompt;
+            self.record(messages, params).await;
 
             if self.should_fail.load(Ordering::SeqCst) {
                 let _ = chunk_sender
@@ -141,8 +233,12 @@ pub mod mocks {
         pub ingest_count: AtomicUsize,
         pub search_count: AtomicUsize,
         pub delete_count: AtomicUsize,
+        pub rerank_count: AtomicUsize,
         pub last_query: Arc<Mutex<Option<String>>>,
+        pub last_file_ids: Arc<Mutex<Option<Vec<String>>>>,
+        pub last_options: Arc<Mutex<Option<SearchOptions>>>,
         pub last_ingested: Arc<Mutex<Option<String>>>,
+        pub last_chunking: Arc<Mutex<Option<ChunkingOptions>>>,
         pub should_fail: std::sync::atomic::AtomicBool,
     }
 
@@ -153,8 +249,12 @@ pub mod mocks {
                 ingest_count: AtomicUsize::new(0),
                 search_count: AtomicUsize::new(0),
                 delete_count: AtomicUsize::new(0),
+                rerank_count: AtomicUsize::new(0),
                 last_query: Arc::new(Mutex::new(None)),
+                last_file_ids: Arc::new(Mutex::new(None)),
+                last_options: Arc::new(Mutex::new(None)),
                 last_ingested: Arc::new(Mutex::new(None)),
+                last_chunking: Arc::new(Mutex::new(None)),
                 should_fail: std::sync::atomic::AtomicBool::new(false),
             }
         }
@@ -181,13 +281,15 @@ pub mod mocks {
 
     #[async_trait]
     impl RagActor for MockRagActor {
-        async fn ingest(
+        async fn ingest_with_options(
             &self,
             content: String,
-            _metadata: Option<String>,
+            _file_id: Option<String>,
+            chunking: ChunkingOptions,
         ) -> Result<String, AppError> {
             self.ingest_count.fetch_add(1, Ordering::SeqCst);
             *self.last_ingested.lock().await = Some(content);
+            *self.last_chunking.lock().await = Some(chunking);
 
             if self.should_fail.load(Ordering::SeqCst) {
                 return Err(AppError::Internal("Mock RAG ingest failure".to_string()));
@@ -199,10 +301,13 @@ pub mod mocks {
         async fn search_with_filters(
             &self,
             query: String,
-            _file_ids: Vec<String>,
+            file_ids: Vec<String>,
+            options: SearchOptions,
         ) -> Result<Vec<SearchResult>, AppError> {
             self.search_count.fetch_add(1, Ordering::SeqCst);
             *self.last_query.lock().await = Some(query);
+            *self.last_file_ids.lock().await = Some(file_ids);
+            *self.last_options.lock().await = Some(options);
 
             if self.should_fail.load(Ordering::SeqCst) {
                 return Err(AppError::Internal("Mock RAG search failure".to_string()));
@@ -211,6 +316,32 @@ pub mod mocks {
             Ok(self.search_results.lock().await.clone())
         }
 
+        async fn rerank(
+            &self,
+            _query: String,
+            mut results: Vec<SearchResult>,
+            options: RerankOptions,
+        ) -> Result<Vec<SearchResult>, AppError> {
+            self.rerank_count.fetch_add(1, Ordering::SeqCst);
+
+            if self.should_fail.load(Ordering::SeqCst) {
+                return Err(AppError::Internal("Mock RAG rerank failure".to_string()));
+            }
+
+            results.truncate(options.top_k);
+            Ok(results)
+        }
+
+        async fn get_chunk(&self, chunk_id: String) -> Result<Option<SearchResult>, AppError> {
+            Ok(self
+                .search_results
+                .lock()
+                .await
+                .iter()
+                .find(|result| result.id == chunk_id)
+                .cloned())
+        }
+
         async fn delete_for_file(&self, _file_id: String) -> Result<(), AppError> {
             self.delete_count.fetch_add(1, Ordering::SeqCst);
 
@@ -227,6 +358,7 @@ pub mod mocks {
 mod tests {
     use super::mocks::*;
     use super::*;
+    use crate::actors::messages::ChunkMetadata;
 
     #[tokio::test]
     async fn test_mock_llm_actor_generate() {
@@ -263,6 +395,26 @@ mod tests {
         assert!(collected.contains("world"));
     }
 
+    #[tokio::test]
+    async fn test_generate_with_params_wraps_single_turn() {
+        let mock = MockLlmActor::new("ok");
+
+        mock.generate_with_params("Hello".to_string(), Some("Be brief".to_string()), None)
+            .await
+            .unwrap();
+
+        let messages = mock.last_messages.lock().await.clone();
+        assert_eq!(
+            messages,
+            vec![ChatMessage::system("Be brief"), ChatMessage::user("Hello")]
+        );
+        assert_eq!(mock.last_prompt.lock().await.as_deref(), Some("Hello"));
+        assert_eq!(
+            mock.last_params.lock().await.clone(),
+            Some(SamplingParams::default())
+        );
+    }
+
     #[tokio::test]
     async fn test_mock_llm_actor_failure() {
         let mock = MockLlmActor::with_failure();
@@ -277,21 +429,29 @@ mod tests {
     #[tokio::test]
     async fn test_mock_rag_actor_search() {
         let results = vec![SearchResult {
+            id: "chunk-1".to_string(),
             content: "Test content".to_string(),
-            metadata: Some("file:test.txt".to_string()),
+            metadata: ChunkMetadata {
+                file_id: Some("test.txt".to_string()),
+                ..ChunkMetadata::default()
+            },
             score: 0.9,
         }];
 
         let mock = MockRagActor::with_results(results).await;
 
         let result = mock
-            .search_with_filters("test query".to_string(), vec![])
+            .search_with_filters("test query".to_string(), vec![], SearchOptions::default())
             .await;
 
         assert!(result.is_ok());
         let results = result.unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].content, "Test content");
+        assert_eq!(
+            mock.last_options.lock().await.clone(),
+            Some(SearchOptions::default())
+        );
     }
 
     #[tokio::test]
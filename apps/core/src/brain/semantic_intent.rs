@@ -1,11 +1,12 @@
 //! Semantic Intent Classification using FastEmbed embeddings.
 //!
-//! Uses the existing AllMiniLML6V2 model to classify intents
+//! Uses the configured RAG embedding model to classify intents
 //! via cosine similarity with intent descriptions.
 //! NO additional model download required!
 
 use crate::brain::intent::Intent;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use crate::embedding_config::active_model;
+use fastembed::TextEmbedding;
 use std::sync::Arc;
 use tracing::{info, warn};
 
@@ -129,13 +130,7 @@ impl SemanticIntentClassifier {
     /// Create a new semantic classifier
     /// Uses the existing FastEmbed model from data/models/embeddings/
     pub fn new() -> Option<Self> {
-        let embeddings_dir =
-            crate::fs_manager::PortablePathManager::models_dir().join("embeddings");
-        let mut options = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
-        options.show_download_progress = false;
-        options.cache_dir = embeddings_dir;
-
-        match TextEmbedding::try_new(options) {
+        match TextEmbedding::try_new(active_model().init_options()) {
             Ok(model) => {
                 let model = Arc::new(model);
                 let mut classifier = Self {
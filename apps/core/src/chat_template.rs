@@ -0,0 +1,406 @@
+//! Chat template registry for local GGUF models.
+//!
+//! `llama-server`'s `/completion` endpoint takes a raw prompt, so the conversation has to be
+//! rendered with the exact turn markers the model was trained on. Each model family uses its
+//! own markers and stop tokens; sending ChatML to a Llama 3 model produces garbage.
+
+use crate::gguf::{self, GgufMetadata};
+use serde::Serialize;
+use std::path::Path;
+
+/// The role of a single turn in a rendered conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    /// Retrieved document context. Rendered as a system turn, since no template has a
+    /// dedicated role for it.
+    Context,
+}
+
+impl ChatRole {
+    /// Parses a role as stored in the `messages` table, treating unknown roles as `User`.
+    pub fn from_db_role(role: &str) -> Self {
+        match role {
+            "system" => ChatRole::System,
+            "assistant" => ChatRole::Assistant,
+            "context" => ChatRole::Context,
+            _ => ChatRole::User,
+        }
+    }
+
+    /// The role name used by ChatML-style templates and OpenAI-compatible APIs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System | ChatRole::Context => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+}
+
+/// A prompt format used by a family of models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatTemplate {
+    /// `<|im_start|>role ... <|im_end|>` (Qwen, Yi, Hermes and most fine-tunes).
+    #[default]
+    ChatMl,
+    /// `<|start_header_id|>role<|end_header_id|> ... <|eot_id|>` (Llama 3.x).
+    Llama3,
+    /// `[INST] ... [/INST]` (Mistral, Mixtral).
+    Mistral,
+    /// `<start_of_turn>user ... <end_of_turn>` (Gemma).
+    Gemma,
+    /// `<|user|> ... <|end|>` (Phi-3, Phi-3.5).
+    Phi3,
+}
+
+impl ChatTemplate {
+    /// Guesses the template from a model name or GGUF file name.
+    ///
+    /// Returns `None` if the name does not mention a known model family.
+    pub fn from_model_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+
+        if name.contains("llama-3") || name.contains("llama3") {
+            Some(ChatTemplate::Llama3)
+        } else if name.contains("mistral") || name.contains("mixtral") {
+            Some(ChatTemplate::Mistral)
+        } else if name.contains("gemma") {
+            Some(ChatTemplate::Gemma)
+        } else if name.contains("phi-3") || name.contains("phi3") {
+            Some(ChatTemplate::Phi3)
+        } else if name.contains("qwen") || name.contains("hermes") || name.contains("yi-") {
+            Some(ChatTemplate::ChatMl)
+        } else {
+            None
+        }
+    }
+
+    /// Identifies the template from the Jinja `tokenizer.chat_template` stored in a GGUF file.
+    ///
+    /// Only the turn markers are inspected; the Jinja itself is never executed.
+    pub fn from_gguf_template(jinja: &str) -> Option<Self> {
+        if jinja.contains("<|start_header_id|>") {
+            Some(ChatTemplate::Llama3)
+        } else if jinja.contains("<start_of_turn>") {
+            Some(ChatTemplate::Gemma)
+        } else if jinja.contains("<|im_start|>") {
+            Some(ChatTemplate::ChatMl)
+        } else if jinja.contains("<|user|>") && jinja.contains("<|end|>") {
+            Some(ChatTemplate::Phi3)
+        } else if jinja.contains("[INST]") {
+            Some(ChatTemplate::Mistral)
+        } else {
+            None
+        }
+    }
+
+    /// Picks the template for a model file from its GGUF metadata, then its file name,
+    /// falling back to ChatML (the bundled default model is Qwen2.5).
+    pub fn for_model_path(model_path: &Path) -> Self {
+        Self::for_model(model_path, gguf::read_metadata(model_path).ok().as_ref())
+    }
+
+    /// Like `for_model_path`, with metadata the caller already read.
+    ///
+    /// The embedded Jinja template is authoritative; `general.name` and the file name are
+    /// only guesses for files without one.
+    pub fn for_model(model_path: &Path, metadata: Option<&GgufMetadata>) -> Self {
+        let from_metadata = metadata.and_then(|metadata| {
+            metadata
+                .chat_template
+                .as_deref()
+                .and_then(Self::from_gguf_template)
+                .or_else(|| metadata.name.as_deref().and_then(Self::from_model_name))
+        });
+
+        from_metadata
+            .or_else(|| {
+                model_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(Self::from_model_name)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Strings that mark the end of an assistant turn and must stop generation.
+    pub fn stop_tokens(&self) -> &'static [&'static str] {
+        match self {
+            ChatTemplate::ChatMl => &["<|im_end|>", "<|im_start|>"],
+            ChatTemplate::Llama3 => &["<|eot_id|>", "<|end_of_text|>", "<|start_header_id|>"],
+            ChatTemplate::Mistral => &["</s>", "[INST]"],
+            ChatTemplate::Gemma => &["<end_of_turn>", "<start_of_turn>"],
+            ChatTemplate::Phi3 => &["<|end|>", "<|endoftext|>", "<|user|>"],
+        }
+    }
+
+    /// Renders a conversation and opens an assistant turn for the model to complete.
+    ///
+    /// The BOS token is not included because `llama-server` adds it when tokenizing.
+    /// Families without a system role (Mistral, Gemma) get the system prompt prepended
+    /// to the first user turn.
+    pub fn render(&self, turns: &[(ChatRole, &str)]) -> String {
+        let mut out = String::new();
+
+        match self {
+            ChatTemplate::ChatMl => {
+                for (role, content) in turns {
+                    out.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role.as_str(),
+                        content
+                    ));
+                }
+                out.push_str("<|im_start|>assistant\n");
+            }
+            ChatTemplate::Llama3 => {
+                for (role, content) in turns {
+                    out.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role.as_str(),
+                        content
+                    ));
+                }
+                out.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+            }
+            ChatTemplate::Phi3 => {
+                for (role, content) in turns {
+                    out.push_str(&format!("<|{}|>\n{}<|end|>\n", role.as_str(), content));
+                }
+                out.push_str("<|assistant|>\n");
+            }
+            ChatTemplate::Mistral => {
+                for (role, content) in merge_system_into_user(turns) {
+                    match role {
+                        ChatRole::Assistant => out.push_str(&format!("{}</s>", content)),
+                        _ => out.push_str(&format!("[INST] {} [/INST]", content)),
+                    }
+                }
+            }
+            ChatTemplate::Gemma => {
+                for (role, content) in merge_system_into_user(turns) {
+                    let gemma_role = match role {
+                        ChatRole::Assistant => "model",
+                        _ => "user",
+                    };
+                    out.push_str(&format!(
+                        "<start_of_turn>{}\n{}<end_of_turn>\n",
+                        gemma_role, content
+                    ));
+                }
+                out.push_str("<start_of_turn>model\n");
+            }
+        }
+
+        out
+    }
+}
+
+/// Folds system and context turns into the following user turn for templates without a
+/// system role.
+fn merge_system_into_user(turns: &[(ChatRole, &str)]) -> Vec<(ChatRole, String)> {
+    let mut merged: Vec<(ChatRole, String)> = Vec::with_capacity(turns.len());
+    let mut pending_system: Option<String> = None;
+
+    for (role, content) in turns {
+        match role {
+            ChatRole::System | ChatRole::Context if content.is_empty() => {}
+            ChatRole::System | ChatRole::Context => {
+                pending_system = Some(match pending_system.take() {
+                    Some(existing) => format!("{}\n\n{}", existing, content),
+                    None => content.to_string(),
+                });
+            }
+            ChatRole::User => {
+                let content = match pending_system.take() {
+                    Some(system) => format!("{}\n\n{}", system, content),
+                    None => content.to_string(),
+                };
+                merged.push((ChatRole::User, content));
+            }
+            ChatRole::Assistant => merged.push((ChatRole::Assistant, content.to_string())),
+        }
+    }
+
+    // A trailing system prompt without a user turn still has to reach the model
+    if let Some(system) = pending_system {
+        merged.push((ChatRole::User, system));
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURNS: &[(ChatRole, &str)] = &[
+        (ChatRole::System, "Be brief."),
+        (ChatRole::User, "Hi"),
+        (ChatRole::Assistant, "Hello!"),
+        (ChatRole::User, "Who are you?"),
+    ];
+
+    #[test]
+    fn test_from_model_name() {
+        assert_eq!(
+            ChatTemplate::from_model_name("Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf"),
+            Some(ChatTemplate::Llama3)
+        );
+        assert_eq!(
+            ChatTemplate::from_model_name("mistral-7b-instruct-v0.3.Q4_K_M.gguf"),
+            Some(ChatTemplate::Mistral)
+        );
+        assert_eq!(
+            ChatTemplate::from_model_name("gemma-2-9b-it-Q4_K_M.gguf"),
+            Some(ChatTemplate::Gemma)
+        );
+        assert_eq!(
+            ChatTemplate::from_model_name("Phi-3.5-mini-instruct-Q4_K_M.gguf"),
+            Some(ChatTemplate::Phi3)
+        );
+        assert_eq!(
+            ChatTemplate::from_model_name("qwen2.5-coder-7b-instruct-q4_k_m.gguf"),
+            Some(ChatTemplate::ChatMl)
+        );
+        assert_eq!(ChatTemplate::from_model_name("default-model.gguf"), None);
+    }
+
+    #[test]
+    fn test_for_model_path_defaults_to_chatml() {
+        let path = Path::new("/models/default-model.gguf");
+        assert_eq!(ChatTemplate::for_model_path(path), ChatTemplate::ChatMl);
+    }
+
+    #[test]
+    fn test_for_model_prefers_embedded_template() {
+        let metadata = GgufMetadata {
+            name: Some("Mistral 7B".to_string()),
+            chat_template: Some("{{ '<|start_header_id|>' + role }}".to_string()),
+            ..GgufMetadata::default()
+        };
+        let path = Path::new("models/qwen2.5-7b.gguf");
+
+        assert_eq!(
+            ChatTemplate::for_model(path, Some(&metadata)),
+            ChatTemplate::Llama3
+        );
+
+        // Without a template, the metadata name wins over the file name
+        let metadata = GgufMetadata {
+            chat_template: None,
+            ..metadata
+        };
+        assert_eq!(
+            ChatTemplate::for_model(path, Some(&metadata)),
+            ChatTemplate::Mistral
+        );
+        assert_eq!(ChatTemplate::for_model(path, None), ChatTemplate::ChatMl);
+    }
+
+    #[test]
+    fn test_from_gguf_template() {
+        assert_eq!(
+            ChatTemplate::from_gguf_template(
+                "{{ '<|start_header_id|>' + message['role'] + '<|end_header_id|>' }}"
+            ),
+            Some(ChatTemplate::Llama3)
+        );
+        assert_eq!(
+            ChatTemplate::from_gguf_template("{{ '<|im_start|>' + message['role'] }}"),
+            Some(ChatTemplate::ChatMl)
+        );
+        assert_eq!(
+            ChatTemplate::from_gguf_template("{{ '<start_of_turn>' + role }}"),
+            Some(ChatTemplate::Gemma)
+        );
+        assert_eq!(
+            ChatTemplate::from_gguf_template("{{ '[INST] ' + message['content'] }}"),
+            Some(ChatTemplate::Mistral)
+        );
+        assert_eq!(ChatTemplate::from_gguf_template("{{ content }}"), None);
+    }
+
+    #[test]
+    fn test_render_chatml() {
+        let prompt = ChatTemplate::ChatMl.render(TURNS);
+        assert_eq!(
+            prompt,
+            "<|im_start|>system\nBe brief.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\nHello!<|im_end|>\n\
+             <|im_start|>user\nWho are you?<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn test_render_llama3() {
+        let prompt = ChatTemplate::Llama3.render(&[(ChatRole::User, "Hi")]);
+        assert_eq!(
+            prompt,
+            "<|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_render_mistral_merges_system_prompt() {
+        let prompt = ChatTemplate::Mistral.render(TURNS);
+        assert_eq!(
+            prompt,
+            "[INST] Be brief.\n\nHi [/INST]Hello!</s>[INST] Who are you? [/INST]"
+        );
+    }
+
+    #[test]
+    fn test_render_gemma_uses_model_role() {
+        let prompt = ChatTemplate::Gemma.render(TURNS);
+        assert!(prompt.starts_with("<start_of_turn>user\nBe brief.\n\nHi<end_of_turn>\n"));
+        assert!(prompt.contains("<start_of_turn>model\nHello!<end_of_turn>\n"));
+        assert!(prompt.ends_with("<start_of_turn>model\n"));
+    }
+
+    #[test]
+    fn test_render_phi3() {
+        let prompt = ChatTemplate::Phi3.render(&[(ChatRole::User, "Hi")]);
+        assert_eq!(prompt, "<|user|>\nHi<|end|>\n<|assistant|>\n");
+    }
+
+    #[test]
+    fn test_render_context_as_system_turn() {
+        let turns = [
+            (ChatRole::Context, "Context:\nRust is fast."),
+            (ChatRole::User, "Is Rust fast?"),
+        ];
+        let prompt = ChatTemplate::ChatMl.render(&turns);
+        assert!(prompt.starts_with("<|im_start|>system\nContext:\nRust is fast.<|im_end|>\n"));
+
+        let prompt = ChatTemplate::Mistral.render(&turns);
+        assert_eq!(
+            prompt,
+            "[INST] Context:\nRust is fast.\n\nIs Rust fast? [/INST]"
+        );
+    }
+
+    #[test]
+    fn test_role_from_db_role() {
+        assert_eq!(ChatRole::from_db_role("assistant"), ChatRole::Assistant);
+        assert_eq!(ChatRole::from_db_role("system"), ChatRole::System);
+        assert_eq!(ChatRole::from_db_role("user"), ChatRole::User);
+        assert_eq!(ChatRole::from_db_role("tool"), ChatRole::User);
+    }
+
+    #[test]
+    fn test_stop_tokens_match_turn_markers() {
+        assert!(ChatTemplate::ChatMl.stop_tokens().contains(&"<|im_end|>"));
+        assert!(ChatTemplate::Llama3.stop_tokens().contains(&"<|eot_id|>"));
+        assert!(ChatTemplate::Gemma.stop_tokens().contains(&"<end_of_turn>"));
+        assert!(ChatTemplate::Phi3.stop_tokens().contains(&"<|end|>"));
+        assert!(ChatTemplate::Mistral.stop_tokens().contains(&"</s>"));
+    }
+}
@@ -0,0 +1,782 @@
+//! Structure-aware splitting of documents into chunks for RAG ingestion.
+//!
+//! A chunk that cuts a table, a code block or a function in half is rarely useful once
+//! retrieved, so documents are first cut into structural pieces (markdown blocks, top-level
+//! code definitions, sentences) and the pieces are then packed into chunks of at most
+//! `max_tokens`. Only a piece that cannot fit a chunk on its own is split further, recursively
+//! on paragraphs, lines, sentences, words and finally characters.
+//!
+//! All cuts fall on `char` boundaries, and every chunk is an exact slice of the document so
+//! its character range points back to the original text.
+
+use crate::error::AppError;
+use crate::fs_manager::PortablePathManager;
+use crate::text_extract::PAGE_BREAK;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+use tracing::warn;
+use validator::{Validate, ValidationError};
+
+/// Source code extensions, ingested as plain text and split with [`ChunkStrategy::Code`].
+pub const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cpp", "hpp", "cs", "rb",
+    "php", "swift", "sh", "sql",
+];
+
+/// Default chunk size, close to the 512 characters chunks used to have.
+pub const DEFAULT_MAX_TOKENS: usize = 170;
+/// Default overlap between consecutive chunks.
+pub const DEFAULT_OVERLAP_TOKENS: usize = 16;
+
+/// Chunks this short (in bytes) are mostly noise and are dropped.
+const MIN_CHUNK_LEN: usize = 20;
+
+/// Separators tried in order when a piece is too large for a chunk.
+const RECURSIVE_SEPARATORS: &[&str] = &["\n\n", "\n", ". ", " "];
+
+/// How a document is cut into pieces before they are packed into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// Sections under `#` headings, keeping tables, lists and code fences whole.
+    Markdown,
+    /// Top-level definitions (functions, classes, impl blocks) with their doc comments.
+    Code,
+    /// Sentences, for prose such as PDFs and Word documents.
+    Sentence,
+    /// Paragraphs, then lines, sentences and words when a paragraph is too large.
+    Recursive,
+}
+
+impl ChunkStrategy {
+    /// Picks the strategy suited to a file from its extension.
+    pub fn for_file_name(file_name: &str) -> Self {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "md" | "markdown" => Self::Markdown,
+            "txt" | "pdf" | "docx" | "doc" => Self::Sentence,
+            ext if CODE_EXTENSIONS.contains(&ext) => Self::Code,
+            _ => Self::Recursive,
+        }
+    }
+}
+
+/// Options for a single [`split`] call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkingOptions {
+    pub strategy: ChunkStrategy,
+    /// Upper bound on the estimated tokens of a chunk.
+    pub max_tokens: usize,
+    /// Tokens of trailing pieces repeated at the start of the next chunk.
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkingOptions {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkStrategy::Recursive,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            overlap_tokens: DEFAULT_OVERLAP_TOKENS,
+        }
+    }
+}
+
+/// A piece of a document to embed, with where it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Character range of the chunk in the document, overlap included.
+    pub char_start: u64,
+    pub char_end: u64,
+    /// 1-based page, only known for paginated documents (see [`PAGE_BREAK`]).
+    pub page: Option<u32>,
+    /// Enclosing markdown headings, e.g. `Guide > Install`.
+    pub heading_path: Option<String>,
+}
+
+/// Persisted chunking settings, applied to the next ingestion or reindex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_overlap"))]
+pub struct ChunkingConfig {
+    /// Strategy used for every file. `None` picks one from the file extension.
+    pub strategy: Option<ChunkStrategy>,
+    #[validate(range(min = 32, max = 1024))]
+    pub max_tokens: usize,
+    #[validate(range(max = 256))]
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: None,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            overlap_tokens: DEFAULT_OVERLAP_TOKENS,
+        }
+    }
+}
+
+/// The overlap must leave room for new content in every chunk.
+fn validate_overlap(config: &ChunkingConfig) -> Result<(), ValidationError> {
+    if config.overlap_tokens * 2 > config.max_tokens {
+        return Err(ValidationError::new("overlap_too_large"));
+    }
+    Ok(())
+}
+
+impl ChunkingConfig {
+    /// Loads the saved settings, falling back to the defaults if none are saved or they are invalid.
+    pub fn load() -> Self {
+        Self::load_from(&PortablePathManager::chunking_config_path())
+    }
+
+    /// Saves the settings; documents already ingested keep their chunks until reindexed.
+    pub fn save(&self) -> Result<(), AppError> {
+        self.save_to(&PortablePathManager::chunking_config_path())
+    }
+
+    /// Options to split the given file with.
+    pub fn options_for(&self, file_name: &str) -> ChunkingOptions {
+        ChunkingOptions {
+            strategy: self
+                .strategy
+                .unwrap_or_else(|| ChunkStrategy::for_file_name(file_name)),
+            max_tokens: self.max_tokens,
+            overlap_tokens: self.overlap_tokens,
+        }
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!(
+                    "Failed to read chunking config {:?}: {}. Using defaults.",
+                    path, e
+                );
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_slice::<Self>(&bytes) {
+            Ok(config) if config.validate().is_ok() => config,
+            Ok(_) => {
+                warn!(
+                    "Chunking config {:?} is out of range. Using defaults.",
+                    path
+                );
+                Self::default()
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to parse chunking config {:?}: {}. Using defaults.",
+                    path, e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), AppError> {
+        self.validate()
+            .map_err(|e| AppError::Validation(format!("Invalid chunking config: {}", e)))?;
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| AppError::Internal(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Estimates the tokens of a text with the same 3 characters per token ratio as the
+/// context budget.
+pub fn count_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(3)
+}
+
+/// A run of pieces that chunks never straddle, e.g. a markdown section.
+struct Section {
+    heading_path: Option<String>,
+    pieces: Vec<Range<usize>>,
+}
+
+/// Splits a document into chunks and records their provenance.
+pub fn split(content: &str, options: &ChunkingOptions) -> Vec<Chunk> {
+    let max_tokens = options.max_tokens.max(1);
+    let overlap_tokens = options.overlap_tokens.min(max_tokens / 2);
+
+    let sections = match options.strategy {
+        ChunkStrategy::Markdown => markdown_sections(content),
+        ChunkStrategy::Code => vec![Section {
+            heading_path: None,
+            pieces: code_pieces(content),
+        }],
+        ChunkStrategy::Sentence => vec![Section {
+            heading_path: None,
+            pieces: sentence_pieces(content),
+        }],
+        ChunkStrategy::Recursive => vec![Section {
+            heading_path: None,
+            pieces: std::iter::once(0..content.len()).collect(),
+        }],
+    };
+
+    let page_breaks: Vec<usize> = content
+        .match_indices(PAGE_BREAK)
+        .map(|(index, _)| index)
+        .collect();
+    let mut starts = CharOffsets::new(content);
+    let mut ends = CharOffsets::new(content);
+    let mut chunks = Vec::new();
+
+    for section in sections {
+        let pieces: Vec<Range<usize>> = section
+            .pieces
+            .into_iter()
+            .flat_map(|piece| split_oversized(content, piece, max_tokens, RECURSIVE_SEPARATORS))
+            .filter_map(|piece| trim_range(content, piece))
+            .collect();
+
+        for range in pack(content, &pieces, max_tokens, overlap_tokens) {
+            let Some(range) = trim_range(content, range) else {
+                continue;
+            };
+            if range.len() <= MIN_CHUNK_LEN {
+                continue;
+            }
+            chunks.push(Chunk {
+                text: content[range.clone()].replace(PAGE_BREAK, ""),
+                char_start: starts.at(range.start),
+                char_end: ends.at(range.end),
+                page: (!page_breaks.is_empty())
+                    .then(|| page_breaks.partition_point(|&b| b < range.start) as u32 + 1),
+                heading_path: section.heading_path.clone(),
+            });
+        }
+    }
+    chunks
+}
+
+/// Greedily packs consecutive pieces into chunk ranges of at most `max_tokens`.
+///
+/// The overlap is made of whole trailing pieces, so it never cuts a sentence or a definition;
+/// a trailing piece larger than the overlap budget is simply not repeated.
+fn pack(
+    content: &str,
+    pieces: &[Range<usize>],
+    max_tokens: usize,
+    overlap_tokens: usize,
+) -> Vec<Range<usize>> {
+    let tokens: Vec<usize> = pieces
+        .iter()
+        .map(|piece| count_tokens(&content[piece.clone()]))
+        .collect();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < pieces.len() {
+        let mut end = start;
+        let mut total = 0;
+        while end < pieces.len() && (end == start || total + tokens[end] <= max_tokens) {
+            total += tokens[end];
+            end += 1;
+        }
+        ranges.push(pieces[start].start..pieces[end - 1].end);
+        if end == pieces.len() {
+            break;
+        }
+
+        // Step back over trailing pieces while they fit the overlap and leave room for
+        // the next new piece, always moving forward by at least one piece.
+        let mut next = end;
+        let mut overlap = 0;
+        while next > start + 1
+            && overlap + tokens[next - 1] <= overlap_tokens
+            && overlap + tokens[next - 1] + tokens[end] <= max_tokens
+        {
+            overlap += tokens[next - 1];
+            next -= 1;
+        }
+        start = next;
+    }
+    ranges
+}
+
+/// Splits a piece larger than `max_tokens` on the first separator it contains, recursing
+/// into the parts with the finer separators, and falls back to fixed character windows.
+fn split_oversized(
+    content: &str,
+    range: Range<usize>,
+    max_tokens: usize,
+    separators: &[&str],
+) -> Vec<Range<usize>> {
+    let text = &content[range.clone()];
+    if count_tokens(text) <= max_tokens {
+        return vec![range];
+    }
+
+    let Some(position) = separators.iter().position(|sep| text.contains(sep)) else {
+        return char_windows(text, max_tokens * 3)
+            .into_iter()
+            .map(|part| part.start + range.start..part.end + range.start)
+            .collect();
+    };
+    let separator = separators[position];
+
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for (index, _) in text.match_indices(separator) {
+        let part_end = index + separator.len();
+        parts.push(part_start..part_end);
+        part_start = part_end;
+    }
+    parts.push(part_start..text.len());
+
+    parts
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| {
+            split_oversized(
+                content,
+                part.start + range.start..part.end + range.start,
+                max_tokens,
+                &separators[position + 1..],
+            )
+        })
+        .collect()
+}
+
+/// Cuts a text into windows of `max_chars` characters.
+fn char_windows(text: &str, max_chars: usize) -> Vec<Range<usize>> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    for (count, (index, _)) in text.char_indices().enumerate() {
+        if count > 0 && count % max_chars.max(1) == 0 {
+            windows.push(start..index);
+            start = index;
+        }
+    }
+    windows.push(start..text.len());
+    windows
+}
+
+/// Narrows a range to its non-whitespace content, or `None` if there is none.
+fn trim_range(content: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let text = &content[range.clone()];
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let start = range.start + (text.len() - trimmed.len());
+    let end = start + trimmed.trim_end().len();
+    Some(start..end)
+}
+
+/// Iterates over the lines of a text with their byte range, newline excluded.
+fn lines_with_ranges(content: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut offset = 0;
+    content.split('\n').map(move |line| {
+        let start = offset;
+        offset += line.len() + 1;
+        (start..start + line.len(), line)
+    })
+}
+
+/// Cuts markdown into heading sections made of blocks: paragraphs, lists, tables and
+/// fenced code, each kept whole unless it does not fit a chunk.
+fn markdown_sections(content: &str) -> Vec<Section> {
+    let mut sections = vec![Section {
+        heading_path: None,
+        pieces: Vec::new(),
+    }];
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut block: Option<Range<usize>> = None;
+    let mut fence: Option<&str> = None;
+    let mut in_table = false;
+
+    let close = |block: &mut Option<Range<usize>>, sections: &mut Vec<Section>| {
+        if let Some(range) = block.take() {
+            sections.last_mut().unwrap().pieces.push(range);
+        }
+    };
+
+    for (range, line) in lines_with_ranges(content) {
+        let trimmed = line.trim();
+
+        if let Some(marker) = fence {
+            // Inside a code fence: everything belongs to the block until the closing marker
+            if let Some(current) = block.as_mut() {
+                current.end = range.end;
+            }
+            if trimmed.starts_with(marker) {
+                fence = None;
+                close(&mut block, &mut sections);
+            }
+            continue;
+        }
+
+        if let Some(marker) = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker))
+        {
+            close(&mut block, &mut sections);
+            fence = Some(marker);
+            block = Some(range);
+            in_table = false;
+            continue;
+        }
+
+        if let Some((level, title)) = markdown_heading(trimmed) {
+            close(&mut block, &mut sections);
+            // A heading directly followed by a subheading opens the subsection
+            let mut pieces = vec![range];
+            let parent_level = headings.last().map(|(parent, _)| *parent);
+            let last = sections.last().unwrap();
+            if parent_level.is_some_and(|parent| parent < level) && last.pieces.len() == 1 {
+                pieces.insert(0, sections.pop().unwrap().pieces[0].clone());
+            }
+            headings.retain(|(parent, _)| *parent < level);
+            headings.push((level, title.to_string()));
+            sections.push(Section {
+                heading_path: Some(
+                    headings
+                        .iter()
+                        .map(|(_, title)| title.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" > "),
+                ),
+                pieces,
+            });
+            in_table = false;
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            close(&mut block, &mut sections);
+            in_table = false;
+            continue;
+        }
+
+        // A table starts a block of its own, even without a blank line before it
+        let is_table_row = trimmed.starts_with('|');
+        if is_table_row != in_table {
+            close(&mut block, &mut sections);
+            in_table = is_table_row;
+        }
+        match block.as_mut() {
+            Some(current) => current.end = range.end,
+            None => block = Some(range),
+        }
+    }
+    close(&mut block, &mut sections);
+
+    sections.retain(|section| !section.pieces.is_empty());
+    sections
+}
+
+/// Parses a markdown ATX heading (`## Title`) into its level and title.
+fn markdown_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let title = line[level..]
+        .strip_prefix(' ')?
+        .trim_end_matches('#')
+        .trim();
+    (!title.is_empty()).then_some((level, title))
+}
+
+/// Cuts source code into top-level items.
+///
+/// Without a parser per language, an item starts at an unindented line that follows a blank
+/// line or the closing brace of the previous item. Bodies are indented, so a function stays
+/// in one piece along with the comments and attributes right above it.
+fn code_pieces(content: &str) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<&str> = None;
+
+    for (range, line) in lines_with_ranges(content) {
+        let unindented = line.starts_with(|c: char| !c.is_whitespace());
+        let after_item = previous.is_some_and(|prev| {
+            prev.trim().is_empty() || prev.starts_with('}') || prev.starts_with("end")
+        });
+        if unindented && after_item && !line.starts_with(['}', ')', ']']) && range.start > start {
+            pieces.push(start..range.start);
+            start = range.start;
+        }
+        previous = Some(line);
+    }
+    pieces.push(start..content.len());
+    pieces
+}
+
+/// Cuts prose into sentences, also breaking at paragraph and page boundaries.
+fn sentence_pieces(content: &str) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let boundary = match c {
+            '.' | '!' | '?' | '…' => next.map_or(true, char::is_whitespace),
+            '。' | '！' | '？' | PAGE_BREAK => true,
+            '\n' => next.is_some_and(|next| next == '\n' || next == PAGE_BREAK),
+            _ => false,
+        };
+        if boundary {
+            let end = index + c.len_utf8();
+            pieces.push(start..end);
+            start = end;
+        }
+    }
+    pieces.push(start..content.len());
+    pieces
+}
+
+/// Converts byte offsets into character offsets, walking forward from the last lookup.
+struct CharOffsets<'a> {
+    content: &'a str,
+    byte: usize,
+    chars: u64,
+}
+
+impl<'a> CharOffsets<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            byte: 0,
+            chars: 0,
+        }
+    }
+
+    fn at(&mut self, byte: usize) -> u64 {
+        if byte < self.byte {
+            self.byte = 0;
+            self.chars = 0;
+        }
+        self.chars += self.content[self.byte..byte].chars().count() as u64;
+        self.byte = byte;
+        self.chars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn options(
+        strategy: ChunkStrategy,
+        max_tokens: usize,
+        overlap_tokens: usize,
+    ) -> ChunkingOptions {
+        ChunkingOptions {
+            strategy,
+            max_tokens,
+            overlap_tokens,
+        }
+    }
+
+    #[test]
+    fn test_strategy_for_file_name() {
+        assert_eq!(
+            ChunkStrategy::for_file_name("README.md"),
+            ChunkStrategy::Markdown
+        );
+        assert_eq!(ChunkStrategy::for_file_name("main.RS"), ChunkStrategy::Code);
+        assert_eq!(ChunkStrategy::for_file_name("app.tsx"), ChunkStrategy::Code);
+        assert_eq!(
+            ChunkStrategy::for_file_name("report.pdf"),
+            ChunkStrategy::Sentence
+        );
+        assert_eq!(
+            ChunkStrategy::for_file_name("notes.txt"),
+            ChunkStrategy::Sentence
+        );
+        assert_eq!(
+            ChunkStrategy::for_file_name("data.csv"),
+            ChunkStrategy::Recursive
+        );
+        assert_eq!(
+            ChunkStrategy::for_file_name("no_extension"),
+            ChunkStrategy::Recursive
+        );
+    }
+
+    #[test]
+    fn test_split_records_provenance() {
+        let section = "This sentence fills the section with enough words to matter.\n".repeat(6);
+        let content = format!(
+            "# Guide\n\n## Install\n{}\x0c\n## Usage\n{}",
+            section, section
+        );
+
+        let chunks = split(&content, &options(ChunkStrategy::Markdown, 64, 8));
+
+        assert!(chunks.len() >= 2);
+        let first = &chunks[0];
+        assert_eq!(first.char_start, 0);
+        assert_eq!(first.page, Some(1));
+        assert_eq!(first.heading_path.as_deref(), Some("Guide > Install"));
+
+        let last = chunks.last().unwrap();
+        assert_eq!(last.page, Some(2));
+        assert_eq!(last.char_end as usize, content.chars().count() - 1);
+        assert!(chunks
+            .iter()
+            .any(|c| c.heading_path.as_deref() == Some("Guide > Usage")));
+        assert!(chunks.windows(2).all(|w| w[0].char_end <= w[1].char_end));
+
+        // Without form feeds or headings, pages and headings are unknown
+        let plain = split(&section, &ChunkingOptions::default());
+        assert!(plain
+            .iter()
+            .all(|c| c.page.is_none() && c.heading_path.is_none()));
+    }
+
+    #[test]
+    fn test_split_is_utf8_safe_and_respects_max_tokens() {
+        // No whitespace at all, multi-byte characters everywhere
+        let content = "日本語のテキストé🦀".repeat(200);
+
+        for strategy in [
+            ChunkStrategy::Markdown,
+            ChunkStrategy::Code,
+            ChunkStrategy::Sentence,
+            ChunkStrategy::Recursive,
+        ] {
+            let chunks = split(&content, &options(strategy, 40, 8));
+
+            assert!(chunks.len() > 1);
+            for chunk in &chunks {
+                assert!(count_tokens(&chunk.text) <= 40);
+                let expected: String = content
+                    .chars()
+                    .skip(chunk.char_start as usize)
+                    .take((chunk.char_end - chunk.char_start) as usize)
+                    .collect();
+                assert_eq!(chunk.text, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_markdown_keeps_tables_and_code_fences_whole() {
+        let intro = "Some introduction text that explains what the table below is about.\n";
+        let table = "| Option | Default |\n|---|---|\n| port | 8080 |\n| threads | 4 |";
+        let code = "```rust\nfn main() {\n\n    println!(\"hello\");\n}\n```";
+        let content = format!(
+            "# Config\n{}\n{}\n\n{}\n\nAfter.",
+            intro.repeat(3),
+            table,
+            code
+        );
+
+        let chunks = split(&content, &options(ChunkStrategy::Markdown, 48, 0));
+
+        assert!(chunks.iter().any(|c| c.text.contains(table)));
+        assert!(chunks.iter().any(|c| c.text.contains(code)));
+        assert!(chunks
+            .iter()
+            .all(|c| c.heading_path.as_deref() == Some("Config")));
+    }
+
+    #[test]
+    fn test_code_keeps_functions_whole() {
+        let content = "\
+use std::io;
+
+/// Adds two numbers together and returns the sum.
+fn add(left: i32, right: i32) -> i32 {
+    let sum = left + right;
+
+    sum
+}
+
+/// Multiplies two numbers together and returns the product.
+fn multiply(left: i32, right: i32) -> i32 {
+    left * right
+}
+";
+
+        let chunks = split(content, &options(ChunkStrategy::Code, 48, 0));
+
+        assert!(chunks
+            .iter()
+            .any(|c| c.text.contains("/// Adds") && c.text.ends_with("    sum\n}")));
+        assert!(chunks
+            .iter()
+            .any(|c| c.text.contains("/// Multiplies") && c.text.ends_with("left * right\n}")));
+    }
+
+    #[test]
+    fn test_sentence_chunks_overlap_on_whole_sentences() {
+        let content = (1..=12)
+            .map(|n| format!("Sentence number {} says something.", n))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let chunks = split(&content, &options(ChunkStrategy::Sentence, 40, 12));
+
+        assert!(chunks.len() > 1);
+        for pair in chunks.windows(2) {
+            // The next chunk starts with the last sentence of the previous one
+            let last_sentence = pair[0].text.rsplit(". ").next().unwrap();
+            assert!(pair[1].text.starts_with(last_sentence));
+            assert!(pair[1].char_start < pair[0].char_end);
+        }
+        assert!(chunks.iter().all(|c| c.text.ends_with('.')));
+    }
+
+    #[test]
+    fn test_config_validation_and_options() {
+        assert!(ChunkingConfig::default().validate().is_ok());
+        let too_much_overlap = ChunkingConfig {
+            max_tokens: 64,
+            overlap_tokens: 40,
+            ..ChunkingConfig::default()
+        };
+        assert!(too_much_overlap.validate().is_err());
+
+        let by_type = ChunkingConfig::default().options_for("lib.py");
+        assert_eq!(by_type.strategy, ChunkStrategy::Code);
+        let forced = ChunkingConfig {
+            strategy: Some(ChunkStrategy::Recursive),
+            ..ChunkingConfig::default()
+        };
+        assert_eq!(
+            forced.options_for("lib.py").strategy,
+            ChunkStrategy::Recursive
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("chunking.json");
+        let config = ChunkingConfig {
+            strategy: Some(ChunkStrategy::Sentence),
+            max_tokens: 256,
+            overlap_tokens: 32,
+        };
+
+        config.save_to(&path).unwrap();
+        assert_eq!(ChunkingConfig::load_from(&path), config);
+
+        std::fs::write(&path, r#"{"max_tokens": 8}"#).unwrap();
+        assert_eq!(ChunkingConfig::load_from(&path), ChunkingConfig::default());
+    }
+}
@@ -0,0 +1,245 @@
+//! Token-budgeted context assembly.
+//!
+//! `llama-server` gives each parallel slot an equal share of its context, shared by the
+//! prompt and the generated response. Long sessions plus retrieved documents overflow
+//! that silently, so the supervisor trims its inputs to this budget before every request.
+
+use crate::actors::llm::{CONTEXT_SIZE, PARALLEL_SLOTS};
+use crate::launch_profile::LaunchProfile;
+use crate::models::{Message, MAX_RESPONSE_TOKENS};
+
+/// Tokens taken by the chat template markers around each message (role header, end of turn).
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// Share of the remaining budget that retrieved chunks may claim before history is considered.
+const CONTEXT_SHARE: f32 = 0.5;
+
+/// Estimates the number of tokens a message will take once rendered.
+///
+/// Three characters per token is deliberately pessimistic: it holds for French prose and
+/// code, where the usual four-characters rule underestimates.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(3) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// The number of prompt tokens available for a single request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    pub prompt_tokens: usize,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self::new(CONTEXT_SIZE / PARALLEL_SLOTS, MAX_RESPONSE_TOKENS)
+    }
+}
+
+/// The inputs that fit the budget, plus what had to be left out.
+#[derive(Debug)]
+pub struct FittedContext<'a> {
+    /// The most recent history messages that fit, in chronological order.
+    pub history: &'a [Message],
+    /// The best-ranked chunks that fit, in rank order.
+    pub chunks: &'a [String],
+    pub dropped_messages: usize,
+    pub dropped_chunks: usize,
+    pub estimated_tokens: usize,
+}
+
+impl FittedContext<'_> {
+    pub fn is_trimmed(&self) -> bool {
+        self.dropped_messages > 0 || self.dropped_chunks > 0
+    }
+}
+
+impl ContextBudget {
+    /// Creates a budget for a context window, keeping `response_tokens` free for the answer.
+    pub fn new(context_tokens: usize, response_tokens: usize) -> Self {
+        Self {
+            prompt_tokens: context_tokens.saturating_sub(response_tokens),
+        }
+    }
+
+    /// Creates the budget of one slot of a `llama-server` started with `profile`.
+    pub fn for_profile(profile: &LaunchProfile) -> Self {
+        Self::new(profile.slot_context_size(), profile.response_tokens())
+    }
+
+    /// Selects the retrieved chunks and history turns that fit alongside the system prompt
+    /// and the new user message.
+    ///
+    /// The system prompt and user message are always kept. Chunks are taken in rank order
+    /// up to half of what remains, then history is filled from the newest turn backwards;
+    /// the oldest turns are dropped first.
+    pub fn fit<'a>(
+        &self,
+        system_prompt: &str,
+        history: &'a [Message],
+        chunks: &'a [String],
+        content: &str,
+    ) -> FittedContext<'a> {
+        let mut used = estimate_tokens(system_prompt) + estimate_tokens(content);
+        let remaining = self.prompt_tokens.saturating_sub(used);
+
+        // Retrieved chunks
+        let chunk_budget = (remaining as f32 * CONTEXT_SHARE) as usize;
+        let mut chunk_tokens = 0;
+        let mut kept_chunks = 0;
+        for chunk in chunks {
+            let tokens = estimate_tokens(chunk);
+            if chunk_tokens + tokens > chunk_budget {
+                break;
+            }
+            chunk_tokens += tokens;
+            kept_chunks += 1;
+        }
+        used += chunk_tokens;
+
+        // History, newest first
+        let history_budget = self.prompt_tokens.saturating_sub(used);
+        let mut history_tokens = 0;
+        let mut first_kept = history.len();
+        for (index, message) in history.iter().enumerate().rev() {
+            let tokens = estimate_tokens(&message.content);
+            if history_tokens + tokens > history_budget {
+                break;
+            }
+            history_tokens += tokens;
+            first_kept = index;
+        }
+        used += history_tokens;
+
+        FittedContext {
+            history: &history[first_kept..],
+            chunks: &chunks[..kept_chunks],
+            dropped_messages: first_kept,
+            dropped_chunks: chunks.len() - kept_chunks,
+            estimated_tokens: used,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf::GgufMetadata;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            id: 0,
+            session_id: "session".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: 0,
+            interrupted: false,
+            parent_id: None,
+            sibling_ids: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), MESSAGE_OVERHEAD_TOKENS);
+        assert_eq!(estimate_tokens("abcdef"), 2 + MESSAGE_OVERHEAD_TOKENS);
+        assert_eq!(estimate_tokens("abcdefg"), 3 + MESSAGE_OVERHEAD_TOKENS);
+    }
+
+    #[test]
+    fn test_default_budget_is_one_slot_minus_response() {
+        let budget = ContextBudget::default();
+        assert_eq!(
+            budget.prompt_tokens,
+            CONTEXT_SIZE / PARALLEL_SLOTS - MAX_RESPONSE_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_budget_follows_launch_profile() {
+        let profile = LaunchProfile {
+            context_size: 32768,
+            parallel_slots: 4,
+            ..LaunchProfile::default()
+        };
+
+        assert_eq!(
+            ContextBudget::for_profile(&profile).prompt_tokens,
+            8192 - MAX_RESPONSE_TOKENS
+        );
+        assert_eq!(
+            ContextBudget::for_profile(&LaunchProfile::default()),
+            ContextBudget::default()
+        );
+    }
+
+    #[test]
+    fn test_budget_leaves_room_for_prompt_on_small_context_model() {
+        let small = GgufMetadata {
+            context_length: Some(2048),
+            ..GgufMetadata::default()
+        };
+        let profile = LaunchProfile::default().fit_to_model(Some(&small));
+
+        assert_eq!(ContextBudget::for_profile(&profile).prompt_tokens, 1024);
+    }
+
+    #[test]
+    fn test_fit_keeps_everything_when_small() {
+        let history = vec![message("user", "Hi"), message("assistant", "Hello!")];
+        let chunks = vec!["Rust is fast.".to_string()];
+
+        let fitted = ContextBudget::default().fit("Be brief.", &history, &chunks, "Why?");
+
+        assert_eq!(fitted.history.len(), 2);
+        assert_eq!(fitted.chunks.len(), 1);
+        assert!(!fitted.is_trimmed());
+    }
+
+    #[test]
+    fn test_fit_drops_oldest_history_first() {
+        let history: Vec<Message> = (0..200)
+            .map(|i| message("user", &format!("message number {i} {}", "x".repeat(60))))
+            .collect();
+
+        let budget = ContextBudget::new(1000, 200);
+        let fitted = budget.fit("System", &history, &[], "Question");
+
+        assert!(fitted.dropped_messages > 0);
+        assert_eq!(
+            fitted.dropped_messages + fitted.history.len(),
+            history.len()
+        );
+        assert!(fitted
+            .history
+            .last()
+            .unwrap()
+            .content
+            .starts_with("message number 199"));
+        assert!(fitted.estimated_tokens <= budget.prompt_tokens);
+    }
+
+    #[test]
+    fn test_fit_limits_chunks_to_half_of_budget() {
+        let chunks: Vec<String> = (0..10).map(|_| "y".repeat(300)).collect();
+        let history = vec![message("user", "Earlier question")];
+
+        let budget = ContextBudget::new(500, 0);
+        let fitted = budget.fit("", &history, &chunks, "Question");
+
+        // Each chunk is ~104 tokens and roughly 240 tokens are available for chunks
+        assert_eq!(fitted.chunks.len(), 2);
+        assert_eq!(fitted.dropped_chunks, 8);
+        assert_eq!(fitted.history.len(), 1);
+    }
+
+    #[test]
+    fn test_fit_keeps_required_parts_when_over_budget() {
+        let history = vec![message("user", "Hi")];
+        let huge = "z".repeat(10_000);
+
+        let fitted = ContextBudget::new(100, 50).fit(&huge, &history, &[], "Question");
+
+        assert!(fitted.history.is_empty());
+        assert_eq!(fitted.dropped_messages, 1);
+        assert!(fitted.estimated_tokens > 50);
+    }
+}
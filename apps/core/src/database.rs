@@ -1,10 +1,14 @@
 use crate::encryption;
 use crate::fs_manager::PortablePathManager;
-use crate::models::{Folder, LibraryFile, Message, ModelConfig, Session, SessionFile};
+use crate::models::{
+    Folder, IngestJob, IngestJobCounts, IngestJobStatus, LibraryFile, Message, MessageSource,
+    ModelConfig, RetrievalScope, Session, SessionFile,
+};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use sqlx::types::Json;
+use std::collections::HashMap;
 use std::str::FromStr;
 use tracing::info;
 use uuid::Uuid;
@@ -24,6 +28,8 @@ struct SessionRow {
     folder_id: Option<String>,
     sort_order: i32,
     updated_at: i64,
+    retrieval_scope: RetrievalScope,
+    retrieval_folder_id: Option<String>,
 }
 
 impl SessionRow {
@@ -41,6 +47,8 @@ impl SessionRow {
             folder_id: self.folder_id,
             sort_order: self.sort_order,
             updated_at: self.updated_at,
+            retrieval_scope: self.retrieval_scope,
+            retrieval_folder_id: self.retrieval_folder_id,
         })
     }
 }
@@ -141,6 +149,8 @@ pub async fn create_session_with_id(
         folder_id: None,
         sort_order: 0,
         updated_at,
+        retrieval_scope: RetrievalScope::default(),
+        retrieval_folder_id: None,
     })
 }
 
@@ -160,7 +170,8 @@ pub async fn create_session_with_id(
 pub async fn get_session(pool: &SqlitePool, id: &str) -> Result<Session, sqlx::Error> {
     let row = sqlx::query_as::<_, SessionRow>(
         r#"
-        SELECT id, title, created_at, model_config, is_favorite, folder_id, sort_order, updated_at
+        SELECT id, title, created_at, model_config, is_favorite, folder_id, sort_order, updated_at,
+            retrieval_scope, retrieval_folder_id
         FROM sessions
         WHERE id = ?
         "#,
@@ -189,7 +200,8 @@ pub async fn get_session(pool: &SqlitePool, id: &str) -> Result<Session, sqlx::E
 pub async fn list_sessions(pool: &SqlitePool) -> Result<Vec<Session>, sqlx::Error> {
     let rows = sqlx::query_as::<_, SessionRow>(
         r#"
-        SELECT id, title, created_at, model_config, is_favorite, folder_id, sort_order, updated_at
+        SELECT id, title, created_at, model_config, is_favorite, folder_id, sort_order, updated_at,
+            retrieval_scope, retrieval_folder_id
         FROM sessions
         ORDER BY is_favorite DESC, updated_at DESC
         "#,
@@ -245,6 +257,8 @@ pub async fn update_session(
         folder_id: current_session.folder_id,
         sort_order: current_session.sort_order,
         updated_at,
+        retrieval_scope: current_session.retrieval_scope,
+        retrieval_folder_id: current_session.retrieval_folder_id,
     })
 }
 
@@ -309,8 +323,8 @@ pub async fn clear_session_messages(
         .execute(pool)
         .await?;
 
-    // Update session timestamp
-    sqlx::query("UPDATE sessions SET updated_at = ? WHERE id = ?")
+    // Update session timestamp and forget the removed branch
+    sqlx::query("UPDATE sessions SET updated_at = ?, active_message_id = NULL WHERE id = ?")
         .bind(Utc::now().timestamp())
         .bind(session_id)
         .execute(pool)
@@ -342,6 +356,34 @@ pub async fn move_session_to_folder(
     Ok(())
 }
 
+/// Sets which documents a session searches; `folder_id` is only kept for the folder scope.
+pub async fn set_session_retrieval_scope(
+    pool: &SqlitePool,
+    session_id: &str,
+    scope: RetrievalScope,
+    folder_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let folder_id = folder_id.filter(|_| scope == RetrievalScope::Folder);
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions
+        SET retrieval_scope = ?, retrieval_folder_id = ?, updated_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(scope)
+    .bind(folder_id)
+    .bind(Utc::now().timestamp())
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
 // --- Folders CRUD ---
 
 /// Create a new folder.
@@ -405,6 +447,18 @@ pub async fn delete_folder(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Erro
     .execute(pool)
     .await?;
 
+    // Sessions searching this folder fall back to their linked files
+    sqlx::query(
+        r#"
+        UPDATE sessions
+        SET retrieval_scope = 'linked', retrieval_folder_id = NULL
+        WHERE retrieval_folder_id = ?
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
     // Unfile all library files in this folder
     sqlx::query(
         r#"
@@ -429,45 +483,255 @@ pub async fn delete_folder(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Erro
 }
 
 // --- Messages CRUD ---
+//
+// Messages form a tree: each one points at the message it replies to, and the session's
+// `active_message_id` is the leaf of the branch currently shown.
 
+/// Appends a message to the active branch of a session.
 pub async fn add_message(
     pool: &SqlitePool,
     session_id: &str,
     role: &str,
     content: &str,
 ) -> Result<Message, sqlx::Error> {
-    let created_at = Utc::now().timestamp();
+    let mut tx = pool.begin().await?;
 
-    sqlx::query_as::<_, Message>(
+    let message = sqlx::query_as::<_, Message>(
         r#"
-        INSERT INTO messages (session_id, role, content, created_at)
-        VALUES (?, ?, ?, ?)
-        RETURNING id, session_id, role, content, created_at
+        INSERT INTO messages (session_id, role, content, created_at, interrupted, parent_id)
+        VALUES (?, ?, ?, ?, 0, (SELECT active_message_id FROM sessions WHERE id = ?))
+        RETURNING id, session_id, role, content, created_at, interrupted, parent_id
         "#,
     )
     .bind(session_id)
     .bind(role)
     .bind(content)
-    .bind(created_at)
+    .bind(Utc::now().timestamp())
+    .bind(session_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    set_active_message_in(&mut tx, session_id, message.id).await?;
+    tx.commit().await?;
+
+    Ok(message)
+}
+
+/// Adds a message under `parent_id` (starting a new branch if the parent already has
+/// replies) and makes it the active leaf of the session.
+pub async fn add_child_message(
+    pool: &SqlitePool,
+    session_id: &str,
+    parent_id: Option<i64>,
+    role: &str,
+    content: &str,
+    interrupted: bool,
+) -> Result<Message, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let message = sqlx::query_as::<_, Message>(
+        r#"
+        INSERT INTO messages (session_id, role, content, created_at, interrupted, parent_id)
+        VALUES (?, ?, ?, ?, ?, ?)
+        RETURNING id, session_id, role, content, created_at, interrupted, parent_id
+        "#,
+    )
+    .bind(session_id)
+    .bind(role)
+    .bind(content)
+    .bind(Utc::now().timestamp())
+    .bind(interrupted)
+    .bind(parent_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    set_active_message_in(&mut tx, session_id, message.id).await?;
+    tx.commit().await?;
+
+    Ok(message)
+}
+
+async fn set_active_message_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    session_id: &str,
+    message_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE sessions SET active_message_id = ? WHERE id = ?")
+        .bind(message_id)
+        .bind(session_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Makes `message_id` the active leaf of its session.
+pub async fn set_active_message(
+    pool: &SqlitePool,
+    session_id: &str,
+    message_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE sessions SET active_message_id = ? WHERE id = ?")
+        .bind(message_id)
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_message(pool: &SqlitePool, id: i64) -> Result<Message, sqlx::Error> {
+    sqlx::query_as::<_, Message>(
+        r#"
+        SELECT id, session_id, role, content, created_at, interrupted, parent_id
+        FROM messages
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
     .fetch_one(pool)
     .await
 }
 
+/// Returns the path from the root of the tree down to `leaf_id`, oldest first.
+pub async fn get_branch(pool: &SqlitePool, leaf_id: i64) -> Result<Vec<Message>, sqlx::Error> {
+    sqlx::query_as::<_, Message>(
+        r#"
+        WITH RECURSIVE branch(id, parent_id) AS (
+            SELECT id, parent_id FROM messages WHERE id = ?
+            UNION ALL
+            SELECT m.id, m.parent_id FROM messages m JOIN branch b ON m.id = b.parent_id
+        )
+        SELECT id, session_id, role, content, created_at, interrupted, parent_id
+        FROM messages
+        WHERE id IN (SELECT id FROM branch)
+        ORDER BY id ASC
+        "#,
+    )
+    .bind(leaf_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns the messages of the active branch of a session, oldest first, with the
+/// `sibling_ids` of each message filled in so the UI can switch branches.
 pub async fn get_session_messages(
     pool: &SqlitePool,
     session_id: &str,
 ) -> Result<Vec<Message>, sqlx::Error> {
-    sqlx::query_as::<_, Message>(
+    let active_message_id: Option<i64> =
+        sqlx::query_scalar("SELECT active_message_id FROM sessions WHERE id = ?")
+            .bind(session_id)
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+
+    let Some(leaf_id) = active_message_id else {
+        return Ok(Vec::new());
+    };
+
+    let mut messages = get_branch(pool, leaf_id).await?;
+
+    let links: Vec<(i64, Option<i64>)> =
+        sqlx::query_as("SELECT id, parent_id FROM messages WHERE session_id = ? ORDER BY id ASC")
+            .bind(session_id)
+            .fetch_all(pool)
+            .await?;
+    let mut children: HashMap<Option<i64>, Vec<i64>> = HashMap::new();
+    for (id, parent_id) in links {
+        children.entry(parent_id).or_default().push(id);
+    }
+    for message in &mut messages {
+        message.sibling_ids = children.remove(&message.parent_id).unwrap_or_default();
+    }
+
+    let rows: Vec<MessageSourceRow> = sqlx::query_as(
         r#"
-        SELECT id, session_id, role, content, created_at
-        FROM messages
-        WHERE session_id = ?
-        ORDER BY created_at ASC
+        SELECT s.message_id, s.chunk_id, s.file_id, s.file_name, s.chunk_index, s.page,
+               s.char_start, s.char_end, s.heading_path, s.score
+        FROM message_sources s
+        JOIN messages m ON m.id = s.message_id
+        WHERE m.session_id = ?
+        ORDER BY s.message_id ASC, s.position ASC
         "#,
     )
     .bind(session_id)
     .fetch_all(pool)
-    .await
+    .await?;
+    let mut sources: HashMap<i64, Vec<MessageSource>> = HashMap::new();
+    for row in rows {
+        sources.entry(row.message_id).or_default().push(row.source);
+    }
+    for message in &mut messages {
+        message.sources = sources.remove(&message.id).unwrap_or_default();
+    }
+
+    Ok(messages)
+}
+
+#[derive(sqlx::FromRow)]
+struct MessageSourceRow {
+    message_id: i64,
+    #[sqlx(flatten)]
+    source: MessageSource,
+}
+
+/// Stores the sources an assistant message was grounded on, in prompt order.
+pub async fn add_message_sources(
+    pool: &SqlitePool,
+    message_id: i64,
+    sources: &[MessageSource],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (position, source) in sources.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO message_sources (message_id, position, chunk_id, file_id, file_name,
+                chunk_index, page, char_start, char_end, heading_path, score)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(message_id)
+        .bind(position as i64)
+        .bind(&source.chunk_id)
+        .bind(&source.file_id)
+        .bind(&source.file_name)
+        .bind(source.chunk_index)
+        .bind(source.page)
+        .bind(source.char_start)
+        .bind(source.char_end)
+        .bind(&source.heading_path)
+        .bind(source.score)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Shows the branch containing `message_id`, following the most recent reply at each
+/// level below it, and returns the new active branch.
+pub async fn switch_branch(
+    pool: &SqlitePool,
+    session_id: &str,
+    message_id: i64,
+) -> Result<Vec<Message>, sqlx::Error> {
+    let message = get_message(pool, message_id).await?;
+    if message.session_id != session_id {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let mut leaf_id = message.id;
+    while let Some(child_id) = sqlx::query_scalar::<_, i64>(
+        "SELECT id FROM messages WHERE parent_id = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(leaf_id)
+    .fetch_optional(pool)
+    .await?
+    {
+        leaf_id = child_id;
+    }
+
+    set_active_message(pool, session_id, leaf_id).await?;
+    get_session_messages(pool, session_id).await
 }
 
 // --- Library Files CRUD ---
@@ -478,7 +742,8 @@ pub async fn get_library_file(
 ) -> Result<LibraryFile, sqlx::Error> {
     sqlx::query_as::<_, LibraryFile>(
         r#"
-        SELECT id, name, path, file_type, size, created_at, folder_id
+        SELECT id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
         FROM library_files
         WHERE id = ?
         "#,
@@ -488,6 +753,29 @@ pub async fn get_library_file(
     .await
 }
 
+/// Finds the oldest indexed library file whose content has the given SHA-256 hash.
+///
+/// Files whose ingestion failed or has not finished are skipped, so a duplicate upload is
+/// ingested on its own rather than linked to a file that retrieval cannot find.
+pub async fn find_library_file_by_hash(
+    pool: &SqlitePool,
+    content_hash: &str,
+) -> Result<Option<LibraryFile>, sqlx::Error> {
+    sqlx::query_as::<_, LibraryFile>(
+        r#"
+        SELECT id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
+        FROM library_files
+        WHERE content_hash = ? AND embedding_model IS NOT NULL
+        ORDER BY created_at ASC
+        LIMIT 1
+        "#,
+    )
+    .bind(content_hash)
+    .fetch_optional(pool)
+    .await
+}
+
 pub async fn add_library_file(
     pool: &SqlitePool,
     id: &str,
@@ -502,7 +790,8 @@ pub async fn add_library_file(
         r#"
         INSERT INTO library_files (id, name, path, file_type, size, created_at, folder_id)
         VALUES (?, ?, ?, ?, ?, ?, NULL)
-        RETURNING id, name, path, file_type, size, created_at, folder_id
+        RETURNING id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
         "#,
     )
     .bind(id)
@@ -564,7 +853,8 @@ pub async fn get_session_files(
 pub async fn list_library_files(pool: &SqlitePool) -> Result<Vec<LibraryFile>, sqlx::Error> {
     sqlx::query_as::<_, LibraryFile>(
         r#"
-        SELECT id, name, path, file_type, size, created_at, folder_id
+        SELECT id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
         FROM library_files
         ORDER BY created_at DESC
         "#,
@@ -573,6 +863,43 @@ pub async fn list_library_files(pool: &SqlitePool) -> Result<Vec<LibraryFile>, s
     .await
 }
 
+/// Lists the library files in a folder.
+pub async fn list_folder_files(
+    pool: &SqlitePool,
+    folder_id: &str,
+) -> Result<Vec<LibraryFile>, sqlx::Error> {
+    sqlx::query_as::<_, LibraryFile>(
+        r#"
+        SELECT id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
+        FROM library_files
+        WHERE folder_id = ?
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(folder_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Records that the files indexed with the embedding model `from` were re-embedded with `to`.
+pub async fn replace_library_embedding_model(
+    pool: &SqlitePool,
+    from: &str,
+    to: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE library_files SET embedding_model = ? WHERE embedding_model = ?
+        "#,
+    )
+    .bind(to)
+    .bind(from)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
 /// Move a file to a folder.
 pub async fn move_file_to_folder(
     pool: &SqlitePool,
@@ -600,7 +927,8 @@ pub async fn delete_library_file(pool: &SqlitePool, file_id: &str) -> Result<Str
     // Get file path first
     let file = sqlx::query_as::<_, LibraryFile>(
         r#"
-        SELECT id, name, path, file_type, size, created_at, folder_id
+        SELECT id, name, path, file_type, size, created_at, folder_id, content_hash,
+            embedding_model
         FROM library_files
         WHERE id = ?
         "#,
@@ -615,6 +943,12 @@ pub async fn delete_library_file(pool: &SqlitePool, file_id: &str) -> Result<Str
         .execute(pool)
         .await?;
 
+    // Drop its ingestion job
+    sqlx::query("DELETE FROM ingest_jobs WHERE file_id = ?")
+        .bind(file_id)
+        .execute(pool)
+        .await?;
+
     // Delete file record
     sqlx::query("DELETE FROM library_files WHERE id = ?")
         .bind(file_id)
@@ -624,6 +958,230 @@ pub async fn delete_library_file(pool: &SqlitePool, file_id: &str) -> Result<Str
     Ok(file.path)
 }
 
+// --- Ingestion Jobs ---
+
+/// Queues a library file for (re)indexing, replacing any previous job for that file.
+///
+/// Unless `force` is set, the worker skips the file if its content and embedding model did
+/// not change since it was last indexed.
+pub async fn enqueue_ingest_job(
+    pool: &SqlitePool,
+    file_id: &str,
+    force: bool,
+) -> Result<IngestJob, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().timestamp();
+
+    sqlx::query(
+        r#"
+        INSERT INTO ingest_jobs (id, file_id, status, attempts, error, run_after, created_at, updated_at, force)
+        VALUES (?, ?, 'pending', 0, NULL, 0, ?, ?, ?)
+        ON CONFLICT(file_id) DO UPDATE SET
+            id = excluded.id,
+            status = 'pending',
+            attempts = 0,
+            error = NULL,
+            run_after = 0,
+            created_at = excluded.created_at,
+            updated_at = excluded.updated_at,
+            force = excluded.force
+        "#,
+    )
+    .bind(&id)
+    .bind(file_id)
+    .bind(now)
+    .bind(now)
+    .bind(force)
+    .execute(pool)
+    .await?;
+
+    get_ingest_job(pool, &id).await
+}
+
+pub async fn get_ingest_job(pool: &SqlitePool, id: &str) -> Result<IngestJob, sqlx::Error> {
+    sqlx::query_as::<_, IngestJob>(
+        r#"
+        SELECT j.id, j.file_id, l.name AS file_name, j.status, j.attempts, j.error,
+            j.run_after, j.created_at, j.updated_at, j.force
+        FROM ingest_jobs j
+        JOIN library_files l ON l.id = j.file_id
+        WHERE j.id = ?
+        "#,
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Lists the ingestion jobs, most recently queued first.
+pub async fn list_ingest_jobs(pool: &SqlitePool) -> Result<Vec<IngestJob>, sqlx::Error> {
+    sqlx::query_as::<_, IngestJob>(
+        r#"
+        SELECT j.id, j.file_id, l.name AS file_name, j.status, j.attempts, j.error,
+            j.run_after, j.created_at, j.updated_at, j.force
+        FROM ingest_jobs j
+        JOIN library_files l ON l.id = j.file_id
+        ORDER BY j.created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Marks the oldest pending job that is due as running and returns it.
+pub async fn claim_next_ingest_job(pool: &SqlitePool) -> Result<Option<IngestJob>, sqlx::Error> {
+    let now = Utc::now().timestamp();
+    let claimed = sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE ingest_jobs
+        SET status = 'running', attempts = attempts + 1, updated_at = ?
+        WHERE id = (
+            SELECT id FROM ingest_jobs
+            WHERE status = 'pending' AND run_after <= ?
+            ORDER BY run_after ASC, created_at ASC
+            LIMIT 1
+        )
+        RETURNING id
+        "#,
+    )
+    .bind(now)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    match claimed {
+        Some(id) => get_ingest_job(pool, &id).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Records the outcome of a running job.
+///
+/// Returns `false` if the job is no longer running under this id, i.e. the file was queued
+/// again or deleted meanwhile.
+pub async fn finish_ingest_job(
+    pool: &SqlitePool,
+    id: &str,
+    status: IngestJobStatus,
+    error: Option<&str>,
+    run_after: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE ingest_jobs
+        SET status = ?, error = ?, run_after = ?, updated_at = ?
+        WHERE id = ? AND status = 'running'
+        "#,
+    )
+    .bind(status)
+    .bind(error)
+    .bind(run_after)
+    .bind(Utc::now().timestamp())
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Puts jobs interrupted by a shutdown back in the queue. Returns how many were requeued.
+pub async fn requeue_running_ingest_jobs(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE ingest_jobs SET status = 'pending', updated_at = ? WHERE status = 'running'",
+    )
+    .bind(Utc::now().timestamp())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Returns when the next pending job is due, or `None` if the queue is empty.
+pub async fn next_ingest_job_at(pool: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MIN(run_after) FROM ingest_jobs WHERE status = 'pending'",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Counts the jobs in each status. Every file keeps its latest job, so once a reindex is
+/// over these are its results.
+pub async fn count_ingest_jobs(pool: &SqlitePool) -> Result<IngestJobCounts, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (IngestJobStatus, i64)>(
+        "SELECT status, COUNT(*) FROM ingest_jobs GROUP BY status",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut counts = IngestJobCounts::default();
+    for (status, count) in rows {
+        match status {
+            IngestJobStatus::Pending => counts.pending = count,
+            IngestJobStatus::Running => counts.running = count,
+            IngestJobStatus::Done => counts.done = count,
+            IngestJobStatus::Skipped => counts.skipped = count,
+            IngestJobStatus::Failed => counts.failed = count,
+        }
+    }
+    Ok(counts)
+}
+
+/// Records the SHA-256 of a file's content, used to deduplicate uploads.
+pub async fn set_library_file_hash(
+    pool: &SqlitePool,
+    file_id: &str,
+    content_hash: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE library_files SET content_hash = ? WHERE id = ?")
+        .bind(content_hash)
+        .bind(file_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Records that a file's chunks are about to be replaced with ones computed from the
+/// content hashed as `content_hash`, so it no longer counts as indexed.
+pub async fn invalidate_library_file_index(
+    pool: &SqlitePool,
+    file_id: &str,
+    content_hash: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE library_files SET content_hash = ?, embedding_model = NULL WHERE id = ?")
+        .bind(content_hash)
+        .bind(file_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Records that a file was indexed with `embedding_model` by the job `job_id`.
+///
+/// Nothing is recorded if the job is no longer running or the content changed meanwhile, so
+/// a file queued again is always indexed again.
+pub async fn mark_library_file_indexed(
+    pool: &SqlitePool,
+    file_id: &str,
+    job_id: &str,
+    content_hash: &str,
+    embedding_model: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE library_files
+        SET embedding_model = ?
+        WHERE id = ? AND content_hash = ?
+            AND EXISTS (SELECT 1 FROM ingest_jobs WHERE id = ? AND status = 'running')
+        "#,
+    )
+    .bind(embedding_model)
+    .bind(file_id)
+    .bind(content_hash)
+    .bind(job_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -779,6 +1337,7 @@ mod tests {
             model_id: "custom-model.gguf".to_string(),
             temperature: 0.9,
             system_prompt: "You are a creative writer.".to_string(),
+            ..Default::default()
         };
 
         let updated = update_session(&pool, &session.id, None, Some(new_config.clone()))
@@ -953,6 +1512,60 @@ mod tests {
         assert!(session_after.folder_id.is_none());
     }
 
+    #[tokio::test]
+    async fn test_session_retrieval_scope() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let folder = create_folder(
+            &pool,
+            "Docs".to_string(),
+            None,
+            Some("document".to_string()),
+        )
+        .await
+        .unwrap();
+        let session = create_session(&pool, "Scoped".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(session.retrieval_scope, RetrievalScope::Linked);
+
+        set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Folder, Some(&folder.id))
+            .await
+            .unwrap();
+        let scoped = get_session(&pool, &session.id).await.unwrap();
+        assert_eq!(scoped.retrieval_scope, RetrievalScope::Folder);
+        assert_eq!(
+            scoped.retrieval_folder_id.as_deref(),
+            Some(folder.id.as_str())
+        );
+
+        // The folder is only kept for the folder scope
+        set_session_retrieval_scope(
+            &pool,
+            &session.id,
+            RetrievalScope::Library,
+            Some(&folder.id),
+        )
+        .await
+        .unwrap();
+        let library = get_session(&pool, &session.id).await.unwrap();
+        assert_eq!(library.retrieval_scope, RetrievalScope::Library);
+        assert!(library.retrieval_folder_id.is_none());
+
+        // Deleting the folder sends its sessions back to their linked files
+        set_session_retrieval_scope(&pool, &session.id, RetrievalScope::Folder, Some(&folder.id))
+            .await
+            .unwrap();
+        delete_folder(&pool, &folder.id).await.unwrap();
+        let after = get_session(&pool, &session.id).await.unwrap();
+        assert_eq!(after.retrieval_scope, RetrievalScope::Linked);
+        assert!(after.retrieval_folder_id.is_none());
+
+        let missing =
+            set_session_retrieval_scope(&pool, "missing", RetrievalScope::None, None).await;
+        assert!(matches!(missing, Err(sqlx::Error::RowNotFound)));
+    }
+
     #[tokio::test]
     async fn test_move_session_to_folder() {
         let (pool, _temp) = setup_test_db().await;
@@ -990,6 +1603,25 @@ mod tests {
         assert_eq!(message.role, "user");
         assert_eq!(message.content, "Hello, AI!");
         assert_eq!(message.session_id, session.id);
+        assert!(!message.interrupted);
+    }
+
+    #[tokio::test]
+    async fn test_add_interrupted_message() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = create_session(&pool, "Cancel Test".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+
+        add_child_message(&pool, &session.id, None, "assistant", "Partial ans", true)
+            .await
+            .expect("Failed to add interrupted message");
+
+        let messages = get_session_messages(&pool, &session.id).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].interrupted);
+        assert_eq!(messages[0].content, "Partial ans");
     }
 
     #[tokio::test]
@@ -1048,6 +1680,125 @@ mod tests {
         assert_eq!(messages_b[0].content, "Msg for B");
     }
 
+    #[tokio::test]
+    async fn test_add_message_links_to_previous() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = create_session(&pool, "Tree".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+
+        let first = add_message(&pool, &session.id, "user", "Q1").await.unwrap();
+        let second = add_message(&pool, &session.id, "assistant", "A1")
+            .await
+            .unwrap();
+
+        assert_eq!(first.parent_id, None);
+        assert_eq!(second.parent_id, Some(first.id));
+    }
+
+    #[tokio::test]
+    async fn test_branching_and_switching() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = create_session(&pool, "Branches".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+
+        let question = add_message(&pool, &session.id, "user", "Q").await.unwrap();
+        let first_answer = add_message(&pool, &session.id, "assistant", "A1")
+            .await
+            .unwrap();
+        add_message(&pool, &session.id, "user", "Follow-up")
+            .await
+            .unwrap();
+
+        // Regenerated answer: a sibling of the first one
+        let second_answer = add_child_message(
+            &pool,
+            &session.id,
+            Some(question.id),
+            "assistant",
+            "A2",
+            false,
+        )
+        .await
+        .unwrap();
+
+        let messages = get_session_messages(&pool, &session.id).await.unwrap();
+        let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["Q", "A2"]);
+        assert_eq!(
+            messages[1].sibling_ids,
+            vec![first_answer.id, second_answer.id]
+        );
+        assert_eq!(messages[0].sibling_ids, vec![question.id]);
+
+        // Switching back restores the original branch down to its latest leaf
+        let messages = switch_branch(&pool, &session.id, first_answer.id)
+            .await
+            .unwrap();
+        let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["Q", "A1", "Follow-up"]);
+
+        // New messages continue the active branch
+        let next = add_message(&pool, &session.id, "assistant", "A-follow")
+            .await
+            .unwrap();
+        assert_eq!(next.parent_id, Some(messages[2].id));
+    }
+
+    #[tokio::test]
+    async fn test_message_sources_round_trip() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session = create_session(&pool, "Sources".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+        add_message(&pool, &session.id, "user", "Q").await.unwrap();
+        let answer = add_message(&pool, &session.id, "assistant", "A")
+            .await
+            .unwrap();
+
+        let source = |chunk_id: &str, page: Option<i64>| MessageSource {
+            chunk_id: chunk_id.to_string(),
+            file_id: Some("file-1".to_string()),
+            file_name: Some("report.pdf".to_string()),
+            chunk_index: 2,
+            page,
+            char_start: Some(120),
+            char_end: Some(640),
+            heading_path: None,
+            score: 0.82,
+        };
+        let sources = vec![source("chunk-b", Some(3)), source("chunk-a", None)];
+        add_message_sources(&pool, answer.id, &sources)
+            .await
+            .unwrap();
+
+        let messages = get_session_messages(&pool, &session.id).await.unwrap();
+        assert!(messages[0].sources.is_empty());
+        assert_eq!(messages[1].sources, sources);
+    }
+
+    #[tokio::test]
+    async fn test_switch_branch_rejects_foreign_message() {
+        let (pool, _temp) = setup_test_db().await;
+
+        let session_a = create_session(&pool, "A".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+        let session_b = create_session(&pool, "B".to_string(), ModelConfig::default())
+            .await
+            .unwrap();
+        let message = add_message(&pool, &session_a.id, "user", "Hi")
+            .await
+            .unwrap();
+
+        let result = switch_branch(&pool, &session_b.id, message.id).await;
+        assert!(result.is_err());
+    }
+
     // ==================== Library File Tests ====================
 
     #[tokio::test]
@@ -1199,6 +1950,173 @@ mod tests {
         assert!(result.is_err());
     }
 
+    // ==================== Ingestion Job Tests ====================
+
+    #[tokio::test]
+    async fn test_ingest_job_lifecycle() {
+        let (pool, _temp) = setup_test_db().await;
+        add_library_file(&pool, "f1", "report.pdf", "/f1", "application/pdf", 10)
+            .await
+            .unwrap();
+
+        let job = enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        assert_eq!(job.file_name, "report.pdf");
+        assert_eq!(job.status, IngestJobStatus::Pending);
+        assert_eq!(count_ingest_jobs(&pool).await.unwrap().remaining(), 1);
+
+        let claimed = claim_next_ingest_job(&pool).await.unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+        assert_eq!(claimed.status, IngestJobStatus::Running);
+        assert_eq!(claimed.attempts, 1);
+        assert!(claim_next_ingest_job(&pool).await.unwrap().is_none());
+
+        // A retry later is not claimed before it is due
+        let retry_at = Utc::now().timestamp() + 60;
+        let finished = finish_ingest_job(
+            &pool,
+            &job.id,
+            IngestJobStatus::Pending,
+            Some("embedding failed"),
+            retry_at,
+        )
+        .await
+        .unwrap();
+        assert!(finished);
+        assert!(claim_next_ingest_job(&pool).await.unwrap().is_none());
+        assert_eq!(next_ingest_job_at(&pool).await.unwrap(), Some(retry_at));
+
+        // Queuing the file again resets its job
+        let requeued = enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        assert_ne!(requeued.id, job.id);
+        assert_eq!(requeued.attempts, 0);
+        assert!(requeued.error.is_none());
+        assert_eq!(list_ingest_jobs(&pool).await.unwrap().len(), 1);
+
+        let claimed = claim_next_ingest_job(&pool).await.unwrap().unwrap();
+        let finished = finish_ingest_job(&pool, &claimed.id, IngestJobStatus::Done, None, 0)
+            .await
+            .unwrap();
+        assert!(finished);
+        let counts = count_ingest_jobs(&pool).await.unwrap();
+        assert_eq!(counts.remaining(), 0);
+        assert_eq!(counts.done, 1);
+        assert_eq!(next_ingest_job_at(&pool).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_job_requeued_after_restart_and_stale_finish_ignored() {
+        let (pool, _temp) = setup_test_db().await;
+        add_library_file(&pool, "f1", "notes.md", "/f1", "text/markdown", 10)
+            .await
+            .unwrap();
+
+        let job = enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        claim_next_ingest_job(&pool).await.unwrap().unwrap();
+
+        // The app stopped while the job was running
+        assert_eq!(requeue_running_ingest_jobs(&pool).await.unwrap(), 1);
+        let claimed = claim_next_ingest_job(&pool).await.unwrap().unwrap();
+        assert_eq!(claimed.attempts, 2);
+
+        // The file is queued again while the old job runs: the old outcome is dropped
+        enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        let finished = finish_ingest_job(&pool, &job.id, IngestJobStatus::Done, None, 0)
+            .await
+            .unwrap();
+        assert!(!finished);
+        assert_eq!(
+            list_ingest_jobs(&pool).await.unwrap()[0].status,
+            IngestJobStatus::Pending
+        );
+
+        // Deleting the file drops its job
+        delete_library_file(&pool, "f1").await.unwrap();
+        assert!(list_ingest_jobs(&pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_library_file_index_state() {
+        let (pool, _temp) = setup_test_db().await;
+        let file = add_library_file(&pool, "f1", "notes.md", "/f1", "text/markdown", 10)
+            .await
+            .unwrap();
+        assert!(file.content_hash.is_none());
+        assert!(file.embedding_model.is_none());
+
+        let job = enqueue_ingest_job(&pool, "f1", true).await.unwrap();
+        assert!(job.force);
+        let job = claim_next_ingest_job(&pool).await.unwrap().unwrap();
+        invalidate_library_file_index(&pool, "f1", "abc")
+            .await
+            .unwrap();
+        assert!(
+            mark_library_file_indexed(&pool, "f1", &job.id, "abc", "model-a")
+                .await
+                .unwrap()
+        );
+        let file = get_library_file(&pool, "f1").await.unwrap();
+        assert_eq!(file.content_hash.as_deref(), Some("abc"));
+        assert_eq!(file.embedding_model.as_deref(), Some("model-a"));
+
+        // Re-embedding the knowledge base keeps the file indexed under the new model
+        assert_eq!(
+            replace_library_embedding_model(&pool, "model-a", "model-b")
+                .await
+                .unwrap(),
+            1
+        );
+        let file = get_library_file(&pool, "f1").await.unwrap();
+        assert_eq!(file.embedding_model.as_deref(), Some("model-b"));
+
+        // A job queued again meanwhile leaves the file to be indexed by the new one
+        invalidate_library_file_index(&pool, "f1", "def")
+            .await
+            .unwrap();
+        let requeued = enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        assert!(!requeued.force);
+        assert!(
+            !mark_library_file_indexed(&pool, "f1", &job.id, "def", "model-a")
+                .await
+                .unwrap()
+        );
+        assert!(get_library_file(&pool, "f1")
+            .await
+            .unwrap()
+            .embedding_model
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_find_library_file_by_hash() {
+        let (pool, _temp) = setup_test_db().await;
+        add_library_file(&pool, "f1", "spec.pdf", "/f1", "application/pdf", 10)
+            .await
+            .unwrap();
+        add_library_file(&pool, "f2", "other.pdf", "/f2", "application/pdf", 10)
+            .await
+            .unwrap();
+        set_library_file_hash(&pool, "f1", "abc").await.unwrap();
+        set_library_file_hash(&pool, "f2", "def").await.unwrap();
+
+        // Not indexed yet
+        assert!(find_library_file_by_hash(&pool, "abc")
+            .await
+            .unwrap()
+            .is_none());
+
+        enqueue_ingest_job(&pool, "f1", false).await.unwrap();
+        let job = claim_next_ingest_job(&pool).await.unwrap().unwrap();
+        mark_library_file_indexed(&pool, "f1", &job.id, "abc", "model-a")
+            .await
+            .unwrap();
+        let found = find_library_file_by_hash(&pool, "abc").await.unwrap();
+        assert_eq!(found.map(|file| file.id).as_deref(), Some("f1"));
+        assert!(find_library_file_by_hash(&pool, "missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn test_move_file_to_folder() {
         let (pool, _temp) = setup_test_db().await;
@@ -1241,6 +2159,7 @@ mod tests {
             model_id: "secret-model.gguf".to_string(),
             temperature: 0.5,
             system_prompt: "This is a secret prompt!".to_string(),
+            ..Default::default()
         };
 
         let session = create_session(&pool, "Encryption Test".to_string(), config.clone())
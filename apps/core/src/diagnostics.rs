@@ -7,7 +7,9 @@
 
 use crate::brain::BrainAnalyzer;
 use crate::database;
+use crate::embedding_config::active_model;
 use crate::fs_manager::PortablePathManager;
+use crate::llama_release;
 use crate::models::ModelConfig;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -281,6 +283,7 @@ async fn test_db_create_session() -> TestResult {
         model_id: "test-model".to_string(),
         temperature: 0.7,
         system_prompt: "Test prompt".to_string(),
+        ..Default::default()
     };
 
     match database::create_session(&pool, "Test Session".to_string(), config).await {
@@ -462,21 +465,17 @@ async fn test_rag_embeddings() -> TestResult {
     let name = "rag_embeddings";
     let category = "rag";
 
-    let embeddings_dir = PortablePathManager::models_dir().join("embeddings");
+    let embedding = active_model();
 
     let result: Result<Result<(), String>, _> = tokio::task::spawn_blocking(move || {
-        use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+        use fastembed::TextEmbedding;
 
-        let mut options = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
-        options.show_download_progress = false;
-        options.cache_dir = embeddings_dir;
-
-        let model = TextEmbedding::try_new(options).map_err(|e| e.to_string())?;
+        let model = TextEmbedding::try_new(embedding.init_options()).map_err(|e| e.to_string())?;
         let embeddings = model
             .embed(vec!["Test embedding generation".to_string()], None)
             .map_err(|e| e.to_string())?;
 
-        if embeddings.len() == 1 && embeddings[0].len() == 384 {
+        if embeddings.len() == 1 && embeddings[0].len() == embedding.dimension() {
             Ok(())
         } else {
             Err(format!(
@@ -493,7 +492,11 @@ async fn test_rag_embeddings() -> TestResult {
             name,
             category,
             start.elapsed(),
-            "Generated 384-dim embedding",
+            &format!(
+                "Generated {}-dim embedding with {}",
+                embedding.dimension(),
+                embedding.name()
+            ),
         ),
         Ok(Err(e)) => {
             TestResult::fail(name, category, start.elapsed(), "Embedding failed", Some(e))
@@ -547,6 +550,7 @@ async fn test_rag_search() -> TestResult {
     let name = "rag_search";
     let category = "rag";
 
+    use crate::actors::messages::SearchOptions;
     use crate::actors::rag::RagActorHandle;
     use crate::actors::traits::RagActor;
 
@@ -554,7 +558,11 @@ async fn test_rag_search() -> TestResult {
 
     // Search for content we just ingested
     match rag
-        .search_with_filters("WhytChat diagnostic test".to_string(), vec![])
+        .search_with_filters(
+            "WhytChat diagnostic test".to_string(),
+            vec![],
+            SearchOptions::default(),
+        )
         .await
     {
         Ok(results) => TestResult::pass(
@@ -737,11 +745,7 @@ fn test_brain_complexity() -> TestResult {
 
 /// Starts the LLM server and waits for it to be ready
 async fn ensure_llm_server_running() -> Result<(), String> {
-    let server_exe = if cfg!(windows) {
-        "llama-server.exe"
-    } else {
-        "llama-server"
-    };
+    let server_exe = llama_release::server_binary_name();
     let server_path = PortablePathManager::tools_dir()
         .join("llama")
         .join(server_exe);
@@ -773,6 +777,12 @@ async fn ensure_llm_server_running() -> Result<(), String> {
 
     // Start the server
     let mut cmd = tokio::process::Command::new(&server_path);
+    if let Some((var, value)) = server_path
+        .parent()
+        .and_then(llama_release::library_path_env)
+    {
+        cmd.env(var, value);
+    }
     cmd.arg("-m")
         .arg(&model_path)
         .arg("--host")
@@ -1443,6 +1453,7 @@ async fn test_rag_context_retrieval() -> TestResult {
     let name = "rag_context_retrieval";
     let category = "rag";
 
+    use crate::actors::messages::SearchOptions;
     use crate::actors::rag::RagActorHandle;
     use crate::actors::traits::RagActor;
 
@@ -1456,7 +1467,11 @@ async fn test_rag_context_retrieval() -> TestResult {
 
     // Search and verify relevance
     match rag
-        .search_with_filters("What is WhytChat?".to_string(), vec![])
+        .search_with_filters(
+            "What is WhytChat?".to_string(),
+            vec![],
+            SearchOptions::default(),
+        )
         .await
     {
         Ok(results) => {
@@ -1581,11 +1596,7 @@ async fn test_fs_llama_server() -> TestResult {
     let name = "fs_llama_server";
     let category = "filesystem";
 
-    let server_exe = if cfg!(windows) {
-        "llama-server.exe"
-    } else {
-        "llama-server"
-    };
+    let server_exe = llama_release::server_binary_name();
     let server_path = PortablePathManager::tools_dir()
         .join("llama")
         .join(server_exe);
@@ -1600,28 +1611,16 @@ async fn test_fs_llama_server() -> TestResult {
         );
     }
 
-    // Check required DLLs on Windows
-    #[cfg(windows)]
-    {
-        let llama_dir = PortablePathManager::tools_dir().join("llama");
-        let required = ["llama.dll", "ggml.dll"];
-        let mut missing = Vec::new();
-
-        for dll in &required {
-            if !llama_dir.join(dll).exists() {
-                missing.push(*dll);
-            }
-        }
-
-        if !missing.is_empty() {
-            return TestResult::fail(
-                name,
-                category,
-                start.elapsed(),
-                "Missing DLLs",
-                Some(missing.join(", ")),
-            );
-        }
+    // Checks the shared libraries and that the build matches this platform
+    let llama_dir = PortablePathManager::tools_dir().join("llama");
+    if let Err(e) = llama_release::validate_installation(&llama_dir, std::env::consts::OS) {
+        return TestResult::fail(
+            name,
+            category,
+            start.elapsed(),
+            "Invalid llama-server installation",
+            Some(e),
+        );
     }
 
     match std::fs::metadata(&server_path) {
@@ -1714,11 +1713,12 @@ async fn test_full_conversation_flow() -> TestResult {
     }
 
     // 4. Get RAG context (optional, may be empty)
+    use crate::actors::messages::SearchOptions;
     use crate::actors::rag::RagActorHandle;
     use crate::actors::traits::RagActor;
     let rag = RagActorHandle::new();
     let _context = rag
-        .search_with_filters(user_input.to_string(), vec![])
+        .search_with_filters(user_input.to_string(), vec![], SearchOptions::default())
         .await
         .ok();
 
@@ -0,0 +1,1163 @@
+//! Resumable, segmented downloads that can be paused and cancelled.
+//!
+//! A download is split into byte ranges fetched over parallel connections and written in place
+//! into `<file>.part`. Progress is checkpointed to `<file>.part.json`, so an interrupted
+//! download (network loss, app restart) resumes where each range stopped. Mid-stream errors are
+//! retried with exponential backoff. The `.part` file is only renamed to its final name once
+//! every range is complete, so a partial download is never mistaken for a finished one.
+
+use futures::StreamExt;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Smallest range worth its own connection.
+const MIN_SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+/// Data is flushed to disk (and counted as downloaded) in blocks of this size.
+const FLUSH_BYTES: usize = 1024 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(2);
+/// Window over which the download speed is averaged.
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Errors that end a download.
+#[derive(Debug, Error, PartialEq)]
+pub enum DownloadError {
+    #[error("Download cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Requested state of a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Pause/resume/cancel switch shared between a download and the commands controlling it.
+pub struct DownloadControl {
+    state: watch::Sender<DownloadState>,
+}
+
+impl Default for DownloadControl {
+    fn default() -> Self {
+        Self {
+            state: watch::channel(DownloadState::Running).0,
+        }
+    }
+}
+
+impl DownloadControl {
+    pub fn state(&self) -> DownloadState {
+        *self.state.borrow()
+    }
+
+    pub fn pause(&self) {
+        self.state.send_if_modified(|state| {
+            let running = *state == DownloadState::Running;
+            if running {
+                *state = DownloadState::Paused;
+            }
+            running
+        });
+    }
+
+    pub fn resume(&self) {
+        self.state.send_if_modified(|state| {
+            let paused = *state == DownloadState::Paused;
+            if paused {
+                *state = DownloadState::Running;
+            }
+            paused
+        });
+    }
+
+    pub fn cancel(&self) {
+        self.state.send_replace(DownloadState::Cancelled);
+    }
+
+    fn subscribe(&self) -> watch::Receiver<DownloadState> {
+        self.state.subscribe()
+    }
+}
+
+/// Registry of the downloads in progress, managed as Tauri state so commands can reach them.
+#[derive(Default)]
+pub struct DownloadManager {
+    active: Mutex<HashMap<String, Arc<DownloadControl>>>,
+}
+
+impl DownloadManager {
+    /// Registers a download. It stays controllable until the returned guard is dropped.
+    pub fn start(&self, id: &str) -> Result<ActiveDownload<'_>, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        if active.contains_key(id) {
+            return Err(format!("Download '{}' is already in progress", id));
+        }
+        let control = Arc::new(DownloadControl::default());
+        active.insert(id.to_string(), control.clone());
+        Ok(ActiveDownload {
+            manager: self,
+            id: id.to_string(),
+            control,
+        })
+    }
+
+    /// Returns the control of an active download.
+    pub fn get(&self, id: &str) -> Option<Arc<DownloadControl>> {
+        self.active.lock().ok()?.get(id).cloned()
+    }
+}
+
+/// A registered download; unregisters itself when dropped.
+pub struct ActiveDownload<'a> {
+    manager: &'a DownloadManager,
+    id: String,
+    control: Arc<DownloadControl>,
+}
+
+impl ActiveDownload<'_> {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn control(&self) -> &DownloadControl {
+        &self.control
+    }
+}
+
+impl Drop for ActiveDownload<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.manager.active.lock() {
+            active.remove(&self.id);
+        }
+    }
+}
+
+/// Tuning of the connections and retries.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Parallel connections used when the server supports ranges.
+    pub segments: usize,
+    /// Consecutive failed attempts tolerated per range before giving up.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled at each further attempt.
+    pub retry_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            segments: 4,
+            max_retries: 8,
+            retry_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl DownloadOptions {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.retry_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Payload of the `download-progress` event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DownloadProgress {
+    /// Overall progress of the installation, in percent.
+    pub percent: u64,
+    /// Id to pass to the pause/resume/cancel commands, or `None` between downloads.
+    pub download_id: Option<String>,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub bytes_per_second: u64,
+    /// Estimated time left, or `None` while the speed is unknown.
+    pub eta_seconds: Option<u64>,
+    pub paused: bool,
+}
+
+impl DownloadProgress {
+    /// Progress of an installation step that is not a download.
+    pub fn at_percent(percent: u64) -> Self {
+        Self {
+            percent,
+            ..Self::default()
+        }
+    }
+}
+
+/// Download speed averaged over the last few seconds.
+#[derive(Debug, Default)]
+struct SpeedMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedMeter {
+    /// Records the bytes downloaded so far and returns the current speed in bytes per second.
+    fn record(&mut self, now: Instant, downloaded: u64) -> u64 {
+        self.samples.push_back((now, downloaded));
+        while self.samples.len() > 2 && now.duration_since(self.samples[0].0) > SPEED_WINDOW {
+            self.samples.pop_front();
+        }
+
+        let (first_at, first_bytes) = self.samples[0];
+        let elapsed = now.duration_since(first_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0;
+        }
+        (downloaded.saturating_sub(first_bytes) as f64 / elapsed) as u64
+    }
+
+    /// Forgets the history, e.g. while paused.
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+fn eta_seconds(remaining: u64, bytes_per_second: u64) -> Option<u64> {
+    (bytes_per_second > 0).then(|| remaining.div_ceil(bytes_per_second))
+}
+
+/// A byte range of the file; `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Segment {
+    start: u64,
+    end: u64,
+    done: u64,
+}
+
+/// Saved state of an interrupted download.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Checkpoint {
+    url: String,
+    total_bytes: u64,
+    segments: Vec<Segment>,
+}
+
+/// Splits `[0, total)` into ranges, the first `already_done` bytes counting as downloaded.
+fn plan_segments(total: u64, already_done: u64, count: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let already_done = already_done.min(total);
+    if already_done > 0 {
+        segments.push(Segment {
+            start: 0,
+            end: already_done,
+            done: already_done,
+        });
+    }
+
+    let remaining = total - already_done;
+    if remaining == 0 {
+        return segments;
+    }
+    let count = (count as u64).min(remaining / MIN_SEGMENT_BYTES).max(1);
+    let size = remaining.div_ceil(count);
+    let mut start = already_done;
+    while start < total {
+        let end = (start + size).min(total);
+        segments.push(Segment {
+            start,
+            end,
+            done: 0,
+        });
+        start = end;
+    }
+    segments
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".part")
+}
+
+fn checkpoint_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".part.json")
+}
+
+fn load_checkpoint(path: &Path, url: &str, total: u64) -> Option<Vec<Segment>> {
+    let part_len = std::fs::metadata(part_path(path)).ok()?.len();
+    let json = std::fs::read(checkpoint_path(path)).ok()?;
+    let checkpoint: Checkpoint = serde_json::from_slice(&json).ok()?;
+    (checkpoint.url == url && checkpoint.total_bytes == total && part_len == total)
+        .then_some(checkpoint.segments)
+}
+
+fn save_checkpoint(path: &Path, url: &str, total: u64, segments: &[Segment], done: &[AtomicU64]) {
+    let checkpoint = Checkpoint {
+        url: url.to_string(),
+        total_bytes: total,
+        segments: segments
+            .iter()
+            .zip(done)
+            .map(|(segment, done)| Segment {
+                done: done.load(Ordering::SeqCst),
+                ..*segment
+            })
+            .collect(),
+    };
+    let result = serde_json::to_vec(&checkpoint)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(checkpoint_path(path), json));
+    if let Err(e) = result {
+        warn!("Failed to save download checkpoint for {:?}: {}", path, e);
+    }
+}
+
+/// Builds the HTTP client used for downloads.
+pub fn build_client() -> Result<Client, String> {
+    Client::builder()
+        .user_agent("WhytChat/1.0")
+        .tcp_keepalive(Duration::from_secs(30))
+        .connect_timeout(Duration::from_secs(30))
+        // A stalled connection is treated as an error, so it gets retried
+        .read_timeout(Duration::from_secs(60))
+        .redirect(reqwest::redirect::Policy::limited(10)) // Follow up to 10 redirects
+        .build()
+        .map_err(|e| format!("Failed to build client: {}", e))
+}
+
+/// SHA-256 the server publishes for the file at `url`, if any.
+///
+/// Hugging Face answers a `HEAD` on a `resolve` URL of a large (LFS) file with a redirect whose
+/// `X-Linked-Etag` header is the file's SHA-256. The redirect is not followed, since the CDN
+/// it points to does not send the header.
+pub async fn published_sha256(url: &str) -> Option<String> {
+    let client = Client::builder()
+        .user_agent("WhytChat/1.0")
+        .connect_timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .ok()?;
+    let res = match client.head(url).send().await {
+        Ok(res) => res,
+        Err(e) => {
+            warn!("Failed to fetch the published SHA-256 of '{}': {}", url, e);
+            return None;
+        }
+    };
+    let etag = res.headers().get("x-linked-etag")?.to_str().ok()?;
+    let hash = etag.trim_start_matches("W/").trim_matches('"');
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| hash.to_ascii_lowercase())
+}
+
+/// Total size of the file, and whether the server accepts range requests.
+async fn probe(client: &Client, url: &str) -> Result<(u64, bool), String> {
+    // HuggingFace uses 302 redirects that might not preserve Content-Length on HEAD
+    if let Ok(res) = client.head(url).send().await {
+        let headers = res.headers();
+        let length = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0);
+        let ranges = headers
+            .get(ACCEPT_RANGES)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"bytes"));
+        if res.status().is_success() && length > 0 && ranges {
+            return Ok((length, true));
+        }
+    }
+
+    info!("HEAD request didn't confirm size and ranges, trying GET with Range header");
+    let res = client
+        .get(url)
+        .header(RANGE, "bytes=0-0")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to GET range from '{}': {}", url, e))?;
+
+    // Content-Range header format: "bytes 0-0/TOTAL_SIZE"
+    let total = res
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split('/').next_back())
+        .and_then(|total| total.parse::<u64>().ok());
+    match total {
+        Some(total) if res.status() == StatusCode::PARTIAL_CONTENT => Ok((total, true)),
+        // Last resort: the server sent the whole file, so it does not support ranges
+        _ => Ok((res.content_length().unwrap_or(0), false)),
+    }
+}
+
+/// Why a single request for a range stopped.
+enum FetchError {
+    /// Paused or cancelled.
+    Interrupted,
+    Retryable {
+        error: String,
+        progressed: bool,
+    },
+    Fatal(String),
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Waits until the download is running, failing if it is cancelled.
+async fn wait_until_running(
+    state: &mut watch::Receiver<DownloadState>,
+) -> Result<(), DownloadError> {
+    loop {
+        match *state.borrow_and_update() {
+            DownloadState::Running => return Ok(()),
+            DownloadState::Cancelled => return Err(DownloadError::Cancelled),
+            DownloadState::Paused => {}
+        }
+        state
+            .changed()
+            .await
+            .map_err(|_| DownloadError::Cancelled)?;
+    }
+}
+
+/// Fetches `[offset, end)` of one segment, counting the bytes written to disk in `done`.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_range(
+    client: &Client,
+    url: &str,
+    part: &Path,
+    offset: u64,
+    end: u64,
+    ranged: bool,
+    done: &AtomicU64,
+    state: &mut watch::Receiver<DownloadState>,
+) -> Result<(), FetchError> {
+    let mut request = client.get(url);
+    if ranged {
+        request = request.header(RANGE, format!("bytes={}-{}", offset, end - 1));
+    }
+    let res = request.send().await.map_err(|e| FetchError::Retryable {
+        error: format!("Failed to GET from '{}': {}", url, e),
+        progressed: false,
+    })?;
+
+    let status = res.status();
+    let expected = if ranged {
+        StatusCode::PARTIAL_CONTENT
+    } else {
+        StatusCode::OK
+    };
+    if status != expected {
+        let error = format!("Download failed. Status: {}. URL: {}", status, url);
+        return Err(if is_retryable_status(status) {
+            FetchError::Retryable {
+                error,
+                progressed: false,
+            }
+        } else {
+            FetchError::Fatal(error)
+        });
+    }
+
+    let io_error =
+        |e: std::io::Error| FetchError::Fatal(format!("Error writing {:?}: {}", part, e));
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(part)
+        .await
+        .map_err(io_error)?;
+    file.seek(std::io::SeekFrom::Start(offset))
+        .await
+        .map_err(io_error)?;
+    let mut writer = tokio::io::BufWriter::with_capacity(FLUSH_BYTES, file);
+    let mut stream = res.bytes_stream();
+    let mut position = offset;
+    let mut pending = 0u64;
+    let mut progressed = false;
+
+    let outcome = loop {
+        tokio::select! {
+            changed = state.changed() => {
+                if changed.is_err() || *state.borrow() != DownloadState::Running {
+                    break Err(FetchError::Interrupted);
+                }
+            }
+            item = stream.next() => match item {
+                None => break Ok(()),
+                Some(Err(e)) => {
+                    break Err(FetchError::Retryable {
+                        error: format!("Error while downloading file: {}", e),
+                        progressed: false,
+                    })
+                }
+                Some(Ok(chunk)) => {
+                    // Never write past the segment, whatever the server sends
+                    let take = (chunk.len() as u64).min(end - position) as usize;
+                    writer.write_all(&chunk[..take]).await.map_err(io_error)?;
+                    position += take as u64;
+                    pending += take as u64;
+                    if pending >= FLUSH_BYTES as u64 {
+                        writer.flush().await.map_err(io_error)?;
+                        done.fetch_add(pending, Ordering::SeqCst);
+                        pending = 0;
+                        progressed = true;
+                    }
+                    if position >= end {
+                        break Ok(());
+                    }
+                }
+            }
+        }
+    };
+
+    // Only bytes that reached the file count as downloaded
+    writer.flush().await.map_err(io_error)?;
+    done.fetch_add(pending, Ordering::SeqCst);
+    progressed |= pending > 0;
+
+    match outcome {
+        Err(FetchError::Retryable { error, .. }) => {
+            Err(FetchError::Retryable { error, progressed })
+        }
+        Ok(()) if position < end => Err(FetchError::Retryable {
+            error: format!("Connection closed at byte {} of {}", position, end),
+            progressed,
+        }),
+        other => other,
+    }
+}
+
+/// Downloads one segment, retrying with backoff and waiting while paused.
+#[allow(clippy::too_many_arguments)]
+async fn run_segment(
+    client: &Client,
+    url: &str,
+    part: &Path,
+    segment: Segment,
+    done: &AtomicU64,
+    ranged: bool,
+    control: &DownloadControl,
+    options: &DownloadOptions,
+) -> Result<(), DownloadError> {
+    let mut state = control.subscribe();
+    let mut attempt = 0;
+    loop {
+        wait_until_running(&mut state).await?;
+        if !ranged {
+            // Without ranges every request starts over from the first byte
+            done.store(0, Ordering::SeqCst);
+        }
+        let offset = segment.start + done.load(Ordering::SeqCst);
+        if offset >= segment.end {
+            return Ok(());
+        }
+
+        match fetch_range(
+            client,
+            url,
+            part,
+            offset,
+            segment.end,
+            ranged,
+            done,
+            &mut state,
+        )
+        .await
+        {
+            Ok(()) | Err(FetchError::Interrupted) => {}
+            Err(FetchError::Fatal(error)) => return Err(DownloadError::Failed(error)),
+            Err(FetchError::Retryable { error, progressed }) => {
+                if progressed {
+                    attempt = 0;
+                }
+                attempt += 1;
+                if attempt > options.max_retries {
+                    return Err(DownloadError::Failed(format!(
+                        "{} (gave up after {} retries)",
+                        error, options.max_retries
+                    )));
+                }
+
+                let delay = options.backoff(attempt);
+                warn!(
+                    "{}. Retrying bytes {}-{} in {:?} (attempt {}/{})",
+                    error, offset, segment.end, delay, attempt, options.max_retries
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = state.changed() => {}
+                }
+                if control.state() == DownloadState::Cancelled {
+                    return Err(DownloadError::Cancelled);
+                }
+            }
+        }
+    }
+}
+
+/// Downloads `url` to `path`, resuming a previous attempt if possible.
+///
+/// `on_progress` is called a few times per second; its `percent` is left at 0 for the caller
+/// to fill in. A cancelled download deletes its partial data, a failed one keeps it so the
+/// next attempt resumes.
+pub async fn download(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    control: &DownloadControl,
+    options: &DownloadOptions,
+    mut on_progress: impl FnMut(DownloadProgress),
+) -> Result<(), DownloadError> {
+    info!("Starting download from '{}' to '{:?}'", url, path);
+    let (total, ranged) = probe(client, url).await.map_err(DownloadError::Failed)?;
+    if total == 0 {
+        return Err(DownloadError::Failed(format!(
+            "Failed to get valid content length from '{}', size was 0.",
+            url
+        )));
+    }
+
+    let existing_size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    if existing_size >= total {
+        info!("File already fully downloaded, skipping");
+        on_progress(DownloadProgress {
+            downloaded_bytes: total,
+            total_bytes: total,
+            ..DownloadProgress::default()
+        });
+        return Ok(());
+    }
+
+    let part = part_path(path);
+    let io_error = |e: std::io::Error| DownloadError::Failed(format!("{:?}: {}", part, e));
+    let segments = match load_checkpoint(path, url, total).filter(|_| ranged) {
+        Some(segments) => segments,
+        None => {
+            // A partial file left by a plain sequential download is kept as the first range
+            let already_done = if ranged { existing_size } else { 0 };
+            if already_done > 0 {
+                std::fs::rename(path, &part).map_err(io_error)?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(already_done == 0)
+                .open(&part)
+                .map_err(io_error)?;
+            file.set_len(total).map_err(io_error)?;
+            let count = if ranged { options.segments.max(1) } else { 1 };
+            plan_segments(total, already_done, count)
+        }
+    };
+
+    let done: Vec<AtomicU64> = segments.iter().map(|s| AtomicU64::new(s.done)).collect();
+    let resumed: u64 = segments.iter().map(|s| s.done).sum();
+    info!(
+        "Total file size: {} bytes, already downloaded: {} bytes, {} range(s), ranges supported: {}",
+        total,
+        resumed,
+        segments.len(),
+        ranged
+    );
+
+    let workers =
+        futures::future::try_join_all(segments.iter().zip(&done).map(|(segment, done)| {
+            run_segment(client, url, &part, *segment, done, ranged, control, options)
+        }));
+    tokio::pin!(workers);
+
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut speed = SpeedMeter::default();
+    let mut last_checkpoint = Instant::now();
+    let result = loop {
+        tokio::select! {
+            result = &mut workers => break result,
+            _ = ticker.tick() => {
+                let downloaded: u64 = done.iter().map(|d| d.load(Ordering::SeqCst)).sum();
+                let paused = control.state() == DownloadState::Paused;
+                let bytes_per_second = if paused {
+                    speed.reset();
+                    0
+                } else {
+                    speed.record(Instant::now(), downloaded)
+                };
+                on_progress(DownloadProgress {
+                    downloaded_bytes: downloaded,
+                    total_bytes: total,
+                    bytes_per_second,
+                    eta_seconds: eta_seconds(total - downloaded, bytes_per_second),
+                    paused,
+                    ..DownloadProgress::default()
+                });
+
+                if ranged && last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                    save_checkpoint(path, url, total, &segments, &done);
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+    };
+
+    match result {
+        Ok(_) => {
+            std::fs::rename(&part, path).map_err(io_error)?;
+            let _ = std::fs::remove_file(checkpoint_path(path));
+            on_progress(DownloadProgress {
+                downloaded_bytes: total,
+                total_bytes: total,
+                ..DownloadProgress::default()
+            });
+            info!("Download complete: {:?} ({} bytes)", path, total);
+            Ok(())
+        }
+        Err(DownloadError::Cancelled) => {
+            info!("Download of {:?} cancelled, deleting partial data", path);
+            let _ = std::fs::remove_file(&part);
+            let _ = std::fs::remove_file(checkpoint_path(path));
+            Err(DownloadError::Cancelled)
+        }
+        Err(e) => {
+            if ranged {
+                save_checkpoint(path, url, total, &segments, &done);
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path as url_path};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    /// Serves `data` with range support, failing the first `failures` GET requests.
+    struct RangeServer {
+        data: Vec<u8>,
+        failures: AtomicUsize,
+        requested: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Respond for RangeServer {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let len = self.data.len();
+            if request.method.as_str() == "HEAD" {
+                return ResponseTemplate::new(200)
+                    .insert_header("Content-Length", len.to_string().as_str())
+                    .insert_header("Accept-Ranges", "bytes");
+            }
+
+            let range = request
+                .headers
+                .get("range")
+                .map(|value| value.to_str().unwrap().to_string());
+            self.requested
+                .lock()
+                .unwrap()
+                .push(range.clone().unwrap_or_default());
+            let remaining_failures = self.failures.load(Ordering::SeqCst);
+            if remaining_failures > 0 {
+                self.failures
+                    .store(remaining_failures - 1, Ordering::SeqCst);
+                return ResponseTemplate::new(503);
+            }
+
+            let Some(range) = range else {
+                return ResponseTemplate::new(200).set_body_bytes(self.data.clone());
+            };
+            let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+            let start: usize = start.parse().unwrap();
+            let end: usize = end.parse::<usize>().unwrap().min(len - 1);
+            ResponseTemplate::new(206)
+                .insert_header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, end, len).as_str(),
+                )
+                .set_body_bytes(self.data[start..=end].to_vec())
+        }
+    }
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    async fn serve(data: &[u8], failures: usize) -> (MockServer, Arc<Mutex<Vec<String>>>) {
+        let server = MockServer::start().await;
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        Mock::given(url_path("/model.gguf"))
+            .respond_with(RangeServer {
+                data: data.to_vec(),
+                failures: AtomicUsize::new(failures),
+                requested: requested.clone(),
+            })
+            .mount(&server)
+            .await;
+        (server, requested)
+    }
+
+    fn fast_options(segments: usize) -> DownloadOptions {
+        DownloadOptions {
+            segments,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn test_plan_segments() {
+        let total = 4 * MIN_SEGMENT_BYTES + 3;
+        let segments = plan_segments(total, 0, 4);
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0].start, 0);
+        assert_eq!(segments.last().unwrap().end, total);
+        assert!(segments.windows(2).all(|w| w[0].end == w[1].start));
+
+        // Small files use a single connection
+        assert_eq!(plan_segments(1000, 0, 4).len(), 1);
+
+        // An existing prefix is kept as a completed range
+        let segments = plan_segments(1000, 400, 4);
+        assert_eq!(
+            segments,
+            vec![
+                Segment {
+                    start: 0,
+                    end: 400,
+                    done: 400
+                },
+                Segment {
+                    start: 400,
+                    end: 1000,
+                    done: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_speed_meter_and_eta() {
+        let mut meter = SpeedMeter::default();
+        let start = Instant::now();
+
+        assert_eq!(meter.record(start, 0), 0);
+        assert_eq!(
+            meter.record(start + Duration::from_secs(2), 2_000_000),
+            1_000_000
+        );
+        // Older samples drop out of the window
+        let later = start + Duration::from_secs(10);
+        meter.record(start + Duration::from_secs(9), 2_000_000);
+        assert_eq!(meter.record(later, 2_500_000), 500_000);
+
+        assert_eq!(eta_seconds(1_000_001, 1_000_000), Some(2));
+        assert_eq!(eta_seconds(1_000, 0), None);
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let options = DownloadOptions::default();
+        assert_eq!(options.backoff(1), Duration::from_secs(1));
+        assert_eq!(options.backoff(3), Duration::from_secs(4));
+        assert_eq!(options.backoff(20), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_manager_registers_downloads_until_dropped() {
+        let manager = DownloadManager::default();
+        let active = manager.start("model.gguf").unwrap();
+        assert!(manager.start("model.gguf").is_err());
+
+        manager.get("model.gguf").unwrap().pause();
+        assert_eq!(active.control().state(), DownloadState::Paused);
+        manager.get("model.gguf").unwrap().resume();
+        assert_eq!(active.control().state(), DownloadState::Running);
+
+        drop(active);
+        assert!(manager.get("model.gguf").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_segments_download_whole_file() {
+        let data = test_data(3 * MIN_SEGMENT_BYTES as usize + 17);
+        let (server, requested) = serve(&data, 0).await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+        let mut reports = Vec::new();
+
+        download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &DownloadControl::default(),
+            &fast_options(3),
+            |progress| reports.push(progress),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert!(!part_path(&target).exists());
+        assert!(!checkpoint_path(&target).exists());
+        assert_eq!(requested.lock().unwrap().len(), 3);
+        let last = reports.last().unwrap();
+        assert_eq!(last.downloaded_bytes, data.len() as u64);
+        assert_eq!(last.total_bytes, data.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors_with_backoff() {
+        let data = test_data(4096);
+        let (server, requested) = serve(&data, 2).await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+
+        download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &DownloadControl::default(),
+            &fast_options(1),
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert_eq!(requested.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries_and_keeps_checkpoint() {
+        let data = test_data(4096);
+        let (server, _) = serve(&data, 100).await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+
+        let result = download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &DownloadControl::default(),
+            &fast_options(1),
+            |_| {},
+        )
+        .await;
+
+        assert!(
+            matches!(result, Err(DownloadError::Failed(e)) if e.contains("gave up after 3 retries"))
+        );
+        assert!(!target.exists());
+        assert!(part_path(&target).exists());
+        assert!(checkpoint_path(&target).exists());
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_checkpoint() {
+        let data = test_data(2000);
+        let (server, requested) = serve(&data, 0).await;
+        let url = format!("{}/model.gguf", server.uri());
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+
+        // A previous run got the first 500 bytes of the only range
+        let mut partial = data[..500].to_vec();
+        partial.resize(data.len(), 0);
+        std::fs::write(part_path(&target), partial).unwrap();
+        let checkpoint = Checkpoint {
+            url: url.clone(),
+            total_bytes: data.len() as u64,
+            segments: vec![Segment {
+                start: 0,
+                end: 2000,
+                done: 500,
+            }],
+        };
+        std::fs::write(
+            checkpoint_path(&target),
+            serde_json::to_vec(&checkpoint).unwrap(),
+        )
+        .unwrap();
+
+        download(
+            &build_client().unwrap(),
+            &url,
+            &target,
+            &DownloadControl::default(),
+            &fast_options(4),
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert_eq!(*requested.lock().unwrap(), vec!["bytes=500-1999"]);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_legacy_partial_file() {
+        let data = test_data(2000);
+        let (server, requested) = serve(&data, 0).await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+        std::fs::write(&target, &data[..1200]).unwrap();
+
+        download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &DownloadControl::default(),
+            &fast_options(4),
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert_eq!(*requested.lock().unwrap(), vec!["bytes=1200-1999"]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_then_resume() {
+        let data = test_data(4096);
+        let (server, requested) = serve(&data, 0).await;
+        let url = format!("{}/model.gguf", server.uri());
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+        let control = Arc::new(DownloadControl::default());
+        control.pause();
+
+        let task = {
+            let control = control.clone();
+            let target = target.clone();
+            tokio::spawn(async move {
+                let mut paused_reports = 0;
+                let result = download(
+                    &build_client().unwrap(),
+                    &url,
+                    &target,
+                    &control,
+                    &fast_options(1),
+                    |progress| paused_reports += usize::from(progress.paused),
+                )
+                .await;
+                (result, paused_reports)
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(requested.lock().unwrap().is_empty());
+        assert!(!target.exists());
+
+        control.resume();
+        let (result, paused_reports) = task.await.unwrap();
+        result.unwrap();
+        assert!(paused_reports > 0);
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_deletes_partial_data() {
+        let data = test_data(4096);
+        let (server, _) = serve(&data, 0).await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+        let control = DownloadControl::default();
+        control.cancel();
+
+        let result = download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &control,
+            &fast_options(1),
+            |_| {},
+        )
+        .await;
+
+        assert_eq!(result, Err(DownloadError::Cancelled));
+        assert!(!target.exists());
+        assert!(!part_path(&target).exists());
+        assert!(!checkpoint_path(&target).exists());
+        // A cancelled download cannot be resumed
+        control.resume();
+        assert_eq!(control.state(), DownloadState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_server_without_ranges_downloads_in_one_request() {
+        let data = test_data(3000);
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data.clone()))
+            .mount(&server)
+            .await;
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("model.gguf");
+
+        download(
+            &build_client().unwrap(),
+            &format!("{}/model.gguf", server.uri()),
+            &target,
+            &DownloadControl::default(),
+            &fast_options(4),
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_published_sha256_reads_linked_etag() {
+        let hash = "B94D27B9934D3E08A52E52D7DA7DABFAC484EFE37A5380EE9088F7ACE2EFCDE9";
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(url_path("/lfs.gguf"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("Location", "https://cdn.example.com/lfs.gguf")
+                    .insert_header("X-Linked-Etag", format!("\"{}\"", hash).as_str()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(url_path("/small.json"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"abc\""))
+            .mount(&server)
+            .await;
+
+        assert_eq!(
+            published_sha256(&format!("{}/lfs.gguf", server.uri())).await,
+            Some(hash.to_ascii_lowercase())
+        );
+        assert_eq!(
+            published_sha256(&format!("{}/small.json", server.uri())).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Choice of the embedding model used by the knowledge base and the intent classifier.
+//!
+//! Vectors from different models cannot be compared, so the LanceDB table records the model
+//! that embedded it and is re-embedded when the configured model changes (see the RAG actor).
+//! The choice therefore only applies on the next start.
+
+use crate::error::AppError;
+use crate::fs_manager::PortablePathManager;
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Embedding models that can be selected, serialized under their [`name`](Self::name).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmbeddingModelKind {
+    /// Small English model, the historical default.
+    #[default]
+    #[serde(rename = "all-MiniLM-L6-v2")]
+    AllMiniLmL6V2,
+    /// Multilingual model, much better on French documents.
+    #[serde(rename = "multilingual-e5-small")]
+    MultilingualE5Small,
+    #[serde(rename = "bge-small-en-v1.5")]
+    BgeSmallEnV15,
+    /// Larger English model with a long context.
+    #[serde(rename = "nomic-embed-text-v1.5")]
+    NomicEmbedTextV15,
+}
+
+impl EmbeddingModelKind {
+    pub const ALL: [Self; 4] = [
+        Self::AllMiniLmL6V2,
+        Self::MultilingualE5Small,
+        Self::BgeSmallEnV15,
+        Self::NomicEmbedTextV15,
+    ];
+
+    /// Name recorded with every chunk and on the table embedded by this model.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AllMiniLmL6V2 => "all-MiniLM-L6-v2",
+            Self::MultilingualE5Small => "multilingual-e5-small",
+            Self::BgeSmallEnV15 => "bge-small-en-v1.5",
+            Self::NomicEmbedTextV15 => "nomic-embed-text-v1.5",
+        }
+    }
+
+    pub fn fastembed_model(self) -> EmbeddingModel {
+        match self {
+            Self::AllMiniLmL6V2 => EmbeddingModel::AllMiniLML6V2,
+            Self::MultilingualE5Small => EmbeddingModel::MultilingualE5Small,
+            Self::BgeSmallEnV15 => EmbeddingModel::BGESmallENV15,
+            Self::NomicEmbedTextV15 => EmbeddingModel::NomicEmbedTextV15,
+        }
+    }
+
+    /// Dimension of the vectors the model produces.
+    pub fn dimension(self) -> usize {
+        TextEmbedding::get_model_info(&self.fastembed_model())
+            .map(|info| info.dim)
+            .expect("fastembed describes every selectable model")
+    }
+
+    /// Prefix the model expects before a search query.
+    pub fn query_prefix(self) -> &'static str {
+        match self {
+            Self::MultilingualE5Small => "query: ",
+            Self::NomicEmbedTextV15 => "search_query: ",
+            Self::AllMiniLmL6V2 | Self::BgeSmallEnV15 => "",
+        }
+    }
+
+    /// Prefix the model expects before a document chunk.
+    pub fn passage_prefix(self) -> &'static str {
+        match self {
+            Self::MultilingualE5Small => "passage: ",
+            Self::NomicEmbedTextV15 => "search_document: ",
+            Self::AllMiniLmL6V2 | Self::BgeSmallEnV15 => "",
+        }
+    }
+
+    /// Options loading the model from (or downloading it to) `data/models/embeddings`.
+    pub fn init_options(self) -> InitOptions {
+        let mut options = InitOptions::new(self.fastembed_model());
+        options.show_download_progress = false;
+        options.cache_dir = PortablePathManager::models_dir().join("embeddings");
+        options
+    }
+}
+
+/// The model used during this run: the saved choice, read at the first call.
+pub fn active_model() -> EmbeddingModelKind {
+    static ACTIVE: OnceLock<EmbeddingModelKind> = OnceLock::new();
+    *ACTIVE.get_or_init(|| EmbeddingConfig::load().model)
+}
+
+/// Persisted embedding settings, applied on the next start.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingConfig {
+    pub model: EmbeddingModelKind,
+}
+
+impl EmbeddingConfig {
+    /// Loads the saved settings, falling back to the defaults if none are saved or they are invalid.
+    pub fn load() -> Self {
+        Self::load_from(&PortablePathManager::embedding_config_path())
+    }
+
+    /// Saves the settings; the knowledge base is re-embedded on the next start.
+    pub fn save(&self) -> Result<(), AppError> {
+        self.save_to(&PortablePathManager::embedding_config_path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!(
+                    "Failed to read embedding config {:?}: {}. Using defaults.",
+                    path, e
+                );
+                return Self::default();
+            }
+        };
+
+        serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(
+                "Failed to parse embedding config {:?}: {}. Using defaults.",
+                path, e
+            );
+            Self::default()
+        })
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), AppError> {
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| AppError::Internal(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// A selectable model and the dimension of its vectors.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingModelInfo {
+    pub model: EmbeddingModelKind,
+    pub dimension: usize,
+}
+
+/// What the settings view shows: the saved choice, the model in use and the alternatives.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingSettings {
+    pub config: EmbeddingConfig,
+    /// Differs from `config.model` until the next start.
+    pub active_model: EmbeddingModelKind,
+    pub models: Vec<EmbeddingModelInfo>,
+}
+
+impl EmbeddingSettings {
+    pub fn current() -> Self {
+        Self {
+            config: EmbeddingConfig::load(),
+            active_model: active_model(),
+            models: EmbeddingModelKind::ALL
+                .into_iter()
+                .map(|model| EmbeddingModelInfo {
+                    model,
+                    dimension: model.dimension(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_models_have_known_names_and_dimensions() {
+        for model in EmbeddingModelKind::ALL {
+            let json = serde_json::to_string(&model).unwrap();
+            assert_eq!(json, format!("\"{}\"", model.name()));
+            assert!(model.dimension() > 0);
+        }
+        assert_eq!(EmbeddingModelKind::AllMiniLmL6V2.dimension(), 384);
+        assert_eq!(EmbeddingModelKind::MultilingualE5Small.dimension(), 384);
+        assert_eq!(EmbeddingModelKind::NomicEmbedTextV15.dimension(), 768);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("embedding.json");
+        let config = EmbeddingConfig {
+            model: EmbeddingModelKind::MultilingualE5Small,
+        };
+
+        config.save_to(&path).unwrap();
+        assert_eq!(EmbeddingConfig::load_from(&path), config);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("multilingual-e5-small"));
+
+        std::fs::write(&path, r#"{"model": "word2vec"}"#).unwrap();
+        assert_eq!(
+            EmbeddingConfig::load_from(&path),
+            EmbeddingConfig::default()
+        );
+    }
+}
@@ -122,6 +122,28 @@ impl PortablePathManager {
         Self::root_dir().join("tools")
     }
 
+    /// Returns the path to the saved `llama-server` launch profile
+    /// (`<root>/data/llama_profile.json`).
+    pub fn launch_profile_path() -> PathBuf {
+        Self::data_dir().join("llama_profile.json")
+    }
+
+    /// Returns the path to the saved chunking settings (`<root>/data/chunking.json`).
+    pub fn chunking_config_path() -> PathBuf {
+        Self::data_dir().join("chunking.json")
+    }
+
+    /// Returns the path to the saved embedding settings (`<root>/data/embedding.json`).
+    pub fn embedding_config_path() -> PathBuf {
+        Self::data_dir().join("embedding.json")
+    }
+
+    /// Returns the path to the local model catalog that overrides the bundled one
+    /// (`<root>/data/model_catalog.json`).
+    pub fn model_catalog_path() -> PathBuf {
+        Self::data_dir().join("model_catalog.json")
+    }
+
     /// Returns the path to the vector storage directory (`<root>/data/vectors`).
     pub fn vectors_dir() -> PathBuf {
         Self::data_dir().join("vectors")
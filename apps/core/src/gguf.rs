@@ -0,0 +1,562 @@
+//! Minimal GGUF header reader.
+//!
+//! Only the metadata key/value section and the tensor descriptors are parsed; tensor data
+//! is never read, so inspecting a multi-gigabyte model takes a few milliseconds. Large
+//! arrays (the tokenizer vocabulary) are skipped without being loaded.
+//!
+//! Format reference: <https://github.com/ggml-org/ggml/blob/master/docs/gguf.md>
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+/// Upper bounds that reject garbage headers before they cause huge allocations.
+const MAX_STRING_LEN: u64 = 16 * 1024 * 1024;
+const MAX_ENTRIES: u64 = 1 << 20;
+const MAX_TENSOR_DIMS: u32 = 8;
+
+// Metadata value types
+const TYPE_UINT8: u32 = 0;
+const TYPE_INT8: u32 = 1;
+const TYPE_UINT16: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_UINT32: u32 = 4;
+const TYPE_INT32: u32 = 5;
+const TYPE_FLOAT32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_UINT64: u32 = 10;
+const TYPE_INT64: u32 = 11;
+const TYPE_FLOAT64: u32 = 12;
+
+/// Model information read from a GGUF header.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GgufMetadata {
+    pub version: u32,
+    /// `general.architecture`, e.g. `llama` or `qwen2`.
+    pub architecture: Option<String>,
+    /// `general.name`, the human-readable model name.
+    pub name: Option<String>,
+    /// Total number of weights, summed over all tensors.
+    pub parameter_count: u64,
+    /// Quantization of the bulk of the weights (`general.file_type`), e.g. `Q4_K_M`.
+    pub quantization: Option<String>,
+    /// Context length the model was trained with (`<arch>.context_length`).
+    pub context_length: Option<u64>,
+    /// The Jinja chat template (`tokenizer.chat_template`).
+    #[serde(skip)]
+    pub chat_template: Option<String>,
+    pub tensor_count: u64,
+    /// Offset of the tensor data section from the start of the file.
+    #[serde(skip)]
+    pub data_offset: u64,
+    /// Offset of the last tensor within the data section.
+    #[serde(skip)]
+    pub last_tensor_offset: u64,
+}
+
+impl GgufMetadata {
+    /// Whether a file of `file_len` bytes can hold all tensors described by the header.
+    ///
+    /// A truncated download keeps a valid header but loses the end of the tensor data. Offsets
+    /// too large to add up cannot fit in any file.
+    pub fn is_complete(&self, file_len: u64) -> bool {
+        self.tensor_count > 0
+            && self
+                .data_offset
+                .checked_add(self.last_tensor_offset)
+                .is_some_and(|end| file_len > end)
+    }
+
+    /// A one-line description such as `Qwen2.5 Coder 7B Instruct (qwen2, 7.6B, Q4_K_M)`.
+    pub fn summary(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(architecture) = &self.architecture {
+            details.push(architecture.clone());
+        }
+        if self.parameter_count > 0 {
+            details.push(format_parameter_count(self.parameter_count));
+        }
+        if let Some(quantization) = &self.quantization {
+            details.push(quantization.clone());
+        }
+
+        let name = self.name.as_deref().unwrap_or("Unknown model");
+        if details.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} ({})", name, details.join(", "))
+        }
+    }
+}
+
+/// Formats a weight count as `7.6B` or `494M`.
+fn format_parameter_count(count: u64) -> String {
+    if count >= 1_000_000_000 {
+        format!("{:.1}B", count as f64 / 1e9)
+    } else if count >= 1_000_000 {
+        format!("{}M", count / 1_000_000)
+    } else {
+        count.to_string()
+    }
+}
+
+/// Name of a `general.file_type` value (`llama_ftype` in llama.cpp).
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        _ => return None,
+    })
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A decoded metadata value. Arrays are skipped, not decoded.
+enum Value {
+    Int(i128),
+    String(String),
+    Other,
+}
+
+impl Value {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    fn into_string(self) -> Option<String> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct GgufReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> GgufReader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf)?;
+        self.position += N as u64;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(len), &mut io::sink())?;
+        if skipped != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.position += len;
+        Ok(())
+    }
+
+    fn string_len(&mut self) -> io::Result<u64> {
+        let len = self.u64()?;
+        if len > MAX_STRING_LEN {
+            return Err(invalid(format!("string of {len} bytes in GGUF header")));
+        }
+        Ok(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.string_len()?;
+        let mut buf = vec![0u8; len as usize];
+        self.inner.read_exact(&mut buf)?;
+        self.position += len;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn value(&mut self, value_type: u32) -> io::Result<Value> {
+        Ok(match value_type {
+            TYPE_UINT8 => Value::Int(u8::from_le_bytes(self.bytes()?).into()),
+            TYPE_INT8 => Value::Int(i8::from_le_bytes(self.bytes()?).into()),
+            TYPE_UINT16 => Value::Int(u16::from_le_bytes(self.bytes()?).into()),
+            TYPE_INT16 => Value::Int(i16::from_le_bytes(self.bytes()?).into()),
+            TYPE_UINT32 => Value::Int(self.u32()?.into()),
+            TYPE_INT32 => Value::Int(i32::from_le_bytes(self.bytes()?).into()),
+            TYPE_UINT64 => Value::Int(self.u64()?.into()),
+            TYPE_INT64 => Value::Int(i64::from_le_bytes(self.bytes()?).into()),
+            TYPE_FLOAT32 | TYPE_BOOL | TYPE_FLOAT64 => {
+                self.skip_value(value_type)?;
+                Value::Other
+            }
+            TYPE_STRING => Value::String(self.string()?),
+            TYPE_ARRAY => {
+                self.skip_array()?;
+                Value::Other
+            }
+            other => return Err(invalid(format!("unknown GGUF value type {other}"))),
+        })
+    }
+
+    fn skip_value(&mut self, value_type: u32) -> io::Result<()> {
+        match value_type {
+            TYPE_UINT8 | TYPE_INT8 | TYPE_BOOL => self.skip(1),
+            TYPE_UINT16 | TYPE_INT16 => self.skip(2),
+            TYPE_UINT32 | TYPE_INT32 | TYPE_FLOAT32 => self.skip(4),
+            TYPE_UINT64 | TYPE_INT64 | TYPE_FLOAT64 => self.skip(8),
+            TYPE_STRING => {
+                let len = self.string_len()?;
+                self.skip(len)
+            }
+            TYPE_ARRAY => self.skip_array(),
+            other => Err(invalid(format!("unknown GGUF value type {other}"))),
+        }
+    }
+
+    fn skip_array(&mut self) -> io::Result<()> {
+        let element_type = self.u32()?;
+        let len = self.u64()?;
+        let fixed_size = match element_type {
+            TYPE_UINT8 | TYPE_INT8 | TYPE_BOOL => Some(1),
+            TYPE_UINT16 | TYPE_INT16 => Some(2),
+            TYPE_UINT32 | TYPE_INT32 | TYPE_FLOAT32 => Some(4),
+            TYPE_UINT64 | TYPE_INT64 | TYPE_FLOAT64 => Some(8),
+            _ => None,
+        };
+
+        match fixed_size {
+            Some(size) => self.skip(
+                len.checked_mul(size)
+                    .ok_or_else(|| invalid("GGUF array too large"))?,
+            ),
+            None => {
+                for _ in 0..len {
+                    self.skip_value(element_type)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the metadata of a GGUF file.
+pub fn read_metadata(path: &Path) -> io::Result<GgufMetadata> {
+    parse(BufReader::new(File::open(path)?))
+}
+
+/// Reads the metadata of a GGUF file and checks that no tensor data is missing.
+pub fn validate(path: &Path) -> io::Result<GgufMetadata> {
+    let file_len = std::fs::metadata(path)?.len();
+    let metadata = read_metadata(path)?;
+    if !metadata.is_complete(file_len) {
+        return Err(invalid(format!(
+            "GGUF file is truncated ({file_len} bytes for {} tensors)",
+            metadata.tensor_count
+        )));
+    }
+    Ok(metadata)
+}
+
+fn parse<R: Read>(inner: R) -> io::Result<GgufMetadata> {
+    let mut reader = GgufReader { inner, position: 0 };
+
+    let magic: [u8; 4] = reader.bytes()?;
+    if &magic != GGUF_MAGIC {
+        return Err(invalid(format!(
+            "invalid GGUF header: expected 'GGUF', got {magic:?}"
+        )));
+    }
+
+    let version = reader.u32()?;
+    if !(2..=3).contains(&version) {
+        return Err(invalid(format!("unsupported GGUF version {version}")));
+    }
+
+    let tensor_count = reader.u64()?;
+    let kv_count = reader.u64()?;
+    if tensor_count > MAX_ENTRIES || kv_count > MAX_ENTRIES {
+        return Err(invalid("implausible GGUF entry counts"));
+    }
+
+    let mut metadata = GgufMetadata {
+        version,
+        tensor_count,
+        ..GgufMetadata::default()
+    };
+    let mut alignment = DEFAULT_ALIGNMENT;
+    // The context length key depends on the architecture, which may come later
+    let mut context_lengths: Vec<(String, u64)> = Vec::new();
+
+    for _ in 0..kv_count {
+        let key = reader.string()?;
+        let value_type = reader.u32()?;
+        let wanted = matches!(
+            key.as_str(),
+            "general.architecture"
+                | "general.name"
+                | "general.file_type"
+                | "general.alignment"
+                | "tokenizer.chat_template"
+        ) || key.ends_with(".context_length");
+
+        if !wanted {
+            reader.skip_value(value_type)?;
+            continue;
+        }
+
+        let value = reader.value(value_type)?;
+        match key.as_str() {
+            "general.architecture" => metadata.architecture = value.into_string(),
+            "general.name" => metadata.name = value.into_string(),
+            "general.file_type" => {
+                metadata.quantization = value.as_u64().and_then(file_type_name).map(str::to_string)
+            }
+            "general.alignment" => {
+                alignment = value
+                    .as_u64()
+                    .filter(|a| *a > 0)
+                    .unwrap_or(DEFAULT_ALIGNMENT)
+            }
+            "tokenizer.chat_template" => metadata.chat_template = value.into_string(),
+            _ => {
+                if let Some(length) = value.as_u64() {
+                    context_lengths.push((key, length));
+                }
+            }
+        }
+    }
+
+    if let Some(architecture) = &metadata.architecture {
+        let key = format!("{architecture}.context_length");
+        metadata.context_length = context_lengths
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, length)| *length);
+    }
+
+    for _ in 0..tensor_count {
+        let name_len = reader.string_len()?;
+        reader.skip(name_len)?;
+
+        let n_dims = reader.u32()?;
+        if n_dims > MAX_TENSOR_DIMS {
+            return Err(invalid(format!("tensor with {n_dims} dimensions")));
+        }
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements.saturating_mul(reader.u64()?);
+        }
+        let _ggml_type = reader.u32()?;
+        let offset = reader.u64()?;
+
+        metadata.parameter_count = metadata.parameter_count.saturating_add(elements);
+        metadata.last_tensor_offset = metadata.last_tensor_offset.max(offset);
+    }
+
+    metadata.data_offset = reader.position.div_ceil(alignment) * alignment;
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Builds a GGUF v3 file with the given metadata and two tensors.
+    struct GgufBuilder {
+        kv: Vec<u8>,
+        kv_count: u64,
+    }
+
+    impl GgufBuilder {
+        fn new() -> Self {
+            Self {
+                kv: Vec::new(),
+                kv_count: 0,
+            }
+        }
+
+        fn key(&mut self, key: &str, value_type: u32) {
+            self.kv.extend((key.len() as u64).to_le_bytes());
+            self.kv.extend(key.as_bytes());
+            self.kv.extend(value_type.to_le_bytes());
+            self.kv_count += 1;
+        }
+
+        fn string(mut self, key: &str, value: &str) -> Self {
+            self.key(key, TYPE_STRING);
+            self.kv.extend((value.len() as u64).to_le_bytes());
+            self.kv.extend(value.as_bytes());
+            self
+        }
+
+        fn u32(mut self, key: &str, value: u32) -> Self {
+            self.key(key, TYPE_UINT32);
+            self.kv.extend(value.to_le_bytes());
+            self
+        }
+
+        fn f32(mut self, key: &str, value: f32) -> Self {
+            self.key(key, TYPE_FLOAT32);
+            self.kv.extend(value.to_le_bytes());
+            self
+        }
+
+        fn string_array(mut self, key: &str, values: &[&str]) -> Self {
+            self.key(key, TYPE_ARRAY);
+            self.kv.extend(TYPE_STRING.to_le_bytes());
+            self.kv.extend((values.len() as u64).to_le_bytes());
+            for value in values {
+                self.kv.extend((value.len() as u64).to_le_bytes());
+                self.kv.extend(value.as_bytes());
+            }
+            self
+        }
+
+        /// Header plus `data_len` bytes of tensor data.
+        fn build(&self, data_len: usize) -> Vec<u8> {
+            let tensors: [(&str, &[u64], u64); 2] = [
+                ("token_embd.weight", &[64, 100], 0),
+                ("output.weight", &[64, 100], 3200),
+            ];
+
+            let mut out = Vec::new();
+            out.extend(GGUF_MAGIC);
+            out.extend(3u32.to_le_bytes());
+            out.extend((tensors.len() as u64).to_le_bytes());
+            out.extend(self.kv_count.to_le_bytes());
+            out.extend(&self.kv);
+            for (name, dims, offset) in tensors {
+                out.extend((name.len() as u64).to_le_bytes());
+                out.extend(name.as_bytes());
+                out.extend((dims.len() as u32).to_le_bytes());
+                for dim in dims {
+                    out.extend(dim.to_le_bytes());
+                }
+                out.extend(1u32.to_le_bytes()); // F16
+                out.extend(offset.to_le_bytes());
+            }
+            while out.len() % 32 != 0 {
+                out.push(0);
+            }
+            out.extend(vec![0u8; data_len]);
+            out
+        }
+    }
+
+    fn sample() -> GgufBuilder {
+        GgufBuilder::new()
+            .string("general.architecture", "qwen2")
+            .string("general.name", "Qwen2.5 Coder 7B Instruct")
+            .f32("qwen2.rope.freq_base", 1_000_000.0)
+            .u32("qwen2.context_length", 32768)
+            .u32("general.file_type", 15)
+            .string_array("tokenizer.ggml.tokens", &["<|im_start|>", "hello", "world"])
+            .string(
+                "tokenizer.chat_template",
+                "{% for m in messages %}<|im_start|>{{ m.role }}{% endfor %}",
+            )
+    }
+
+    #[test]
+    fn test_parse_reads_model_information() {
+        let metadata = parse(sample().build(6400).as_slice()).unwrap();
+
+        assert_eq!(metadata.version, 3);
+        assert_eq!(metadata.architecture.as_deref(), Some("qwen2"));
+        assert_eq!(metadata.name.as_deref(), Some("Qwen2.5 Coder 7B Instruct"));
+        assert_eq!(metadata.context_length, Some(32768));
+        assert_eq!(metadata.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(metadata.parameter_count, 12800);
+        assert_eq!(metadata.tensor_count, 2);
+        assert!(metadata
+            .chat_template
+            .as_deref()
+            .unwrap()
+            .contains("<|im_start|>"));
+        assert_eq!(
+            metadata.summary(),
+            "Qwen2.5 Coder 7B Instruct (qwen2, 12800, Q4_K_M)"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_headers() {
+        assert!(parse(&b"GGML\x03\x00\x00\x00"[..]).is_err());
+        assert!(parse(&b"GGUF\x01\x00\x00\x00"[..]).is_err());
+
+        // Header cut in the middle of the metadata
+        let bytes = sample().build(0);
+        assert!(parse(&bytes[..60]).is_err());
+    }
+
+    #[test]
+    fn test_validate_detects_truncated_files() {
+        let temp = TempDir::new().unwrap();
+        let complete = temp.path().join("complete.gguf");
+        let truncated = temp.path().join("truncated.gguf");
+        std::fs::write(&complete, sample().build(6400)).unwrap();
+        std::fs::write(&truncated, sample().build(1000)).unwrap();
+
+        assert!(validate(&complete).is_ok());
+        let err = validate(&truncated).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+    }
+
+    #[test]
+    fn test_is_complete_rejects_overflowing_offsets() {
+        let metadata = GgufMetadata {
+            tensor_count: 1,
+            data_offset: 64,
+            last_tensor_offset: u64::MAX,
+            ..Default::default()
+        };
+        assert!(!metadata.is_complete(u64::MAX));
+    }
+
+    #[test]
+    fn test_format_parameter_count() {
+        assert_eq!(format_parameter_count(7_615_616_512), "7.6B");
+        assert_eq!(format_parameter_count(494_032_768), "494M");
+    }
+}
@@ -0,0 +1,134 @@
+//! Lexical scoring and rank fusion for hybrid retrieval.
+//!
+//! Embeddings match paraphrases well but miss exact tokens such as identifiers, error codes
+//! and function names. The RAG actor therefore also ranks chunks with BM25, using LanceDB's
+//! full-text index, and merges both rankings with reciprocal-rank fusion (RRF), which only
+//! looks at ranks and so needs no calibration between cosine distances and BM25 scores.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// RRF damping constant; 60 is the value from the original paper.
+pub const RRF_K: f32 = 60.0;
+
+/// Splits text into lowercase terms for lexical matching.
+///
+/// Underscores, dots, dashes and colons are kept inside terms so `snake_case` names,
+/// `module::paths`, `E0382` or `ERR-42` stay whole; surrounding punctuation is trimmed.
+pub fn tokenize(text: &str) -> Vec<String> {
+    term_spans(text)
+        .into_iter()
+        .map(|span| text[span].to_lowercase())
+        .collect()
+}
+
+/// Byte ranges of the terms [`tokenize`] finds in `text`, before lowercasing.
+pub fn term_spans(text: &str) -> Vec<Range<usize>> {
+    let is_edge = |c: char| !c.is_alphanumeric();
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        let inside = c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | ':');
+        match (inside, start) {
+            (true, None) => start = Some(index),
+            (false, Some(begin)) => {
+                let piece = &text[begin..index];
+                let trimmed = piece.trim_matches(is_edge);
+                if !trimmed.is_empty() {
+                    let offset = begin + piece.len() - piece.trim_start_matches(is_edge).len();
+                    spans.push(offset..offset + trimmed.len());
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Merges rankings of item ids with weighted reciprocal-rank fusion.
+///
+/// Each list is ordered best first and paired with its weight; an item scores
+/// `Σ weight / (RRF_K + rank)` over the lists it appears in (ranks start at 1).
+/// Returns `(id, score)` pairs, best first; ties keep the order of first appearance.
+pub fn reciprocal_rank_fusion(rankings: &[(&[String], f32)]) -> Vec<(String, f32)> {
+    let mut scores: Vec<(String, f32)> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+
+    for (ranking, weight) in rankings {
+        if *weight <= 0.0 {
+            continue;
+        }
+        for (rank, id) in ranking.iter().enumerate() {
+            let contribution = weight / (RRF_K + rank as f32 + 1.0);
+            match positions.get(id.as_str()) {
+                Some(&position) => scores[position].1 += contribution,
+                None => {
+                    positions.insert(id, scores.len());
+                    scores.push((id.clone(), contribution));
+                }
+            }
+        }
+    }
+
+    // Stable sort, so equal scores stay in order of first appearance
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tokenize_keeps_identifiers_whole() {
+        assert_eq!(
+            tokenize("Call parse_config() in std::fs; got E0382, ERR-42."),
+            vec![
+                "call",
+                "parse_config",
+                "in",
+                "std::fs",
+                "got",
+                "e0382",
+                "err-42"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_term_spans_point_into_the_original_text() {
+        let text = "Réglez « max_tokens » (défaut: 170).";
+        let terms: Vec<&str> = term_spans(text).into_iter().map(|s| &text[s]).collect();
+        assert_eq!(terms, vec!["Réglez", "max_tokens", "défaut", "170"]);
+    }
+
+    #[test]
+    fn test_rrf_rewards_agreement_between_rankings() {
+        let vector = ids(&["a", "b", "c"]);
+        let lexical = ids(&["c", "d"]);
+        let fused = reciprocal_rank_fusion(&[(vector.as_slice(), 1.0), (lexical.as_slice(), 1.0)]);
+
+        let order: Vec<&str> = fused.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(order, vec!["c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn test_rrf_weights() {
+        let vector = ids(&["a", "b"]);
+        let lexical = ids(&["b", "a"]);
+
+        let fused = reciprocal_rank_fusion(&[(vector.as_slice(), 1.0), (lexical.as_slice(), 3.0)]);
+        assert_eq!(fused[0].0, "b");
+
+        // A zero weight disables a ranking entirely
+        let ignored = ids(&["z"]);
+        let fused = reciprocal_rank_fusion(&[(vector.as_slice(), 1.0), (ignored.as_slice(), 0.0)]);
+        assert_eq!(fused.len(), 2);
+        assert_eq!(fused[0].0, "a");
+    }
+}
@@ -0,0 +1,364 @@
+//! Persisted `llama-server` launch settings.
+//!
+//! The profile is stored as JSON in the data directory so it can be tuned per machine
+//! (CPU-only boxes, small GPUs, ...) without recompiling. It is read each time the server
+//! starts, so a saved change applies the next time `llama-server` is launched.
+
+use crate::actors::llm::{CONTEXT_SIZE, PARALLEL_SLOTS};
+use crate::error::AppError;
+use crate::fs_manager::PortablePathManager;
+use crate::gguf::{self, GgufMetadata};
+use crate::models::MAX_RESPONSE_TOKENS;
+use serde::{Deserialize, Serialize};
+use std::net::TcpListener;
+use std::path::Path;
+use tracing::warn;
+use validator::{Validate, ValidationError};
+
+/// Launch settings for the local `llama-server` process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_slot_size"))]
+pub struct LaunchProfile {
+    /// Fixed port to listen on. `None` picks a free port at every start.
+    pub port: Option<u16>,
+    /// Total context length (`-c`), shared evenly by the parallel slots.
+    #[validate(range(min = 512, max = 262144))]
+    pub context_size: usize,
+    /// Number of requests served concurrently (`-np`).
+    #[validate(range(min = 1, max = 16))]
+    pub parallel_slots: usize,
+    /// CPU threads used for generation (`-t`). `None` lets llama.cpp decide.
+    #[validate(range(min = 1, max = 256))]
+    pub threads: Option<usize>,
+    /// Logical batch size for prompt processing (`-b`). `None` keeps the llama.cpp default.
+    #[validate(range(min = 32, max = 8192))]
+    pub batch_size: Option<usize>,
+    /// Layers offloaded to the GPU (`-ngl`). Ignored when no GPU is available.
+    pub gpu_layers: u32,
+    /// Memory-map the model file. Disabling it (`--no-mmap`) loads the whole model up front.
+    pub mmap: bool,
+    /// Lock the model in RAM (`--mlock`) so it is never swapped out.
+    pub mlock: bool,
+    /// Additional arguments appended verbatim to the command line.
+    pub extra_args: Vec<String>,
+}
+
+impl Default for LaunchProfile {
+    fn default() -> Self {
+        Self {
+            port: None,
+            context_size: CONTEXT_SIZE,
+            parallel_slots: PARALLEL_SLOTS,
+            threads: None,
+            batch_size: None,
+            gpu_layers: 99,
+            mmap: true,
+            mlock: false,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// Each slot must have room for a full response on top of the prompt.
+fn validate_slot_size(profile: &LaunchProfile) -> Result<(), ValidationError> {
+    if profile.slot_context_size() < 2 * MAX_RESPONSE_TOKENS {
+        return Err(ValidationError::new("context_size_per_slot_too_small"));
+    }
+    Ok(())
+}
+
+impl LaunchProfile {
+    /// Loads the saved profile, falling back to the defaults if none is saved or it is invalid.
+    pub fn load() -> Self {
+        Self::load_from(&PortablePathManager::launch_profile_path())
+    }
+
+    /// Loads the saved profile, adjusted to the model file it will serve.
+    pub fn for_model(model_path: &Path) -> Self {
+        Self::load().fit_to_model(gguf::read_metadata(model_path).ok().as_ref())
+    }
+
+    /// Caps the context so no slot exceeds the context length the model was trained with;
+    /// going beyond it only produces degraded output.
+    ///
+    /// A slot is then never larger than the trained length, so merging slots cannot make
+    /// room for a full response. Models trained on fewer than `2 * MAX_RESPONSE_TOKENS`
+    /// tokens get a shorter response budget instead (see [`Self::response_tokens`]).
+    pub fn fit_to_model(mut self, metadata: Option<&GgufMetadata>) -> Self {
+        let trained = metadata
+            .and_then(|m| m.context_length)
+            .and_then(|length| usize::try_from(length).ok());
+        if let Some(trained) = trained.filter(|t| *t > 0) {
+            let max_context = trained.saturating_mul(self.parallel_slots.max(1));
+            if self.context_size > max_context {
+                self.context_size = max_context;
+            }
+            if validate_slot_size(&self).is_err() {
+                warn!(
+                    "Model is trained on {} tokens per slot; responses are limited to {} tokens",
+                    self.slot_context_size(),
+                    self.response_tokens()
+                );
+            }
+        }
+        self
+    }
+
+    /// Saves the profile so the next `llama-server` start uses it.
+    pub fn save(&self) -> Result<(), AppError> {
+        self.save_to(&PortablePathManager::launch_profile_path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!(
+                    "Failed to read launch profile {:?}: {}. Using defaults.",
+                    path, e
+                );
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_slice::<Self>(&bytes) {
+            Ok(profile) if profile.validate().is_ok() => profile,
+            Ok(_) => {
+                warn!("Launch profile {:?} is out of range. Using defaults.", path);
+                Self::default()
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to parse launch profile {:?}: {}. Using defaults.",
+                    path, e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), AppError> {
+        self.validate()
+            .map_err(|e| AppError::Validation(format!("Invalid launch profile: {}", e)))?;
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| AppError::Internal(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Context tokens available to a single request.
+    pub fn slot_context_size(&self) -> usize {
+        self.context_size / self.parallel_slots.max(1)
+    }
+
+    /// Tokens kept free for the answer in each slot.
+    ///
+    /// This is `MAX_RESPONSE_TOKENS` unless the slot is too small for it, in which case half
+    /// of the slot is left for the prompt.
+    pub fn response_tokens(&self) -> usize {
+        MAX_RESPONSE_TOKENS.min(self.slot_context_size() / 2)
+    }
+
+    /// Returns the configured port, or a free one if none is configured.
+    pub fn resolve_port(&self) -> Result<u16, AppError> {
+        match self.port {
+            Some(port) => Ok(port),
+            None => free_port(),
+        }
+    }
+
+    /// Builds the `llama-server` arguments for everything but the model and API key.
+    pub fn server_args(&self, port: u16) -> Vec<String> {
+        let mut args = vec![
+            "--host".to_string(),
+            "127.0.0.1".to_string(),
+            "--port".to_string(),
+            port.to_string(),
+            "-c".to_string(),
+            self.context_size.to_string(),
+            "-np".to_string(),
+            self.parallel_slots.to_string(),
+            "-ngl".to_string(),
+            self.gpu_layers.to_string(),
+        ];
+
+        if let Some(threads) = self.threads {
+            args.push("-t".to_string());
+            args.push(threads.to_string());
+        }
+        if let Some(batch_size) = self.batch_size {
+            args.push("-b".to_string());
+            args.push(batch_size.to_string());
+        }
+        if !self.mmap {
+            args.push("--no-mmap".to_string());
+        }
+        if self.mlock {
+            args.push("--mlock".to_string());
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+/// Asks the OS for a port that is free on the loopback interface.
+pub fn free_port() -> Result<u16, AppError> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_default_profile_matches_previous_flags() {
+        let profile = LaunchProfile::default();
+
+        assert!(profile.validate().is_ok());
+        assert_eq!(
+            profile.server_args(8080),
+            vec![
+                "--host",
+                "127.0.0.1",
+                "--port",
+                "8080",
+                "-c",
+                "8192",
+                "-np",
+                "2",
+                "-ngl",
+                "99"
+            ]
+        );
+        assert_eq!(profile.slot_context_size(), CONTEXT_SIZE / PARALLEL_SLOTS);
+    }
+
+    #[test]
+    fn test_server_args_for_cpu_profile() {
+        let profile = LaunchProfile {
+            context_size: 4096,
+            parallel_slots: 1,
+            threads: Some(6),
+            batch_size: Some(256),
+            gpu_layers: 0,
+            mmap: false,
+            mlock: true,
+            extra_args: vec!["--flash-attn".to_string()],
+            ..LaunchProfile::default()
+        };
+
+        let args = profile.server_args(41234).join(" ");
+
+        assert!(args.contains("--port 41234"));
+        assert!(args.contains("-c 4096 -np 1 -ngl 0"));
+        assert!(args.contains("-t 6 -b 256"));
+        assert!(args.ends_with("--no-mmap --mlock --flash-attn"));
+    }
+
+    #[test]
+    fn test_validate_rejects_too_small_slots() {
+        let profile = LaunchProfile {
+            context_size: 4096,
+            parallel_slots: 4,
+            ..LaunchProfile::default()
+        };
+
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("profile.json");
+        let profile = LaunchProfile {
+            port: Some(9000),
+            threads: Some(4),
+            ..LaunchProfile::default()
+        };
+
+        profile.save_to(&path).unwrap();
+
+        assert_eq!(LaunchProfile::load_from(&path), profile);
+    }
+
+    #[test]
+    fn test_load_falls_back_to_defaults() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("profile.json");
+
+        // Missing file
+        assert_eq!(LaunchProfile::load_from(&path), LaunchProfile::default());
+
+        // Partial file keeps defaults for missing fields
+        std::fs::write(&path, r#"{"threads": 8}"#).unwrap();
+        let partial = LaunchProfile::load_from(&path);
+        assert_eq!(partial.threads, Some(8));
+        assert_eq!(partial.context_size, CONTEXT_SIZE);
+
+        // Invalid values
+        std::fs::write(&path, r#"{"parallel_slots": 0}"#).unwrap();
+        assert_eq!(LaunchProfile::load_from(&path), LaunchProfile::default());
+    }
+
+    #[test]
+    fn test_fit_to_model_caps_context_to_trained_length() {
+        let medium = GgufMetadata {
+            context_length: Some(4096),
+            ..GgufMetadata::default()
+        };
+        let large = GgufMetadata {
+            context_length: Some(131072),
+            ..GgufMetadata::default()
+        };
+
+        let profile = LaunchProfile {
+            context_size: 32768,
+            parallel_slots: 4,
+            ..LaunchProfile::default()
+        }
+        .fit_to_model(Some(&medium));
+        assert_eq!(profile.context_size, 4096 * 4);
+        assert!(profile.validate().is_ok());
+
+        let profile = LaunchProfile::default().fit_to_model(Some(&large));
+        assert_eq!(profile.context_size, CONTEXT_SIZE);
+
+        let profile = LaunchProfile::default().fit_to_model(None);
+        assert_eq!(profile.context_size, CONTEXT_SIZE);
+    }
+
+    #[test]
+    fn test_fit_to_model_shrinks_response_for_small_context_model() {
+        let small = GgufMetadata {
+            context_length: Some(2048),
+            ..GgufMetadata::default()
+        };
+
+        let profile = LaunchProfile::default().fit_to_model(Some(&small));
+        assert_eq!(profile.context_size, 2048 * PARALLEL_SLOTS);
+        assert!(profile.validate().is_err());
+        // Half of each slot stays free for the prompt
+        assert_eq!(profile.slot_context_size(), 2048);
+        assert_eq!(profile.response_tokens(), 1024);
+    }
+
+    #[test]
+    fn test_resolve_port() {
+        let fixed = LaunchProfile {
+            port: Some(8181),
+            ..LaunchProfile::default()
+        };
+        assert_eq!(fixed.resolve_port().unwrap(), 8181);
+
+        let port = LaunchProfile::default().resolve_port().unwrap();
+        assert_ne!(port, 0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Library-wide semantic search, outside of any chat session.
+//!
+//! The knowledge view searches every indexed file, optionally narrowed to a folder or to
+//! some file types, and shows the matching chunks grouped by file with the query terms
+//! highlighted.
+
+use crate::actors::messages::SearchResult;
+use crate::hybrid_search::{term_spans, tokenize};
+use crate::models::LibraryFile;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Number of chunks fetched when the caller does not say.
+pub const DEFAULT_LIMIT: usize = 20;
+/// Upper bound on the number of chunks fetched by one search.
+pub const MAX_LIMIT: usize = 100;
+
+/// Narrows a library search to some of the files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LibrarySearchFilter {
+    /// Only search the files in this folder.
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Only search files with these extensions, e.g. `pdf` or `.md`.
+    #[serde(default)]
+    pub file_types: Vec<String>,
+}
+
+impl LibrarySearchFilter {
+    /// Whether the filter lets every file through.
+    pub fn is_empty(&self) -> bool {
+        self.folder_id.is_none() && self.file_types.is_empty()
+    }
+
+    pub fn matches(&self, file: &LibraryFile) -> bool {
+        if self.folder_id.is_some() && file.folder_id != self.folder_id {
+            return false;
+        }
+        if self.file_types.is_empty() {
+            return true;
+        }
+        let extension = std::path::Path::new(&file.name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        self.file_types.iter().any(|file_type| {
+            file_type
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(extension)
+        })
+    }
+}
+
+/// A run of chunk text, highlighted if it is one of the query terms.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextSegment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+/// A chunk matching the query.
+#[derive(Debug, Clone, Serialize)]
+pub struct LibrarySearchMatch {
+    pub chunk_id: String,
+    /// Fused relevance score; higher is better.
+    pub score: f32,
+    pub page: Option<u32>,
+    pub heading_path: Option<String>,
+    /// The chunk text, split around the query terms it contains.
+    pub segments: Vec<TextSegment>,
+}
+
+/// The matches found in one file, best first.
+#[derive(Debug, Clone, Serialize)]
+pub struct LibrarySearchHit {
+    pub file_id: String,
+    pub file_name: String,
+    pub file_type: String,
+    pub folder_id: Option<String>,
+    /// Score of the best match.
+    pub score: f32,
+    pub matches: Vec<LibrarySearchMatch>,
+}
+
+/// Groups search results by file, keeping the search order of the files' best matches.
+///
+/// Results from files that are not in `files`, e.g. removed since, are dropped.
+pub fn group_by_file(
+    query: &str,
+    results: Vec<SearchResult>,
+    files: &HashMap<String, LibraryFile>,
+) -> Vec<LibrarySearchHit> {
+    let terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let mut hits: Vec<LibrarySearchHit> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for result in results {
+        let Some(file) = result
+            .metadata
+            .file_id
+            .as_ref()
+            .and_then(|file_id| files.get(file_id))
+        else {
+            continue;
+        };
+        let matched = LibrarySearchMatch {
+            segments: highlight(&result.content, &terms),
+            chunk_id: result.id,
+            score: result.score,
+            page: result.metadata.page,
+            heading_path: result.metadata.heading_path,
+        };
+        match positions.get(&file.id) {
+            Some(&position) => hits[position].matches.push(matched),
+            None => {
+                positions.insert(file.id.clone(), hits.len());
+                hits.push(LibrarySearchHit {
+                    file_id: file.id.clone(),
+                    file_name: file.name.clone(),
+                    file_type: file.file_type.clone(),
+                    folder_id: file.folder_id.clone(),
+                    score: matched.score,
+                    matches: vec![matched],
+                });
+            }
+        }
+    }
+    hits
+}
+
+/// Splits `text` into segments, highlighting the terms found in `terms` (lowercase).
+pub fn highlight(text: &str, terms: &HashSet<String>) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    let mut end = 0;
+    for span in term_spans(text) {
+        if !terms.contains(&text[span.clone()].to_lowercase()) {
+            continue;
+        }
+        if span.start > end {
+            segments.push(TextSegment {
+                text: text[end..span.start].to_string(),
+                highlighted: false,
+            });
+        }
+        end = span.end;
+        segments.push(TextSegment {
+            text: text[span].to_string(),
+            highlighted: true,
+        });
+    }
+    if end < text.len() || segments.is_empty() {
+        segments.push(TextSegment {
+            text: text[end..].to_string(),
+            highlighted: false,
+        });
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::ChunkMetadata;
+
+    fn file(id: &str, name: &str, folder_id: Option<&str>) -> LibraryFile {
+        LibraryFile {
+            id: id.to_string(),
+            name: name.to_string(),
+            path: format!("/files/{}", id),
+            file_type: "text/plain".to_string(),
+            size: 10,
+            created_at: 0,
+            folder_id: folder_id.map(str::to_string),
+            content_hash: None,
+            embedding_model: None,
+        }
+    }
+
+    fn result(id: &str, file_id: &str, content: &str, score: f32) -> SearchResult {
+        SearchResult {
+            id: id.to_string(),
+            content: content.to_string(),
+            metadata: ChunkMetadata {
+                file_id: Some(file_id.to_string()),
+                ..Default::default()
+            },
+            score,
+        }
+    }
+
+    #[test]
+    fn test_filter_matches_folder_and_extension() {
+        let report = file("f1", "Report.PDF", Some("docs"));
+
+        assert!(LibrarySearchFilter::default().matches(&report));
+        let filter = LibrarySearchFilter {
+            folder_id: Some("docs".to_string()),
+            file_types: vec![".pdf".to_string(), "md".to_string()],
+        };
+        assert!(filter.matches(&report));
+        assert!(!filter.matches(&file("f2", "notes.md", None)));
+        assert!(!filter.matches(&file("f3", "data.csv", Some("docs"))));
+    }
+
+    #[test]
+    fn test_highlight_marks_query_terms() {
+        let terms = tokenize("license ERR-42").into_iter().collect();
+        let segments = highlight("Error ERR-42: the License is missing.", &terms);
+
+        let highlighted: Vec<&str> = segments
+            .iter()
+            .filter(|s| s.highlighted)
+            .map(|s| s.text.as_str())
+            .collect();
+        assert_eq!(highlighted, vec!["ERR-42", "License"]);
+        let text: String = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(text, "Error ERR-42: the License is missing.");
+
+        let plain = highlight("Nothing here", &terms);
+        assert_eq!(plain.len(), 1);
+        assert!(!plain[0].highlighted);
+    }
+
+    #[test]
+    fn test_group_by_file_keeps_rank_order() {
+        let files: HashMap<String, LibraryFile> =
+            [file("a", "a.md", None), file("b", "b.md", None)]
+                .into_iter()
+                .map(|f| (f.id.clone(), f))
+                .collect();
+        let results = vec![
+            result("1", "b", "best chunk", 0.9),
+            result("2", "a", "second chunk", 0.8),
+            result("3", "gone", "removed file", 0.7),
+            result("4", "b", "third chunk", 0.6),
+        ];
+
+        let hits = group_by_file("chunk", results, &files);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].file_id, "b");
+        assert_eq!(hits[0].score, 0.9);
+        let chunk_ids: Vec<&str> = hits[0]
+            .matches
+            .iter()
+            .map(|m| m.chunk_id.as_str())
+            .collect();
+        assert_eq!(chunk_ids, vec!["1", "4"]);
+        assert_eq!(hits[1].file_id, "a");
+    }
+}
@@ -0,0 +1,680 @@
+//! Prebuilt `llama-server` releases for the current platform.
+//!
+//! llama.cpp publishes one archive per OS, architecture and x86 instruction set. This module
+//! picks the archive that runs on this machine, extracts it (zip or tar.gz) and checks that
+//! the result is a complete build for this platform: the server binary is executable and in
+//! the platform's executable format, and the shared libraries next to it are intact.
+//!
+//! Linux hosts the release has no build for (ARM64, x86 without AVX2) get the source archive
+//! of the same release instead, compiled on the machine as a native CPU build.
+
+use crate::error::AppError;
+use std::ffi::OsString;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// llama.cpp release installed by the onboarding.
+pub const RELEASE_TAG: &str = "b4154";
+const RELEASE_BASE_URL: &str = "https://github.com/ggml-org/llama.cpp/releases/download";
+
+/// Below this size the server binary is certainly a truncated download.
+const MIN_SERVER_SIZE_BYTES: u64 = 1024 * 1024;
+
+/// Name of the server executable on this platform.
+pub fn server_binary_name() -> &'static str {
+    if cfg!(windows) {
+        "llama-server.exe"
+    } else {
+        "llama-server"
+    }
+}
+
+/// CPU features that decide which x86 build can run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    pub avx: bool,
+    pub avx2: bool,
+    pub avx512: bool,
+    pub neon: bool,
+}
+
+impl CpuFeatures {
+    /// Detects the features of the running CPU.
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            Self {
+                avx: std::arch::is_x86_feature_detected!("avx"),
+                avx2: std::arch::is_x86_feature_detected!("avx2"),
+                avx512: std::arch::is_x86_feature_detected!("avx512f"),
+                neon: false,
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            Self {
+                neon: std::arch::is_aarch64_feature_detected!("neon"),
+                ..Self::default()
+            }
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            Self::default()
+        }
+    }
+}
+
+/// The OS, architecture and CPU a release must run on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    /// As in `std::env::consts::OS` (`windows`, `linux`, `macos`).
+    pub os: &'static str,
+    /// As in `std::env::consts::ARCH` (`x86_64`, `aarch64`).
+    pub arch: &'static str,
+    pub cpu: CpuFeatures,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        Self {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            cpu: CpuFeatures::detect(),
+        }
+    }
+}
+
+/// Archive formats used by releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Detects the format from the file name, or `None` if it is not an archive.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// A downloadable release archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseAsset {
+    pub file_name: String,
+    pub url: String,
+    /// The archive holds the sources, to be compiled with [`build_from_source`].
+    pub source: bool,
+}
+
+impl ReleaseAsset {
+    fn prebuilt(variant: &str) -> Self {
+        let file_name = format!("llama-{}-bin-{}.zip", RELEASE_TAG, variant);
+        Self {
+            url: format!("{}/{}/{}", RELEASE_BASE_URL, RELEASE_TAG, file_name),
+            file_name,
+            source: false,
+        }
+    }
+
+    fn source() -> Self {
+        Self {
+            file_name: format!("llama.cpp-{}.tar.gz", RELEASE_TAG),
+            url: format!(
+                "https://github.com/ggml-org/llama.cpp/archive/refs/tags/{}.tar.gz",
+                RELEASE_TAG
+            ),
+            source: true,
+        }
+    }
+}
+
+/// Picks the release archive for `platform`.
+pub fn select_asset(platform: &Platform) -> Result<ReleaseAsset, AppError> {
+    let cpu = platform.cpu;
+    let variant = match (platform.os, platform.arch) {
+        ("windows", "x86_64") if cpu.avx512 => "win-avx512-x64",
+        ("windows", "x86_64") if cpu.avx2 => "win-avx2-x64",
+        ("windows", "x86_64") if cpu.avx => "win-avx-x64",
+        ("windows", "x86_64") => "win-noavx-x64",
+        ("windows", "aarch64") => "win-llvm-arm64",
+        ("macos", "aarch64") => "macos-arm64",
+        ("macos", "x86_64") => "macos-x64",
+        // The Linux build targets AVX2; other Linux CPUs (ARM64 with NEON, older x86)
+        // compile the release themselves
+        ("linux", "x86_64") if cpu.avx2 => "ubuntu-x64",
+        ("linux", "x86_64" | "aarch64") => return Ok(ReleaseAsset::source()),
+        (os, arch) => {
+            return Err(AppError::Config(format!(
+                "No prebuilt llama-server for {} {} (AVX2: {}, NEON: {}). \
+                 Install llama-server in PATH or import a local build.",
+                os, arch, cpu.avx2, cpu.neon
+            )))
+        }
+    };
+
+    Ok(ReleaseAsset::prebuilt(variant))
+}
+
+/// Compiles `llama-server` from the extracted source archive in `dir` for the CPU of this
+/// machine and returns the directory holding the binary.
+///
+/// Needs `cmake` and a C++ compiler; the error says so when they are missing.
+pub fn build_from_source(dir: &Path) -> Result<PathBuf, String> {
+    let root = find_source_root(dir)
+        .ok_or_else(|| format!("No llama.cpp sources (CMakeLists.txt) in {:?}", dir))?;
+    let build_dir = root.join("build");
+    let jobs = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    let configure = [
+        "-S".into(),
+        root.as_os_str().to_owned(),
+        "-B".into(),
+        build_dir.as_os_str().to_owned(),
+        "-DCMAKE_BUILD_TYPE=Release".into(),
+        "-DGGML_NATIVE=ON".into(),
+        "-DBUILD_SHARED_LIBS=OFF".into(),
+    ];
+    let build = [
+        "--build".into(),
+        build_dir.as_os_str().to_owned(),
+        "--config".into(),
+        "Release".into(),
+        "--target".into(),
+        "llama-server".into(),
+        "-j".into(),
+        jobs.to_string().into(),
+    ];
+    for args in [&configure[..], &build[..]] {
+        run_cmake(args)?;
+    }
+
+    Ok(build_dir.join("bin"))
+}
+
+/// The directory of an extracted source archive holding the top-level `CMakeLists.txt`,
+/// either `dir` itself or the single folder GitHub archives wrap the sources in.
+fn find_source_root(dir: &Path) -> Option<PathBuf> {
+    if dir.join("CMakeLists.txt").is_file() {
+        return Some(dir.to_path_buf());
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.join("CMakeLists.txt").is_file())
+}
+
+fn run_cmake(args: &[OsString]) -> Result<(), String> {
+    let output = Command::new("cmake").args(args).output().map_err(|e| {
+        format!(
+            "Building llama-server needs cmake and a C++ compiler ({}). \
+             Install them, or install llama-server in PATH or import a local build.",
+            e
+        )
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+        return Err(format!(
+            "Building llama-server failed ({}):\n{}",
+            output.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ));
+    }
+    Ok(())
+}
+
+/// Extracts a zip or tar.gz archive into `dest`.
+pub fn extract_archive(archive: &Path, dest: &Path) -> Result<(), String> {
+    let kind = ArchiveKind::from_path(archive)
+        .ok_or_else(|| format!("Unsupported archive format: {:?}", archive))?;
+    let file = File::open(archive).map_err(|e| format!("Failed to open archive: {}", e))?;
+    std::fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+
+    match kind {
+        ArchiveKind::Zip => zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dest))
+            .map_err(|e| format!("Failed to extract zip archive: {}", e)),
+        ArchiveKind::TarGz => {
+            let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+            tar.set_preserve_permissions(true);
+            tar.unpack(dest)
+                .map_err(|e| format!("Failed to extract tar.gz archive: {}", e))
+        }
+    }
+}
+
+/// Sets the executable bits on `path`. Archives do not always carry Unix permissions.
+#[cfg(unix)]
+pub fn ensure_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+pub fn ensure_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Environment variable the dynamic loader searches for shared libraries, set to the server
+/// directory so the libraries shipped with the build are found.
+/// Returns `None` on Windows, where DLLs next to the executable are always found.
+pub fn library_path_env(server_dir: &Path) -> Option<(&'static str, OsString)> {
+    let var = match std::env::consts::OS {
+        "linux" => "LD_LIBRARY_PATH",
+        "macos" => "DYLD_LIBRARY_PATH",
+        _ => return None,
+    };
+
+    let mut paths = vec![server_dir.to_path_buf()];
+    if let Some(existing) = std::env::var_os(var) {
+        paths.extend(std::env::split_paths(&existing));
+    }
+    std::env::join_paths(paths).ok().map(|value| (var, value))
+}
+
+/// Libraries a build must ship next to the server on `os`. Only the Windows releases are
+/// always dynamically linked.
+fn required_libraries(os: &str) -> &'static [&'static str] {
+    match os {
+        "windows" => &["llama.dll", "ggml.dll"],
+        _ => &[],
+    }
+}
+
+fn is_shared_library(name: &str, os: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    match os {
+        "windows" => name.ends_with(".dll"),
+        "macos" => name.ends_with(".dylib"),
+        _ => name.ends_with(".so") || name.contains(".so."),
+    }
+}
+
+/// Checks the magic number of an executable or shared library against the format `os` loads.
+fn has_native_format(path: &Path, os: &str) -> bool {
+    let mut magic = [0u8; 4];
+    let read = File::open(path).and_then(|mut file| {
+        use std::io::Read;
+        file.read_exact(&mut magic)
+    });
+    if read.is_err() {
+        return false;
+    }
+
+    match os {
+        "windows" => magic.starts_with(b"MZ"),
+        // 64-bit Mach-O (little endian) or a universal binary
+        "macos" => magic == [0xcf, 0xfa, 0xed, 0xfe] || magic == [0xca, 0xfe, 0xba, 0xbe],
+        _ => magic == *b"\x7fELF",
+    }
+}
+
+/// Validates that a `llama-server` build in `dir` is complete and made for `os`.
+pub fn validate_installation(dir: &Path, os: &str) -> Result<(), String> {
+    let server_name = if os == "windows" {
+        "llama-server.exe"
+    } else {
+        "llama-server"
+    };
+
+    let missing: Vec<&str> = std::iter::once(server_name)
+        .chain(required_libraries(os).iter().copied())
+        .filter(|file| !dir.join(file).is_file())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Incomplete llama-server installation. Missing files: {}. \
+             Try deleting {:?} and restarting the app.",
+            missing.join(", "),
+            dir
+        ));
+    }
+
+    let server_path = dir.join(server_name);
+    let size = std::fs::metadata(&server_path)
+        .map_err(|e| e.to_string())?
+        .len();
+    if size < MIN_SERVER_SIZE_BYTES {
+        return Err(format!(
+            "llama-server binary appears corrupted (size: {} bytes). \
+             Delete {:?} and restart the app.",
+            size, dir
+        ));
+    }
+    if !has_native_format(&server_path, os) {
+        return Err(format!(
+            "{:?} is not a {} executable. The build is for another platform.",
+            server_path, os
+        ));
+    }
+
+    let mut broken: Vec<PathBuf> = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let is_library = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| is_shared_library(name, os));
+        if is_library && path.is_file() && !has_native_format(&path, os) {
+            broken.push(path);
+        }
+    }
+    if !broken.is_empty() {
+        return Err(format!(
+            "Corrupted or foreign shared libraries: {:?}. Delete {:?} and restart the app.",
+            broken, dir
+        ));
+    }
+
+    #[cfg(unix)]
+    if os != "windows" {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(&server_path)
+            .map_err(|e| e.to_string())?
+            .permissions()
+            .mode();
+        if mode & 0o111 == 0 {
+            return Err(format!("{:?} is not executable", server_path));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    const ELF: &[u8] = b"\x7fELF";
+
+    fn platform(os: &'static str, arch: &'static str, cpu: CpuFeatures) -> Platform {
+        Platform { os, arch, cpu }
+    }
+
+    fn write_binary(path: &Path, magic: &[u8], len: usize) {
+        let mut data = magic.to_vec();
+        data.resize(len, 0);
+        std::fs::write(path, data).unwrap();
+        ensure_executable(path).unwrap();
+    }
+
+    #[test]
+    fn test_select_asset_by_cpu_features() {
+        let avx512 = CpuFeatures {
+            avx: true,
+            avx2: true,
+            avx512: true,
+            neon: false,
+        };
+        let avx2 = CpuFeatures {
+            avx512: false,
+            ..avx512
+        };
+        let old = CpuFeatures::default();
+        let name = |p: Platform| select_asset(&p).map(|asset| asset.file_name);
+
+        assert_eq!(
+            name(platform("windows", "x86_64", avx512)).unwrap(),
+            "llama-b4154-bin-win-avx512-x64.zip"
+        );
+        assert_eq!(
+            name(platform("windows", "x86_64", avx2)).unwrap(),
+            "llama-b4154-bin-win-avx2-x64.zip"
+        );
+        assert_eq!(
+            name(platform("windows", "x86_64", old)).unwrap(),
+            "llama-b4154-bin-win-noavx-x64.zip"
+        );
+        assert_eq!(
+            name(platform("linux", "x86_64", avx2)).unwrap(),
+            "llama-b4154-bin-ubuntu-x64.zip"
+        );
+        assert_eq!(
+            name(platform(
+                "macos",
+                "aarch64",
+                CpuFeatures { neon: true, ..old }
+            ))
+            .unwrap(),
+            "llama-b4154-bin-macos-arm64.zip"
+        );
+        assert!(name(platform("freebsd", "x86_64", avx2)).is_err());
+
+        let asset = select_asset(&platform("macos", "x86_64", avx2)).unwrap();
+        assert_eq!(
+            asset.url,
+            "https://github.com/ggml-org/llama.cpp/releases/download/b4154/llama-b4154-bin-macos-x64.zip"
+        );
+    }
+
+    #[test]
+    fn test_select_asset_builds_unsupported_linux_cpus_from_source() {
+        let neon = CpuFeatures {
+            neon: true,
+            ..CpuFeatures::default()
+        };
+        let no_avx2 = CpuFeatures {
+            avx: true,
+            ..CpuFeatures::default()
+        };
+        let source = "https://github.com/ggml-org/llama.cpp/archive/refs/tags/b4154.tar.gz";
+
+        for platform in [
+            platform("linux", "aarch64", neon),
+            platform("linux", "x86_64", no_avx2),
+        ] {
+            let asset = select_asset(&platform).unwrap();
+            assert!(asset.source, "{:?}", platform);
+            assert_eq!(asset.url, source);
+            assert_eq!(asset.file_name, "llama.cpp-b4154.tar.gz");
+            assert_eq!(
+                ArchiveKind::from_path(Path::new(&asset.file_name)),
+                Some(ArchiveKind::TarGz)
+            );
+        }
+
+        let prebuilt = select_asset(&platform(
+            "linux",
+            "x86_64",
+            CpuFeatures {
+                avx2: true,
+                ..no_avx2
+            },
+        ))
+        .unwrap();
+        assert!(!prebuilt.source);
+    }
+
+    #[test]
+    fn test_find_source_root() {
+        let temp = TempDir::new().unwrap();
+        assert_eq!(find_source_root(temp.path()), None);
+
+        let root = temp.path().join("llama.cpp-b4154");
+        std::fs::create_dir_all(root.join("examples")).unwrap();
+        std::fs::write(root.join("CMakeLists.txt"), "project(llama.cpp)").unwrap();
+        assert_eq!(find_source_root(temp.path()), Some(root.clone()));
+        assert_eq!(find_source_root(&root), Some(root));
+    }
+
+    #[test]
+    fn test_archive_kind_from_path() {
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("llama.ZIP")),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("llama-bin.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("llama.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(ArchiveKind::from_path(Path::new("llama-server")), None);
+    }
+
+    #[test]
+    fn test_extract_tar_gz_keeps_layout() {
+        let temp = TempDir::new().unwrap();
+        let archive_path = temp.path().join("llama.tar.gz");
+        {
+            let encoder = flate2::write::GzEncoder::new(
+                File::create(&archive_path).unwrap(),
+                flate2::Compression::fast(),
+            );
+            let mut builder = tar::Builder::new(encoder);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "build/bin/llama-server", ELF)
+                .unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+        let dest = temp.path().join("out");
+
+        extract_archive(&archive_path, &dest).unwrap();
+
+        let server = dest.join("build").join("bin").join("llama-server");
+        assert_eq!(std::fs::read(&server).unwrap(), ELF);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&server).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let temp = TempDir::new().unwrap();
+        let archive_path = temp.path().join("llama.zip");
+        {
+            let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+            zip.start_file("llama-server", zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(ELF).unwrap();
+            zip.finish().unwrap();
+        }
+        let dest = temp.path().join("out");
+
+        extract_archive(&archive_path, &dest).unwrap();
+
+        assert_eq!(std::fs::read(dest.join("llama-server")).unwrap(), ELF);
+        assert!(extract_archive(&temp.path().join("llama.rar"), &dest).is_err());
+    }
+
+    #[test]
+    fn test_validate_linux_installation() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let size = MIN_SERVER_SIZE_BYTES as usize;
+
+        // Missing binary
+        assert!(validate_installation(dir, "linux").is_err());
+
+        // A Windows build extracted on Linux
+        write_binary(&dir.join("llama-server"), b"MZ", size);
+        let err = validate_installation(dir, "linux").unwrap_err();
+        assert!(err.contains("another platform"), "{}", err);
+
+        // Statically linked build
+        write_binary(&dir.join("llama-server"), ELF, size);
+        assert!(validate_installation(dir, "linux").is_ok());
+
+        // Shared libraries must be intact, including versioned names
+        write_binary(&dir.join("libllama.so"), ELF, 16);
+        write_binary(&dir.join("libggml.so.1"), b"", 0);
+        let err = validate_installation(dir, "linux").unwrap_err();
+        assert!(err.contains("libggml.so.1"), "{}", err);
+        write_binary(&dir.join("libggml.so.1"), ELF, 16);
+        assert!(validate_installation(dir, "linux").is_ok());
+
+        // Truncated binary
+        write_binary(&dir.join("llama-server"), ELF, 1024);
+        assert!(validate_installation(dir, "linux").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_requires_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let server = temp.path().join("llama-server");
+        write_binary(&server, ELF, MIN_SERVER_SIZE_BYTES as usize);
+        std::fs::set_permissions(&server, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let err = validate_installation(temp.path(), "linux").unwrap_err();
+        assert!(err.contains("not executable"), "{}", err);
+
+        ensure_executable(&server).unwrap();
+        assert!(validate_installation(temp.path(), "linux").is_ok());
+    }
+
+    #[test]
+    fn test_validate_windows_requires_dlls() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        write_binary(
+            &dir.join("llama-server.exe"),
+            b"MZ",
+            MIN_SERVER_SIZE_BYTES as usize,
+        );
+
+        let err = validate_installation(dir, "windows").unwrap_err();
+        assert!(err.contains("llama.dll, ggml.dll"), "{}", err);
+
+        write_binary(&dir.join("llama.dll"), b"MZ", 16);
+        write_binary(&dir.join("ggml.dll"), b"MZ", 16);
+        assert!(validate_installation(dir, "windows").is_ok());
+    }
+
+    #[test]
+    fn test_validate_macos_installation() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        write_binary(
+            &dir.join("llama-server"),
+            &[0xcf, 0xfa, 0xed, 0xfe],
+            MIN_SERVER_SIZE_BYTES as usize,
+        );
+        write_binary(&dir.join("libllama.dylib"), &[0xcf, 0xfa, 0xed, 0xfe], 16);
+
+        assert!(validate_installation(dir, "macos").is_ok());
+        assert!(validate_installation(dir, "linux").is_err());
+    }
+
+    #[test]
+    fn test_library_path_env_prepends_server_dir() {
+        let dir = Path::new("/opt/whytchat/tools/llama");
+
+        match library_path_env(dir) {
+            Some((var, value)) => {
+                assert!(var == "LD_LIBRARY_PATH" || var == "DYLD_LIBRARY_PATH");
+                let first = std::env::split_paths(&value).next().unwrap();
+                assert_eq!(first, dir);
+            }
+            None => assert_eq!(std::env::consts::OS, "windows"),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Installing user-supplied files for offline setups.
+//!
+//! Machines without internet access cannot download the model or `llama-server`, so their
+//! files are imported from a local path instead. Files are hard-linked when the source is on
+//! the same volume (no extra disk space for multi-GB models) and copied otherwise. Imports are
+//! staged next to their destination and only moved into place once validated.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How deep `find_server_dir` looks for the server binary (release archives nest it in
+/// `build/bin`).
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// Hard-links `src` to `dst`, falling back to a copy (e.g. across volumes).
+pub fn link_or_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    if fs::hard_link(src, dst).is_ok() {
+        return Ok(());
+    }
+    fs::copy(src, dst).map(|_| ())
+}
+
+/// Replaces `path` with a copy of itself, so that changing it (e.g. its permissions) does not
+/// change the file it was hard-linked from.
+pub fn detach_link(path: &Path) -> io::Result<()> {
+    let mut copy_name = path.file_name().unwrap_or_default().to_os_string();
+    copy_name.push(".copy");
+    let copy = path.with_file_name(copy_name);
+    fs::copy(path, &copy)?;
+    fs::rename(&copy, path)
+}
+
+/// Links or copies the files directly inside `src_dir` into `dst_dir`, creating it if needed.
+/// Returns the number of files installed.
+pub fn link_or_copy_dir(src_dir: &Path, dst_dir: &Path) -> io::Result<usize> {
+    fs::create_dir_all(dst_dir)?;
+    let mut count = 0;
+    for entry in fs::read_dir(src_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            link_or_copy(&entry.path(), &dst_dir.join(entry.file_name()))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the directory holding `binary_name`, given either the binary itself or a
+/// directory containing it (possibly in a subdirectory).
+pub fn find_server_dir(source: &Path, binary_name: &str) -> Option<PathBuf> {
+    if source.is_file() {
+        if source.file_name()? != binary_name {
+            return None;
+        }
+        return source.parent().map(Path::to_path_buf);
+    }
+    find_in_dir(source, binary_name, MAX_SEARCH_DEPTH)
+}
+
+fn find_in_dir(dir: &Path, binary_name: &str, depth: usize) -> Option<PathBuf> {
+    if dir.join(binary_name).is_file() {
+        return Some(dir.to_path_buf());
+    }
+    if depth == 0 {
+        return None;
+    }
+
+    let mut subdirs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    subdirs.sort();
+    subdirs
+        .iter()
+        .find_map(|subdir| find_in_dir(subdir, binary_name, depth - 1))
+}
+
+/// Returns the staging path used while importing into `target`.
+pub fn staging_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".import");
+    target.with_file_name(name)
+}
+
+/// Removes a file or directory if it exists.
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Moves a validated staging file or directory to `target`, replacing what is there.
+pub fn commit_staged(staging: &Path, target: &Path) -> io::Result<()> {
+    remove_path(target)?;
+    fs::rename(staging, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_link_or_copy_dir_installs_top_level_files() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("build");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("llama-server"), b"server").unwrap();
+        fs::write(src.join("libllama.so"), b"lib").unwrap();
+        fs::write(src.join("nested").join("ignored"), b"x").unwrap();
+        let dst = temp.path().join("tools").join("llama");
+
+        let count = link_or_copy_dir(&src, &dst).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(fs::read(dst.join("llama-server")).unwrap(), b"server");
+        assert!(dst.join("libllama.so").is_file());
+        assert!(!dst.join("nested").exists());
+    }
+
+    #[test]
+    fn test_detach_link_leaves_source_unchanged() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("llama-server");
+        fs::write(&source, b"server").unwrap();
+        let staged = temp.path().join("staged");
+        link_or_copy(&source, &staged).unwrap();
+
+        detach_link(&staged).unwrap();
+        fs::write(&staged, b"changed").unwrap();
+
+        assert_eq!(fs::read(&source).unwrap(), b"server");
+        assert!(!temp.path().join("staged.copy").exists());
+    }
+
+    #[test]
+    fn test_find_server_dir() {
+        let temp = TempDir::new().unwrap();
+        let bin = temp.path().join("build").join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("llama-server"), b"server").unwrap();
+
+        assert_eq!(
+            find_server_dir(temp.path(), "llama-server"),
+            Some(bin.clone())
+        );
+        assert_eq!(
+            find_server_dir(&bin.join("llama-server"), "llama-server"),
+            Some(bin.clone())
+        );
+        assert_eq!(find_server_dir(&bin.join("llama-server"), "other"), None);
+        assert_eq!(find_server_dir(temp.path(), "missing"), None);
+    }
+
+    #[test]
+    fn test_commit_staged_replaces_target() {
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("llama");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("old"), b"old").unwrap();
+        let staging = staging_path(&target);
+        assert_eq!(staging, temp.path().join("llama.import"));
+        fs::create_dir(&staging).unwrap();
+        fs::write(staging.join("new"), b"new").unwrap();
+
+        commit_staged(&staging, &target).unwrap();
+
+        assert!(target.join("new").is_file());
+        assert!(!target.join("old").exists());
+        assert!(!staging.exists());
+    }
+
+    #[test]
+    fn test_commit_staged_file() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.gguf");
+        fs::write(&source, b"GGUF").unwrap();
+        let target = temp.path().join("models").join("model.gguf");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        let staging = staging_path(&target);
+
+        link_or_copy(&source, &staging).unwrap();
+        commit_staged(&staging, &target).unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), b"GGUF");
+        // The source is left in place
+        assert!(source.is_file());
+        remove_path(&target).unwrap();
+        remove_path(&target).unwrap();
+        assert!(!target.exists());
+    }
+}
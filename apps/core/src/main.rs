@@ -6,10 +6,23 @@
 /// "The Brain" - Orchestrator of Cognitive Actors
 mod actors;
 mod brain;
+mod chat_template;
+mod chunking;
+mod context_budget;
 mod database;
 mod diagnostics;
+mod download_manager;
+mod embedding_config;
 mod error;
 mod fs_manager;
+mod gguf;
+mod hybrid_search;
+mod launch_profile;
+mod library_search;
+mod llama_release;
+mod local_import;
+mod model_catalog;
+mod model_store;
 mod models;
 mod preflight;
 mod rate_limiter;
@@ -18,25 +31,21 @@ mod text_extract;
 use actors::supervisor::SupervisorHandle;
 use fs_manager::PortablePathManager;
 use rate_limiter::RateLimiter;
+use sha2::{Digest, Sha256};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex,
 };
 mod encryption;
-use futures::StreamExt;
-use std::fs::File;
 use std::time::Duration;
 use tauri::{Emitter, RunEvent, State, WindowEvent};
-use tokio::io::AsyncWriteExt;
 use tracing::{error, info, subscriber::set_global_default, warn};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
-use zip::ZipArchive;
+use validator::Validate;
 
 // --- Constants ---
 const DEFAULT_MODEL_FILENAME: &str = "default-model.gguf";
-const LLAMA_SERVER_URL: &str = "https://github.com/ggml-org/llama.cpp/releases/download/b4154/llama-b4154-bin-win-avx2-x64.zip";
-const MODEL_URL: &str = "https://huggingface.co/Qwen/Qwen2.5-Coder-7B-Instruct-GGUF/resolve/main/qwen2.5-coder-7b-instruct-q4_k_m.gguf";
 
 // --- State Management ---
 
@@ -135,7 +144,7 @@ fn check_rate_limit_and_get_resources(
 /// Asynchronously initializes the application's backend services.
 /// This function is called by the frontend after the UI is ready.
 #[tauri::command]
-async fn initialize_app(state: State<'_, AppState>) -> Result<(), String> {
+async fn initialize_app(window: tauri::Window, state: State<'_, AppState>) -> Result<(), String> {
     if state.is_initialized.load(Ordering::SeqCst) {
         info!("Application already initialized.");
         return Ok(());
@@ -158,6 +167,11 @@ async fn initialize_app(state: State<'_, AppState>) -> Result<(), String> {
     let model_path = PortablePathManager::models_dir().join(DEFAULT_MODEL_FILENAME);
     let supervisor = SupervisorHandle::new_with_pool_and_model(Some(db_pool.clone()), model_path);
 
+    // Ingestion jobs resumed from a previous run report their progress to this window
+    if let Err(e) = supervisor.watch_ingest_jobs(window).await {
+        warn!("Failed to watch ingestion jobs: {}", e);
+    }
+
     // Store the initialized state
     let mut app_handle = state
         .app_handle
@@ -230,6 +244,7 @@ async fn debug_chat(
             model_id: DEFAULT_MODEL_FILENAME.to_string(),
             temperature: 0.7,
             system_prompt: String::new(),
+            ..Default::default()
         };
         if let Err(e) = database::create_session_with_id(
             &pool,
@@ -250,6 +265,174 @@ async fn debug_chat(
         .map_err(|e| e.to_string())
 }
 
+/// Tauri command to generate a new answer for a message.
+///
+/// `message_id` may be an assistant message (its user message is answered again) or a user
+/// message. The previous answer stays available as a sibling branch.
+///
+/// # Returns
+///
+/// The full text of the new answer.
+#[tracing::instrument(skip(window, state))]
+#[tauri::command]
+async fn regenerate_message(
+    session_id: String,
+    message_id: i64,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    let (_, supervisor) = check_rate_limit_and_get_resources(&state, &session_id)?;
+
+    supervisor
+        .regenerate_message(session_id, message_id, Some(&window))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to edit an earlier user message and answer the new version.
+///
+/// The original message and everything after it stay available as a sibling branch.
+///
+/// # Returns
+///
+/// The full text of the answer to the edited message.
+#[tracing::instrument(skip(window, state, content))]
+#[tauri::command]
+async fn edit_message(
+    session_id: String,
+    message_id: i64,
+    content: String,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    if content.trim().is_empty() {
+        return Err("Message content cannot be empty".to_string());
+    }
+
+    let (_, supervisor) = check_rate_limit_and_get_resources(&state, &session_id)?;
+
+    supervisor
+        .edit_message(session_id, message_id, content, Some(&window))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to show another version of a message (one of its `sibling_ids`).
+///
+/// # Returns
+///
+/// The messages of the newly active branch.
+#[tracing::instrument(skip(state))]
+#[tauri::command]
+async fn switch_branch(
+    session_id: String,
+    message_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::models::Message>, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    let pool = get_pool(&state)?;
+
+    database::switch_branch(&pool, &session_id, message_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to open a source cited by an assistant message.
+///
+/// # Returns
+///
+/// The chunk's text and provenance, or an error if the chunk no longer exists because its
+/// file was removed or reindexed since the answer.
+#[tracing::instrument(skip(state))]
+#[tauri::command]
+async fn get_source_chunk(
+    chunk_id: String,
+    state: State<'_, AppState>,
+) -> Result<crate::actors::messages::SearchResult, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    let (_, supervisor) = get_pool_and_supervisor(&state)?;
+
+    supervisor
+        .get_chunk(chunk_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            "This source is no longer available: its document was removed or reindexed.".to_string()
+        })
+}
+
+/// Tauri command to search the library for a query, outside of any chat.
+///
+/// Only the files in `folder_id` and with one of the `file_types` extensions are searched
+/// when given. Returns up to `limit` matching chunks, grouped by file, best file first.
+#[tracing::instrument(skip(state))]
+#[tauri::command]
+async fn search_library(
+    query: String,
+    folder_id: Option<String>,
+    file_types: Option<Vec<String>>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<library_search::LibrarySearchHit>, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (_, supervisor) = get_pool_and_supervisor(&state)?;
+    let filter = library_search::LibrarySearchFilter {
+        folder_id,
+        file_types: file_types.unwrap_or_default(),
+    };
+    let limit = limit
+        .unwrap_or(library_search::DEFAULT_LIMIT)
+        .clamp(1, library_search::MAX_LIMIT);
+
+    supervisor
+        .search_library(query, filter, limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to stop the response currently being generated for a session.
+///
+/// The partial answer streamed so far is saved as an interrupted assistant message and
+/// returned by the pending `debug_chat` call.
+///
+/// # Returns
+///
+/// `true` if a generation was running and has been stopped.
+#[tracing::instrument(skip(state))]
+#[tauri::command]
+async fn cancel_generation(session_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    let (_, supervisor) = get_pool_and_supervisor(&state)?;
+
+    supervisor
+        .cancel_generation(session_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Tauri command to create a new chat session.
 ///
 /// # Arguments
@@ -290,6 +473,7 @@ async fn create_session(
         model_id: DEFAULT_MODEL_FILENAME.to_string(),
         temperature: temperature.unwrap_or(0.7),
         system_prompt: final_system_prompt,
+        ..Default::default()
     };
     let session = database::create_session(&pool, title, model_config)
         .await
@@ -369,6 +553,12 @@ async fn update_session(
         return Err("Application is not initialized yet.".to_string());
     }
 
+    if let Some(config) = &model_config {
+        config
+            .validate()
+            .map_err(|e| format!("Invalid model configuration: {}", e))?;
+    }
+
     let pool = get_pool(&state)?;
 
     database::update_session(&pool, &session_id, title, model_config)
@@ -473,6 +663,42 @@ async fn move_session_to_folder(
         .map_err(|e| e.to_string())
 }
 
+/// Sets which documents a session searches: its linked files, a document folder, the
+/// whole library or none.
+#[tracing::instrument(skip(state))]
+#[tauri::command]
+async fn set_session_retrieval_scope(
+    session_id: String,
+    scope: models::RetrievalScope,
+    folder_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
+
+    let pool = get_pool(&state)?;
+
+    if scope == models::RetrievalScope::Folder {
+        let folder_id = folder_id
+            .as_deref()
+            .ok_or("A folder is required to search a folder.")?;
+        let folders = database::list_folders(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !folders
+            .iter()
+            .any(|f| f.id == folder_id && f.folder_type == "document")
+        {
+            return Err(format!("Document folder {} not found.", folder_id));
+        }
+    }
+
+    database::set_session_retrieval_scope(&pool, &session_id, scope, folder_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tracing::instrument(skip(state))]
 #[tauri::command]
 async fn move_file_to_folder(
@@ -518,9 +744,16 @@ async fn delete_file(file_id: String, state: State<'_, AppState>) -> Result<(),
     Ok(())
 }
 
-#[tracing::instrument(skip(state))]
+/// Queues every library file for reindexing and returns their jobs right away.
+/// The background worker reports its progress with `ingest-progress` events, and skips the
+/// files unchanged since they were indexed with the current embedding model unless `force`.
+#[tracing::instrument(skip(window, state))]
 #[tauri::command]
-async fn reindex_library(state: State<'_, AppState>) -> Result<String, String> {
+async fn reindex_library(
+    force: Option<bool>,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<Vec<models::IngestJob>, String> {
     if !state.is_initialized.load(Ordering::SeqCst) {
         return Err("Application is not initialized yet.".to_string());
     }
@@ -532,43 +765,48 @@ async fn reindex_library(state: State<'_, AppState>) -> Result<String, String> {
     let files = database::list_library_files(&pool)
         .await
         .map_err(|e| e.to_string())?;
+    let file_ids = files.into_iter().map(|file| file.id).collect();
 
-    let total_files = files.len();
-    let mut success_count = 0;
-    let mut error_count = 0;
+    let jobs = supervisor
+        .enqueue_ingest(file_ids, force.unwrap_or(false), Some(&window))
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("Queued {} library files for reindexing", jobs.len());
+    Ok(jobs)
+}
 
-    for file in files {
-        let path = std::path::Path::new(&file.path);
-        if !path.exists() {
-            warn!("File not found during reindex: {:?}", path);
-            error_count += 1;
-            continue;
-        }
+/// Tauri command to list the ingestion jobs with their status, most recent first.
+#[tauri::command]
+async fn list_ingest_jobs(state: State<'_, AppState>) -> Result<Vec<models::IngestJob>, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
+    }
 
-        match tokio::fs::read_to_string(path).await {
-            Ok(content) => match supervisor.reindex_file(file.id.clone(), content).await {
-                Ok(_) => {
-                    info!("Reindexed file: {}", file.name);
-                    success_count += 1;
-                }
-                Err(e) => {
-                    error!("Failed to reindex file {}: {}", file.name, e);
-                    error_count += 1;
-                }
-            },
-            Err(e) => {
-                error!("Failed to read file {}: {}", file.name, e);
-                error_count += 1;
-            }
-        }
+    let pool = get_pool(&state)?;
+    database::list_ingest_jobs(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to queue a library file for ingestion again, e.g. after its job failed.
+#[tracing::instrument(skip(window, state))]
+#[tauri::command]
+async fn retry_ingest_job(
+    file_id: String,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> Result<models::IngestJob, String> {
+    if !state.is_initialized.load(Ordering::SeqCst) {
+        return Err("Application is not initialized yet.".to_string());
     }
 
-    let result_msg = format!(
-        "Reindexing complete. Processed {} files. Success: {}, Errors: {}",
-        total_files, success_count, error_count
-    );
-    info!("{}", result_msg);
-    Ok(result_msg)
+    let (_, supervisor) = get_pool_and_supervisor(&state)?;
+    supervisor
+        .enqueue_ingest(vec![file_id], true, Some(&window))
+        .await
+        .map_err(|e| e.to_string())?
+        .pop()
+        .ok_or_else(|| "Failed to queue the file".to_string())
 }
 
 #[tracing::instrument(skip(state))]
@@ -661,21 +899,31 @@ async fn save_generated_file(
 
     // 4. Ingest
     supervisor
-        .ingest_content(content, Some(format!("file:{}", file_uuid)))
+        .ingest_content(
+            content,
+            Some(file_uuid.clone()),
+            chunking::ChunkingConfig::load().options_for(&safe_name),
+        )
         .await
         .map_err(|e| e.to_string())?;
 
     Ok(file_uuid)
 }
 
-#[tracing::instrument(skip(file_data, state))]
+/// Adds a file to the library, links it to the session and queues it for indexing.
+///
+/// If the library already holds a file with the same content, that file is linked instead of
+/// storing and embedding a copy, unless `force_copy` is set.
+#[tracing::instrument(skip(file_data, window, state))]
 #[tauri::command]
 async fn upload_file_for_session(
     session_id: String,
     file_name: String,
     file_data: Vec<u8>,
+    force_copy: Option<bool>,
+    window: tauri::Window,
     state: State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<models::UploadedFile, String> {
     if !state.is_initialized.load(Ordering::SeqCst) {
         return Err("Application is not initialized yet.".to_string());
     }
@@ -701,8 +949,10 @@ async fn upload_file_for_session(
         .map(|s| s.to_lowercase())
         .unwrap_or_default();
 
-    let allowed_extensions = ["txt", "md", "csv", "json", "pdf", "docx", "doc"];
-    if !allowed_extensions.contains(&extension.as_str()) {
+    let allowed_extensions = ["txt", "md", "markdown", "csv", "json", "pdf", "docx", "doc"];
+    if !allowed_extensions.contains(&extension.as_str())
+        && !chunking::CODE_EXTENSIONS.contains(&extension.as_str())
+    {
         return Err(format!("File extension '.{}' is not supported.", extension));
     }
 
@@ -714,15 +964,6 @@ async fn upload_file_for_session(
         return Err("Binary files are not supported".to_string());
     }
 
-    // Extract text content using our text extraction module
-    let content = text_extract::extract_text_from_file(&file_name, &file_data)?;
-
-    if content.trim().is_empty() {
-        return Err("No text content could be extracted from the file".to_string());
-    }
-
-    info!("   ✓ Extracted {} characters from file", content.len());
-
     // Get pool and supervisor from state
     let (pool, supervisor) = get_pool_and_supervisor(&state)?;
 
@@ -736,6 +977,7 @@ async fn upload_file_for_session(
             model_id: DEFAULT_MODEL_FILENAME.to_string(),
             temperature: 0.7,
             system_prompt: String::new(),
+            ..Default::default()
         };
         if let Err(e) = database::create_session_with_id(
             &pool,
@@ -749,6 +991,28 @@ async fn upload_file_for_session(
         }
     }
 
+    // Reuse an identical file already in the library
+    let content_hash = hex::encode(Sha256::digest(&file_data));
+    if !force_copy.unwrap_or(false) {
+        let existing = database::find_library_file_by_hash(&pool, &content_hash)
+            .await
+            .map_err(|e| format!("Failed to look up duplicate files: {}", e))?;
+        if let Some(file) = existing {
+            database::link_file_to_session(&pool, &session_id, &file.id)
+                .await
+                .map_err(|e| format!("Failed to link file to session: {}", e))?;
+            info!(
+                "   ✓ Identical to library file {} ({}), linked instead",
+                file.name, file.id
+            );
+            return Ok(models::UploadedFile {
+                file,
+                deduplicated: true,
+                job: None,
+            });
+        }
+    }
+
     // 1. Save file to disk (Global Library Storage)
     let file_uuid = uuid::Uuid::new_v4().to_string();
     let files_dir = PortablePathManager::data_dir().join("files");
@@ -775,7 +1039,7 @@ async fn upload_file_for_session(
     };
 
     // Add to library
-    let _library_file = database::add_library_file(
+    let mut library_file = database::add_library_file(
         &pool,
         &file_uuid,
         &file_name,
@@ -786,20 +1050,32 @@ async fn upload_file_for_session(
     .await
     .map_err(|e| format!("Failed to add file to library: {}", e))?;
 
+    // Record its hash so later uploads of the same content are deduplicated
+    database::set_library_file_hash(&pool, &file_uuid, &content_hash)
+        .await
+        .map_err(|e| format!("Failed to record file hash: {}", e))?;
+    library_file.content_hash = Some(content_hash);
+
     // Link to session
     database::link_file_to_session(&pool, &session_id, &file_uuid)
         .await
         .map_err(|e| format!("Failed to link file to session: {}", e))?;
 
     info!("   ✓ File record added to database and linked to session");
-    info!("   ✓ Ingesting content into RAG...");
 
-    // 3. Ingest content into RAG
-    // Use file:{uuid} as metadata so we can filter by file later
-    supervisor
-        .ingest_content(content, Some(format!("file:{}", file_uuid)))
+    // 3. Queue the file for text extraction and indexing in the background
+    let job = supervisor
+        .enqueue_ingest(vec![file_uuid], false, Some(&window))
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?
+        .pop()
+        .ok_or_else(|| "Failed to queue the file for indexing".to_string())?;
+    info!("   ✓ File queued for indexing (job {})", job.id);
+    Ok(models::UploadedFile {
+        file: library_file,
+        deduplicated: false,
+        job: Some(job),
+    })
 }
 
 /// Links an existing library file to a session (without re-uploading)
@@ -836,307 +1112,313 @@ async fn link_library_file_to_session(
     Ok(())
 }
 
-/// Downloads a file with resume support.
-/// If the file already exists partially, it will resume from where it left off.
-async fn download_file<P: AsRef<std::path::Path>>(
+/// Downloads a file through the download manager, reporting progress to the window.
+/// While it runs, the download can be paused, resumed or cancelled using its id (the file name).
+/// An interrupted download resumes where it stopped on the next call.
+async fn download_file(
     url: &str,
-    path: P,
+    path: &std::path::Path,
     window: &tauri::Window,
+    downloads: &download_manager::DownloadManager,
     progress_base: u64,
     progress_scale: u64,
 ) -> Result<(), String> {
-    let path_ref = path.as_ref();
-    info!("Starting download from '{}' to '{:?}'", url, path_ref);
-
-    // Check if file already exists (partial download)
-    let existing_size = match std::fs::metadata(path_ref) {
-        Ok(meta) => meta.len(),
-        Err(_) => 0,
-    };
-
-    let client = reqwest::Client::builder()
-        .user_agent("WhytChat/1.0")
-        .tcp_keepalive(Duration::from_secs(30))
-        .redirect(reqwest::redirect::Policy::limited(10)) // Follow up to 10 redirects
-        .build()
-        .map_err(|e| format!("Failed to build client: {}", e))?;
-
-    // Get total size: try HEAD first, fallback to GET with Range: bytes=0-0
-    // HuggingFace uses 302 redirects that might not preserve Content-Length on HEAD
-    let total_size = {
-        // Try HEAD first
-        let head_res = client.head(url).send().await;
-
-        match head_res {
-            Ok(res) if res.status().is_success() => res.content_length().unwrap_or(0),
-            _ => 0,
-        }
-    };
-
-    // If HEAD didn't give us size, try a range request to get it
-    let total_size = if total_size == 0 {
-        info!("HEAD request didn't return content-length, trying GET with Range header");
-        let range_res = client
-            .get(url)
-            .header("Range", "bytes=0-0")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to GET range from '{}': {}", url, e))?;
-
-        // Content-Range header format: "bytes 0-0/TOTAL_SIZE"
-        if let Some(content_range) = range_res.headers().get("content-range") {
-            if let Ok(range_str) = content_range.to_str() {
-                // Parse "bytes 0-0/12345678" to extract 12345678
-                if let Some(total_str) = range_str.split('/').next_back() {
-                    total_str.parse::<u64>().unwrap_or(0)
-                } else {
-                    0
-                }
-            } else {
-                0
+    let id = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let active = downloads.start(&id)?;
+    let client = download_manager::build_client()?;
+
+    download_manager::download(
+        &client,
+        url,
+        path,
+        active.control(),
+        &download_manager::DownloadOptions::default(),
+        |mut progress| {
+            progress.percent = progress_base
+                + progress.downloaded_bytes * progress_scale / progress.total_bytes.max(1);
+            progress.download_id = Some(active.id().to_string());
+            // Make emit non-fatal (log error but continue)
+            if let Err(e) = window.emit("download-progress", progress) {
+                error!("Failed to emit progress event: {}", e);
             }
-        } else {
-            // Last resort: use content-length from response headers if available
-            range_res.content_length().unwrap_or(0)
-        }
-    } else {
-        total_size
-    };
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
 
-    info!(
-        "Total file size: {} bytes, already downloaded: {} bytes",
-        total_size, existing_size
-    );
+/// Looks up a download in progress for the pause/resume/cancel commands.
+fn active_download(
+    downloads: &download_manager::DownloadManager,
+    download_id: &str,
+) -> Result<Arc<download_manager::DownloadControl>, String> {
+    downloads
+        .get(download_id)
+        .ok_or_else(|| format!("No download in progress with id '{}'", download_id))
+}
 
-    // If file is already complete, skip download
-    if total_size > 0 && existing_size >= total_size {
-        info!("File already fully downloaded, skipping");
-        window
-            .emit("download-progress", progress_base + progress_scale)
-            .ok();
-        return Ok(());
-    }
+/// Tauri command to pause a download. Its connections are closed until it is resumed.
+#[tracing::instrument(skip(downloads))]
+#[tauri::command]
+fn pause_download(
+    download_id: String,
+    downloads: State<'_, download_manager::DownloadManager>,
+) -> Result<(), String> {
+    active_download(&downloads, &download_id)?.pause();
+    info!("Paused download '{}'", download_id);
+    Ok(())
+}
 
-    // Also, if total_size is 0, it's an error.
-    if total_size == 0 {
-        return Err(format!(
-            "Failed to get valid content length from '{}', size was 0.",
-            url
-        ));
-    }
+/// Tauri command to resume a paused download.
+#[tracing::instrument(skip(downloads))]
+#[tauri::command]
+fn resume_download(
+    download_id: String,
+    downloads: State<'_, download_manager::DownloadManager>,
+) -> Result<(), String> {
+    active_download(&downloads, &download_id)?.resume();
+    info!("Resumed download '{}'", download_id);
+    Ok(())
+}
 
-    // Build request with Range header for resume
-    let mut request = client.get(url);
-    let mut downloaded: u64 = existing_size;
+/// Tauri command to cancel a download and delete its partial data.
+/// The command that started the download then fails with "Download cancelled".
+#[tracing::instrument(skip(downloads))]
+#[tauri::command]
+fn cancel_download(
+    download_id: String,
+    downloads: State<'_, download_manager::DownloadManager>,
+) -> Result<(), String> {
+    active_download(&downloads, &download_id)?.cancel();
+    info!("Cancelled download '{}'", download_id);
+    Ok(())
+}
+
+/// Validates that the llama-server installation in `llama_dir` is complete and built for
+/// this platform.
+fn validate_llama_installation(llama_dir: &std::path::Path) -> Result<(), String> {
+    llama_release::validate_installation(llama_dir, std::env::consts::OS)
+}
 
-    if existing_size > 0 {
-        info!("Resuming download from byte {}", existing_size);
-        request = request.header("Range", format!("bytes={}-", existing_size));
+/// Validates that the model file is complete and not corrupted.
+fn validate_model_file(model_path: &std::path::Path) -> Result<gguf::GgufMetadata, String> {
+    if !model_path.exists() {
+        return Err(format!("Model file not found at {:?}", model_path));
     }
 
-    let res = request
-        .send()
-        .await
-        .map_err(|e| format!("Failed to GET from '{}': {}", url, e))?;
+    // Parses the GGUF header and checks that the tensor data it describes is all there
+    let metadata = gguf::validate(model_path).map_err(|e| {
+        format!(
+            "Model file is invalid or incomplete: {}. Delete and re-download.",
+            e
+        )
+    })?;
 
-    let status = res.status();
+    info!("✓ Model file validated: {}", metadata.summary());
+    Ok(metadata)
+}
 
-    // 206 Partial Content = resume successful, 200 OK = server doesn't support resume
-    if !status.is_success() && status != reqwest::StatusCode::PARTIAL_CONTENT {
-        let err_msg = format!("Download failed. Status: {}. URL: {}", status, url);
-        error!("{}", err_msg);
-        return Err(err_msg);
-    }
+/// Tauri command to check if the model file exists AND is complete.
+/// Used by frontend to determine if onboarding should be shown.
+/// Returns true only if the model file has a valid GGUF header and all of its tensor data.
+#[tauri::command]
+fn check_model_exists() -> bool {
+    let model_path = PortablePathManager::models_dir().join(DEFAULT_MODEL_FILENAME);
 
-    // If server returned 200 instead of 206, it doesn't support resume - start from scratch
-    if status == reqwest::StatusCode::OK && existing_size > 0 {
-        info!("Server doesn't support resume, starting from scratch");
-        downloaded = 0;
+    match validate_model_file(&model_path) {
+        Ok(_) => true,
+        Err(e) => {
+            info!("Model check: {:?} is not usable: {}", model_path, e);
+            false
+        }
     }
+}
 
-    // Open file in append mode if resuming, create mode if starting fresh
-    let file = if downloaded > 0 {
-        tokio::fs::OpenOptions::new()
-            .append(true)
-            .open(path_ref)
-            .await
-            .map_err(|e| format!("Failed to open file for append at '{:?}': {}", path_ref, e))?
-    } else {
-        tokio::fs::File::create(path_ref)
-            .await
-            .map_err(|e| format!("Failed to create file at '{:?}': {}", path_ref, e))?
-    };
-
-    // Use a massive buffer (8MB) to ensure disk I/O is never the bottleneck
-    let mut writer = tokio::io::BufWriter::with_capacity(8 * 1024 * 1024, file);
-    let mut stream = res.bytes_stream();
-    let mut last_emit = std::time::Instant::now();
+/// Tauri command to list the GGUF models installed in the models directory.
+/// A session selects one by setting its `model_id` to the model's file name.
+#[tauri::command]
+fn list_models() -> Result<Vec<model_store::ModelInfo>, String> {
+    model_store::list_installed_models().map_err(|e| format!("Failed to list models: {}", e))
+}
 
-    // Emit initial progress (especially important for resume)
-    let initial_percentage = progress_base + (downloaded * progress_scale / total_size);
-    window.emit("download-progress", initial_percentage).ok();
+/// Tauri command to list the models that can be downloaded with `download_model`.
+#[tauri::command]
+fn list_model_catalog() -> Vec<model_catalog::CatalogEntry> {
+    model_catalog::ModelCatalog::load().models
+}
 
-    while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| format!("Error while downloading file: {}", e))?;
-        writer
-            .write_all(&chunk)
-            .await
-            .map_err(|e| format!("Error while writing to file: {}", e))?;
-        downloaded += chunk.len() as u64;
+/// Tauri command to read the saved `llama-server` launch profile (or the defaults).
+#[tauri::command]
+fn get_launch_profile() -> launch_profile::LaunchProfile {
+    launch_profile::LaunchProfile::load()
+}
 
-        // Throttle progress events to avoid flooding the frontend
-        if last_emit.elapsed() >= Duration::from_millis(100) || downloaded >= total_size {
-            let percentage = progress_base + (downloaded * progress_scale / total_size);
-            // Make emit non-fatal (log error but continue)
-            if let Err(e) = window.emit("download-progress", percentage) {
-                error!("Failed to emit progress event: {}", e);
-            }
-            last_emit = std::time::Instant::now();
-        }
-    }
-    writer
-        .flush()
-        .await
-        .map_err(|e| format!("Error flushing file: {}", e))?;
-    info!("Download complete: {:?} ({} bytes)", path_ref, downloaded);
+/// Tauri command to save the `llama-server` launch profile.
+/// The new settings apply the next time the server starts.
+#[tracing::instrument]
+#[tauri::command]
+fn save_launch_profile(profile: launch_profile::LaunchProfile) -> Result<(), String> {
+    profile.save().map_err(|e| e.to_string())?;
+    info!("Saved llama-server launch profile: {:?}", profile);
     Ok(())
 }
 
-fn extract_zip<P: AsRef<std::path::Path>>(zip_path: P, extract_to: P) -> Result<(), String> {
-    let file = File::open(zip_path).map_err(|e| format!("Failed to open zip file: {}", e))?;
-    let mut archive =
-        ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {}", e))?;
-    archive
-        .extract(extract_to)
-        .map_err(|e| format!("Failed to extract zip archive: {}", e))?;
-    Ok(())
+/// Tauri command to read the saved chunking settings (or the defaults).
+#[tauri::command]
+fn get_chunking_config() -> chunking::ChunkingConfig {
+    chunking::ChunkingConfig::load()
 }
 
-/// Validates that llama-server installation is complete with all required files.
-/// Returns Ok(()) if valid, Err with details if something is missing.
-fn validate_llama_installation(llama_dir: &std::path::Path) -> Result<(), String> {
-    // Critical files that MUST exist for llama-server to work
-    let required_files = if cfg!(windows) {
-        vec!["llama-server.exe", "llama.dll", "ggml.dll"]
-    } else {
-        vec!["llama-server"]
-    };
+/// Tauri command to save the chunking settings.
+/// They apply to files uploaded or reindexed afterwards.
+#[tracing::instrument]
+#[tauri::command]
+fn save_chunking_config(config: chunking::ChunkingConfig) -> Result<(), String> {
+    config.save().map_err(|e| e.to_string())?;
+    info!("Saved chunking config: {:?}", config);
+    Ok(())
+}
 
-    let mut missing = Vec::new();
+/// Tauri command to read the saved embedding settings (or the defaults) and the model in use.
+#[tauri::command]
+fn get_embedding_config() -> embedding_config::EmbeddingSettings {
+    embedding_config::EmbeddingSettings::current()
+}
 
-    for file in &required_files {
-        let file_path = llama_dir.join(file);
-        if !file_path.exists() {
-            missing.push(file.to_string());
-        }
-    }
+/// Tauri command to save the embedding settings.
+/// A new model applies on the next start, which re-embeds the knowledge base.
+#[tracing::instrument]
+#[tauri::command]
+fn save_embedding_config(config: embedding_config::EmbeddingConfig) -> Result<(), String> {
+    config.save().map_err(|e| e.to_string())?;
+    info!("Saved embedding config: {:?}", config);
+    Ok(())
+}
 
-    if !missing.is_empty() {
-        return Err(format!(
-            "Incomplete llama-server installation. Missing files: {}. \
-             Try deleting {:?} and restarting the app.",
-            missing.join(", "),
-            llama_dir
-        ));
+/// Tauri command to install a GGUF model from a local path, for machines without internet access.
+/// While no valid default model is installed, the import becomes the default model so
+/// onboarding can complete without a download. Returns the model id of the installed model.
+#[tracing::instrument]
+#[tauri::command]
+async fn import_model(path: String) -> Result<String, String> {
+    let source = std::path::PathBuf::from(&path);
+    if !source.is_file() {
+        return Err(format!("Model file not found at {:?}", source));
     }
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| name.to_ascii_lowercase().ends_with(".gguf"))
+        .ok_or_else(|| format!("{:?} is not a .gguf file", source))?;
 
-    // Check that llama-server.exe is not 0 bytes (corrupted download)
-    let server_exe = if cfg!(windows) {
-        "llama-server.exe"
+    let models_dir = PortablePathManager::models_dir();
+    std::fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
+    let model_id = if validate_model_file(&models_dir.join(DEFAULT_MODEL_FILENAME)).is_ok() {
+        file_name
     } else {
-        "llama-server"
+        DEFAULT_MODEL_FILENAME.to_string()
     };
-    let server_path = llama_dir.join(server_exe);
-
-    if let Ok(meta) = std::fs::metadata(&server_path) {
-        if meta.len() < 1024 * 1024 {
-            // Less than 1 MB is definitely wrong
-            return Err(format!(
-                "llama-server binary appears corrupted (size: {} bytes). \
-                 Delete {:?} and restart the app.",
-                meta.len(),
-                llama_dir
-            ));
-        }
+    let target = models_dir.join(&model_id);
+    if model_id != DEFAULT_MODEL_FILENAME && validate_model_file(&target).is_ok() {
+        return Err(format!("A model named '{}' is already installed", model_id));
     }
 
-    Ok(())
+    // Copying and validating a multi-GB file blocks for a while
+    let metadata = tokio::task::spawn_blocking(move || install_model_file(&source, &target))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))??;
+    info!("✓ Imported model '{}': {}", model_id, metadata.summary());
+    Ok(model_id)
 }
 
-/// Validates that the model file is complete and not corrupted.
-fn validate_model_file(model_path: &std::path::Path) -> Result<(), String> {
-    if !model_path.exists() {
-        return Err(format!("Model file not found at {:?}", model_path));
-    }
-
-    let meta =
-        std::fs::metadata(model_path).map_err(|e| format!("Cannot read model file: {}", e))?;
-
-    let size = meta.len();
-
-    if size < MIN_MODEL_SIZE_BYTES {
-        return Err(format!(
-            "Model file incomplete: {} bytes (minimum {} bytes required). \
-             Delete the file and restart the download.",
-            size, MIN_MODEL_SIZE_BYTES
-        ));
-    }
+/// Links or copies a model file next to `target`, validates it and moves it into place.
+fn install_model_file(
+    source: &std::path::Path,
+    target: &std::path::Path,
+) -> Result<gguf::GgufMetadata, String> {
+    let staging = local_import::staging_path(target);
+    local_import::remove_path(&staging).map_err(|e| e.to_string())?;
+    local_import::link_or_copy(source, &staging)
+        .map_err(|e| format!("Failed to copy {:?}: {}", source, e))?;
+
+    let metadata = match validate_model_file(&staging) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            let _ = local_import::remove_path(&staging);
+            return Err(e);
+        }
+    };
+    local_import::commit_staged(&staging, target)
+        .map_err(|e| format!("Failed to install model at {:?}: {}", target, e))?;
+    Ok(metadata)
+}
 
-    // Quick GGUF header check: first 4 bytes should be "GGUF" magic
-    let file =
-        std::fs::File::open(model_path).map_err(|e| format!("Cannot open model file: {}", e))?;
-
-    let mut reader = std::io::BufReader::new(file);
-    let mut magic = [0u8; 4];
-
-    use std::io::Read;
-    reader
-        .read_exact(&mut magic)
-        .map_err(|e| format!("Cannot read model header: {}", e))?;
-
-    // GGUF magic is "GGUF" in little-endian
-    if &magic != b"GGUF" {
-        return Err(format!(
-            "Model file appears corrupted (invalid GGUF header). \
-             Expected 'GGUF', got {:?}. Delete and re-download.",
-            magic
-        ));
+/// Tauri command to install `llama-server` from a local path, for machines without internet
+/// access. `path` can be a release zip, a directory containing the build, or the server
+/// binary itself; the files next to the binary (shared libraries) are installed with it.
+#[tracing::instrument]
+#[tauri::command]
+async fn import_llama_server(path: String) -> Result<(), String> {
+    let source = std::path::PathBuf::from(&path);
+    if !source.exists() {
+        return Err(format!("{:?} not found", source));
     }
 
-    info!(
-        "✓ Model file validated: {:.2} GB, valid GGUF format",
-        size as f64 / 1024.0 / 1024.0 / 1024.0
-    );
+    tokio::task::spawn_blocking(move || install_llama_server(&source, false))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))??;
+    info!("✓ Imported llama-server from {:?}", path);
     Ok(())
 }
 
-/// Minimum expected model size in bytes (3 GB).
-/// The Qwen2.5-Coder-7B-Instruct-Q4_K_M model is ~4.7 GB.
-/// A partial download will be smaller, so we reject anything under 3 GB.
-const MIN_MODEL_SIZE_BYTES: u64 = 3 * 1024 * 1024 * 1024; // 3 GB
-
-/// Tauri command to check if the model file exists AND is complete.
-/// Used by frontend to determine if onboarding should be shown.
-/// Returns true only if the model file exists and is at least 3 GB.
-#[tauri::command]
-fn check_model_exists() -> bool {
-    let model_path = PortablePathManager::models_dir().join(DEFAULT_MODEL_FILENAME);
+/// Stages the `llama-server` build found at `source`, validates it and replaces the
+/// installed one. Archives (zip or tar.gz) are extracted first; with `compile`, the archive
+/// holds the llama.cpp sources and is built for this machine.
+fn install_llama_server(source: &std::path::Path, compile: bool) -> Result<(), String> {
+    let server_exe_name = llama_release::server_binary_name();
+    let llama_dir = PortablePathManager::tools_dir().join("llama");
+    let staging = local_import::staging_path(&llama_dir);
+    let unpacked = llama_dir.with_file_name("llama.unpack");
+    local_import::remove_path(&staging).map_err(|e| e.to_string())?;
+    local_import::remove_path(&unpacked).map_err(|e| e.to_string())?;
+
+    let staged = (|| {
+        let search_root = if llama_release::ArchiveKind::from_path(source).is_some() {
+            llama_release::extract_archive(source, &unpacked)?;
+            if compile {
+                llama_release::build_from_source(&unpacked)?
+            } else {
+                unpacked.clone()
+            }
+        } else {
+            source.to_path_buf()
+        };
 
-    match std::fs::metadata(&model_path) {
-        Ok(metadata) => {
-            let size = metadata.len();
-            let is_complete = size >= MIN_MODEL_SIZE_BYTES;
-            info!(
-                "Model check: {:?} size = {} bytes, min required = {} bytes, complete = {}",
-                model_path, size, MIN_MODEL_SIZE_BYTES, is_complete
-            );
-            is_complete
-        }
-        Err(_) => {
-            info!("Model check: {:?} does not exist", model_path);
-            false
-        }
+        let server_dir = local_import::find_server_dir(&search_root, server_exe_name)
+            .ok_or_else(|| format!("{} not found in {:?}", server_exe_name, source))?;
+        local_import::link_or_copy_dir(&server_dir, &staging)
+            .map_err(|e| format!("Failed to copy {:?}: {}", server_dir, e))?;
+        // The staged binary may be a hard link to the user's file, which must keep its mode
+        let server = staging.join(server_exe_name);
+        local_import::detach_link(&server)
+            .map_err(|e| format!("Failed to copy {:?}: {}", server, e))?;
+        llama_release::ensure_executable(&server)
+            .map_err(|e| format!("Failed to make llama-server executable: {}", e))?;
+        validate_llama_installation(&staging)
+    })();
+
+    // Files extracted from an archive were linked or copied into the staging directory
+    let _ = local_import::remove_path(&unpacked);
+    if let Err(e) = staged {
+        let _ = local_import::remove_path(&staging);
+        return Err(e);
     }
+
+    local_import::commit_staged(&staging, &llama_dir)
+        .map_err(|e| format!("Failed to install llama-server at {:?}: {}", llama_dir, e))
 }
 
 /// Tauri command to run a quick preflight check (no startup tests).
@@ -1165,11 +1447,29 @@ async fn run_diagnostic_category(
     Ok(results)
 }
 
-#[tracing::instrument(skip(window))]
+#[tracing::instrument(skip(window, downloads))]
 #[tauri::command]
-async fn download_model(window: tauri::Window) -> Result<(), String> {
+async fn download_model(
+    window: tauri::Window,
+    model_id: Option<String>,
+    downloads: State<'_, download_manager::DownloadManager>,
+) -> Result<(), String> {
     info!("Starting model download process...");
 
+    // Without an explicit model, install the catalog default
+    let catalog = model_catalog::ModelCatalog::load();
+    let mut entry = match &model_id {
+        Some(id) => catalog.get(id),
+        None => catalog.default_entry(),
+    }
+    .cloned()
+    .ok_or_else(|| {
+        format!(
+            "Unknown model: {}",
+            model_id.as_deref().unwrap_or("default")
+        )
+    })?;
+
     // Helper to emit detailed status
     let emit_status = |w: &tauri::Window, step: &str, detail: &str| {
         let _ = w.emit(
@@ -1183,18 +1483,17 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
 
     // Emit initial progress
     window
-        .emit("download-progress", 0)
+        .emit(
+            "download-progress",
+            download_manager::DownloadProgress::at_percent(0),
+        )
         .map_err(|e| e.to_string())?;
     emit_status(&window, "init", "Initializing download manager...");
 
     // 1. Check and Download llama-server
     let tools_dir = PortablePathManager::tools_dir();
     let llama_dir = tools_dir.join("llama");
-    let server_exe_name = if cfg!(windows) {
-        "llama-server.exe"
-    } else {
-        "llama-server"
-    };
+    let server_exe_name = llama_release::server_binary_name();
 
     emit_status(
         &window,
@@ -1233,58 +1532,79 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
     };
 
     if server_needs_install {
-        if !llama_dir.exists() {
-            std::fs::create_dir_all(&llama_dir).map_err(|e| e.to_string())?;
-        }
+        let platform = llama_release::Platform::current();
+        let asset = llama_release::select_asset(&platform).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&tools_dir).map_err(|e| e.to_string())?;
 
-        let zip_path = llama_dir.join("llama-server.zip");
-        info!("Downloading llama-server to {:?}", zip_path);
+        let archive_path = tools_dir.join(&asset.file_name);
+        info!(
+            "Downloading llama-server for {:?} to {:?}",
+            platform, archive_path
+        );
         emit_status(
             &window,
             "llama_download",
-            "Downloading llama.cpp inference server (~50MB)...",
+            &format!(
+                "Downloading llama.cpp inference server ({})...",
+                asset.file_name
+            ),
         );
 
         // Download server (0-20%)
-        download_file(LLAMA_SERVER_URL, &zip_path, &window, 0, 20).await?;
+        download_file(&asset.url, &archive_path, &window, &downloads, 0, 20).await?;
 
         info!("Extracting llama-server...");
-        emit_status(
-            &window,
-            "llama_extract",
-            "Extracting llama-server.exe, llama.dll, ggml.dll...",
-        );
-        let zip_path_clone = zip_path.clone();
-        let llama_dir_clone = llama_dir.clone();
-
-        tokio::task::spawn_blocking(move || extract_zip(&zip_path_clone, &llama_dir_clone))
-            .await
-            .map_err(|e| format!("Task join error: {}", e))??;
+        if asset.source {
+            emit_status(
+                &window,
+                "llama_extract",
+                "No prebuilt server for this CPU: compiling llama.cpp (this takes a few minutes)...",
+            );
+        } else {
+            emit_status(
+                &window,
+                "llama_extract",
+                "Extracting llama-server and its libraries...",
+            );
+        }
+        // Extracts (and compiles, if needed), checks the build and moves it into the tools directory
+        let install_source = archive_path.clone();
+        let compile = asset.source;
+        let installed =
+            tokio::task::spawn_blocking(move || install_llama_server(&install_source, compile))
+                .await
+                .map_err(|e| format!("Task join error: {}", e))?;
+        // A bad archive must not be resumed on the next attempt, so it goes either way
+        if let Err(e) = std::fs::remove_file(&archive_path) {
+            warn!("Failed to delete llama-server archive: {}", e);
+        }
+        installed?;
 
-        // Validate extraction: check that critical files exist
-        validate_llama_installation(&llama_dir)?;
         emit_status(
             &window,
             "llama_done",
-            "✓ Inference server ready (llama-server.exe)",
+            &format!("✓ Inference server ready ({})", server_exe_name),
         );
         info!("✓ llama-server installation validated");
     }
     window
-        .emit("download-progress", 20)
+        .emit(
+            "download-progress",
+            download_manager::DownloadProgress::at_percent(20),
+        )
         .map_err(|e| e.to_string())?;
 
     // 2. Check and Download Model
     emit_status(
         &window,
         "model_check",
-        "Checking AI model (Qwen2.5 Coder 7B)...",
+        &format!("Checking AI model ({})...", entry.name),
     );
     let models_dir = PortablePathManager::models_dir();
     if !models_dir.exists() {
         std::fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
     }
-    let model_path = models_dir.join(DEFAULT_MODEL_FILENAME);
+    let model_path = models_dir.join(&entry.file_name);
     emit_status(
         &window,
         "model_path",
@@ -1294,12 +1614,12 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
     // Check if model exists AND is valid
     let model_needs_download = if model_path.exists() {
         match validate_model_file(&model_path) {
-            Ok(_) => {
+            Ok(metadata) => {
                 info!("Model already exists and validated. Skipping download.");
                 emit_status(
                     &window,
                     "model_check",
-                    "✓ AI model already installed (4.7GB GGUF)",
+                    &format!("✓ AI model already installed: {}", metadata.summary()),
                 );
                 false
             }
@@ -1319,33 +1639,81 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
 
     if !model_needs_download {
         window
-            .emit("download-progress", 50)
+            .emit(
+                "download-progress",
+                download_manager::DownloadProgress::at_percent(50),
+            )
             .map_err(|e| e.to_string())?;
         tokio::time::sleep(Duration::from_millis(200)).await;
         window
-            .emit("download-progress", 90)
+            .emit(
+                "download-progress",
+                download_manager::DownloadProgress::at_percent(90),
+            )
             .map_err(|e| e.to_string())?;
         tokio::time::sleep(Duration::from_millis(200)).await;
         window
-            .emit("download-progress", 100)
+            .emit(
+                "download-progress",
+                download_manager::DownloadProgress::at_percent(100),
+            )
             .map_err(|e| e.to_string())?;
         return Ok(());
     }
 
+    // Entries the manifest does not pin are checked against the hash the server publishes.
+    // Refuse before downloading gigabytes that could not be verified.
+    if entry.sha256.is_none() {
+        entry.sha256 = download_manager::published_sha256(&entry.url).await;
+    }
+    model_catalog::require_hash(&entry).map_err(|e| e.to_string())?;
+
     info!("Downloading model to {:?}", model_path);
     emit_status(
         &window,
         "model_download",
-        "Downloading Qwen2.5-Coder-7B-Instruct (~4.7GB)...",
+        &format!(
+            "Downloading {} (~{:.1}GB)...",
+            entry.name,
+            entry.size_bytes as f64 / 1e9
+        ),
     );
     emit_status(
         &window,
         "model_info",
-        "Format: GGUF Q4_K_M (quantized for efficiency)",
+        &format!(
+            "Format: GGUF {} (needs ~{:.0}GB of RAM)",
+            entry.quantization,
+            entry.ram_required_bytes as f64 / 1e9
+        ),
     );
 
     // Download model (20-80%)
-    download_file(MODEL_URL, &model_path, &window, 20, 60).await?;
+    download_file(&entry.url, &model_path, &window, &downloads, 20, 60).await?;
+
+    // Check the hash before anything reads the file
+    emit_status(&window, "model_verify", "Verifying SHA-256 checksum...");
+    let hash_entry = entry.clone();
+    let hash_path = model_path.clone();
+    let verified = tokio::task::spawn_blocking(move || {
+        model_catalog::verify_download(&hash_entry, &hash_path)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?;
+    match verified {
+        Ok(true) => info!("✓ SHA-256 verified for {:?}", model_path),
+        Ok(false) => warn!(
+            "No SHA-256 listed for local catalog entry '{}'; skipping checksum",
+            entry.id
+        ),
+        Err(e) => {
+            // Delete the file so the next attempt downloads it from scratch instead of resuming
+            if let Err(del_err) = std::fs::remove_file(&model_path) {
+                warn!("Failed to delete corrupted model: {}", del_err);
+            }
+            return Err(e.to_string());
+        }
+    }
 
     // Validate downloaded model
     emit_status(
@@ -1353,11 +1721,18 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
         "model_verify",
         "Verifying GGUF header and file integrity...",
     );
-    validate_model_file(&model_path)?;
-    emit_status(&window, "model_done", "✓ AI model installed and validated");
+    let metadata = validate_model_file(&model_path)?;
+    emit_status(
+        &window,
+        "model_done",
+        &format!("✓ AI model installed and validated: {}", metadata.summary()),
+    );
     info!("✓ Model download complete and validated.");
     window
-        .emit("download-progress", 80)
+        .emit(
+            "download-progress",
+            download_manager::DownloadProgress::at_percent(80),
+        )
         .map_err(|e| e.to_string())?;
 
     // 3. Initialize FastEmbed model (downloads ONNX model if needed) (80-95%)
@@ -1369,13 +1744,14 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
         "embeddings_init",
         "Initializing RAG embedding model...",
     );
+    let embedding = embedding_config::active_model();
     emit_status(
         &window,
         "embeddings_info",
-        "Model: all-MiniLM-L6-v2 (ONNX, ~90MB)",
+        &format!("Model: {} (ONNX)", embedding.name()),
     );
 
-    // This will download AllMiniLML6V2 ONNX model (~25MB) on first run
+    // This will download the configured ONNX model on first run
     // Store in data/models/embeddings/ to keep everything portable
     let embeddings_dir = PortablePathManager::models_dir().join("embeddings");
     std::fs::create_dir_all(&embeddings_dir).ok();
@@ -1386,10 +1762,9 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
     );
 
     let embedding_result = tokio::task::spawn_blocking(move || {
-        use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
-        let mut options = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
+        use fastembed::TextEmbedding;
+        let mut options = embedding.init_options();
         options.show_download_progress = true;
-        options.cache_dir = embeddings_dir;
         TextEmbedding::try_new(options)
     })
     .await
@@ -1404,7 +1779,10 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
                 "✓ RAG embeddings ready (semantic search enabled)",
             );
             window
-                .emit("download-progress", 95)
+                .emit(
+                    "download-progress",
+                    download_manager::DownloadProgress::at_percent(95),
+                )
                 .map_err(|e| e.to_string())?;
         }
         Err(e) => {
@@ -1429,7 +1807,10 @@ async fn download_model(window: tauri::Window) -> Result<(), String> {
         "Ready: llama-server.exe + GGUF model + ONNX embeddings",
     );
     window
-        .emit("download-progress", 100)
+        .emit(
+            "download-progress",
+            download_manager::DownloadProgress::at_percent(100),
+        )
         .map_err(|e| e.to_string())?;
     info!("╔══════════════════════════════════════════════════════╗");
     info!("║  ✅ ALL MODELS DOWNLOADED SUCCESSFULLY               ║");
@@ -1474,11 +1855,18 @@ fn main() {
 
     let app = tauri::Builder::default()
         .manage(AppState::new())
+        .manage(download_manager::DownloadManager::default())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             initialize_app,
             debug_chat,
+            cancel_generation,
+            regenerate_message,
+            edit_message,
+            switch_branch,
+            get_source_chunk,
+            search_library,
             upload_file_for_session,
             link_library_file_to_session,
             create_session,
@@ -1492,13 +1880,29 @@ fn main() {
             list_folders,
             delete_folder,
             move_session_to_folder,
+            set_session_retrieval_scope,
             move_file_to_folder,
             delete_file,
             reindex_library,
+            list_ingest_jobs,
+            retry_ingest_job,
             list_library_files,
             save_generated_file,
             download_model,
             check_model_exists,
+            list_models,
+            list_model_catalog,
+            pause_download,
+            resume_download,
+            cancel_download,
+            import_model,
+            import_llama_server,
+            get_launch_profile,
+            save_launch_profile,
+            get_chunking_config,
+            save_chunking_config,
+            get_embedding_config,
+            save_embedding_config,
             run_quick_preflight_check,
             run_diagnostic_category
         ])
@@ -0,0 +1,314 @@
+//! Catalog of downloadable models.
+//!
+//! A manifest listing the models offered for download is bundled into the binary. A
+//! `model_catalog.json` file in the data directory with the same layout can add models or
+//! replace bundled entries (matched by `id`), e.g. to pin a hash or use a mirror.
+
+use crate::error::AppError;
+use crate::fs_manager::PortablePathManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use tracing::warn;
+
+/// The manifest bundled with the application.
+const BUNDLED_CATALOG: &str = include_str!("model_catalog.json");
+
+/// A model that can be downloaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// Stable identifier passed to `download_model`.
+    pub id: String,
+    /// Display name.
+    pub name: String,
+    /// File name in the models directory, which becomes the `model_id` of sessions using it.
+    pub file_name: String,
+    pub url: String,
+    pub quantization: String,
+    /// Approximate download size.
+    pub size_bytes: u64,
+    /// Approximate RAM needed to run the model with the default launch profile.
+    pub ram_required_bytes: u64,
+    /// Expected SHA-256 of the file (hex). When `None`, the hash the server publishes is used;
+    /// a bundled model is not installed without one, while models added by the local file are
+    /// then downloaded unverified.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Model installed by the onboarding when no model is requested.
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl CatalogEntry {
+    fn check(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("empty id".to_string());
+        }
+        // The file is written to the models directory, so only bare `.gguf` file names are allowed
+        let bare = Path::new(&self.file_name).file_name() == Some(self.file_name.as_ref());
+        if !bare || !self.file_name.to_ascii_lowercase().ends_with(".gguf") {
+            return Err(format!(
+                "{}: invalid file name {:?}",
+                self.id, self.file_name
+            ));
+        }
+        if !self.url.starts_with("https://") {
+            return Err(format!("{}: only https URLs are allowed", self.id));
+        }
+        if let Some(hash) = &self.sha256 {
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("{}: sha256 must be 64 hex characters", self.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The downloadable models.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelCatalog {
+    pub models: Vec<CatalogEntry>,
+}
+
+impl ModelCatalog {
+    /// Loads the bundled catalog merged with the local override file, if any.
+    pub fn load() -> Self {
+        Self::load_with_override(&PortablePathManager::model_catalog_path())
+    }
+
+    /// Parses and checks a manifest.
+    pub fn parse(json: &str) -> Result<Self, AppError> {
+        let catalog: Self = serde_json::from_str(json)
+            .map_err(|e| AppError::Config(format!("Invalid model catalog: {}", e)))?;
+        for entry in &catalog.models {
+            entry
+                .check()
+                .map_err(|e| AppError::Config(format!("Invalid model catalog entry {}", e)))?;
+        }
+        Ok(catalog)
+    }
+
+    fn bundled() -> Self {
+        Self::parse(BUNDLED_CATALOG).expect("bundled model catalog is valid")
+    }
+
+    fn load_with_override(path: &Path) -> Self {
+        let mut catalog = Self::bundled();
+
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return catalog,
+            Err(e) => {
+                warn!(
+                    "Failed to read model catalog {:?}: {}. Ignoring it.",
+                    path, e
+                );
+                return catalog;
+            }
+        };
+
+        match Self::parse(&json) {
+            Ok(local) => catalog.merge(local),
+            Err(e) => warn!("{} ({:?}). Ignoring it.", e, path),
+        }
+        catalog
+    }
+
+    /// Adds the entries of `other`, replacing the ones with the same id.
+    fn merge(&mut self, other: Self) {
+        for entry in other.models {
+            if entry.default {
+                for existing in &mut self.models {
+                    existing.default = false;
+                }
+            }
+            match self.models.iter_mut().find(|m| m.id == entry.id) {
+                Some(existing) => *existing = entry,
+                None => self.models.push(entry),
+            }
+        }
+    }
+
+    /// Looks up a model by id.
+    pub fn get(&self, id: &str) -> Option<&CatalogEntry> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    /// The model marked as default, or the first one.
+    pub fn default_entry(&self) -> Option<&CatalogEntry> {
+        self.models
+            .iter()
+            .find(|m| m.default)
+            .or_else(|| self.models.first())
+    }
+}
+
+/// Computes the SHA-256 of a file as lowercase hex.
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Fails if `entry` is a bundled model without a hash: bundled downloads are always verified.
+pub fn require_hash(entry: &CatalogEntry) -> Result<(), AppError> {
+    if entry.sha256.is_none() && ModelCatalog::bundled().get(&entry.id).is_some() {
+        return Err(AppError::Config(format!(
+            "No SHA-256 listed for bundled model '{}'; refusing to install an unverified file",
+            entry.id
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a downloaded file against the hash expected by the catalog.
+/// Returns `Ok(false)` if a locally added entry has no hash to check against.
+pub fn verify_download(entry: &CatalogEntry, path: &Path) -> Result<bool, AppError> {
+    require_hash(entry)?;
+    let Some(expected) = &entry.sha256 else {
+        return Ok(false);
+    };
+
+    let actual = sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(AppError::Validation(format!(
+            "SHA-256 mismatch for {}: expected {}, got {}",
+            entry.file_name, expected, actual
+        )));
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// SHA-256 of `b"hello world"`.
+    const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn entry(id: &str) -> CatalogEntry {
+        CatalogEntry {
+            id: id.to_string(),
+            name: id.to_string(),
+            file_name: format!("{}.gguf", id),
+            url: format!("https://example.com/{}.gguf", id),
+            quantization: "Q4_K_M".to_string(),
+            size_bytes: 11,
+            ram_required_bytes: 1024,
+            sha256: None,
+            default: false,
+        }
+    }
+
+    #[test]
+    fn test_bundled_catalog_default_is_the_onboarding_model() {
+        let catalog = ModelCatalog::bundled();
+
+        let default = catalog.default_entry().unwrap();
+        assert_eq!(default.file_name, "default-model.gguf");
+        assert_eq!(catalog.models.iter().filter(|m| m.default).count(), 1);
+        assert!(catalog.get("qwen2.5-coder-3b-instruct-q4_k_m").is_some());
+    }
+
+    #[test]
+    fn test_parse_rejects_unsafe_entries() {
+        for (field, bad) in [
+            ("file_name", "../escape.gguf"),
+            ("file_name", "model.bin"),
+            ("url", "http://example.com/m.gguf"),
+            ("sha256", "abc"),
+        ] {
+            let mut value = serde_json::to_value(entry("m")).unwrap();
+            value[field] = serde_json::json!(bad);
+            let json = serde_json::json!({ "models": [value] }).to_string();
+
+            assert!(ModelCatalog::parse(&json).is_err(), "{} = {}", field, bad);
+        }
+    }
+
+    #[test]
+    fn test_local_file_overrides_and_extends_bundled_catalog() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("model_catalog.json");
+        let mut pinned = ModelCatalog::bundled().default_entry().unwrap().clone();
+        pinned.sha256 = Some(HELLO_SHA256.to_string());
+        let mut extra = entry("my-model");
+        extra.default = true;
+        let local = ModelCatalog {
+            models: vec![pinned.clone(), extra],
+        };
+        std::fs::write(&path, serde_json::to_string(&local).unwrap()).unwrap();
+
+        let catalog = ModelCatalog::load_with_override(&path);
+
+        assert_eq!(
+            catalog.models.len(),
+            ModelCatalog::bundled().models.len() + 1
+        );
+        assert_eq!(
+            catalog.get(&pinned.id).unwrap().sha256.as_deref(),
+            Some(HELLO_SHA256)
+        );
+        assert_eq!(catalog.default_entry().unwrap().id, "my-model");
+    }
+
+    #[test]
+    fn test_invalid_local_file_is_ignored() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("model_catalog.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        assert_eq!(
+            ModelCatalog::load_with_override(&path),
+            ModelCatalog::bundled()
+        );
+        assert_eq!(
+            ModelCatalog::load_with_override(&temp.path().join("missing.json")),
+            ModelCatalog::bundled()
+        );
+    }
+
+    #[test]
+    fn test_verify_download() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("m.gguf");
+        std::fs::write(&path, b"hello world").unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), HELLO_SHA256);
+
+        let mut entry = entry("m");
+        assert!(!verify_download(&entry, &path).unwrap());
+
+        entry.sha256 = Some(HELLO_SHA256.to_uppercase());
+        assert!(verify_download(&entry, &path).unwrap());
+
+        entry.sha256 = Some("0".repeat(64));
+        assert!(matches!(
+            verify_download(&entry, &path),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_bundled_model_without_hash_is_rejected() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("m.gguf");
+        std::fs::write(&path, b"hello world").unwrap();
+
+        let mut bundled = ModelCatalog::bundled().default_entry().unwrap().clone();
+        bundled.sha256 = None;
+        assert!(matches!(require_hash(&bundled), Err(AppError::Config(_))));
+        assert!(verify_download(&bundled, &path).is_err());
+
+        assert!(require_hash(&entry("my-model")).is_ok());
+    }
+}
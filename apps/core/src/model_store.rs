@@ -0,0 +1,145 @@
+//! Installed GGUF models.
+//!
+//! Every `.gguf` file in `PortablePathManager::models_dir()` is a model a session can select
+//! through `ModelConfig.model_id`, which holds the file name.
+
+use crate::chat_template::ChatTemplate;
+use crate::fs_manager::PortablePathManager;
+use crate::gguf::{self, GgufMetadata};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// File extension of model files.
+const MODEL_EXTENSION: &str = "gguf";
+
+/// An installed model file.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ModelInfo {
+    /// The file name, used as `ModelConfig.model_id`.
+    pub model_id: String,
+    pub size_bytes: u64,
+    /// Last modification time (Unix timestamp).
+    pub modified_at: i64,
+    /// The prompt format used for this model.
+    pub chat_template: ChatTemplate,
+    /// Header information, or `None` if the file is not a readable GGUF.
+    pub metadata: Option<GgufMetadata>,
+}
+
+/// Lists the installed models, sorted by file name.
+pub fn list_installed_models() -> std::io::Result<Vec<ModelInfo>> {
+    list_models_in(&PortablePathManager::models_dir())
+}
+
+/// Returns the path of an installed model, or `None` if `model_id` is not a model file
+/// in the models directory.
+pub fn resolve_model_path(model_id: &str) -> Option<PathBuf> {
+    resolve_model_path_in(&PortablePathManager::models_dir(), model_id)
+}
+
+fn is_model_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(MODEL_EXTENSION))
+}
+
+fn list_models_in(dir: &Path) -> std::io::Result<Vec<ModelInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut models = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || !is_model_file(&path) {
+            continue;
+        }
+
+        let modified_at = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+
+        let header = gguf::read_metadata(&path).ok();
+        models.push(ModelInfo {
+            model_id: entry.file_name().to_string_lossy().into_owned(),
+            size_bytes: metadata.len(),
+            modified_at,
+            chat_template: ChatTemplate::for_model(&path, header.as_ref()),
+            metadata: header,
+        });
+    }
+
+    models.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+    Ok(models)
+}
+
+fn resolve_model_path_in(dir: &Path, model_id: &str) -> Option<PathBuf> {
+    // Only bare file names are accepted, so a session cannot point outside the models directory
+    let file_name = Path::new(model_id).file_name()?;
+    if file_name != model_id || !is_model_file(Path::new(file_name)) {
+        return None;
+    }
+
+    let path = dir.join(file_name);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn models_dir() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("qwen2.5-coder-7b.gguf"), b"GGUF1234").unwrap();
+        std::fs::write(temp.path().join("Llama-3.2-3B.GGUF"), b"GGUF").unwrap();
+        std::fs::write(temp.path().join("notes.txt"), b"not a model").unwrap();
+        std::fs::create_dir(temp.path().join("partial.gguf.d")).unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_list_models_only_returns_gguf_files() {
+        let temp = models_dir();
+
+        let models = list_models_in(temp.path()).unwrap();
+
+        let ids: Vec<_> = models.iter().map(|m| m.model_id.as_str()).collect();
+        assert_eq!(ids, vec!["Llama-3.2-3B.GGUF", "qwen2.5-coder-7b.gguf"]);
+        assert_eq!(models[0].chat_template, ChatTemplate::Llama3);
+        assert_eq!(models[1].size_bytes, 8);
+        assert!(models[1].modified_at > 0);
+        // The test files only have a magic number
+        assert!(models[1].metadata.is_none());
+    }
+
+    #[test]
+    fn test_list_models_in_missing_dir() {
+        let temp = TempDir::new().unwrap();
+
+        let models = list_models_in(&temp.path().join("missing")).unwrap();
+
+        assert!(models.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_model_path() {
+        let temp = models_dir();
+
+        assert_eq!(
+            resolve_model_path_in(temp.path(), "qwen2.5-coder-7b.gguf"),
+            Some(temp.path().join("qwen2.5-coder-7b.gguf"))
+        );
+        assert_eq!(resolve_model_path_in(temp.path(), "missing.gguf"), None);
+        assert_eq!(resolve_model_path_in(temp.path(), "notes.txt"), None);
+        assert_eq!(resolve_model_path_in(temp.path(), "../secret.gguf"), None);
+        assert_eq!(resolve_model_path_in(temp.path(), "remote:gpt-4o"), None);
+        assert_eq!(resolve_model_path_in(temp.path(), ""), None);
+    }
+}
@@ -3,6 +3,53 @@ use sqlx::types::Json;
 use sqlx::FromRow;
 use validator::Validate;
 
+/// Maximum number of tokens generated per response (`n_predict`).
+pub const MAX_RESPONSE_TOKENS: usize = 2048;
+
+/// Sampling settings for one generation request. `None` fields fall back to the backend's
+/// defaults; `stop` sequences are added to the ones the chat template already needs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplingParams {
+    pub temperature: Option<f32>,
+    pub top_k: Option<u32>,
+    pub top_p: Option<f32>,
+    pub min_p: Option<f32>,
+    pub repeat_penalty: Option<f32>,
+    pub n_predict: Option<usize>,
+    pub stop: Vec<String>,
+}
+
+impl SamplingParams {
+    /// Parameters that only override the temperature, as used by single-prompt callers.
+    pub fn with_temperature(temperature: Option<f32>) -> Self {
+        Self {
+            temperature,
+            ..Self::default()
+        }
+    }
+}
+
+/// Settings for the cross-encoder rerank stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RerankOptions {
+    /// How many search results are fetched for the reranker to rescore.
+    pub candidates: usize,
+    /// How many results are kept after reranking.
+    pub top_k: usize,
+    /// Minimum relevance (0.0-1.0) a result needs to be kept.
+    pub min_score: f32,
+}
+
+impl Default for RerankOptions {
+    fn default() -> Self {
+        Self {
+            candidates: 20,
+            top_k: 3,
+            min_score: 0.1,
+        }
+    }
+}
+
 /// Represents the configuration for an AI model within a session.
 #[derive(Debug, Serialize, Deserialize, Clone, Validate)]
 pub struct ModelConfig {
@@ -15,6 +62,73 @@ pub struct ModelConfig {
     /// The system-level instructions provided to the model for context.
     #[validate(length(min = 1))]
     pub system_prompt: String,
+    /// Limits sampling to the K most likely tokens (0 disables). Value between 0 and 200.
+    #[serde(default)]
+    #[validate(range(min = 0, max = 200))]
+    pub top_k: Option<u32>,
+    /// Nucleus sampling threshold. Value between 0.0 and 1.0.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub top_p: Option<f32>,
+    /// Minimum probability of a token relative to the most likely one. Value between 0.0 and 1.0.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub min_p: Option<f32>,
+    /// Penalty applied to recently repeated tokens (1.0 disables). Value between 0.0 and 2.0.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 2.0))]
+    pub repeat_penalty: Option<f32>,
+    /// Maximum number of tokens to generate, capped by the space reserved for the answer.
+    #[serde(default)]
+    #[validate(range(min = 1, max = MAX_RESPONSE_TOKENS))]
+    pub n_predict: Option<usize>,
+    /// Extra sequences that end the response.
+    #[serde(default)]
+    #[validate(length(max = 8))]
+    pub stop: Vec<String>,
+    /// Rescores retrieved documents with a cross-encoder before they reach the prompt.
+    #[serde(default)]
+    pub rerank: bool,
+    /// Documents retrieved for the reranker to rescore. Value between 1 and 50.
+    #[serde(default)]
+    #[validate(range(min = 1, max = 50))]
+    pub rerank_candidates: Option<usize>,
+    /// Documents kept after reranking. Value between 1 and 20.
+    #[serde(default)]
+    #[validate(range(min = 1, max = 20))]
+    pub rerank_top_k: Option<usize>,
+    /// Minimum reranker relevance a document needs to be kept. Value between 0.0 and 1.0.
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub rerank_min_score: Option<f32>,
+}
+
+impl ModelConfig {
+    /// The sampling settings sent with every generation request for this session.
+    pub fn sampling_params(&self) -> SamplingParams {
+        SamplingParams {
+            temperature: Some(self.temperature),
+            top_k: self.top_k,
+            top_p: self.top_p,
+            min_p: self.min_p,
+            repeat_penalty: self.repeat_penalty,
+            n_predict: self.n_predict,
+            stop: self.stop.clone(),
+        }
+    }
+
+    /// The rerank stage settings, or `None` when reranking is off for this session.
+    pub fn rerank_options(&self) -> Option<RerankOptions> {
+        if !self.rerank {
+            return None;
+        }
+        let defaults = RerankOptions::default();
+        Some(RerankOptions {
+            candidates: self.rerank_candidates.unwrap_or(defaults.candidates),
+            top_k: self.rerank_top_k.unwrap_or(defaults.top_k),
+            min_score: self.rerank_min_score.unwrap_or(defaults.min_score),
+        })
+    }
 }
 
 impl Default for ModelConfig {
@@ -23,6 +137,16 @@ impl Default for ModelConfig {
             model_id: "default-model.gguf".to_string(),
             temperature: 0.7,
             system_prompt: "You are a helpful assistant.".to_string(),
+            top_k: None,
+            top_p: None,
+            min_p: None,
+            repeat_penalty: None,
+            n_predict: None,
+            stop: Vec::new(),
+            rerank: false,
+            rerank_candidates: None,
+            rerank_top_k: None,
+            rerank_min_score: None,
         }
     }
 }
@@ -50,6 +174,28 @@ pub struct Session {
     /// Unix timestamp of when the session was last updated.
     #[serde(default)]
     pub updated_at: i64,
+    /// Which documents the session searches for context.
+    #[serde(default)]
+    pub retrieval_scope: RetrievalScope,
+    /// The document folder searched when `retrieval_scope` is `folder`.
+    #[serde(default)]
+    pub retrieval_folder_id: Option<String>,
+}
+
+/// Which documents a session searches for context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RetrievalScope {
+    /// Only the files linked to the session.
+    #[default]
+    Linked,
+    /// The files of a document folder.
+    Folder,
+    /// Every file in the library.
+    Library,
+    /// No document search.
+    None,
 }
 
 /// Represents a folder for organizing sessions.
@@ -93,10 +239,57 @@ pub struct Message {
     pub content: String,
     /// Unix timestamp of when the message was created.
     pub created_at: i64,
+    /// Whether generation was cancelled before this (assistant) message was complete.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// The message this one replies to, or `None` for the first message of a session.
+    #[serde(default)]
+    pub parent_id: Option<i64>,
+    /// IDs of all versions of this message (replies to the same parent), including itself.
+    /// Only filled in by `database::get_session_messages`.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub sibling_ids: Vec<i64>,
+    /// The retrieved chunks an assistant answer was grounded on, in prompt order.
+    /// Only filled in by `database::get_session_messages`.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub sources: Vec<MessageSource>,
+}
+
+/// A retrieved chunk cited by an assistant message.
+///
+/// The file name is copied at answer time so citations stay readable after the file is
+/// removed from the library; the chunk text itself is fetched on demand by `chunk_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct MessageSource {
+    /// Id of the chunk in the vector store.
+    pub chunk_id: String,
+    /// The library file the chunk comes from, if any.
+    #[serde(default)]
+    pub file_id: Option<String>,
+    /// Name of that file when the answer was generated.
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// Position of the chunk within its document, from 0.
+    pub chunk_index: i64,
+    /// 1-based page number, for paginated documents.
+    #[serde(default)]
+    pub page: Option<i64>,
+    /// Character range of the document the chunk covers.
+    #[serde(default)]
+    pub char_start: Option<i64>,
+    #[serde(default)]
+    pub char_end: Option<i64>,
+    /// The markdown headings enclosing the chunk.
+    #[serde(default)]
+    pub heading_path: Option<String>,
+    /// Retrieval score (fused rank score, or reranker relevance when reranking is on).
+    pub score: f64,
 }
 
 /// Represents a file in the global library.
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct LibraryFile {
     /// The unique identifier for the file.
     pub id: String,
@@ -114,6 +307,83 @@ pub struct LibraryFile {
     /// Optional folder ID for organization.
     #[serde(default)]
     pub folder_id: Option<String>,
+    /// SHA-256 of the file content, as lowercase hex.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// The embedding model the file's chunks were computed with, if they are up to date.
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+}
+
+/// Outcome of uploading a file to a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedFile {
+    /// The library file now linked to the session.
+    pub file: LibraryFile,
+    /// Whether an identical file already in the library was linked instead of storing a copy.
+    pub deduplicated: bool,
+    /// The ingestion job of a newly stored file.
+    #[serde(default)]
+    pub job: Option<IngestJob>,
+}
+
+/// Processing state of an [`IngestJob`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum IngestJobStatus {
+    /// Waiting for the worker, possibly until `run_after` for a retry.
+    Pending,
+    /// Being extracted and embedded.
+    Running,
+    Done,
+    /// The file was already indexed from the same content with the same model.
+    Skipped,
+    /// Gave up; `error` says why.
+    Failed,
+}
+
+/// A queued (re)indexing of a library file, processed in the background by the supervisor.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct IngestJob {
+    /// The unique identifier of the job, renewed each time the file is queued.
+    pub id: String,
+    /// The library file to index.
+    pub file_id: String,
+    /// Name of that file.
+    pub file_name: String,
+    pub status: IngestJobStatus,
+    /// Number of times processing started.
+    pub attempts: i64,
+    /// The last failure, kept while a retry is pending.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix timestamp before which a pending job is not picked up.
+    pub run_after: i64,
+    /// Unix timestamp of when the file was queued.
+    pub created_at: i64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: i64,
+    /// Reindex the file even if it looks unchanged.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Number of ingestion jobs in each status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestJobCounts {
+    pub pending: i64,
+    pub running: i64,
+    pub done: i64,
+    pub skipped: i64,
+    pub failed: i64,
+}
+
+impl IngestJobCounts {
+    /// Jobs still to process, including the running one.
+    pub fn remaining(&self) -> i64 {
+        self.pending + self.running
+    }
 }
 
 /// Represents a file associated with a chat session (Joined View).
@@ -157,6 +427,7 @@ mod tests {
             model_id: "my-model.gguf".to_string(),
             temperature: 0.5,
             system_prompt: "You are a coding assistant.".to_string(),
+            ..ModelConfig::default()
         };
 
         assert!(config.validate().is_ok());
@@ -168,6 +439,7 @@ mod tests {
             model_id: "".to_string(),
             temperature: 0.7,
             system_prompt: "Valid prompt".to_string(),
+            ..ModelConfig::default()
         };
 
         let result = config.validate();
@@ -182,6 +454,7 @@ mod tests {
             model_id: "model.gguf".to_string(),
             temperature: 0.7,
             system_prompt: "".to_string(),
+            ..ModelConfig::default()
         };
 
         let result = config.validate();
@@ -196,6 +469,7 @@ mod tests {
             model_id: "model.gguf".to_string(),
             temperature: 0.0, // Minimum valid
             system_prompt: "Valid prompt".to_string(),
+            ..ModelConfig::default()
         };
 
         assert!(config.validate().is_ok());
@@ -207,6 +481,7 @@ mod tests {
             model_id: "model.gguf".to_string(),
             temperature: 2.0, // Maximum valid
             system_prompt: "Valid prompt".to_string(),
+            ..ModelConfig::default()
         };
 
         assert!(config.validate().is_ok());
@@ -218,6 +493,7 @@ mod tests {
             model_id: "model.gguf".to_string(),
             temperature: -0.1, // Invalid: below 0
             system_prompt: "Valid prompt".to_string(),
+            ..ModelConfig::default()
         };
 
         let result = config.validate();
@@ -232,6 +508,7 @@ mod tests {
             model_id: "model.gguf".to_string(),
             temperature: 2.1, // Invalid: above 2.0
             system_prompt: "Valid prompt".to_string(),
+            ..ModelConfig::default()
         };
 
         let result = config.validate();
@@ -240,12 +517,105 @@ mod tests {
         assert!(errors.field_errors().contains_key("temperature"));
     }
 
+    #[test]
+    fn test_model_config_sampling_params_valid() {
+        let config = ModelConfig {
+            top_k: Some(20),
+            top_p: Some(0.9),
+            min_p: Some(0.1),
+            repeat_penalty: Some(1.15),
+            n_predict: Some(512),
+            stop: vec!["```".to_string()],
+            ..ModelConfig::default()
+        };
+
+        assert!(config.validate().is_ok());
+
+        let params = config.sampling_params();
+        assert_eq!(params.temperature, Some(0.7));
+        assert_eq!(params.top_k, Some(20));
+        assert_eq!(params.n_predict, Some(512));
+        assert_eq!(params.stop, vec!["```".to_string()]);
+    }
+
+    #[test]
+    fn test_model_config_sampling_params_out_of_range() {
+        let config = ModelConfig {
+            top_k: Some(500),
+            top_p: Some(1.5),
+            min_p: Some(-0.1),
+            repeat_penalty: Some(3.0),
+            n_predict: Some(MAX_RESPONSE_TOKENS + 1),
+            stop: vec!["x".to_string(); 9],
+            ..ModelConfig::default()
+        };
+
+        let errors = config.validate().unwrap_err();
+        let fields = errors.field_errors();
+        for field in [
+            "top_k",
+            "top_p",
+            "min_p",
+            "repeat_penalty",
+            "n_predict",
+            "stop",
+        ] {
+            assert!(fields.contains_key(field), "missing error for {field}");
+        }
+    }
+
+    #[test]
+    fn test_model_config_without_sampling_fields_deserializes() {
+        let json = r#"{"model_id": "model.gguf", "temperature": 0.3, "system_prompt": "Hi"}"#;
+
+        let config: ModelConfig = serde_json::from_str(json).expect("Deserialization failed");
+        assert_eq!(config.top_k, None);
+        assert!(config.stop.is_empty());
+        assert_eq!(
+            config.sampling_params(),
+            SamplingParams::with_temperature(Some(0.3))
+        );
+    }
+
+    #[test]
+    fn test_model_config_rerank_options() {
+        assert_eq!(ModelConfig::default().rerank_options(), None);
+
+        let config = ModelConfig {
+            rerank: true,
+            rerank_top_k: Some(5),
+            ..ModelConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.rerank_options(),
+            Some(RerankOptions {
+                top_k: 5,
+                ..RerankOptions::default()
+            })
+        );
+
+        let config = ModelConfig {
+            rerank: true,
+            rerank_candidates: Some(0),
+            rerank_top_k: Some(50),
+            rerank_min_score: Some(1.5),
+            ..ModelConfig::default()
+        };
+        let errors = config.validate().unwrap_err();
+        let fields = errors.field_errors();
+        for field in ["rerank_candidates", "rerank_top_k", "rerank_min_score"] {
+            assert!(fields.contains_key(field), "missing error for {field}");
+        }
+    }
+
     #[test]
     fn test_model_config_serialization() {
         let config = ModelConfig {
             model_id: "test-model.gguf".to_string(),
             temperature: 0.8,
             system_prompt: "You are helpful.".to_string(),
+            ..ModelConfig::default()
         };
 
         let json = serde_json::to_string(&config).expect("Serialization failed");
@@ -270,6 +640,8 @@ mod tests {
             folder_id: Some("folder-456".to_string()),
             sort_order: 1,
             updated_at: 1700000100,
+            retrieval_scope: RetrievalScope::Folder,
+            retrieval_folder_id: Some("docs-789".to_string()),
         };
 
         let json = serde_json::to_string(&session).expect("Serialization failed");
@@ -277,6 +649,7 @@ mod tests {
         assert!(json.contains("Test Session"));
         assert!(json.contains("\"is_favorite\":true"));
         assert!(json.contains("folder-456"));
+        assert!(json.contains("\"retrieval_scope\":\"folder\""));
     }
 
     #[test]
@@ -298,6 +671,7 @@ mod tests {
         assert!(session.folder_id.is_none()); // Default
         assert_eq!(session.sort_order, 0); // Default
         assert_eq!(session.updated_at, 0); // Default
+        assert_eq!(session.retrieval_scope, RetrievalScope::Linked); // Default
     }
 
     // ==================== Folder Tests ====================
@@ -341,6 +715,10 @@ mod tests {
             role: "user".to_string(),
             content: "Hello, AI!".to_string(),
             created_at: 1700000000,
+            interrupted: false,
+            parent_id: None,
+            sibling_ids: Vec::new(),
+            sources: Vec::new(),
         };
 
         let json = serde_json::to_string(&message).expect("Serialization failed");
@@ -358,6 +736,10 @@ mod tests {
             role: "user".to_string(),
             content: "Question".to_string(),
             created_at: 0,
+            interrupted: false,
+            parent_id: None,
+            sibling_ids: Vec::new(),
+            sources: Vec::new(),
         };
 
         let assistant_msg = Message {
@@ -366,6 +748,10 @@ mod tests {
             role: "assistant".to_string(),
             content: "Answer".to_string(),
             created_at: 1,
+            interrupted: false,
+            parent_id: None,
+            sibling_ids: Vec::new(),
+            sources: Vec::new(),
         };
 
         assert_eq!(user_msg.role, "user");
@@ -384,6 +770,8 @@ mod tests {
             size: 1024,
             created_at: 1700000000,
             folder_id: None,
+            content_hash: None,
+            embedding_model: None,
         };
 
         let json = serde_json::to_string(&file).expect("Serialization failed");
@@ -403,6 +791,8 @@ mod tests {
             size: 256,
             created_at: 1700000000,
             folder_id: Some("folder-789".to_string()),
+            content_hash: None,
+            embedding_model: None,
         };
 
         assert_eq!(file.folder_id, Some("folder-789".to_string()));
@@ -5,13 +5,16 @@
 
 #![allow(dead_code)]
 
+use crate::embedding_config::active_model;
 use crate::fs_manager::PortablePathManager;
+use crate::gguf;
+use crate::launch_profile::free_port;
+use crate::llama_release;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use tracing::{info, warn};
 
 // --- Constants ---
-const MIN_MODEL_SIZE_BYTES: u64 = 3 * 1024 * 1024 * 1024; // 3 GB minimum for GGUF
 const MIN_EMBEDDINGS_SIZE_BYTES: u64 = 20 * 1024 * 1024; // 20 MB minimum for ONNX
 const LLAMA_SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
 const LLAMA_SERVER_HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
@@ -211,38 +214,20 @@ fn check_model_file() -> CheckResult {
         );
     }
 
-    match std::fs::metadata(&model_path) {
-        Ok(meta) => {
-            let size = meta.len();
-            if size >= MIN_MODEL_SIZE_BYTES {
-                CheckResult::pass(
-                    "model_file",
-                    &format!(
-                        "Model OK ({:.2} GB)",
-                        size as f64 / 1024.0 / 1024.0 / 1024.0
-                    ),
-                )
-            } else {
-                CheckResult::fail(
-                    "model_file",
-                    "Model file incomplete (partial download)",
-                    Some(format!(
-                        "Size: {} bytes, minimum required: {} bytes",
-                        size, MIN_MODEL_SIZE_BYTES
-                    )),
-                )
-            }
+    match gguf::validate(&model_path) {
+        Ok(metadata) => {
+            CheckResult::pass("model_file", &format!("Model OK ({})", metadata.summary()))
         }
-        Err(e) => CheckResult::fail("model_file", "Cannot read model file", Some(e.to_string())),
+        Err(e) => CheckResult::fail(
+            "model_file",
+            "Model file invalid or incomplete (partial download)",
+            Some(e.to_string()),
+        ),
     }
 }
 
 fn check_llama_server_binary() -> CheckResult {
-    let server_name = if cfg!(windows) {
-        "llama-server.exe"
-    } else {
-        "llama-server"
-    };
+    let server_name = llama_release::server_binary_name();
 
     // Check in tools directory first
     let tools_path = PortablePathManager::tools_dir()
@@ -429,11 +414,7 @@ fn check_vectors_dir() -> CheckResult {
 }
 
 async fn check_llama_server_starts() -> CheckResult {
-    let server_name = if cfg!(windows) {
-        "llama-server.exe"
-    } else {
-        "llama-server"
-    };
+    let server_name = llama_release::server_binary_name();
 
     let server_path = PortablePathManager::tools_dir()
         .join("llama")
@@ -441,12 +422,27 @@ async fn check_llama_server_starts() -> CheckResult {
 
     let model_path = PortablePathManager::models_dir().join("default-model.gguf");
 
-    // Use a different port to avoid conflicts
-    let test_port = 18080;
+    // Use a free port to avoid conflicts with a running server or another service
+    let test_port = match free_port() {
+        Ok(port) => port,
+        Err(e) => {
+            return CheckResult::fail(
+                "llama_server_startup",
+                "No free port for llama-server",
+                Some(e.to_string()),
+            );
+        }
+    };
 
     info!("Testing llama-server startup on port {}...", test_port);
 
     let mut cmd = tokio::process::Command::new(&server_path);
+    if let Some((var, value)) = server_path
+        .parent()
+        .and_then(llama_release::library_path_env)
+    {
+        cmd.env(var, value);
+    }
     cmd.arg("-m")
         .arg(&model_path)
         .arg("--host")
@@ -525,25 +521,21 @@ async fn check_llama_server_starts() -> CheckResult {
 }
 
 async fn check_embeddings_load() -> CheckResult {
-    let embeddings_dir = PortablePathManager::models_dir().join("embeddings");
+    let embedding = active_model();
 
     info!("Testing FastEmbed model loading...");
 
     let result = tokio::task::spawn_blocking(move || {
-        use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+        use fastembed::TextEmbedding;
 
         let start = std::time::Instant::now();
 
-        let mut options = InitOptions::new(EmbeddingModel::AllMiniLML6V2);
-        options.show_download_progress = false;
-        options.cache_dir = embeddings_dir;
-
-        match TextEmbedding::try_new(options) {
+        match TextEmbedding::try_new(embedding.init_options()) {
             Ok(model) => {
                 // Try to embed a test sentence
                 match model.embed(vec!["test".to_string()], None) {
                     Ok(embeddings) => {
-                        if embeddings.len() == 1 && embeddings[0].len() == 384 {
+                        if embeddings.len() == 1 && embeddings[0].len() == embedding.dimension() {
                             Ok(start.elapsed())
                         } else {
                             Err(format!(
@@ -1,8 +1,12 @@
 //! Text extraction module for various file formats
-//! Supports: TXT, MD, CSV, JSON, PDF, DOCX
+//! Supports: TXT, MD, CSV, JSON, PDF, DOCX and source code
 
+use crate::chunking::CODE_EXTENSIONS;
 use tracing::{info, warn};
 
+/// Separates the pages of paginated documents in extracted text.
+pub const PAGE_BREAK: char = '\x0c';
+
 /// Extract text content from binary file data based on file extension
 pub fn extract_text_from_file(file_name: &str, file_data: &[u8]) -> Result<String, String> {
     let extension = std::path::Path::new(file_name)
@@ -18,7 +22,13 @@ pub fn extract_text_from_file(file_name: &str, file_data: &[u8]) -> Result<Strin
 
     match extension.as_str() {
         // Plain text formats - direct UTF-8 conversion
-        "txt" | "md" | "csv" | "json" => String::from_utf8(file_data.to_vec())
+        "txt" | "md" | "markdown" | "csv" | "json" | "html" | "css" => {
+            String::from_utf8(file_data.to_vec())
+                .map_err(|e| format!("Invalid UTF-8 content: {}", e))
+        }
+
+        // Source code is plain text too
+        ext if CODE_EXTENSIONS.contains(&ext) => String::from_utf8(file_data.to_vec())
             .map_err(|e| format!("Invalid UTF-8 content: {}", e)),
 
         // PDF extraction
@@ -32,13 +42,24 @@ pub fn extract_text_from_file(file_name: &str, file_data: &[u8]) -> Result<Strin
 }
 
 /// Extract text from PDF file
+///
+/// Pages are separated by a form feed line so chunks can record their page number.
 fn extract_pdf_text(file_data: &[u8]) -> Result<String, String> {
     info!("Extracting text from PDF...");
 
-    match pdf_extract::extract_text_from_mem(file_data) {
-        Ok(text) => {
-            let cleaned = clean_extracted_text(&text);
-            info!("PDF extraction successful: {} characters", cleaned.len());
+    match pdf_extract::extract_text_from_mem_by_pages(file_data) {
+        Ok(pages) => {
+            let page_count = pages.len();
+            let cleaned = pages
+                .iter()
+                .map(|page| clean_extracted_text(page))
+                .collect::<Vec<_>>()
+                .join(&format!("\n{}\n", PAGE_BREAK));
+            info!(
+                "PDF extraction successful: {} characters on {} pages",
+                cleaned.len(),
+                page_count
+            );
             Ok(cleaned)
         }
         Err(e) => {
@@ -153,6 +174,14 @@ mod tests {
         assert!(text.contains("42"));
     }
 
+    #[test]
+    fn test_code_extraction() {
+        let content = b"fn main() {\n    println!(\"hello\");\n}\n";
+        let result = extract_text_from_file("main.rs", content);
+        assert!(result.is_ok());
+        assert!(result.unwrap().contains("fn main()"));
+    }
+
     #[test]
     fn test_unsupported_extension() {
         let content = b"Some binary data";
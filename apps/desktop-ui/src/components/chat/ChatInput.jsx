@@ -1,10 +1,10 @@
 import { useState, useRef, useEffect } from 'react';
-import { Send, Loader2 } from 'lucide-react';
+import { Send, Loader2, Square } from 'lucide-react';
 import { cn } from '../../lib/utils';
 import { useTranslation } from 'react-i18next';
 import { logger } from '../../lib/logger';
 
-export function ChatInput({ onSend, disabled }) {
+export function ChatInput({ onSend, onStop, disabled }) {
   const [input, setInput] = useState('');
   const [isFocused, setIsFocused] = useState(false);
   const textareaRef = useRef(null);
@@ -74,24 +74,40 @@ export function ChatInput({ onSend, disabled }) {
             rows={1}
           />
 
-          {/* Send Button */}
-          <button
-            type="submit"
-            disabled={!canSend}
-            className={cn(
-              "p-3 rounded-xl transition-all duration-200",
-              "focus:outline-none focus:ring-2 focus:ring-primary/30",
-              canSend
-                ? "bg-primary text-primary-foreground dark:text-zinc-900 shadow-lg shadow-primary/30 hover:opacity-90 hover:shadow-glow active:scale-95"
-                : "bg-muted/20 text-muted cursor-not-allowed"
-            )}
-          >
-            {disabled ? (
-              <Loader2 size={20} className="animate-spin" />
-            ) : (
-              <Send size={20} className={cn(canSend && "translate-x-0.5")} />
-            )}
-          </button>
+          {/* Stop Button (while a response is being generated) */}
+          {disabled && onStop ? (
+            <button
+              type="button"
+              onClick={onStop}
+              title={t('chat.input.stop', 'Stop generating')}
+              className={cn(
+                "p-3 rounded-xl transition-all duration-200",
+                "focus:outline-none focus:ring-2 focus:ring-primary/30",
+                "bg-primary text-primary-foreground dark:text-zinc-900 shadow-lg shadow-primary/30 hover:opacity-90 active:scale-95"
+              )}
+            >
+              <Square size={20} />
+            </button>
+          ) : (
+            /* Send Button */
+            <button
+              type="submit"
+              disabled={!canSend}
+              className={cn(
+                "p-3 rounded-xl transition-all duration-200",
+                "focus:outline-none focus:ring-2 focus:ring-primary/30",
+                canSend
+                  ? "bg-primary text-primary-foreground dark:text-zinc-900 shadow-lg shadow-primary/30 hover:opacity-90 hover:shadow-glow active:scale-95"
+                  : "bg-muted/20 text-muted cursor-not-allowed"
+              )}
+            >
+              {disabled ? (
+                <Loader2 size={20} className="animate-spin" />
+              ) : (
+                <Send size={20} className={cn(canSend && "translate-x-0.5")} />
+              )}
+            </button>
+          )}
         </div>
 
         {/* Character hint */}
@@ -14,7 +14,7 @@ export function ChatInterface() {
   const { t } = useTranslation();
   const { isThinking, thinkingSteps, currentSessionId, setCurrentSessionId, loadSessions, createSession, quickAction, clearQuickAction, setIsCreatingSession } = useAppStore();
 
-  const { messages, sendMessage, addSystemMessage } = useChatStream(currentSessionId);
+  const { messages, sendMessage, cancelGeneration, regenerateMessage, editMessage, switchBranch, addSystemMessage } = useChatStream(currentSessionId);
 
   const messagesEndRef = useRef(null);
   const processingQuickActionRef = useRef(false);
@@ -105,9 +105,22 @@ export function ChatInterface() {
 
   const renderedMessages = useMemo(() => {
     return messages.map((msg) => (
-      <MessageBubble key={msg.id} role={msg.role} content={msg.content} sessionId={currentSessionId} />
+      <MessageBubble
+        key={msg.id}
+        id={msg.id}
+        role={msg.role}
+        content={msg.content}
+        sources={msg.sources}
+        interrupted={msg.interrupted}
+        siblingIds={msg.siblingIds}
+        sessionId={currentSessionId}
+        disabled={isThinking}
+        onRegenerate={regenerateMessage}
+        onEdit={editMessage}
+        onSwitchBranch={switchBranch}
+      />
     ));
-  }, [messages, currentSessionId]);
+  }, [messages, currentSessionId, isThinking, regenerateMessage, editMessage, switchBranch]);
 
   return (
     <div className="flex flex-col h-full w-full relative">
@@ -156,7 +169,7 @@ export function ChatInterface() {
 
       {/* Input Area (Floating) */}
       <div className="absolute bottom-0 left-0 right-0 bg-gradient-to-t from-background via-background/95 to-transparent pt-8 z-10">
-        <ChatInput onSend={handleSend} disabled={isThinking} />
+        <ChatInput onSend={handleSend} onStop={() => cancelGeneration(currentSessionId)} disabled={isThinking} />
       </div>
     </div>
   );
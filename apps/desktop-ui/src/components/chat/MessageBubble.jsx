@@ -1,15 +1,21 @@
 import React, { useState } from 'react';
-import { User, Sparkles, Save, Check, X, FileText } from 'lucide-react';
+import { User, Sparkles, Save, Check, X, FileText, BookOpen, ChevronDown, ChevronRight, ChevronLeft, RefreshCw, Pencil } from 'lucide-react';
 import { cn } from '../../lib/utils';
 import { invoke } from '@tauri-apps/api/core';
 import { useTranslation } from 'react-i18next';
 import toast from 'react-hot-toast';
 import { logger } from '../../lib/logger';
 
-export const MessageBubble = React.memo(function MessageBubble({ role, content, sessionId }) {
+export const MessageBubble = React.memo(function MessageBubble({
+  id, role, content, sources, interrupted, siblingIds, sessionId, disabled, onRegenerate, onEdit, onSwitchBranch
+}) {
   const isUser = role === 'user';
   const isSystem = role === 'system';
   const { t } = useTranslation();
+  const [isEditing, setIsEditing] = useState(false);
+  const [draft, setDraft] = useState('');
+  // Only stored messages (numeric database ids) can be regenerated, edited or switched
+  const isStored = typeof id === 'number';
 
   if (isSystem) {
     return (
@@ -138,6 +144,25 @@ export const MessageBubble = React.memo(function MessageBubble({ role, content,
     }
   };
 
+  const startEdit = () => {
+    logger.ui.click('MessageBubble:Edit', { id });
+    setDraft(content || '');
+    setIsEditing(true);
+  };
+
+  const submitEdit = () => {
+    const text = draft.trim();
+    setIsEditing(false);
+    if (text && text !== content) {
+      onEdit?.(id, text);
+    }
+  };
+
+  const handleRegenerate = () => {
+    logger.ui.click('MessageBubble:Regenerate', { id });
+    onRegenerate?.(id);
+  };
+
   return (
     <div className={cn(
       "flex w-full mb-4 animate-fade-in",
@@ -166,14 +191,93 @@ export const MessageBubble = React.memo(function MessageBubble({ role, content,
             ? "bg-muted/30 text-foreground rounded-tr-sm"
             : "bg-surface text-foreground rounded-tl-sm border border-border shadow-sm"
         )}>
-          <div className="break-words">
-            {content ? formatContent(content) : <span className="text-muted italic">...</span>}
-          </div>
+          {isEditing ? (
+            <div className="flex flex-col gap-2 animate-fade-in">
+              <textarea
+                value={draft}
+                onChange={(e) => setDraft(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === 'Enter' && !e.shiftKey) {
+                    e.preventDefault();
+                    submitEdit();
+                  } else if (e.key === 'Escape') {
+                    setIsEditing(false);
+                  }
+                }}
+                rows={Math.min(8, Math.max(2, draft.split('\n').length))}
+                className="w-full p-2 text-sm bg-background border border-border rounded-lg resize-none focus:outline-none focus:border-primary"
+                autoFocus
+              />
+              <div className="flex justify-end gap-2">
+                <button
+                  onClick={() => setIsEditing(false)}
+                  className="px-2 py-1 rounded text-xs text-muted hover:bg-destructive/10 hover:text-destructive transition-colors"
+                >
+                  {t('common.cancel')}
+                </button>
+                <button
+                  onClick={submitEdit}
+                  disabled={!draft.trim()}
+                  className="px-2 py-1 rounded text-xs font-medium bg-primary text-primary-foreground hover:opacity-90 disabled:opacity-50 transition-opacity"
+                >
+                  {t('chat.message.send_edit')}
+                </button>
+              </div>
+            </div>
+          ) : (
+            <div className="break-words">
+              {content ? formatContent(content) : !interrupted && <span className="text-muted italic">...</span>}
+            </div>
+          )}
+
+          {/* Stopped by the user before the end */}
+          {!isUser && interrupted && (
+            <div className={cn("text-xs text-muted italic", content && "mt-2")}>
+              {t('chat.message.interrupted')}
+            </div>
+          )}
+
+          {/* Cited Sources (Assistant Only) */}
+          {!isUser && sources?.length > 0 && (
+            <MessageSources sources={sources} t={t} />
+          )}
 
           {/* Message Actions (Assistant Only) */}
           {!isUser && (
-            <div className="mt-2 pt-2 border-t border-border/50 flex justify-end opacity-0 group-hover:opacity-100 transition-opacity">
-               <MessageActions content={content} sessionId={sessionId} t={t} />
+            <div className="mt-2 pt-2 border-t border-border/50 flex items-center justify-between gap-2">
+               <BranchSwitcher id={id} siblingIds={siblingIds} disabled={disabled} onSwitchBranch={onSwitchBranch} t={t} />
+               <div className="flex items-center gap-1 opacity-0 group-hover:opacity-100 transition-opacity">
+                 {isStored && onRegenerate && (
+                   <button
+                     onClick={handleRegenerate}
+                     disabled={disabled}
+                     className="flex items-center gap-1.5 px-2 py-1 rounded text-xs font-medium text-muted hover:text-primary hover:bg-primary/10 disabled:opacity-50 transition-colors"
+                     title={t('chat.message.regenerate')}
+                   >
+                     <RefreshCw size={12} />
+                     <span>{t('chat.message.regenerate')}</span>
+                   </button>
+                 )}
+                 <MessageActions content={content} sessionId={sessionId} t={t} />
+               </div>
+            </div>
+          )}
+
+          {/* Edit and versions (User Only) */}
+          {isUser && isStored && !isEditing && (
+            <div className="mt-2 flex items-center justify-between gap-2">
+               <BranchSwitcher id={id} siblingIds={siblingIds} disabled={disabled} onSwitchBranch={onSwitchBranch} t={t} />
+               {onEdit && (
+                 <button
+                   onClick={startEdit}
+                   disabled={disabled}
+                   className="flex items-center gap-1.5 px-2 py-1 rounded text-xs font-medium text-muted hover:text-primary hover:bg-primary/10 disabled:opacity-50 opacity-0 group-hover:opacity-100 transition-all"
+                   title={t('chat.message.edit')}
+                 >
+                   <Pencil size={12} />
+                   <span>{t('chat.message.edit')}</span>
+                 </button>
+               )}
             </div>
           )}
         </div>
@@ -182,6 +286,117 @@ export const MessageBubble = React.memo(function MessageBubble({ role, content,
   );
 });
 
+// Moves between the versions of a message produced by regenerating or editing
+function BranchSwitcher({ id, siblingIds, disabled, onSwitchBranch, t }) {
+    const index = siblingIds?.indexOf(id) ?? -1;
+    if (!onSwitchBranch || index < 0 || siblingIds.length < 2) {
+        return <span />;
+    }
+
+    const switchTo = (siblingId) => {
+        logger.ui.click('BranchSwitcher:Switch', { from: id, to: siblingId });
+        onSwitchBranch(siblingId);
+    };
+
+    return (
+        <div className="flex items-center gap-1 text-xs text-muted">
+            <button
+                onClick={() => switchTo(siblingIds[index - 1])}
+                disabled={disabled || index === 0}
+                className="p-0.5 rounded hover:text-primary hover:bg-primary/10 disabled:opacity-30 disabled:pointer-events-none transition-colors"
+                title={t('chat.message.previous_version')}
+            >
+                <ChevronLeft size={12} />
+            </button>
+            <span className="font-mono">{index + 1}/{siblingIds.length}</span>
+            <button
+                onClick={() => switchTo(siblingIds[index + 1])}
+                disabled={disabled || index === siblingIds.length - 1}
+                className="p-0.5 rounded hover:text-primary hover:bg-primary/10 disabled:opacity-30 disabled:pointer-events-none transition-colors"
+                title={t('chat.message.next_version')}
+            >
+                <ChevronRight size={12} />
+            </button>
+        </div>
+    );
+}
+
+function MessageSources({ sources, t }) {
+    const [isOpen, setIsOpen] = useState(false);
+    const [openChunk, setOpenChunk] = useState(null); // { chunkId, content, error }
+
+    const handleOpen = async (source) => {
+        if (openChunk?.chunkId === source.chunk_id) {
+            setOpenChunk(null);
+            return;
+        }
+        logger.ui.click('MessageSources:Open', { chunkId: source.chunk_id });
+        setOpenChunk({ chunkId: source.chunk_id, content: null, error: null });
+        try {
+            const chunk = await invoke('get_source_chunk', { chunkId: source.chunk_id });
+            setOpenChunk({ chunkId: source.chunk_id, content: chunk.content, error: null });
+        } catch (error) {
+            setOpenChunk({ chunkId: source.chunk_id, content: null, error: String(error) });
+        }
+    };
+
+    const location = (source) => {
+        const parts = [];
+        if (source.page != null) parts.push(t('chat.sources.page', { page: source.page }));
+        if (source.heading_path) parts.push(source.heading_path);
+        if (!parts.length && source.char_start != null) {
+            parts.push(t('chat.sources.chars', { start: source.char_start, end: source.char_end }));
+        }
+        return parts.join(' · ');
+    };
+
+    return (
+        <div className="mt-3 pt-2 border-t border-border/50 text-xs">
+            <button
+                onClick={() => setIsOpen(!isOpen)}
+                className="flex items-center gap-1.5 text-muted hover:text-primary transition-colors"
+            >
+                {isOpen ? <ChevronDown size={12} /> : <ChevronRight size={12} />}
+                <BookOpen size={12} />
+                <span>{t('chat.sources.title', { count: sources.length })}</span>
+            </button>
+
+            {isOpen && (
+                <ol className="mt-2 space-y-1.5 animate-fade-in">
+                    {sources.map((source, index) => (
+                        <li key={source.chunk_id}>
+                            <button
+                                onClick={() => handleOpen(source)}
+                                className="w-full flex items-center gap-2 px-2 py-1 rounded text-left hover:bg-primary/10 transition-colors"
+                                title={t('chat.sources.open')}
+                            >
+                                <span className="text-primary/70 font-mono">[{index + 1}]</span>
+                                <span className="font-medium text-foreground truncate">
+                                    {source.file_name || source.file_id || t('chat.sources.unknown')}
+                                </span>
+                                <span className="text-muted truncate">{location(source)}</span>
+                                <span className="ml-auto text-muted font-mono">
+                                    {t('chat.sources.score', { score: Math.round(source.score * 100) })}
+                                </span>
+                            </button>
+
+                            {openChunk?.chunkId === source.chunk_id && (
+                                <div className="mt-1 ml-6 p-3 rounded-lg bg-background/60 border border-border/50 whitespace-pre-wrap text-muted">
+                                    {openChunk.error ? (
+                                        <span className="text-destructive">{openChunk.error}</span>
+                                    ) : openChunk.content ?? (
+                                        <span className="italic">{t('chat.sources.loading')}</span>
+                                    )}
+                                </div>
+                            )}
+                        </li>
+                    ))}
+                </ol>
+            )}
+        </div>
+    );
+}
+
 function MessageActions({ content, sessionId, t }) {
     const [isSaving, setIsSaving] = useState(false);
     const [showInput, setShowInput] = useState(false);
@@ -7,6 +7,8 @@ const stepIcons = {
   'thinking.analyzing': Search,
   'thinking.searching_context': Search,
   'thinking.documents_found': CheckCircle2,
+  'thinking.reranking': Search,
+  'thinking.reranked': CheckCircle2,
   'thinking.no_documents': Search,
   'thinking.intent': Lightbulb,
   'thinking.generating_response': MessageSquare,
@@ -43,6 +45,11 @@ export const ThinkingBubble = ({ steps = [], isExpanded = false, isThinking = fa
         return { text: t('thinking.intent', { intent: value }), key };
       } else if (key === 'thinking.documents_found') {
         return { text: t('thinking.documents_found', { count: value }), key };
+      } else if (key === 'thinking.history_trimmed' || key === 'thinking.documents_trimmed') {
+        return { text: t(key, { count: value }), key };
+      } else if (key === 'thinking.reranked') {
+        const [kept, candidates, ms] = parts.slice(1);
+        return { text: t(key, { kept, candidates, ms }), key };
       }
     }
     if (step.startsWith('thinking.')) {
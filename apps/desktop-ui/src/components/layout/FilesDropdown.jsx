@@ -8,7 +8,14 @@ import i18n from '../../i18n';
 export function FilesDropdown({ onClose: _onClose }) {
   const { t } = useTranslation('common');
   const fileInputRef = useRef(null);
-  const { currentSessionId, createSession, setCurrentSessionId } = useAppStore();
+  const {
+    currentSessionId,
+    createSession,
+    setCurrentSessionId,
+    sessions,
+    folders,
+    setSessionRetrievalScope
+  } = useAppStore();
   const [uploadStatus, setUploadStatus] = useState(null); // null, 'uploading', 'success', 'error'
   const [sessionFiles, setSessionFiles] = useState([]);
   const [isLoading, setIsLoading] = useState(false);
@@ -81,6 +88,20 @@ export function FilesDropdown({ onClose: _onClose }) {
     event.target.value = '';
   };
 
+  const currentSession = sessions.find(s => s.id === currentSessionId);
+  const documentFolders = folders.filter(f => f.folder_type === 'document' || f.type === 'document');
+  const scope = currentSession?.retrieval_scope || 'linked';
+  // Folder scopes are stored as "folder:<id>" in the select
+  const scopeValue = scope === 'folder' ? `folder:${currentSession.retrieval_folder_id}` : scope;
+
+  const handleScopeChange = (event) => {
+    const value = event.target.value;
+    const [nextScope, folderId] = value.startsWith('folder:')
+      ? ['folder', value.slice('folder:'.length)]
+      : [value, null];
+    setSessionRetrievalScope(currentSessionId, nextScope, folderId).catch(() => {});
+  };
+
   const getFileName = (filePath) => {
     // Extract filename from path like "session-id/filename.txt"
     const parts = filePath.split('/');
@@ -102,6 +123,29 @@ export function FilesDropdown({ onClose: _onClose }) {
         </div>
       </div>
 
+      {/* Retrieval Scope */}
+      {currentSession && (
+        <div className="px-4 py-2 border-b border-border">
+          <label className="block text-[10px] uppercase tracking-wide text-muted mb-1">
+            {t('retrieval_scope.label', 'Search in')}
+          </label>
+          <select
+            value={scopeValue}
+            onChange={handleScopeChange}
+            className="w-full px-2 py-1.5 rounded-lg bg-background border border-border text-xs focus:outline-none focus:border-primary"
+          >
+            <option value="linked">{t('retrieval_scope.linked', 'Attached files')}</option>
+            {documentFolders.map(folder => (
+              <option key={folder.id} value={`folder:${folder.id}`}>
+                {t('retrieval_scope.folder', { name: folder.name })}
+              </option>
+            ))}
+            <option value="library">{t('retrieval_scope.library', 'Whole library')}</option>
+            <option value="none">{t('retrieval_scope.none', 'No documents')}</option>
+          </select>
+        </div>
+      )}
+
       {/* File List */}
       <div className="max-h-48 overflow-y-auto custom-scrollbar">
         {isLoading ? (
@@ -1,5 +1,6 @@
-import { useState, useMemo } from 'react';
-import { Sliders, Thermometer, Save, RotateCcw, MessageSquare, Activity } from 'lucide-react';
+import { useState, useMemo, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { Sliders, Thermometer, Save, RotateCcw, MessageSquare, Activity, Cpu, ListFilter } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import { useAppStore } from '../../store/appStore';
 import { cn } from '../../lib/utils';
@@ -24,27 +25,44 @@ export function SettingsDropdown({ onClose }) {
   // Get current session config
   const currentSession = sessions.find(s => s.id === currentSessionId);
 
+  const [models, setModels] = useState([]);
   const [config, setConfig] = useState({
+    model_id: currentSession?.model_config?.model_id || 'default-model.gguf',
     temperature: currentSession?.model_config?.temperature ?? DEFAULT_CONFIG.temperature,
-    system_prompt: currentSession?.model_config?.system_prompt ?? DEFAULT_CONFIG.system_prompt
+    // Auto-created sessions have an empty prompt, which the backend rejects on save
+    system_prompt: currentSession?.model_config?.system_prompt || DEFAULT_CONFIG.system_prompt,
+    rerank: currentSession?.model_config?.rerank ?? false
   });
 
+  // Installed GGUF files the session can switch to
+  useEffect(() => {
+    invoke('list_models')
+      .then(setModels)
+      .catch(error => logger.system.error('SettingsDropdown:listModels', error));
+  }, []);
+
   const hasChanges = useMemo(() => {
+    const originalModel = currentSession?.model_config?.model_id || 'default-model.gguf';
     const originalTemp = currentSession?.model_config?.temperature ?? DEFAULT_CONFIG.temperature;
     const originalPrompt = currentSession?.model_config?.system_prompt ?? DEFAULT_CONFIG.system_prompt;
-    return config.temperature !== originalTemp ||
-           config.system_prompt !== originalPrompt;
-  }, [config, currentSession?.model_config?.temperature, currentSession?.model_config?.system_prompt]);
+    const originalRerank = currentSession?.model_config?.rerank ?? false;
+    return config.model_id !== originalModel ||
+           config.temperature !== originalTemp ||
+           config.system_prompt !== originalPrompt ||
+           config.rerank !== originalRerank;
+  }, [config, currentSession?.model_config?.model_id, currentSession?.model_config?.temperature, currentSession?.model_config?.system_prompt, currentSession?.model_config?.rerank]);
 
   const handleSave = async () => {
     if (!currentSessionId) return;
     logger.ui.click('SettingsDropdown:Save', { sessionId: currentSessionId });
     try {
-      // Build full model_config for backend
+      // Build full model_config for backend, keeping sampling settings not edited here
       const fullConfig = {
-        model_id: currentSession?.model_config?.model_id || 'default-model.gguf',
+        ...currentSession?.model_config,
+        model_id: config.model_id,
         temperature: config.temperature,
-        system_prompt: config.system_prompt
+        system_prompt: config.system_prompt,
+        rerank: config.rerank
       };
       await updateSession(currentSessionId, currentSession?.title, fullConfig);
       logger.store.action('updateSession', { sessionId: currentSessionId, temperature: config.temperature });
@@ -57,6 +75,7 @@ export function SettingsDropdown({ onClose }) {
   const handleReset = () => {
     logger.ui.click('SettingsDropdown:Reset');
     setConfig({
+      ...config,
       ...DEFAULT_CONFIG,
       system_prompt: getDefaultPrompt(i18n.language)
     });
@@ -74,6 +93,35 @@ export function SettingsDropdown({ onClose }) {
 
       {/* Settings */}
       <div className="p-4 space-y-4 max-h-80 overflow-y-auto custom-scrollbar">
+        {/* Model */}
+        {models.length > 0 && (
+          <div className="space-y-2">
+            <label className="flex items-center gap-2 text-sm font-medium">
+              <Cpu size={14} className="text-accent" />
+              {t('settings.model.label', 'Model')}
+            </label>
+            <select
+              value={config.model_id}
+              onChange={(e) => setConfig({ ...config, model_id: e.target.value })}
+              className="w-full px-3 py-2 text-xs bg-background border border-border rounded-lg focus:outline-none focus:ring-2 focus:ring-primary/30 focus:border-primary/50"
+            >
+              {!models.some(m => m.model_id === config.model_id) && (
+                <option value={config.model_id}>{config.model_id}</option>
+              )}
+              {models.map(model => (
+                <option key={model.model_id} value={model.model_id}>
+                  {model.metadata?.name || model.model_id}
+                  {model.metadata?.quantization ? ` · ${model.metadata.quantization}` : ''}
+                  {` · ${(model.size_bytes / 1024 ** 3).toFixed(1)} GB`}
+                </option>
+              ))}
+            </select>
+            <p className="text-[10px] text-muted">
+              {t('settings.model.description', 'Switching models reloads the local server on the next message')}
+            </p>
+          </div>
+        )}
+
         {/* System Prompt */}
         <div className="space-y-2">
           <div className="flex items-center gap-2">
@@ -117,6 +165,25 @@ export function SettingsDropdown({ onClose }) {
             {t('settings.temperature.description', 'Controls randomness (0=focused, 2=creative)')}
           </p>
         </div>
+
+        {/* Rerank */}
+        <div className="space-y-2">
+          <label className="flex items-center justify-between text-sm font-medium cursor-pointer">
+            <span className="flex items-center gap-2">
+              <ListFilter size={14} className="text-accent" />
+              {t('settings.rerank.label', 'Rerank documents')}
+            </span>
+            <input
+              type="checkbox"
+              checked={config.rerank}
+              onChange={(e) => setConfig({ ...config, rerank: e.target.checked })}
+              className="accent-primary"
+            />
+          </label>
+          <p className="text-[10px] text-muted">
+            {t('settings.rerank.description', 'Rescore retrieved documents with a local cross-encoder before answering')}
+          </p>
+        </div>
       </div>
 
       {/* Actions */}
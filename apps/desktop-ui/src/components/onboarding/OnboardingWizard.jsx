@@ -1,5 +1,5 @@
 import { useState, useEffect } from 'react';
-import { Shield, ArrowRight, Terminal, Server, Brain, Database, Cpu } from 'lucide-react';
+import { Shield, ArrowRight, Terminal, Server, Brain, Database, Cpu, Pause, Play, X } from 'lucide-react';
 import { useAppStore } from '../../store/appStore';
 import { cn } from '../../lib/utils';
 import { invoke } from '@tauri-apps/api/core';
@@ -7,6 +7,22 @@ import { listen } from '@tauri-apps/api/event';
 import { useTranslation } from 'react-i18next';
 import { TestConsole } from '../diagnostics/TestConsole';
 
+const formatBytes = (bytes) => {
+  const units = ['B', 'KB', 'MB', 'GB'];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit += 1;
+  }
+  return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+};
+
+const formatEta = (seconds) => {
+  const minutes = Math.floor(seconds / 60);
+  return minutes > 0 ? `${minutes}m ${seconds % 60}s` : `${seconds}s`;
+};
+
 const TIPS = [
   'onboarding.model.education.local_vs_cloud',
   'onboarding.model.education.privacy',
@@ -19,6 +35,7 @@ export function OnboardingWizard() {
   const [selectedLanguage, setSelectedLanguage] = useState(i18n.language || 'en');
   const [downloadProgress, setDownloadProgress] = useState(0);
   const [downloadStatus, setDownloadStatus] = useState('waiting'); // waiting, downloading, complete, error
+  const [transfer, setTransfer] = useState(null); // bytes, speed and ETA of the file being downloaded
   const [diagnosticsComplete, setDiagnosticsComplete] = useState(false);
   const [diagnosticsPassed, setDiagnosticsPassed] = useState(false);
   const [statusLogs, setStatusLogs] = useState([]);
@@ -56,8 +73,10 @@ export function OnboardingWizard() {
 
     // Listen for progress events
     const unlistenProgress = await listen('download-progress', (event) => {
-      setDownloadProgress(event.payload);
-      if (event.payload >= 100) {
+      const { percent } = event.payload;
+      setDownloadProgress(percent);
+      setTransfer(event.payload.download_id ? event.payload : null);
+      if (percent >= 100) {
         setDownloadStatus('complete');
       }
     });
@@ -90,11 +109,21 @@ export function OnboardingWizard() {
       setDownloadStatus('error');
       setStatusLogs(prev => [...prev, { step: 'error', detail: error.toString(), time: new Date().toLocaleTimeString() }]);
     } finally {
+      setTransfer(null);
       unlistenProgress();
       unlistenStatus();
     }
   };
 
+  const controlDownload = async (command) => {
+    if (!transfer) return;
+    try {
+      await invoke(command, { downloadId: transfer.download_id });
+    } catch (error) {
+      console.error(`${command} failed:`, error);
+    }
+  };
+
   const handleDiagnosticsComplete = (results) => {
     setDiagnosticsComplete(true);
     setDiagnosticsPassed(results.failed === 0);
@@ -197,6 +226,36 @@ export function OnboardingWizard() {
                   />
                 </div>
 
+                {/* Current file transfer */}
+                {transfer && (
+                  <div className="flex items-center justify-between text-xs text-muted">
+                    <span>
+                      {formatBytes(transfer.downloaded_bytes)} / {formatBytes(transfer.total_bytes)}
+                      {transfer.paused
+                        ? ` — ${t('onboarding.model.paused')}`
+                        : transfer.bytes_per_second > 0 &&
+                          ` — ${formatBytes(transfer.bytes_per_second)}/s` +
+                          (transfer.eta_seconds != null ? ` — ${t('onboarding.model.eta', { time: formatEta(transfer.eta_seconds) })}` : '')}
+                    </span>
+                    <div className="flex gap-1">
+                      <button
+                        onClick={() => controlDownload(transfer.paused ? 'resume_download' : 'pause_download')}
+                        className="p-1.5 rounded-md hover:bg-primary/10 text-text"
+                        title={transfer.paused ? t('onboarding.model.resume') : t('onboarding.model.pause')}
+                      >
+                        {transfer.paused ? <Play size={14} /> : <Pause size={14} />}
+                      </button>
+                      <button
+                        onClick={() => controlDownload('cancel_download')}
+                        className="p-1.5 rounded-md hover:bg-red-500/10 text-red-400"
+                        title={t('onboarding.model.cancel')}
+                      >
+                        <X size={14} />
+                      </button>
+                    </div>
+                  </div>
+                )}
+
                 {/* Backend Console - Shows what's happening */}
                 <div className="mt-4 bg-gray-900 rounded-lg border border-gray-700 overflow-hidden">
                   <div className="flex items-center gap-2 px-3 py-2 bg-gray-800 border-b border-gray-700">
@@ -1,6 +1,8 @@
 import { useEffect, useRef, useState } from 'react';
 import { useAppStore } from '../../store/appStore';
 import { useTranslation } from 'react-i18next';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import toast from 'react-hot-toast';
 import { logger } from '../../lib/logger';
 import {
@@ -16,7 +18,11 @@ import {
   Folder,
   ChevronRight,
   ChevronDown,
-  RefreshCw
+  RefreshCw,
+  Loader2,
+  AlertCircle,
+  RotateCcw,
+  Search
 } from 'lucide-react';
 
 export function KnowledgeView() {
@@ -42,12 +48,110 @@ export function KnowledgeView() {
   const [newFolderName, setNewFolderName] = useState('');
   const [expandedFolders, setExpandedFolders] = useState({});
   const [isReindexing, setIsReindexing] = useState(false);
+  // Latest ingestion job per file id, kept up to date by `ingest-progress` events
+  const [ingestJobs, setIngestJobs] = useState({});
+  const [remainingJobs, setRemainingJobs] = useState(0);
+  const [searchQuery, setSearchQuery] = useState('');
+  const [searchFolderId, setSearchFolderId] = useState('');
+  const [searchResults, setSearchResults] = useState(null);
+  const [isSearching, setIsSearching] = useState(false);
+  const [embeddingSettings, setEmbeddingSettings] = useState(null);
+  // Set while a reindex runs, so its results are reported once the queue is empty
+  const reindexRunningRef = useRef(false);
 
   useEffect(() => {
     loadLibraryFiles();
     loadFolders();
   }, [loadLibraryFiles, loadFolders]);
 
+  useEffect(() => {
+    invoke('get_embedding_config')
+      .then(setEmbeddingSettings)
+      .catch((error) => logger.store.error('getEmbeddingConfig', error));
+  }, []);
+
+  useEffect(() => {
+    let unlisten = null;
+    let cancelled = false;
+
+    invoke('list_ingest_jobs').then((jobs) => {
+      if (cancelled) return;
+      setIngestJobs(Object.fromEntries(jobs.map((job) => [job.file_id, job])));
+      setRemainingJobs(jobs.filter((job) => job.status === 'pending' || job.status === 'running').length);
+    }).catch((error) => logger.store.error('listIngestJobs', error));
+
+    listen('ingest-progress', (event) => {
+      const { remaining, counts, ...job } = event.payload;
+      setIngestJobs((prev) => ({ ...prev, [job.file_id]: job }));
+      setRemainingJobs(remaining);
+      if (remaining === 0 && reindexRunningRef.current) {
+        reindexRunningRef.current = false;
+        toast.success(t('knowledge.reindex_summary', '{{updated}} updated, {{skipped}} unchanged, {{failed}} failed', {
+          updated: counts.done,
+          skipped: counts.skipped,
+          failed: counts.failed
+        }));
+      }
+    }).then((fn) => {
+      if (cancelled) fn();
+      else unlisten = fn;
+    });
+
+    return () => {
+      cancelled = true;
+      if (unlisten) unlisten();
+    };
+  }, [t]);
+
+  const handleSearch = async () => {
+    const query = searchQuery.trim();
+    if (!query) {
+      setSearchResults(null);
+      return;
+    }
+    logger.ui.click('KnowledgeView:Search', { query });
+    setIsSearching(true);
+    try {
+      const hits = await invoke('search_library', {
+        query,
+        folderId: searchFolderId || null,
+        limit: 20
+      });
+      setSearchResults(hits);
+    } catch (error) {
+      logger.store.error('searchLibrary', error);
+      toast.error(t('knowledge.search.error', 'Search failed'));
+    } finally {
+      setIsSearching(false);
+    }
+  };
+
+  // A new embedding model applies on the next start, which re-embeds the knowledge base
+  const handleEmbeddingModelChange = async (model) => {
+    const config = { ...embeddingSettings.config, model };
+    try {
+      await invoke('save_embedding_config', { config });
+      setEmbeddingSettings((prev) => ({ ...prev, config }));
+      if (model !== embeddingSettings.active_model) {
+        toast.success(t('knowledge.embedding.restart', 'Restart the app to re-embed the knowledge base with this model'));
+      }
+    } catch (error) {
+      logger.store.error('saveEmbeddingConfig', error);
+      toast.error(t('knowledge.embedding.error', 'Could not save the embedding model'));
+    }
+  };
+
+  const handleRetryIngest = async (fileId) => {
+    logger.ui.click('KnowledgeView:RetryIngest', { fileId });
+    try {
+      const job = await invoke('retry_ingest_job', { fileId });
+      setIngestJobs((prev) => ({ ...prev, [job.file_id]: job }));
+    } catch (error) {
+      logger.store.error('retryIngestJob', error);
+      toast.error(t('knowledge.ingest.retry_failed', 'Could not queue the file again'));
+    }
+  };
+
   const handleUploadClick = () => {
     logger.ui.click('KnowledgeView:Upload');
     fileInputRef.current?.click();
@@ -59,7 +163,7 @@ export function KnowledgeView() {
 
     toast((toastInstance) => (
       <div className="flex flex-col gap-2 min-w-[200px]">
-        <span className="font-medium text-sm">{t('knowledge.reindex_confirm', 'This will re-process all files in your library. It may take some time. Continue?')}</span>
+        <span className="font-medium text-sm">{t('knowledge.reindex_confirm', 'Files changed since they were last indexed will be re-processed. It may take some time. Continue?')}</span>
         <div className="flex gap-2 justify-end">
           <button
             className="px-3 py-1.5 bg-surface border border-border rounded-lg text-xs hover:bg-background transition-colors"
@@ -86,14 +190,12 @@ export function KnowledgeView() {
     logger.file.reindex();
     const toastId = toast.loading(t('knowledge.reindexing', 'Reindexing...'));
     try {
-        const result = await reindexLibrary();
-        if (result.success) {
-            logger.file.reindexComplete(result);
-            toast.success(result.message, { id: toastId });
-        } else {
-            logger.store.error('reindexLibrary', result.error);
-            toast.error(t('knowledge.reindex_failed', 'Reindexing failed: ') + result.error, { id: toastId });
-        }
+        const jobs = await reindexLibrary();
+        reindexRunningRef.current = jobs.length > 0;
+        toast.success(
+          t('knowledge.reindex_queued', '{{count}} file(s) queued for indexing', { count: jobs.length }),
+          { id: toastId }
+        );
     } catch (error) {
         logger.store.error('reindexLibrary', error);
         toast.error(t('knowledge.reindex_error', 'An error occurred during reindexing'), { id: toastId });
@@ -174,13 +276,15 @@ export function KnowledgeView() {
 
     let successCount = 0;
     let failCount = 0;
+    let linkedCount = 0;
 
     // Upload files sequentially to avoid overwhelming the backend
     for (const file of files) {
       try {
-        await uploadFile(currentSessionId, file);
+        const result = await uploadFile(currentSessionId, file);
         logger.file.uploadSuccess(file.name);
         successCount++;
+        if (result?.deduplicated) linkedCount++;
       } catch (error) {
         logger.file.uploadError(file.name, error);
         failCount++;
@@ -205,6 +309,13 @@ export function KnowledgeView() {
       );
     }
 
+    if (linkedCount > 0) {
+      toast(
+        t('knowledge.upload_deduplicated', '{{count}} file(s) already in the library were linked instead of copied', { count: linkedCount }),
+        { icon: '🔗' }
+      );
+    }
+
     loadLibraryFiles();
     if (fileInputRef.current) fileInputRef.current.value = '';
   };
@@ -240,6 +351,36 @@ export function KnowledgeView() {
 
   const unfiledFiles = filesByFolder['unfiled'] || [];
 
+  const renderIngestStatus = (file) => {
+    const job = ingestJobs[file.id];
+    if (!job || job.status === 'done' || job.status === 'skipped') return null;
+
+    if (job.status === 'failed') {
+      return (
+        <span className="flex items-center gap-1 text-destructive" title={job.error || ''}>
+          <AlertCircle size={10} />
+          {t('knowledge.ingest.failed', 'Indexing failed')}
+          <button
+            onClick={() => handleRetryIngest(file.id)}
+            className="flex items-center gap-0.5 ml-1 hover:underline"
+          >
+            <RotateCcw size={10} />
+            {t('knowledge.ingest.retry', 'Retry')}
+          </button>
+        </span>
+      );
+    }
+
+    return (
+      <span className="flex items-center gap-1 text-primary">
+        <Loader2 size={10} className={job.status === 'running' ? 'animate-spin' : ''} />
+        {job.status === 'running'
+          ? t('knowledge.ingest.running', 'Indexing...')
+          : t('knowledge.ingest.pending', 'Queued')}
+      </span>
+    );
+  };
+
   const renderFileItem = (file) => (
     <div key={file.id} className="p-3 hover:bg-surface/50 transition-colors flex items-center gap-3 group border-b border-border/50 last:border-0">
         <div className="w-8 h-8 rounded-lg bg-surface border border-border flex items-center justify-center text-muted group-hover:text-primary group-hover:border-primary/30 transition-colors">
@@ -252,6 +393,7 @@ export function KnowledgeView() {
                 <span>{formatSize(file.size)}</span>
                 <span>•</span>
                 <span className="uppercase">{file.file_type.split('/').pop()}</span>
+                {renderIngestStatus(file)}
             </div>
         </div>
 
@@ -316,6 +458,22 @@ export function KnowledgeView() {
         </div>
 
         <div className="flex items-center gap-3">
+            {embeddingSettings && (
+                <select
+                    value={embeddingSettings.config.model}
+                    onChange={(e) => handleEmbeddingModelChange(e.target.value)}
+                    className={`px-3 py-2 bg-surface border rounded-xl text-sm focus:outline-none focus:border-primary ${embeddingSettings.config.model !== embeddingSettings.active_model ? 'border-yellow-500/50' : 'border-border'}`}
+                    title={embeddingSettings.config.model !== embeddingSettings.active_model
+                        ? t('knowledge.embedding.pending', 'Applies after restart (current: {{model}})', { model: embeddingSettings.active_model })
+                        : t('knowledge.embedding.label', 'Embedding model')}
+                >
+                    {embeddingSettings.models.map(({ model, dimension }) => (
+                        <option key={model} value={model}>
+                            {t('knowledge.embedding.option', '{{model}} ({{dimension}} dims)', { model, dimension })}
+                        </option>
+                    ))}
+                </select>
+            )}
             <button
                 onClick={handleReindex}
                 disabled={isReindexing}
@@ -406,6 +564,74 @@ export function KnowledgeView() {
             </div>
         </div>
 
+        {/* Library Search */}
+        <div className="bg-surface rounded-2xl border border-border overflow-hidden mb-8">
+            <div className="p-4 flex items-center gap-3">
+                <Search size={18} className="text-muted" />
+                <input
+                    type="text"
+                    placeholder={t('knowledge.search.placeholder', 'Which document mentions...?')}
+                    className="flex-1 bg-background border border-border rounded-lg px-3 py-1.5 text-sm focus:outline-none focus:ring-2 focus:ring-primary/50"
+                    value={searchQuery}
+                    onChange={(e) => setSearchQuery(e.target.value)}
+                    onKeyDown={(e) => e.key === 'Enter' && handleSearch()}
+                />
+                <select
+                    className="bg-background border border-border rounded-lg px-2 py-1.5 text-sm text-text"
+                    value={searchFolderId}
+                    onChange={(e) => setSearchFolderId(e.target.value)}
+                >
+                    <option value="">{t('knowledge.search.all_folders', 'All folders')}</option>
+                    {documentFolders.map(folder => (
+                        <option key={folder.id} value={folder.id}>{folder.name}</option>
+                    ))}
+                </select>
+                <button
+                    onClick={handleSearch}
+                    disabled={isSearching}
+                    className="flex items-center gap-2 px-3 py-1.5 bg-primary text-primary-foreground dark:text-zinc-900 rounded-lg hover:opacity-90 transition-all text-sm font-medium"
+                >
+                    {isSearching ? <Loader2 size={14} className="animate-spin" /> : <Search size={14} />}
+                    {t('knowledge.search.button', 'Search')}
+                </button>
+            </div>
+
+            {searchResults && (
+                <div className="border-t border-border max-h-[400px] overflow-y-auto">
+                    {searchResults.length === 0 ? (
+                        <div className="p-4 text-sm text-muted">{t('knowledge.search.no_results', 'No document matches this search')}</div>
+                    ) : searchResults.map(hit => (
+                        <div key={hit.file_id} className="p-4 border-b border-border/50 last:border-0">
+                            <div className="flex items-center gap-2 mb-2">
+                                <FileText size={14} className="text-primary" />
+                                <span className="font-medium text-sm text-text truncate">{hit.file_name}</span>
+                                <span className="text-[10px] text-muted">
+                                    {t('knowledge.search.matches', '{{count}} passage(s)', { count: hit.matches.length })}
+                                </span>
+                            </div>
+                            {hit.matches.map(match => (
+                                <div key={match.chunk_id} className="ml-6 mb-2 last:mb-0">
+                                    {(match.heading_path || match.page) && (
+                                        <div className="text-[10px] text-muted mb-0.5">
+                                            {[match.heading_path, match.page && t('knowledge.search.page', 'p. {{page}}', { page: match.page })]
+                                              .filter(Boolean)
+                                              .join(' · ')}
+                                        </div>
+                                    )}
+                                    <p className="text-xs text-muted line-clamp-3">
+                                        {match.segments.map((segment, i) => segment.highlighted
+                                          ? <mark key={i} className="bg-primary/20 text-text rounded-sm px-0.5">{segment.text}</mark>
+                                          : <span key={i}>{segment.text}</span>
+                                        )}
+                                    </p>
+                                </div>
+                            ))}
+                        </div>
+                    ))}
+                </div>
+            )}
+        </div>
+
         {/* File Browser */}
         <div className="bg-surface rounded-2xl border border-border overflow-hidden flex flex-col">
             <div className="p-4 border-b border-border flex items-center justify-between">
@@ -413,6 +639,12 @@ export function KnowledgeView() {
                     <Clock size={18} className="text-muted" />
                     {t('knowledge.library', 'Library')}
                 </h2>
+                {remainingJobs > 0 && (
+                    <span className="flex items-center gap-1.5 text-xs text-muted">
+                        <Loader2 size={12} className="animate-spin" />
+                        {t('knowledge.ingest.remaining', '{{count}} file(s) left to index', { count: remainingJobs })}
+                    </span>
+                )}
             </div>
 
             <div className="flex-1 overflow-y-auto min-h-[300px]">
@@ -478,7 +710,7 @@ export function KnowledgeView() {
         ref={fileInputRef}
         onChange={handleFileChange}
         className="hidden"
-        accept=".txt,.md,.csv,.json,.pdf,.docx,.doc"
+        accept=".txt,.md,.markdown,.csv,.json,.pdf,.docx,.doc,.rs,.py,.js,.jsx,.ts,.tsx,.go,.java,.kt,.c,.h,.cpp,.hpp,.cs,.rb,.php,.swift,.sh,.sql"
         multiple
       />
     </div>
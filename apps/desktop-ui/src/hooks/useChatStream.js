@@ -10,16 +10,28 @@ import { logger } from '../lib/logger';
 let globalListenersSetup = false;
 let messageHandler = null;
 let thinkingHandler = null;
+let sourcesHandler = null;
 let unlistenFunctions = [];
 
 // Counter for generating unique message IDs
 let messageIdCounter = 0;
 const generateMessageId = () => `msg_${Date.now()}_${++messageIdCounter}`;
 
+// Stored messages keep their database id, which regenerate, edit and branch switching need
+const formatMessage = (msg) => ({
+  id: msg.id || generateMessageId(),
+  role: msg.role,
+  content: msg.content,
+  interrupted: msg.interrupted,
+  parentId: msg.parent_id,
+  siblingIds: msg.sibling_ids || [],
+  sources: msg.sources || []
+});
+
 /**
  * Hook to handle chat streaming logic, message history, and Tauri events.
  * @param {string} sessionId - The current session ID.
- * @returns {Object} - { messages, sendMessage, refreshMessages }
+ * @returns {Object} - { messages, sendMessage, regenerateMessage, editMessage, switchBranch, refreshMessages }
  */
 export function useChatStream(sessionId) {
   const { t } = useTranslation();
@@ -52,11 +64,7 @@ export function useChatStream(sessionId) {
             sessionId: sessionId
           });
           if (isMountedRef.current && isActive) {
-            const formattedMessages = sessionMessages.map((msg) => ({
-              id: msg.id || generateMessageId(),
-              role: msg.role,
-              content: msg.content
-            }));
+            const formattedMessages = sessionMessages.map(formatMessage);
             logger.chat.loadMessages(sessionId, formattedMessages.length);
             setMessages(formattedMessages);
           }
@@ -121,6 +129,20 @@ export function useChatStream(sessionId) {
         addThinkingStep(step);
       }
     };
+
+    // Sources arrive once the answer is stored: attach them to the streamed message
+    sourcesHandler = (payload) => {
+      if (!isMountedRef.current || payload?.session_id !== sessionId) return;
+
+      setMessages(prev => {
+        const lastMsg = prev[prev.length - 1];
+        if (!lastMsg || lastMsg.role !== 'assistant') return prev;
+        return [
+          ...prev.slice(0, -1),
+          { ...lastMsg, sources: payload.sources || [] }
+        ];
+      });
+    };
   }, [addThinkingStep, sessionId]);
 
   // Setup global listeners ONCE with proper cleanup
@@ -150,6 +172,13 @@ export function useChatStream(sessionId) {
         });
         unlistenFunctions.push(unlistenToken);
 
+        const unlistenSources = await listen('chat-sources', (event) => {
+          if (sourcesHandler) {
+            sourcesHandler(event.payload);
+          }
+        });
+        unlistenFunctions.push(unlistenSources);
+
         logger.system.init('Chat event listeners ready');
       } catch (error) {
         logger.system.error('setupListeners', error);
@@ -166,6 +195,23 @@ export function useChatStream(sessionId) {
     };
   }, []);
 
+  const refreshMessages = useCallback(async () => {
+      if (sessionId) {
+          try {
+            // Tauri auto-converts camelCase to snake_case
+            const sessionMessages = await invoke('get_session_messages', {
+              sessionId: sessionId
+            });
+            const formattedMessages = sessionMessages.map(formatMessage);
+            logger.chat.loadMessages(sessionId, formattedMessages.length);
+            setMessages(formattedMessages);
+          } catch (error) {
+            logger.chat.error(error);
+            toast.error('Failed to refresh messages');
+          }
+      }
+  }, [sessionId]); // Note: 't' removed - use static string for error
+
   const sendMessage = useCallback(async (text, activeSessionId, isHidden = false) => {
       // Use provided activeSessionId (in case it was just created) or prop sessionId
       const targetSessionId = activeSessionId || sessionId;
@@ -197,33 +243,71 @@ export function useChatStream(sessionId) {
         logger.chat.streamEnd(targetSessionId, null);
         setThinking(false);
 
+        // Reload the stored messages so they can be regenerated or edited
+        if (!isHidden && targetSessionId === sessionId) {
+          await refreshMessages();
+        }
+
       } catch (error) {
         logger.chat.error(error);
         setMessages(prev => [...prev, { id: generateMessageId(), role: 'assistant', content: `${t('chat.error')}: ${error}` }]);
         setThinking(false);
       }
-  }, [sessionId, setThinking, clearThinkingSteps, t]);
+  }, [sessionId, setThinking, clearThinkingSteps, refreshMessages, t]);
 
-  const refreshMessages = useCallback(async () => {
-      if (sessionId) {
-          try {
-            // Tauri auto-converts camelCase to snake_case
-            const sessionMessages = await invoke('get_session_messages', {
-              sessionId: sessionId
-            });
-            const formattedMessages = sessionMessages.map(msg => ({
-              id: msg.id || generateMessageId(),
-              role: msg.role,
-              content: msg.content
-            }));
-            logger.chat.loadMessages(sessionId, formattedMessages.length);
-            setMessages(formattedMessages);
-          } catch (error) {
-            logger.chat.error(error);
-            toast.error('Failed to refresh messages');
-          }
+  const cancelGeneration = useCallback(async (activeSessionId) => {
+      const targetSessionId = activeSessionId || sessionId;
+      if (!targetSessionId) return;
+
+      try {
+        // The pending debug_chat call resolves with the partial answer
+        await invoke('cancel_generation', { sessionId: targetSessionId });
+      } catch (error) {
+        logger.chat.error(error);
       }
-  }, [sessionId]); // Note: 't' removed - use static string for error
+  }, [sessionId]);
+
+  // Regenerate, edit and branch switching rewrite the active branch, so reload it afterwards
+  const runBranchCommand = useCallback(async (command, args) => {
+      if (!sessionId) return;
+
+      try {
+        await invoke(command, { sessionId, ...args });
+      } catch (error) {
+        logger.chat.error(error);
+        toast.error(String(error));
+      } finally {
+        setThinking(false);
+        await refreshMessages();
+      }
+  }, [sessionId, setThinking, refreshMessages]);
+
+  // The new answer streams into a fresh bubble, so hide what it replaces until the reload
+  const regenerateMessage = useCallback((messageId) => {
+      setMessages(prev => {
+        const index = prev.findIndex(msg => msg.id === messageId);
+        if (index < 0) return prev;
+        return prev.slice(0, prev[index].role === 'user' ? index + 1 : index);
+      });
+      setThinking(true);
+      clearThinkingSteps();
+      return runBranchCommand('regenerate_message', { messageId });
+  }, [runBranchCommand, setThinking, clearThinkingSteps]);
+
+  const editMessage = useCallback((messageId, content) => {
+      setMessages(prev => {
+        const index = prev.findIndex(msg => msg.id === messageId);
+        if (index < 0) return prev;
+        return [...prev.slice(0, index), { id: generateMessageId(), role: 'user', content }];
+      });
+      setThinking(true);
+      clearThinkingSteps();
+      return runBranchCommand('edit_message', { messageId, content });
+  }, [runBranchCommand, setThinking, clearThinkingSteps]);
+
+  const switchBranch = useCallback((messageId) => {
+      return runBranchCommand('switch_branch', { messageId });
+  }, [runBranchCommand]);
 
   const addSystemMessage = useCallback((text) => {
     setMessages(prev => [...prev, { id: generateMessageId(), role: 'system', content: text }]);
@@ -236,12 +320,17 @@ export function useChatStream(sessionId) {
     globalListenersSetup = false;
     messageHandler = null;
     thinkingHandler = null;
+    sourcesHandler = null;
     logger.system.init('Chat event listeners cleaned up');
   }, []);
 
   return {
     messages,
     sendMessage,
+    cancelGeneration,
+    regenerateMessage,
+    editMessage,
+    switchBranch,
     refreshMessages,
     addSystemMessage,
     cleanupListeners
@@ -122,7 +122,8 @@ export const useAppStore = create(
       });
     },
 
-    uploadFile: function(sessionId, file) {
+    // Resolves with the upload outcome; an identical library file is linked unless forceCopy
+    uploadFile: function(sessionId, file, forceCopy) {
       logger.file.upload(file.name, sessionId);
       return new Promise(function(resolve, reject) {
         const reader = new FileReader();
@@ -132,16 +133,17 @@ export const useAppStore = create(
             // Convert Uint8Array to regular array for Tauri
             const fileData = Array.from(content);
 
-            await invoke('upload_file_for_session', {
+            const result = await invoke('upload_file_for_session', {
               sessionId: sessionId,
               fileName: file.name,
-              fileData: fileData
+              fileData: fileData,
+              forceCopy: forceCopy || false
             });
 
             logger.file.uploadSuccess(file.name);
             // Reload files
             await get().loadSessionFiles(sessionId);
-            resolve();
+            resolve(result);
           } catch (error) {
             logger.file.uploadError(file.name, error);
             get().showError('Failed to upload file: ' + (error.message || error));
@@ -365,6 +367,34 @@ export const useAppStore = create(
       });
     },
 
+    // scope: 'linked' | 'folder' | 'library' | 'none'; folderId only for 'folder'
+    setSessionRetrievalScope: function(sessionId, scope, folderId) {
+      logger.store.action('setSessionRetrievalScope', { sessionId, scope, folderId });
+      const retrievalFolderId = scope === 'folder' ? folderId : null;
+      return new Promise(function(resolve, reject) {
+        invoke('set_session_retrieval_scope', {
+          sessionId: sessionId,
+          scope: scope,
+          folderId: retrievalFolderId
+        }).then(function() {
+          set(function(state) {
+            return {
+              sessions: state.sessions.map(function(s) {
+                return s.id === sessionId
+                  ? { ...s, retrieval_scope: scope, retrieval_folder_id: retrievalFolderId }
+                  : s;
+              })
+            };
+          });
+          resolve();
+        }).catch(function(error) {
+          logger.store.error('setSessionRetrievalScope', error);
+          get().showError('Failed to change the document search scope.');
+          reject(error);
+        });
+      });
+    },
+
     moveFileToFolder: function(fileId, folderId) {
       logger.store.action('moveFileToFolder', { fileId, folderId });
       return new Promise(function(resolve, reject) {